@@ -0,0 +1,148 @@
+//! Benchmarks for waiting on events and registering sources.
+
+use std::io::{self, Write};
+use std::net::{TcpListener, TcpStream};
+use std::time::Duration;
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use polling::{Event, PollMode, Poller, Source};
+
+fn wait(c: &mut Criterion) {
+    let mut group = c.benchmark_group("wait");
+
+    group.bench_function("wait_empty", |b| {
+        let poller = Poller::new().unwrap();
+        let mut events = Vec::new();
+        b.iter(|| {
+            events.clear();
+            poller
+                .wait(&mut events, Some(Duration::from_millis(1)))
+                .unwrap();
+        });
+    });
+
+    for &count in &[1, 64] {
+        let name = if count == 1 {
+            "wait_one_event".to_string()
+        } else {
+            format!("wait_{}_events", count)
+        };
+
+        group.bench_function(name, |b| {
+            let poller = Poller::new().unwrap();
+            let pairs = readable_pairs(count).unwrap();
+            let mode = readiness_mode(&poller);
+            for (i, (read, _)) in pairs.iter().enumerate() {
+                poller
+                    .add_with_mode(read, Event::readable(i), mode)
+                    .unwrap();
+            }
+
+            let mut events = Vec::new();
+            b.iter(|| {
+                if mode == PollMode::Oneshot {
+                    for (i, (read, _)) in pairs.iter().enumerate() {
+                        poller.modify(read, Event::readable(i)).unwrap();
+                    }
+                }
+
+                events.clear();
+                while events.len() < count {
+                    poller.wait(&mut events, None).unwrap();
+                }
+            });
+
+            for (read, _) in &pairs {
+                poller.delete(read).unwrap();
+            }
+        });
+    }
+
+    group.finish();
+}
+
+fn registration(c: &mut Criterion) {
+    let mut group = c.benchmark_group("registration");
+
+    group.bench_function("add_modify_delete_cycle", |b| {
+        let poller = Poller::new().unwrap();
+        let (read, _write) = readable_pairs(1).unwrap().pop().unwrap();
+        let mut events = Vec::new();
+        b.iter(|| {
+            for _ in 0..1000 {
+                poller.add(&read, Event::none(1)).unwrap();
+                poller.modify(&read, Event::readable(1)).unwrap();
+                events.clear();
+                poller.wait(&mut events, None).unwrap();
+                poller.delete(&read).unwrap();
+            }
+        });
+    });
+
+    group.bench_function("individual_add_100", |b| {
+        let poller = Poller::new().unwrap();
+        let pairs = readable_pairs(100).unwrap();
+        b.iter(|| {
+            for (i, (read, _)) in pairs.iter().enumerate() {
+                poller.add(read, Event::readable(i)).unwrap();
+            }
+            for (read, _) in &pairs {
+                poller.delete(read).unwrap();
+            }
+        });
+    });
+
+    group.bench_function("batch_add_100", |b| {
+        let poller = Poller::new().unwrap();
+        let pairs = readable_pairs(100).unwrap();
+        let batch = pairs
+            .iter()
+            .enumerate()
+            .map(|(i, (read, _))| (read.raw(), Event::readable(i), PollMode::Oneshot))
+            .collect::<Vec<_>>();
+        b.iter(|| {
+            poller.add_many_from_slice(&batch).unwrap();
+            for (read, _) in &pairs {
+                poller.delete(read).unwrap();
+            }
+        });
+    });
+
+    group.finish();
+}
+
+fn notify(c: &mut Criterion) {
+    c.bench_function("notify", |b| {
+        let poller = Poller::new().unwrap();
+        let mut events = Vec::new();
+        b.iter(|| {
+            poller.notify().unwrap();
+            poller.wait(&mut events, None).unwrap();
+        });
+    });
+}
+
+/// Uses level-triggered mode if possible, so that sources stay ready without re-arming them.
+fn readiness_mode(poller: &Poller) -> PollMode {
+    if poller.supports_level() {
+        PollMode::Level
+    } else {
+        PollMode::Oneshot
+    }
+}
+
+/// Creates `count` connected pairs of sockets where the first one is always readable.
+fn readable_pairs(count: usize) -> io::Result<Vec<(TcpStream, TcpStream)>> {
+    let listener = TcpListener::bind("127.0.0.1:0")?;
+    (0..count)
+        .map(|_| {
+            let mut write = TcpStream::connect(listener.local_addr()?)?;
+            let (read, _) = listener.accept()?;
+            write.write_all(&[1])?;
+            Ok((read, write))
+        })
+        .collect()
+}
+
+criterion_group!(benches, wait, registration, notify);
+criterion_main!(benches);
@@ -0,0 +1,102 @@
+//! Fuzzes the conversion of raw kqueue events into `Event`s.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+#[cfg(any(
+    target_os = "macos",
+    target_os = "ios",
+    target_os = "tvos",
+    target_os = "watchos",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd",
+    target_os = "dragonfly",
+))]
+mod kqueue {
+    use arbitrary::Arbitrary;
+
+    /// The fields of a `libc::kevent`.
+    #[derive(Arbitrary, Debug)]
+    pub struct Kevent {
+        ident: usize,
+        filter: i16,
+        flags: u16,
+        fflags: u32,
+        data: i64,
+        udata: usize,
+    }
+
+    pub fn run(input: Vec<Kevent>) {
+        let raw = input
+            .iter()
+            .map(|kev| {
+                polling::fuzz::kevent(
+                    kev.ident, kev.filter, kev.flags, kev.fflags, kev.data, kev.udata,
+                )
+            })
+            .collect::<Vec<_>>();
+
+        // The fields are read back exactly, even for extreme values.
+        for (kev, raw) in input.iter().zip(&raw) {
+            assert_eq!(raw.data(), kev.data);
+            assert_eq!(raw.udata(), kev.udata);
+            assert_eq!(raw.fflags(), kev.fflags);
+        }
+
+        let events = polling::fuzz::events_from_raw(raw.clone());
+        let expected = input
+            .iter()
+            .zip(&raw)
+            .filter(|(kev, _)| kev.udata != polling::NOTIFY_KEY);
+
+        let mut count = 0;
+        for (ev, (kev, raw)) in events.iter().zip(expected) {
+            // The key is the `udata` field, without truncation.
+            assert_eq!(ev.key, kev.udata);
+
+            // Write filters are never reported as readable.
+            if raw.filter() == libc::EVFILT_WRITE as i32 {
+                assert!(!ev.readable);
+            }
+
+            count += 1;
+        }
+
+        // No events are dropped other than notifications.
+        assert_eq!(count, events.iter().count());
+        assert_eq!(
+            count,
+            input
+                .iter()
+                .filter(|kev| kev.udata != polling::NOTIFY_KEY)
+                .count()
+        );
+    }
+}
+
+#[cfg(any(
+    target_os = "macos",
+    target_os = "ios",
+    target_os = "tvos",
+    target_os = "watchos",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd",
+    target_os = "dragonfly",
+))]
+fuzz_target!(|input: Vec<kqueue::Kevent>| kqueue::run(input));
+
+// kqueue is not available on this platform.
+#[cfg(not(any(
+    target_os = "macos",
+    target_os = "ios",
+    target_os = "tvos",
+    target_os = "watchos",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd",
+    target_os = "dragonfly",
+)))]
+fuzz_target!(|_input: &[u8]| {});
@@ -0,0 +1,91 @@
+//! Fuzzes `Poller::add()`, `modify()` and `delete()` with arbitrary file descriptors or sockets.
+
+#![no_main]
+
+use arbitrary::Arbitrary;
+use libfuzzer_sys::fuzz_target;
+use polling::{Event, PollMode, Poller, RawSource};
+
+#[derive(Arbitrary, Debug)]
+enum Op {
+    Add(RawSource, Interest, Mode),
+    Modify(RawSource, Interest, Mode),
+    Delete(RawSource),
+}
+
+#[derive(Arbitrary, Debug)]
+struct Interest {
+    key: usize,
+    readable: bool,
+    writable: bool,
+}
+
+impl Interest {
+    fn event(&self) -> Event {
+        Event {
+            key: self.key,
+            readable: self.readable,
+            writable: self.writable,
+        }
+    }
+}
+
+#[derive(Arbitrary, Debug)]
+enum Mode {
+    Oneshot,
+    Level,
+    Edge,
+    EdgeOneshot,
+}
+
+impl Mode {
+    fn mode(&self) -> PollMode {
+        match self {
+            Mode::Oneshot => PollMode::Oneshot,
+            Mode::Level => PollMode::Level,
+            Mode::Edge => PollMode::Edge,
+            Mode::EdgeOneshot => PollMode::EdgeOneshot,
+        }
+    }
+}
+
+fuzz_target!(|ops: Vec<Op>| {
+    let poller = Poller::new().unwrap();
+
+    for op in ops {
+        let (fd, res) = match op {
+            Op::Add(fd, interest, mode) => {
+                (fd, poller.add_with_mode(fd, interest.event(), mode.mode()))
+            }
+            Op::Modify(fd, interest, mode) => (
+                fd,
+                poller.modify_with_mode(fd, interest.event(), mode.mode()),
+            ),
+            Op::Delete(fd) => (fd, poller.delete(fd)),
+        };
+
+        // The poll backend only looks at the file descriptors when waiting.
+        if res.is_ok() && polling::BACKEND != "poll" {
+            assert!(
+                is_valid(fd),
+                "operation on invalid source {:?} succeeded",
+                fd
+            );
+        }
+    }
+
+    poller
+        .wait(&mut Vec::new(), Some(std::time::Duration::from_secs(0)))
+        .ok();
+});
+
+#[cfg(unix)]
+fn is_valid(fd: RawSource) -> bool {
+    unsafe { libc::fcntl(fd, libc::F_GETFD) != -1 }
+}
+
+#[cfg(windows)]
+fn is_valid(_socket: RawSource) -> bool {
+    // There is no cheap way to check whether a socket is valid.
+    true
+}
@@ -0,0 +1,401 @@
+//! A `mio`-compatible interface on top of [`Poller`].
+//!
+//! This module mirrors the core of the public API of [`mio`] 0.8, so that code written against
+//! `mio` can be ported to `polling` with minimal changes. The types in this module are thin
+//! wrappers around [`Poller`] and [`Event`].
+//!
+//! Like `mio`, sources are registered in edge-triggered mode where the operating system supports
+//! it. On other platforms level-triggered mode is used instead, and where neither is available
+//! the registration is re-armed after every delivered event.
+//!
+//! [`mio`]: https://docs.rs/mio/0.8
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use polling::compat::{Events, Interest, Poll, Token};
+//! use std::net::TcpListener;
+//!
+//! const SERVER: Token = Token(0);
+//!
+//! let mut poll = Poll::new()?;
+//! let mut events = Events::with_capacity(128);
+//!
+//! let mut server = TcpListener::bind("127.0.0.1:8000")?;
+//! server.set_nonblocking(true)?;
+//! poll.registry()
+//!     .register(&mut server, SERVER, Interest::READABLE)?;
+//!
+//! loop {
+//!     poll.poll(&mut events, None)?;
+//!
+//!     for event in events.iter() {
+//!         if event.token() == SERVER {
+//!             // Accept connections until the listener would block.
+//!             while let Ok((_connection, _addr)) = server.accept() {}
+//!         }
+//!     }
+//! }
+//! # std::io::Result::Ok(())
+//! ```
+
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
+use std::ops;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::{PollMode, Poller};
+
+#[cfg(unix)]
+use std::os::unix::io::{AsRawFd as AsRaw, RawFd as Raw};
+#[cfg(windows)]
+use std::os::windows::io::{AsRawSocket as AsRaw, RawSocket as Raw};
+
+/// Associates readiness events with a registered source.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Token(pub usize);
+
+impl From<Token> for usize {
+    fn from(token: Token) -> usize {
+        token.0
+    }
+}
+
+/// Interest used in registering a source.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Interest(u8);
+
+const READABLE: u8 = 0b01;
+const WRITABLE: u8 = 0b10;
+
+impl Interest {
+    /// Returns an `Interest` set representing readable interests.
+    pub const READABLE: Interest = Interest(READABLE);
+
+    /// Returns an `Interest` set representing writable interests.
+    pub const WRITABLE: Interest = Interest(WRITABLE);
+
+    /// Add together two `Interest`s.
+    #[allow(clippy::should_implement_trait)]
+    pub const fn add(self, other: Interest) -> Interest {
+        Interest(self.0 | other.0)
+    }
+
+    /// Removes `other` from `self`.
+    ///
+    /// Returns `None` if the set would be empty after removing `other`.
+    pub fn remove(self, other: Interest) -> Option<Interest> {
+        match self.0 & !other.0 {
+            0 => None,
+            bits => Some(Interest(bits)),
+        }
+    }
+
+    /// Returns true if the value includes readable readiness.
+    pub const fn is_readable(self) -> bool {
+        self.0 & READABLE != 0
+    }
+
+    /// Returns true if the value includes writable readiness.
+    pub const fn is_writable(self) -> bool {
+        self.0 & WRITABLE != 0
+    }
+
+    /// Convert this interest into an [`Event`](crate::Event) with the given key.
+    fn to_event(self, key: usize) -> crate::Event {
+        crate::Event::new(key, self.is_readable(), self.is_writable())
+    }
+}
+
+impl ops::BitOr for Interest {
+    type Output = Self;
+
+    #[inline]
+    fn bitor(self, other: Self) -> Self {
+        self.add(other)
+    }
+}
+
+impl ops::BitOrAssign for Interest {
+    #[inline]
+    fn bitor_assign(&mut self, other: Self) {
+        self.0 |= other.0;
+    }
+}
+
+impl fmt::Debug for Interest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.is_readable(), self.is_writable()) {
+            (true, true) => f.write_str("READABLE | WRITABLE"),
+            (true, false) => f.write_str("READABLE"),
+            (false, true) => f.write_str("WRITABLE"),
+            (false, false) => unreachable!("empty interest"),
+        }
+    }
+}
+
+/// A readiness event.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Event {
+    inner: crate::Event,
+}
+
+impl Event {
+    /// Returns the event's token.
+    pub fn token(&self) -> Token {
+        Token(self.inner.key)
+    }
+
+    /// Returns true if the event contains readable readiness.
+    pub fn is_readable(&self) -> bool {
+        self.inner.readable
+    }
+
+    /// Returns true if the event contains writable readiness.
+    pub fn is_writable(&self) -> bool {
+        self.inner.writable
+    }
+}
+
+impl fmt::Debug for Event {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Event")
+            .field("token", &self.token())
+            .field("readable", &self.is_readable())
+            .field("writable", &self.is_writable())
+            .finish()
+    }
+}
+
+/// A collection of readiness events.
+///
+/// This is passed to [`Poll::poll`], which fills it with the events that were delivered.
+#[derive(Debug)]
+pub struct Events {
+    /// The events delivered by the last call to `poll`.
+    list: Vec<Event>,
+
+    /// Scratch buffer passed to `Poller::wait`.
+    buffer: Vec<crate::Event>,
+}
+
+impl Events {
+    /// Return a new `Events` capable of holding up to `capacity` events.
+    pub fn with_capacity(capacity: usize) -> Events {
+        Events {
+            list: Vec::with_capacity(capacity),
+            buffer: Vec::with_capacity(capacity),
+        }
+    }
+
+    /// Returns the number of `Event` values that `self` can hold.
+    pub fn capacity(&self) -> usize {
+        self.list.capacity()
+    }
+
+    /// Returns `true` if `self` contains no `Event` values.
+    pub fn is_empty(&self) -> bool {
+        self.list.is_empty()
+    }
+
+    /// Returns an iterator over the `Event` values.
+    pub fn iter(&self) -> Iter<'_> {
+        Iter {
+            inner: self.list.iter(),
+        }
+    }
+
+    /// Clearing all `Event` values from container explicitly.
+    pub fn clear(&mut self) {
+        self.list.clear();
+        self.buffer.clear();
+    }
+}
+
+impl<'a> IntoIterator for &'a Events {
+    type Item = &'a Event;
+    type IntoIter = Iter<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// [`Events`] iterator.
+#[derive(Debug, Clone)]
+pub struct Iter<'a> {
+    inner: std::slice::Iter<'a, Event>,
+}
+
+impl<'a> Iterator for Iter<'a> {
+    type Item = &'a Event;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+/// Polls for readiness events on all registered sources.
+#[derive(Debug)]
+pub struct Poll {
+    registry: Registry,
+}
+
+impl Poll {
+    /// Return a new `Poll` handle.
+    pub fn new() -> io::Result<Poll> {
+        let poller = Poller::new()?;
+
+        // Prefer the mode that `mio` uses, falling back to whatever the platform supports.
+        let mode = if poller.supports_edge() {
+            PollMode::Edge
+        } else if poller.supports_level() {
+            PollMode::Level
+        } else {
+            PollMode::Oneshot
+        };
+
+        Ok(Poll {
+            registry: Registry {
+                poller: Arc::new(poller),
+                mode,
+                sources: Arc::new(Mutex::new(HashMap::new())),
+            },
+        })
+    }
+
+    /// Create a separate `Registry` which can be used to register sources.
+    pub fn registry(&self) -> &Registry {
+        &self.registry
+    }
+
+    /// Wait for readiness events.
+    ///
+    /// Blocks the current thread and waits for readiness events for any of the sources that are
+    /// registered with this `Poll` instance. The function will block until either at least one
+    /// readiness event has been received or `timeout` has elapsed. `events` is cleared before
+    /// new events are written into it.
+    pub fn poll(&mut self, events: &mut Events, timeout: Option<Duration>) -> io::Result<()> {
+        events.clear();
+        self.registry.poller.wait(&mut events.buffer, timeout)?;
+
+        for ev in events.buffer.drain(..) {
+            events.list.push(Event { inner: ev });
+        }
+
+        // Re-arm the sources that fired if the poller only supports oneshot mode.
+        if self.registry.mode == PollMode::Oneshot {
+            let sources = self.registry.sources.lock().unwrap();
+            for ev in &events.list {
+                if let Some(&(raw, interest)) = sources.get(&ev.inner.key) {
+                    self.registry
+                        .poller
+                        .modify(raw, interest.to_event(ev.inner.key))?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Registers I/O resources.
+#[derive(Debug)]
+pub struct Registry {
+    /// The underlying poller.
+    poller: Arc<Poller>,
+
+    /// The mode that sources are registered in.
+    mode: PollMode,
+
+    /// Registered sources, used to re-arm them if only oneshot mode is available.
+    sources: Arc<Mutex<HashMap<usize, (Raw, Interest)>>>,
+}
+
+impl Registry {
+    /// Register a source with the `Poll` instance.
+    ///
+    /// # Errors
+    ///
+    /// This returns an error if the token is `Token(usize::MAX)`, since that value is reserved
+    /// for internal use.
+    pub fn register<S: AsRaw>(
+        &self,
+        source: &mut S,
+        token: Token,
+        interests: Interest,
+    ) -> io::Result<()> {
+        self.poller
+            .add_with_mode(&*source, interests.to_event(token.0), self.mode)?;
+        self.track(&*source, token, interests);
+        Ok(())
+    }
+
+    /// Re-register a source with the `Poll` instance.
+    pub fn reregister<S: AsRaw>(
+        &self,
+        source: &mut S,
+        token: Token,
+        interests: Interest,
+    ) -> io::Result<()> {
+        self.poller
+            .modify_with_mode(&*source, interests.to_event(token.0), self.mode)?;
+        self.track(&*source, token, interests);
+        Ok(())
+    }
+
+    /// Deregister a source with the `Poll` instance.
+    pub fn deregister<S: AsRaw>(&self, source: &mut S) -> io::Result<()> {
+        self.poller.delete(&*source)?;
+
+        if self.mode == PollMode::Oneshot {
+            let raw = raw(&*source);
+            self.sources
+                .lock()
+                .unwrap()
+                .retain(|_, &mut (other, _)| other != raw);
+        }
+
+        Ok(())
+    }
+
+    /// Creates a new independently owned `Registry`.
+    ///
+    /// Sources registered with the returned `Registry` are polled by the same `Poll` instance.
+    pub fn try_clone(&self) -> io::Result<Registry> {
+        Ok(Registry {
+            poller: self.poller.clone(),
+            mode: self.mode,
+            sources: self.sources.clone(),
+        })
+    }
+
+    /// Keep track of a registered source if it needs to be re-armed manually.
+    fn track<S: AsRaw>(&self, source: &S, token: Token, interests: Interest) {
+        if self.mode == PollMode::Oneshot {
+            self.sources
+                .lock()
+                .unwrap()
+                .insert(token.0, (raw(source), interests));
+        }
+    }
+}
+
+/// Get the raw file descriptor or socket of a source.
+fn raw<S: AsRaw>(source: &S) -> Raw {
+    #[cfg(unix)]
+    {
+        source.as_raw_fd()
+    }
+
+    #[cfg(windows)]
+    {
+        source.as_raw_socket()
+    }
+}
@@ -1,12 +1,22 @@
 //! Bindings to epoll (Linux, Android).
 
+use std::collections::HashMap;
 use std::convert::TryInto;
 use std::io;
-use std::os::unix::io::{AsRawFd, RawFd};
+use std::mem::{ManuallyDrop, MaybeUninit};
+use std::os::raw::c_int;
+use std::os::unix::io::{AsRawFd, FromRawFd, IntoRawFd, RawFd};
+use std::process::Child;
+use std::ptr;
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
 use rustix::fd::OwnedFd;
-use rustix::io::{epoll, eventfd, read, write, EventfdFlags};
+use rustix::io::{
+    epoll, eventfd, fcntl_dupfd_cloexec, fcntl_getfd, fcntl_setfd, read, write, EventfdFlags,
+    FdFlags,
+};
 use rustix::time::{
     timerfd_create, timerfd_settime, Itimerspec, TimerfdClockId, TimerfdFlags, TimerfdTimerFlags,
     Timespec,
@@ -15,7 +25,10 @@ use rustix::time::{
 #[cfg(not(polling_no_io_safety))]
 use std::os::unix::io::{AsFd, BorrowedFd};
 
-use crate::{Event, PollMode};
+use crate::{BackendKind, Event, EventDetails, PollMode, PollerBuilder, Registration};
+
+/// The backend implemented by this module.
+pub(crate) const BACKEND: BackendKind = BackendKind::Epoll;
 
 /// Interface to epoll.
 #[derive(Debug)]
@@ -25,58 +38,244 @@ pub struct Poller {
     /// File descriptor for the eventfd that produces notifications.
     event_fd: OwnedFd,
     /// File descriptor for the timerfd that produces timeouts.
+    ///
+    /// This is not created if the poller waits with `epoll_pwait2()`.
     timer_fd: Option<OwnedFd>,
+    /// Whether waits use `epoll_pwait2()`, which was enabled with the builder and is supported.
+    pwait2: bool,
+    /// The key used for notifications.
+    notify_key: usize,
+    /// Whether the epoll instance is shared with another poller created by `try_clone()`.
+    ///
+    /// Either poller may receive the events of the other's eventfd and timerfd. To make sure
+    /// that they keep working, the eventfd of a shared poller is registered in edge-triggered
+    /// mode and the timerfd is not used.
+    shared: AtomicBool,
+    /// The timerfds of the timers added with `add_timer()` and whether they repeat, by key.
+    ///
+    /// This is shared with the pollers created by `try_clone()`, since any of them may receive
+    /// the events of the timers.
+    timers: Arc<Mutex<HashMap<usize, (OwnedFd, bool)>>>,
+    /// Whether `timers` is not empty, so that waits don't have to lock it otherwise.
+    has_timers: Arc<AtomicBool>,
+    /// The pidfds of the processes added with `add_process()`, by process ID.
+    ///
+    /// This is shared with the pollers created by `try_clone()`, like `timers`.
+    processes: Arc<Mutex<HashMap<u32, OwnedFd>>>,
+    /// The keys and signalfds of the signals added with `add_signal()`, by signal number.
+    ///
+    /// This is shared with the pollers created by `try_clone()`, like `timers`.
+    signals: Arc<Mutex<HashMap<c_int, (usize, OwnedFd)>>>,
+    /// Whether `signals` is not empty, so that waits don't have to lock it otherwise.
+    has_signals: Arc<AtomicBool>,
+    /// The registrations in exclusive mode, which can't be modified, only replaced, by file
+    /// descriptor.
+    ///
+    /// This is shared with the pollers created by `try_clone()`, since they share the epoll
+    /// instance.
+    exclusive: Arc<Mutex<HashMap<RawFd, Registration>>>,
+    /// Whether `exclusive` was ever used, so that other sources don't have to lock it.
+    has_exclusive: Arc<AtomicBool>,
 }
 
 impl Poller {
     /// Creates a new poller.
-    pub fn new() -> io::Result<Poller> {
+    pub fn new(builder: &PollerBuilder) -> io::Result<Poller> {
         // Create an epoll instance.
         //
-        // Use `epoll_create1` with `EPOLL_CLOEXEC`.
-        let epoll_fd = epoll::epoll_create(epoll::CreateFlags::CLOEXEC)?;
-
-        // Set up eventfd and timerfd.
-        let event_fd = eventfd(0, EventfdFlags::CLOEXEC | EventfdFlags::NONBLOCK)?;
-        let timer_fd = timerfd_create(
-            TimerfdClockId::Monotonic,
-            TimerfdFlags::CLOEXEC | TimerfdFlags::NONBLOCK,
-        )
-        .ok();
+        // Use `epoll_create1` with `EPOLL_CLOEXEC` unless told otherwise.
+        let create_flags = if builder.close_on_exec {
+            epoll::CreateFlags::CLOEXEC
+        } else {
+            epoll::CreateFlags::empty()
+        };
+        let epoll_fd = epoll::epoll_create(create_flags)?;
+        let pwait2 = builder.epoll_pwait2 && supports_pwait2(epoll_fd.as_raw_fd());
+        let (event_fd, timer_fd) = notify_fds(builder, !pwait2)?;
 
         let poller = Poller {
             epoll_fd,
             event_fd,
             timer_fd,
+            pwait2,
+            notify_key: builder.notify_key,
+            shared: AtomicBool::new(false),
+            timers: Arc::new(Mutex::new(HashMap::new())),
+            has_timers: Arc::new(AtomicBool::new(false)),
+            processes: Arc::new(Mutex::new(HashMap::new())),
+            signals: Arc::new(Mutex::new(HashMap::new())),
+            has_signals: Arc::new(AtomicBool::new(false)),
+            exclusive: Arc::new(Mutex::new(HashMap::new())),
+            has_exclusive: Arc::new(AtomicBool::new(false)),
         };
+        poller.register_notify()?;
+
+        log::trace!(
+            "new: epoll_fd={}, event_fd={}, timer_fd={:?}",
+            poller.epoll_fd.as_raw_fd(),
+            poller.event_fd.as_raw_fd(),
+            poller.timer_fd
+        );
+        Ok(poller)
+    }
+
+    /// Creates a poller from an existing epoll instance.
+    ///
+    /// Registering the eventfd fails unless `fd` is an epoll instance, in which case `fd` is left
+    /// open.
+    ///
+    /// # Safety
+    ///
+    /// `fd` must be an open file descriptor owned by the caller.
+    pub unsafe fn from_raw_fd(fd: RawFd, builder: &PollerBuilder) -> io::Result<Poller> {
+        let pwait2 = builder.epoll_pwait2 && supports_pwait2(fd);
+        let (event_fd, timer_fd) = notify_fds(builder, !pwait2)?;
 
-        if let Some(ref timer_fd) = poller.timer_fd {
-            poller.add(
+        let poller = Poller {
+            epoll_fd: OwnedFd::from_raw_fd(fd),
+            event_fd,
+            timer_fd,
+            pwait2,
+            notify_key: builder.notify_key,
+            shared: AtomicBool::new(false),
+            timers: Arc::new(Mutex::new(HashMap::new())),
+            has_timers: Arc::new(AtomicBool::new(false)),
+            processes: Arc::new(Mutex::new(HashMap::new())),
+            signals: Arc::new(Mutex::new(HashMap::new())),
+            has_signals: Arc::new(AtomicBool::new(false)),
+            exclusive: Arc::new(Mutex::new(HashMap::new())),
+            has_exclusive: Arc::new(AtomicBool::new(false)),
+        };
+        if let Err(e) = poller.register_notify() {
+            poller.into_raw_fd();
+            return Err(e);
+        }
+
+        if builder.close_on_exec {
+            if let Err(e) = set_cloexec(&poller.epoll_fd) {
+                poller.into_raw_fd();
+                return Err(e);
+            }
+        }
+
+        log::trace!(
+            "from_raw_fd: epoll_fd={}, event_fd={}, timer_fd={:?}",
+            poller.epoll_fd.as_raw_fd(),
+            poller.event_fd.as_raw_fd(),
+            poller.timer_fd
+        );
+        Ok(poller)
+    }
+
+    /// Releases the epoll instance without closing it.
+    pub fn into_raw_fd(self) -> RawFd {
+        let mut poller = ManuallyDrop::new(self);
+        poller.deregister_notify();
+
+        // SAFETY: `poller` is never used or dropped again.
+        unsafe {
+            ptr::drop_in_place(&mut poller.event_fd);
+            ptr::drop_in_place(&mut poller.timer_fd);
+            ptr::read(&poller.epoll_fd).into_raw_fd()
+        }
+    }
+
+    /// Registers the eventfd and the timerfd in the epoll instance.
+    fn register_notify(&self) -> io::Result<()> {
+        if let Some(ref timer_fd) = self.timer_fd {
+            self.add(
                 timer_fd.as_raw_fd(),
-                Event::none(crate::NOTIFY_KEY),
+                Event::none(self.notify_key).into(),
                 PollMode::Oneshot,
             )?;
         }
 
+        self.add(
+            self.event_fd.as_raw_fd(),
+            Event::readable(self.notify_key).into(),
+            PollMode::Oneshot,
+        )
+    }
+
+    /// Removes the eventfd and the timerfd from the epoll instance.
+    fn deregister_notify(&mut self) {
+        if let Some(timer_fd) = self.timer_fd.take() {
+            let _ = self.delete(timer_fd.as_raw_fd());
+        }
+        let _ = self.delete(self.event_fd.as_raw_fd());
+    }
+
+    /// Creates a new poller that shares the epoll instance of this one.
+    pub fn try_clone(&self) -> io::Result<Poller> {
+        // Duplicate the epoll instance, keeping the `CLOEXEC` flag of the original.
+        let fd_flags = fcntl_getfd(&self.epoll_fd)?;
+        let epoll_fd = fcntl_dupfd_cloexec(&self.epoll_fd, 0)?;
+        if !fd_flags.contains(FdFlags::CLOEXEC) {
+            fcntl_setfd(&epoll_fd, fd_flags)?;
+        }
+
+        let eventfd_flags = if fd_flags.contains(FdFlags::CLOEXEC) {
+            EventfdFlags::CLOEXEC
+        } else {
+            EventfdFlags::empty()
+        };
+        let event_fd = eventfd(0, eventfd_flags | EventfdFlags::NONBLOCK)?;
+
+        let poller = Poller {
+            epoll_fd,
+            event_fd,
+            timer_fd: None,
+            pwait2: self.pwait2,
+            notify_key: self.notify_key,
+            shared: AtomicBool::new(true),
+            timers: self.timers.clone(),
+            has_timers: self.has_timers.clone(),
+            processes: self.processes.clone(),
+            signals: self.signals.clone(),
+            has_signals: self.has_signals.clone(),
+            exclusive: self.exclusive.clone(),
+            has_exclusive: self.has_exclusive.clone(),
+        };
+
         poller.add(
             poller.event_fd.as_raw_fd(),
-            Event {
-                key: crate::NOTIFY_KEY,
-                readable: true,
-                writable: false,
-            },
-            PollMode::Oneshot,
+            Event::readable(poller.notify_key).into(),
+            PollMode::Edge,
         )?;
 
+        // Switch this poller over as well.
+        self.enable_concurrent_wait()?;
+
         log::trace!(
-            "new: epoll_fd={}, event_fd={}, timer_fd={:?}",
+            "try_clone: epoll_fd={}, event_fd={}",
             poller.epoll_fd.as_raw_fd(),
-            poller.event_fd.as_raw_fd(),
-            poller.timer_fd
+            poller.event_fd.as_raw_fd()
         );
         Ok(poller)
     }
 
+    /// Prepares the poller for several threads waiting on it at the same time.
+    ///
+    /// This switches the poller to the mode of a shared epoll instance, in which waiting doesn't
+    /// modify the eventfd or the timerfd.
+    pub fn enable_concurrent_wait(&self) -> io::Result<()> {
+        if !self.shared.swap(true, Ordering::SeqCst) {
+            self.modify(
+                self.event_fd.as_raw_fd(),
+                Event::readable(self.notify_key).into(),
+                PollMode::Edge,
+            )?;
+            if let Some(ref timer_fd) = self.timer_fd {
+                self.modify(
+                    timer_fd.as_raw_fd(),
+                    Event::none(self.notify_key).into(),
+                    PollMode::Oneshot,
+                )?;
+            }
+        }
+        Ok(())
+    }
+
     /// Whether this poller supports level-triggered events.
     pub fn supports_level(&self) -> bool {
         true
@@ -87,8 +286,73 @@ impl Poller {
         true
     }
 
+    /// Whether the poller wakes up only one poller for exclusive registrations.
+    pub fn supports_exclusive(&self) -> bool {
+        supports_exclusive()
+    }
+
+    /// The precision of the timeouts of `wait()`.
+    pub fn timeout_granularity(&self) -> Duration {
+        // Without `epoll_pwait2()` or a timerfd, the timeout is rounded up to a whole millisecond.
+        if self.pwait2 || (self.timer_fd.is_some() && !self.shared.load(Ordering::SeqCst)) {
+            Duration::from_nanos(1)
+        } else {
+            Duration::from_millis(1)
+        }
+    }
+
+    /// Checks that the file descriptors of this poller are still open and registered.
+    pub fn check_invariants(&self, registered: Option<usize>) -> io::Result<()> {
+        fcntl_getfd(&self.epoll_fd)?;
+        fcntl_getfd(&self.event_fd)?;
+        if let Some(ref timer_fd) = self.timer_fd {
+            fcntl_getfd(timer_fd)?;
+        }
+
+        // The file descriptors in the epoll instance are listed in `/proc`, if it is mounted.
+        let path = format!("/proc/self/fdinfo/{}", self.epoll_fd.as_raw_fd());
+        let fdinfo = match std::fs::read_to_string(path) {
+            Ok(fdinfo) => fdinfo,
+            Err(_) => return Ok(()),
+        };
+        let fds = fdinfo
+            .lines()
+            .filter_map(|line| line.strip_prefix("tfd:"))
+            .filter_map(|rest| rest.split_whitespace().next()?.parse::<RawFd>().ok())
+            .collect::<Vec<_>>();
+
+        let broken = |msg: &str| Err(io::Error::new(io::ErrorKind::Other, msg));
+        if !fds.contains(&self.event_fd.as_raw_fd()) {
+            return broken("the eventfd is not registered");
+        }
+        if let Some(ref timer_fd) = self.timer_fd {
+            if !fds.contains(&timer_fd.as_raw_fd()) {
+                return broken("the timerfd is not registered");
+            }
+        }
+
+        // Pollers created by `try_clone()` register their own file descriptors in the instance.
+        if let (Some(registered), false) = (registered, self.shared.load(Ordering::SeqCst)) {
+            let internal = 1
+                + self.timer_fd.is_some() as usize
+                + self.timers.lock().unwrap_or_else(|e| e.into_inner()).len()
+                + self
+                    .processes
+                    .lock()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .len()
+                + self.signals.lock().unwrap_or_else(|e| e.into_inner()).len();
+            if fds.len() != registered + internal {
+                return broken(
+                    "the number of registered file descriptors doesn't match the sources",
+                );
+            }
+        }
+        Ok(())
+    }
+
     /// Adds a new file descriptor.
-    pub fn add(&self, fd: RawFd, ev: Event, mode: PollMode) -> io::Result<()> {
+    pub fn add(&self, fd: RawFd, ev: Registration, mode: PollMode) -> io::Result<()> {
         log::trace!(
             "add: epoll_fd={}, fd={}, ev={:?}",
             self.epoll_fd.as_raw_fd(),
@@ -103,11 +367,57 @@ impl Poller {
             epoll_flags(&ev, mode),
         )?;
 
+        // Older kernels ignore `EPOLLEXCLUSIVE`, and their registrations can be modified.
+        if mode == PollMode::Exclusive && supports_exclusive() {
+            self.has_exclusive.store(true, Ordering::Relaxed);
+            self.exclusive
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .insert(fd, ev);
+        }
+
         Ok(())
     }
 
+    /// Adds several file descriptors, returning the result for each of them.
+    pub fn add_batch(&self, fds: &[(RawFd, Event, PollMode)]) -> Vec<io::Result<()>> {
+        fds.iter()
+            .map(|&(fd, ev, mode)| self.add(fd, ev.into(), mode))
+            .collect()
+    }
+
+    /// Adds several file descriptors, returning the first error.
+    ///
+    /// A failure to add one doesn't stop the others from being added. `added` is called with the
+    /// index of each file descriptor that was added.
+    pub fn add_many(
+        &self,
+        fds: &[(RawFd, Event, PollMode)],
+        mut added: impl FnMut(usize),
+    ) -> io::Result<()> {
+        let mut result = Ok(());
+        for (i, &(fd, ev, mode)) in fds.iter().enumerate() {
+            match self.add(fd, ev.into(), mode) {
+                Ok(()) => added(i),
+                Err(err) => {
+                    if result.is_ok() {
+                        result = Err(err);
+                    }
+                }
+            }
+        }
+        result
+    }
+
+    /// Modifies several file descriptors, returning the result for each of them.
+    pub fn modify_batch(&self, fds: &[(RawFd, Event, PollMode)]) -> Vec<io::Result<()>> {
+        fds.iter()
+            .map(|&(fd, ev, mode)| self.modify(fd, ev.into(), mode))
+            .collect()
+    }
+
     /// Modifies an existing file descriptor.
-    pub fn modify(&self, fd: RawFd, ev: Event, mode: PollMode) -> io::Result<()> {
+    pub fn modify(&self, fd: RawFd, ev: Registration, mode: PollMode) -> io::Result<()> {
         log::trace!(
             "modify: epoll_fd={}, fd={}, ev={:?}",
             self.epoll_fd.as_raw_fd(),
@@ -115,6 +425,17 @@ impl Poller {
             ev
         );
 
+        // Registrations with `EPOLLEXCLUSIVE` can't be modified, only replaced.
+        if let Some(old) = self.exclusive_registration(fd) {
+            return self.replace(fd, old, ev, mode);
+        }
+        if mode == PollMode::Exclusive && supports_exclusive() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "only sources added in exclusive mode can be modified to exclusive mode",
+            ));
+        }
+
         epoll::epoll_mod(
             &self.epoll_fd,
             unsafe { rustix::fd::BorrowedFd::borrow_raw(fd) },
@@ -125,6 +446,47 @@ impl Poller {
         Ok(())
     }
 
+    /// The registration of `fd`, if it was added in exclusive mode.
+    fn exclusive_registration(&self, fd: RawFd) -> Option<Registration> {
+        if !self.has_exclusive.load(Ordering::Relaxed) {
+            return None;
+        }
+        self.exclusive
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(&fd)
+            .copied()
+    }
+
+    /// Removes an exclusive registration and adds the file descriptor again with new flags.
+    ///
+    /// If it can't be added again, the `old` registration is restored.
+    fn replace(
+        &self,
+        fd: RawFd,
+        old: Registration,
+        ev: Registration,
+        mode: PollMode,
+    ) -> io::Result<()> {
+        self.delete(fd)?;
+        if let Err(err) = self.add(fd, ev, mode) {
+            self.add(fd, old, PollMode::Exclusive)?;
+            return Err(err);
+        }
+        Ok(())
+    }
+
+    /// Stops delivering events for a file descriptor without removing it.
+    pub fn disable(&self, fd: RawFd, ev: Registration, mode: PollMode) -> io::Result<()> {
+        // Emulated by clearing the interest.
+        self.modify(fd, Registration::none(ev.key), mode)
+    }
+
+    /// Resumes delivering events for a disabled file descriptor.
+    pub fn enable(&self, fd: RawFd, ev: Registration, mode: PollMode) -> io::Result<()> {
+        self.modify(fd, ev, mode)
+    }
+
     /// Deletes a file descriptor.
     pub fn delete(&self, fd: RawFd) -> io::Result<()> {
         log::trace!("remove: epoll_fd={}, fd={}", self.epoll_fd.as_raw_fd(), fd);
@@ -133,19 +495,350 @@ impl Poller {
             rustix::fd::BorrowedFd::borrow_raw(fd)
         })?;
 
+        if self.has_exclusive.load(Ordering::Relaxed) {
+            self.exclusive
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .remove(&fd);
+        }
+
+        Ok(())
+    }
+
+    /// Sets the number of bytes that must be buffered before a read event is reported for `fd`.
+    ///
+    /// This sets `SO_RCVLOWAT`, so it only works for sockets.
+    pub fn set_low_water_mark(&self, fd: RawFd, bytes: usize) -> io::Result<()> {
+        log::trace!(
+            "set_low_water_mark: epoll_fd={}, fd={}, bytes={}",
+            self.epoll_fd.as_raw_fd(),
+            fd,
+            bytes
+        );
+
+        crate::sockopt::set_recv_low_water_mark(fd, bytes)
+    }
+
+    /// Adds a child process that reports a readable event with `key` when it exits.
+    ///
+    /// This registers a pidfd for the process, which becomes readable when it exits.
+    #[cfg(target_os = "linux")]
+    pub fn add_process(&self, child: &mut Child, key: usize) -> io::Result<()> {
+        use rustix::process::{pidfd_open, Pid, PidfdFlags};
+
+        log::trace!(
+            "add_process: epoll_fd={}, pid={}, key={}",
+            self.epoll_fd.as_raw_fd(),
+            child.id(),
+            key
+        );
+
+        // The process ID of a child that was reaped may belong to another process by now.
+        if child.try_wait()?.is_some() {
+            return Err(io::Error::from_raw_os_error(libc::ESRCH));
+        }
+
+        let mut processes = self.processes.lock().unwrap_or_else(|e| e.into_inner());
+        if processes.contains_key(&child.id()) {
+            return Err(io::Error::from(io::ErrorKind::AlreadyExists));
+        }
+
+        let pidfd = pidfd_open(Pid::from_child(child), PidfdFlags::empty())?;
+        self.add(
+            pidfd.as_raw_fd(),
+            Event::readable(key).into(),
+            PollMode::Oneshot,
+        )?;
+        processes.insert(child.id(), pidfd);
+        Ok(())
+    }
+
+    /// Adds a child process that reports a readable event with `key` when it exits.
+    #[cfg(not(target_os = "linux"))]
+    pub fn add_process(&self, child: &mut Child, key: usize) -> io::Result<()> {
+        let _ = (child, key);
+        Err(crate::unsupported_error(
+            "watching processes needs pidfds, which are only available on Linux",
+        ))
+    }
+
+    /// Removes a child process added with `add_process()`.
+    pub fn delete_process(&self, child: &Child) -> io::Result<()> {
+        log::trace!(
+            "delete_process: epoll_fd={}, pid={}",
+            self.epoll_fd.as_raw_fd(),
+            child.id()
+        );
+
+        // Closing the pidfd removes it from the epoll instance.
+        self.processes
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(&child.id());
+        Ok(())
+    }
+
+    /// Adds a signal that reports a readable event with `key` when it is delivered.
+    ///
+    /// Each signal has its own signalfd, which is read by `wait()` when it reports the signal.
+    pub fn add_signal(&self, signum: c_int, key: usize) -> io::Result<()> {
+        log::trace!(
+            "add_signal: epoll_fd={}, signum={}, key={}",
+            self.epoll_fd.as_raw_fd(),
+            signum,
+            key
+        );
+
+        let mut signals = self.signals.lock().unwrap_or_else(|e| e.into_inner());
+        let res = self.add_signal_locked(&mut signals, signum, key);
+        self.has_signals
+            .store(!signals.is_empty(), Ordering::SeqCst);
+        res
+    }
+
+    /// Adds a signal while `signals` is locked, so that `wait()` sees it before its events.
+    fn add_signal_locked(
+        &self,
+        signals: &mut HashMap<c_int, (usize, OwnedFd)>,
+        signum: c_int,
+        key: usize,
+    ) -> io::Result<()> {
+        self.has_signals.store(true, Ordering::SeqCst);
+        if let Some(&mut (ref mut old_key, ref signal_fd)) = signals.get_mut(&signum) {
+            self.modify(
+                signal_fd.as_raw_fd(),
+                Event::readable(key).into(),
+                PollMode::Edge,
+            )?;
+            *old_key = key;
+            return Ok(());
+        }
+
+        let mut mask = MaybeUninit::<libc::sigset_t>::uninit();
+        let signal_fd = unsafe {
+            libc::sigemptyset(mask.as_mut_ptr());
+            if libc::sigaddset(mask.as_mut_ptr(), signum) == -1 {
+                return Err(io::Error::last_os_error());
+            }
+
+            // Keep the `CLOEXEC` flag of the epoll instance.
+            let mut flags = libc::SFD_NONBLOCK;
+            if fcntl_getfd(&self.epoll_fd)?.contains(FdFlags::CLOEXEC) {
+                flags |= libc::SFD_CLOEXEC;
+            }
+            let fd = libc::signalfd(-1, mask.as_ptr(), flags);
+            if fd == -1 {
+                return Err(io::Error::last_os_error());
+            }
+            OwnedFd::from_raw_fd(fd)
+        };
+
+        self.add(
+            signal_fd.as_raw_fd(),
+            Event::readable(key).into(),
+            PollMode::Edge,
+        )?;
+        signals.insert(signum, (key, signal_fd));
         Ok(())
     }
 
-    /// Waits for I/O events with an optional timeout.
+    /// Removes a signal added with `add_signal()`.
+    pub fn delete_signal(&self, signum: c_int) -> io::Result<()> {
+        log::trace!(
+            "delete_signal: epoll_fd={}, signum={}",
+            self.epoll_fd.as_raw_fd(),
+            signum
+        );
+
+        // Closing the signalfd removes it from the epoll instance.
+        let mut signals = self.signals.lock().unwrap_or_else(|e| e.into_inner());
+        signals.remove(&signum);
+        self.has_signals
+            .store(!signals.is_empty(), Ordering::SeqCst);
+        Ok(())
+    }
+
+    /// Adds a timer that reports a readable event with `key` after `interval`, and then every
+    /// `interval` if `repeat` is set.
+    ///
+    /// Each timer has its own timerfd, which is read by `wait()` when it reports the timer.
     #[allow(clippy::needless_update)]
-    pub fn wait(&self, events: &mut Events, timeout: Option<Duration>) -> io::Result<()> {
+    pub fn add_timer(&self, key: usize, interval: Duration, repeat: bool) -> io::Result<()> {
+        log::trace!(
+            "add_timer: epoll_fd={}, key={}, interval={:?}, repeat={}",
+            self.epoll_fd.as_raw_fd(),
+            key,
+            interval,
+            repeat
+        );
+
+        // Events of timers and signals with the same key couldn't be told apart.
+        if self
+            .signals
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .values()
+            .any(|&(signal_key, _)| signal_key == key)
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "the key is used by a signal",
+            ));
+        }
+
+        // Keep the `CLOEXEC` flag of the epoll instance.
+        let timerfd_flags = if fcntl_getfd(&self.epoll_fd)?.contains(FdFlags::CLOEXEC) {
+            TimerfdFlags::CLOEXEC
+        } else {
+            TimerfdFlags::empty()
+        };
+        let timer_fd = timerfd_create(
+            TimerfdClockId::Monotonic,
+            timerfd_flags | TimerfdFlags::NONBLOCK,
+        )?;
+
+        // A zero value disarms the timer, so expire after a nanosecond instead.
+        let interval = interval.max(Duration::from_nanos(1));
+        let mut value = TS_ZERO;
+        value.tv_sec = interval.as_secs() as libc::time_t;
+        value.tv_nsec = (interval.subsec_nanos() as libc::c_long).into();
+        let new_val = Itimerspec {
+            it_interval: if repeat { value } else { TS_ZERO },
+            it_value: value,
+            ..unsafe { std::mem::zeroed() }
+        };
+        timerfd_settime(&timer_fd, TimerfdTimerFlags::empty(), &new_val)?;
+
+        let mode = if repeat {
+            PollMode::Edge
+        } else {
+            PollMode::EdgeOneshot
+        };
+
+        // Keep `timers` locked, so that `wait()` sees the timer before its events.
+        let mut timers = self.timers.lock().unwrap_or_else(|e| e.into_inner());
+        self.has_timers.store(true, Ordering::SeqCst);
+        let res = self.add(timer_fd.as_raw_fd(), Event::readable(key).into(), mode);
+        if res.is_ok() {
+            // Replace the previous timer with this key, which closes its timerfd.
+            timers.insert(key, (timer_fd, repeat));
+        }
+        self.has_timers.store(!timers.is_empty(), Ordering::SeqCst);
+        res
+    }
+
+    /// Removes a timer added with `add_timer()`.
+    pub fn delete_timer(&self, key: usize) -> io::Result<()> {
+        log::trace!(
+            "delete_timer: epoll_fd={}, key={}",
+            self.epoll_fd.as_raw_fd(),
+            key
+        );
+
+        // Closing the timerfd removes it from the epoll instance.
+        let mut timers = self.timers.lock().unwrap_or_else(|e| e.into_inner());
+        timers.remove(&key);
+        self.has_timers.store(!timers.is_empty(), Ordering::SeqCst);
+        Ok(())
+    }
+
+    /// Waits for I/O events with an optional timeout, storing at most `max` of them.
+    ///
+    /// `max` is at most the capacity of `events`.
+    pub fn wait(
+        &self,
+        events: &mut Events,
+        max: usize,
+        timeout: Option<Duration>,
+    ) -> io::Result<()> {
         log::trace!(
             "wait: epoll_fd={}, timeout={:?}",
             self.epoll_fd.as_raw_fd(),
             timeout
         );
 
-        if let Some(ref timer_fd) = self.timer_fd {
+        let shared = self.shared.load(Ordering::SeqCst);
+
+        // Wait for I/O events.
+        //
+        // This calls `epoll_pwait2` or `epoll_wait` directly so that the raw `epoll_event`s can be
+        // exposed.
+        let res = if self.pwait2 {
+            epoll_pwait2(&self.epoll_fd, events, max, timeout)
+        } else {
+            self.epoll_wait(events, max, timeout, shared)?
+        };
+        if res == -1 {
+            events.list.clear();
+            return Err(io::Error::last_os_error());
+        }
+        unsafe {
+            events.list.set_len(res as usize);
+        }
+        log::trace!(
+            "new events: epoll_fd={}, res={}",
+            self.epoll_fd.as_raw_fd(),
+            events.list.len()
+        );
+
+        // A periodic timerfd only restarts once it is read. Reading also tells whether the timer
+        // fired, and a timer that doesn't repeat is closed once it did.
+        let mut buf = [0u8; 8];
+        if !events.list.is_empty() && self.has_timers.load(Ordering::SeqCst) {
+            let mut timers = self.timers.lock().unwrap_or_else(|e| e.into_inner());
+            for ev in &events.list {
+                let key = ev.u64 as usize;
+                let fired = match timers.get(&key) {
+                    Some((timer_fd, repeat)) => read(timer_fd, &mut buf).is_ok() && !repeat,
+                    None => false,
+                };
+                if fired {
+                    timers.remove(&key);
+                }
+            }
+            self.has_timers.store(!timers.is_empty(), Ordering::SeqCst);
+        }
+
+        // Consume the signals that were reported, so that they are no longer pending.
+        if !events.list.is_empty() && self.has_signals.load(Ordering::SeqCst) {
+            let signals = self.signals.lock().unwrap_or_else(|e| e.into_inner());
+            for ev in &events.list {
+                let key = ev.u64 as usize;
+                for (_, signal_fd) in signals.values().filter(|(k, _)| *k == key) {
+                    let mut info = [0u8; 128];
+                    while read(signal_fd, &mut info).is_ok() {}
+                }
+            }
+        }
+
+        // Clear the notification (if received) and re-register interest in it.
+        let _ = read(&self.event_fd, &mut buf);
+        if shared {
+            return Ok(());
+        }
+        self.modify(
+            self.event_fd.as_raw_fd(),
+            Event::readable(self.notify_key).into(),
+            PollMode::Oneshot,
+        )?;
+        Ok(())
+    }
+
+    /// Waits with `epoll_wait()`, using the timerfd for timeouts finer than a millisecond unless
+    /// the poller is `shared`.
+    ///
+    /// Returns the result of `epoll_wait()`.
+    #[allow(clippy::needless_update)]
+    fn epoll_wait(
+        &self,
+        events: &mut Events,
+        max: usize,
+        timeout: Option<Duration>,
+        shared: bool,
+    ) -> io::Result<c_int> {
+        let timer_fd = self.timer_fd.as_ref().filter(|_| !shared);
+
+        if let Some(timer_fd) = timer_fd {
             // Configure the timeout using timerfd.
             let new_val = Itimerspec {
                 it_interval: TS_ZERO,
@@ -166,17 +859,13 @@ impl Poller {
             // Set interest in timerfd.
             self.modify(
                 timer_fd.as_raw_fd(),
-                Event {
-                    key: crate::NOTIFY_KEY,
-                    readable: true,
-                    writable: false,
-                },
+                Event::readable(self.notify_key).into(),
                 PollMode::Oneshot,
             )?;
         }
 
         // Timeout in milliseconds for epoll.
-        let timeout_ms = match (&self.timer_fd, timeout) {
+        let timeout_ms = match (timer_fd, timeout) {
             (_, Some(t)) if t == Duration::from_secs(0) => 0,
             (None, Some(t)) => {
                 // Round up to a whole millisecond.
@@ -189,27 +878,14 @@ impl Poller {
             _ => -1,
         };
 
-        // Wait for I/O events.
-        epoll::epoll_wait(&self.epoll_fd, &mut events.list, timeout_ms)?;
-        log::trace!(
-            "new events: epoll_fd={}, res={}",
-            self.epoll_fd.as_raw_fd(),
-            events.list.len()
-        );
-
-        // Clear the notification (if received) and re-register interest in it.
-        let mut buf = [0u8; 8];
-        let _ = read(&self.event_fd, &mut buf);
-        self.modify(
-            self.event_fd.as_raw_fd(),
-            Event {
-                key: crate::NOTIFY_KEY,
-                readable: true,
-                writable: false,
-            },
-            PollMode::Oneshot,
-        )?;
-        Ok(())
+        Ok(unsafe {
+            libc::epoll_wait(
+                self.epoll_fd.as_raw_fd(),
+                events.list.as_mut_ptr(),
+                max.try_into().unwrap_or(std::i32::MAX),
+                timeout_ms,
+            )
+        })
     }
 
     /// Sends a notification to wake up the current or next `wait()` call.
@@ -248,23 +924,184 @@ impl Drop for Poller {
             self.timer_fd
         );
 
-        if let Some(timer_fd) = self.timer_fd.take() {
-            let _ = self.delete(timer_fd.as_raw_fd());
-        }
-        let _ = self.delete(self.event_fd.as_raw_fd());
+        self.deregister_notify();
+    }
+}
+
+/// Whether `epoll_pwait2()` is available: `UNKNOWN` until a poller that may use it is created.
+static EPOLL_PWAIT2: AtomicU8 = AtomicU8::new(UNKNOWN);
+const UNKNOWN: u8 = 0;
+const AVAILABLE: u8 = 1;
+const UNAVAILABLE: u8 = 2;
+
+/// Whether `EPOLLEXCLUSIVE` is supported: `UNKNOWN` until it is first needed.
+static EPOLL_EXCLUSIVE: AtomicU8 = AtomicU8::new(UNKNOWN);
+
+/// Whether the kernel supports `EPOLLEXCLUSIVE`, which was added in Linux 4.5.
+fn supports_exclusive() -> bool {
+    match EPOLL_EXCLUSIVE.load(Ordering::Relaxed) {
+        AVAILABLE => true,
+        UNAVAILABLE => false,
+        _ => match probe_exclusive() {
+            Ok(available) => {
+                let state = if available { AVAILABLE } else { UNAVAILABLE };
+                EPOLL_EXCLUSIVE.store(state, Ordering::Relaxed);
+                available
+            }
+            // Try again next time, the probe may have run out of file descriptors.
+            Err(_) => false,
+        },
     }
 }
 
+/// Probes for `EPOLLEXCLUSIVE` with a new epoll instance and eventfd.
+///
+/// Older kernels ignore the flag, so this checks whether modifying an exclusive registration
+/// fails, like it does on the kernels that support it.
+fn probe_exclusive() -> io::Result<bool> {
+    let epoll_fd = epoll::epoll_create(epoll::CreateFlags::CLOEXEC)?;
+    let event_fd = eventfd(0, EventfdFlags::CLOEXEC | EventfdFlags::NONBLOCK)?;
+    epoll::epoll_add(
+        &epoll_fd,
+        &event_fd,
+        0,
+        epoll::EventFlags::IN | epoll::EventFlags::EXCLUSIVE,
+    )?;
+    match epoll::epoll_mod(&epoll_fd, &event_fd, 0, epoll::EventFlags::IN) {
+        Err(rustix::io::Errno::INVAL) => Ok(true),
+        res => res.map(|()| false).map_err(Into::into),
+    }
+}
+
+/// The number of the `epoll_pwait2()` system call, which older versions of `libc` don't have.
+#[cfg(target_arch = "mips")]
+const SYS_EPOLL_PWAIT2: libc::c_long = 4441;
+#[cfg(all(target_arch = "mips64", target_pointer_width = "64"))]
+const SYS_EPOLL_PWAIT2: libc::c_long = 5441;
+#[cfg(all(target_arch = "mips64", target_pointer_width = "32"))]
+const SYS_EPOLL_PWAIT2: libc::c_long = 6441;
+#[cfg(not(any(target_arch = "mips", target_arch = "mips64")))]
+const SYS_EPOLL_PWAIT2: libc::c_long = 441;
+
+/// The `struct __kernel_timespec` that `epoll_pwait2()` takes, which has 64-bit fields on every
+/// architecture.
+#[repr(C)]
+struct KernelTimespec {
+    tv_sec: i64,
+    tv_nsec: i64,
+}
+
+/// Whether the kernel supports `epoll_pwait2()`, which is checked once with `epoll_fd`.
+///
+/// This is never the case before Linux 5.11 and where seccomp filters reject the system call. It
+/// is only checked on Linux, because Android kills processes that make system calls it doesn't
+/// allow.
+fn supports_pwait2(epoll_fd: RawFd) -> bool {
+    match EPOLL_PWAIT2.load(Ordering::Relaxed) {
+        AVAILABLE => return true,
+        UNAVAILABLE => return false,
+        _ => {}
+    }
+
+    #[cfg(target_os = "linux")]
+    let available = {
+        // No events fit in the list, so the call fails without waiting or taking any events.
+        let res = unsafe {
+            libc::syscall(
+                SYS_EPOLL_PWAIT2,
+                epoll_fd,
+                ptr::null_mut::<libc::epoll_event>(),
+                0,
+                ptr::null::<KernelTimespec>(),
+                ptr::null::<libc::sigset_t>(),
+                0usize,
+            )
+        };
+        let errno = io::Error::last_os_error().raw_os_error();
+        res == -1 && errno == Some(libc::EINVAL)
+    };
+    #[cfg(not(target_os = "linux"))]
+    let available = {
+        let _ = epoll_fd;
+        false
+    };
+
+    log::trace!("epoll_pwait2: available={}", available);
+    let state = if available { AVAILABLE } else { UNAVAILABLE };
+    EPOLL_PWAIT2.store(state, Ordering::Relaxed);
+    available
+}
+
+/// Waits with `epoll_pwait2()`, which takes the timeout in nanoseconds instead of milliseconds.
+///
+/// This must only be called once `supports_pwait2()` returned `true`.
+fn epoll_pwait2(
+    epoll_fd: &OwnedFd,
+    events: &mut Events,
+    max: usize,
+    timeout: Option<Duration>,
+) -> c_int {
+    let timeout = timeout.map(|t| KernelTimespec {
+        tv_sec: t.as_secs().min(i64::max_value() as u64) as i64,
+        tv_nsec: t.subsec_nanos().into(),
+    });
+    let res = unsafe {
+        libc::syscall(
+            SYS_EPOLL_PWAIT2,
+            epoll_fd.as_raw_fd(),
+            events.list.as_mut_ptr(),
+            max.try_into().unwrap_or(std::i32::MAX),
+            timeout
+                .as_ref()
+                .map_or(ptr::null(), |t| t as *const KernelTimespec),
+            ptr::null::<libc::sigset_t>(),
+            0usize,
+        )
+    };
+    res as c_int
+}
+
+/// Sets the `CLOEXEC` flag of a file descriptor if it isn't set already.
+fn set_cloexec(fd: &OwnedFd) -> io::Result<()> {
+    let fd_flags = fcntl_getfd(fd)?;
+    if !fd_flags.contains(FdFlags::CLOEXEC) {
+        fcntl_setfd(fd, fd_flags | FdFlags::CLOEXEC)?;
+    }
+    Ok(())
+}
+
+/// Creates the eventfd of a poller, and its timerfd if `timer` is set.
+fn notify_fds(builder: &PollerBuilder, timer: bool) -> io::Result<(OwnedFd, Option<OwnedFd>)> {
+    let (eventfd_flags, timerfd_flags) = if builder.close_on_exec {
+        (EventfdFlags::CLOEXEC, TimerfdFlags::CLOEXEC)
+    } else {
+        (EventfdFlags::empty(), TimerfdFlags::empty())
+    };
+
+    let event_fd = eventfd(0, eventfd_flags | EventfdFlags::NONBLOCK)?;
+    let timer_fd = if timer {
+        timerfd_create(
+            TimerfdClockId::Monotonic,
+            timerfd_flags | TimerfdFlags::NONBLOCK,
+        )
+        .ok()
+    } else {
+        None
+    };
+    Ok((event_fd, timer_fd))
+}
+
 /// `timespec` value that equals zero.
 const TS_ZERO: Timespec = unsafe { std::mem::transmute([0u8; std::mem::size_of::<Timespec>()]) };
 
 /// Get the EPOLL flags for the interest.
-fn epoll_flags(interest: &Event, mode: PollMode) -> epoll::EventFlags {
+fn epoll_flags(interest: &Registration, mode: PollMode) -> epoll::EventFlags {
     let mut flags = match mode {
-        PollMode::Oneshot => epoll::EventFlags::ONESHOT,
+        PollMode::Oneshot | PollMode::Dispatch => epoll::EventFlags::ONESHOT,
         PollMode::Level => epoll::EventFlags::empty(),
         PollMode::Edge => epoll::EventFlags::ET,
         PollMode::EdgeOneshot => epoll::EventFlags::ET | epoll::EventFlags::ONESHOT,
+        PollMode::Exclusive => epoll::EventFlags::EXCLUSIVE,
     };
     if interest.readable {
         flags |= read_flags();
@@ -272,13 +1109,24 @@ fn epoll_flags(interest: &Event, mode: PollMode) -> epoll::EventFlags {
     if interest.writable {
         flags |= write_flags();
     }
+    if interest.priority {
+        flags |= epoll::EventFlags::PRI;
+    }
+    if interest.hup {
+        flags |= epoll::EventFlags::RDHUP;
+    }
+    if mode == PollMode::Exclusive {
+        // `EPOLLEXCLUSIVE` can only be combined with a few other flags.
+        use epoll::EventFlags as Epoll;
+        flags &= Epoll::IN | Epoll::OUT | Epoll::HUP | Epoll::ERR | Epoll::EXCLUSIVE;
+    }
     flags
 }
 
 /// Epoll flags for all possible readability events.
 fn read_flags() -> epoll::EventFlags {
     use epoll::EventFlags as Epoll;
-    Epoll::IN | Epoll::HUP | Epoll::ERR | Epoll::PRI
+    Epoll::IN | Epoll::HUP | Epoll::RDHUP | Epoll::ERR | Epoll::PRI
 }
 
 /// Epoll flags for all possible writability events.
@@ -289,7 +1137,7 @@ fn write_flags() -> epoll::EventFlags {
 
 /// A list of reported I/O events.
 pub struct Events {
-    list: epoll::EventVec,
+    list: Vec<libc::epoll_event>,
 }
 
 unsafe impl Send for Events {}
@@ -297,17 +1145,69 @@ unsafe impl Send for Events {}
 impl Events {
     /// Creates an empty list.
     pub fn new() -> Events {
+        Events::with_capacity(1024)
+    }
+
+    /// Creates an empty list that holds at most `capacity` events per wait.
+    pub fn with_capacity(capacity: usize) -> Events {
         Events {
-            list: epoll::EventVec::with_capacity(1024),
+            list: Vec::with_capacity(capacity),
+        }
+    }
+
+    /// Creates a list from raw events.
+    #[cfg(fuzzing)]
+    pub fn from_raw(list: Vec<crate::sys::RawEvent>) -> Events {
+        Events {
+            list: list
+                .into_iter()
+                .map(crate::sys::RawEvent::into_inner)
+                .collect(),
         }
     }
 
     /// Iterates over I/O events.
-    pub fn iter(&self) -> impl Iterator<Item = Event> + '_ {
-        self.list.iter().map(|(flags, data)| Event {
-            key: data as usize,
-            readable: flags.intersects(read_flags()),
-            writable: flags.intersects(write_flags()),
+    pub fn iter(&self) -> impl Iterator<Item = (Event, EventDetails)> + '_ {
+        self.list.iter().map(|ev| {
+            let flags = epoll::EventFlags::from_bits_truncate(ev.events);
+            let event = Event::new(
+                ev.u64 as usize,
+                flags.intersects(read_flags()),
+                flags.intersects(write_flags()),
+            );
+            let details = EventDetails::new(
+                flags.contains(epoll::EventFlags::ERR),
+                flags.intersects(epoll::EventFlags::HUP | epoll::EventFlags::RDHUP),
+                flags.contains(epoll::EventFlags::PRI),
+            )
+            .with_eof(flags.intersects(epoll::EventFlags::HUP | epoll::EventFlags::RDHUP))
+            .with_write_closed(flags.intersects(epoll::EventFlags::HUP | epoll::EventFlags::ERR));
+            (event, details)
         })
     }
+
+    /// Iterates over the keys of the I/O events.
+    pub fn keys(&self) -> impl Iterator<Item = usize> + '_ {
+        self.list.iter().map(|ev| ev.u64 as usize)
+    }
+
+    /// Clears the list.
+    pub fn clear(&mut self) {
+        self.list.clear();
+    }
+
+    /// The maximum number of events that a single call to `wait` stores.
+    pub fn capacity(&self) -> usize {
+        self.list.capacity()
+    }
+
+    /// Moves the events of another list to the end of this one, leaving the other one empty.
+    pub fn append(&mut self, other: &mut Events) {
+        self.list.append(&mut other.list);
+    }
+
+    /// The raw events reported by epoll.
+    pub fn as_raw_slice(&self) -> &[crate::sys::RawEvent] {
+        crate::sys::RawEvent::from_slice(&self.list)
+    }
 }
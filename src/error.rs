@@ -0,0 +1,83 @@
+//! Errors with information about the failed operation.
+
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+use crate::RawSource;
+
+/// An error from a [`Poller`] operation, with information about the operation that failed.
+///
+/// This is returned by the `*_detailed` methods of [`Poller`], such as
+/// [`add_detailed()`][`Poller::add_detailed()`]. It can be converted into an [`io::Error`] with
+/// the same [`io::ErrorKind`].
+///
+/// [`Poller`]: crate::Poller
+/// [`Poller::add_detailed()`]: crate::Poller::add_detailed()
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum PollerError {
+    /// Adding or modifying a file descriptor or socket failed.
+    Register {
+        /// The file descriptor or socket.
+        fd: RawSource,
+        /// The underlying error.
+        source: io::Error,
+    },
+
+    /// Waiting for events failed.
+    Wait(io::Error),
+
+    /// Notifying the poller failed.
+    Notify(io::Error),
+
+    /// Removing a file descriptor or socket failed.
+    Deregister {
+        /// The file descriptor or socket.
+        fd: RawSource,
+        /// The underlying error.
+        source: io::Error,
+    },
+}
+
+impl PollerError {
+    /// The underlying I/O error.
+    pub fn io_error(&self) -> &io::Error {
+        match self {
+            PollerError::Register { source, .. } | PollerError::Deregister { source, .. } => source,
+            PollerError::Wait(err) | PollerError::Notify(err) => err,
+        }
+    }
+
+    /// The kind of the underlying I/O error.
+    pub fn kind(&self) -> io::ErrorKind {
+        self.io_error().kind()
+    }
+}
+
+impl fmt::Display for PollerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PollerError::Register { fd, source } => {
+                write!(f, "failed to register {:?}: {}", fd, source)
+            }
+            PollerError::Wait(err) => write!(f, "failed to wait for events: {}", err),
+            PollerError::Notify(err) => write!(f, "failed to notify the poller: {}", err),
+            PollerError::Deregister { fd, source } => {
+                write!(f, "failed to deregister {:?}: {}", fd, source)
+            }
+        }
+    }
+}
+
+impl Error for PollerError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(self.io_error())
+    }
+}
+
+impl From<PollerError> for io::Error {
+    fn from(err: PollerError) -> io::Error {
+        io::Error::new(err.kind(), err)
+    }
+}
@@ -0,0 +1,195 @@
+//! Events with 64-bit keys.
+
+use std::convert::TryFrom;
+use std::io;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+
+use crate::{Event, PollMode, Poller, Source};
+
+/// An [`Event`] with a 64-bit key.
+///
+/// The keys of [`Event`] are `usize`, which is only 32 bits wide on 32-bit platforms. Sources
+/// added with [`Poller::add64()`] can use any `u64` as their key, and their events are reported
+/// with the full key by [`Poller::wait64()`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Event64 {
+    /// Key identifying the file descriptor or socket.
+    pub key: u64,
+    /// Can it do a read operation without blocking?
+    pub readable: bool,
+    /// Can it do a write operation without blocking?
+    pub writable: bool,
+}
+
+impl Event64 {
+    /// All kinds of events (readable and writable).
+    ///
+    /// Equivalent to: `Event64 { key, readable: true, writable: true }`
+    pub fn all(key: u64) -> Event64 {
+        Event64 {
+            key,
+            readable: true,
+            writable: true,
+        }
+    }
+
+    /// Only the readable event.
+    ///
+    /// Equivalent to: `Event64 { key, readable: true, writable: false }`
+    pub fn readable(key: u64) -> Event64 {
+        Event64 {
+            key,
+            readable: true,
+            writable: false,
+        }
+    }
+
+    /// Only the writable event.
+    ///
+    /// Equivalent to: `Event64 { key, readable: false, writable: true }`
+    pub fn writable(key: u64) -> Event64 {
+        Event64 {
+            key,
+            readable: false,
+            writable: true,
+        }
+    }
+
+    /// No events.
+    ///
+    /// Equivalent to: `Event64 { key, readable: false, writable: false }`
+    pub fn none(key: u64) -> Event64 {
+        Event64 {
+            key,
+            readable: false,
+            writable: false,
+        }
+    }
+}
+
+impl From<Event> for Event64 {
+    fn from(ev: Event) -> Event64 {
+        Event64 {
+            key: ev.key as u64,
+            readable: ev.readable,
+            writable: ev.writable,
+        }
+    }
+}
+
+impl Poller {
+    /// Adds a file descriptor or socket to the poller with a 64-bit key.
+    ///
+    /// This is identical to [`add_with_mode()`][`Poller::add_with_mode()`], but the key is a
+    /// `u64` even on 32-bit platforms. Events of sources added this way should be collected with
+    /// [`wait64()`][`Poller::wait64()`].
+    ///
+    /// Keys that fit into a `usize` are passed to the operating system as they are. Otherwise,
+    /// the source is registered under the lower bits of the key, and the full key is kept in a
+    /// table until the source is deleted. An error of kind [`io::ErrorKind::AlreadyExists`] is
+    /// returned if another source added with this method is already registered under the same
+    /// lower bits. Sources added with other methods are only checked if they are tracked with
+    /// [`PollerBuilder::track_sources()`][`crate::PollerBuilder::track_sources()`].
+    ///
+    /// The table is used with every backend, including kqueue on Apple platforms, where
+    /// `kevent64()` could carry the full key instead. Only the 32-bit watchOS targets have keys
+    /// that don't fit into a `usize` there, so they share the portable implementation.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use polling::{Event64, PollMode, Poller};
+    /// use std::net::TcpListener;
+    ///
+    /// let socket = TcpListener::bind("127.0.0.1:0")?;
+    /// socket.set_nonblocking(true)?;
+    /// let key = 1 << 40;
+    ///
+    /// let poller = Poller::new()?;
+    /// poller.add64(&socket, Event64::readable(key), PollMode::Oneshot)?;
+    /// # poller.delete(&socket)?;
+    /// # std::io::Result::Ok(())
+    /// ```
+    pub fn add64(&self, source: impl Source, interest: Event64, mode: PollMode) -> io::Result<()> {
+        let fd = source.raw();
+        let key = match usize::try_from(interest.key) {
+            Ok(key) => return self.add_with_mode(fd, event(key, interest), mode),
+            // Only reachable on platforms where `usize` is narrower than 64 bits.
+            Err(_) => interest.key as usize,
+        };
+
+        let mut keys64 = self.keys64.lock().unwrap_or_else(|e| e.into_inner());
+        let taken = keys64.contains_key(&key)
+            || self.sources.as_ref().map_or(false, |sources| {
+                sources
+                    .lock()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .values()
+                    .any(|(ev, _)| ev.key == key)
+            });
+        if taken {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                "another source is registered under the lower bits of the key",
+            ));
+        }
+
+        self.add_with_mode(fd, event(key, interest), mode)?;
+        keys64.insert(key, (fd, interest.key));
+        self.has_keys64.store(true, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Waits for at least one I/O event and returns the number of new events.
+    ///
+    /// This is identical to [`wait()`][`Poller::wait()`], but the events carry the 64-bit keys
+    /// of the sources added with [`add64()`][`Poller::add64()`]. Events of other sources are
+    /// reported with their key converted to a `u64`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use polling::{Event64, PollMode, Poller};
+    /// use std::net::TcpListener;
+    /// use std::time::Duration;
+    ///
+    /// let socket = TcpListener::bind("127.0.0.1:0")?;
+    /// socket.set_nonblocking(true)?;
+    ///
+    /// let poller = Poller::new()?;
+    /// poller.add64(&socket, Event64::readable(1 << 40), PollMode::Oneshot)?;
+    ///
+    /// let mut events = Vec::new();
+    /// poller.wait64(&mut events, Some(Duration::from_millis(10)))?;
+    /// # poller.delete(&socket)?;
+    /// # std::io::Result::Ok(())
+    /// ```
+    pub fn wait64(
+        &self,
+        events: &mut Vec<Event64>,
+        timeout: Option<Duration>,
+    ) -> io::Result<usize> {
+        let mut batch = Vec::new();
+        let n = self.wait(&mut batch, timeout)?;
+
+        if !self.has_keys64.load(Ordering::Relaxed) {
+            events.extend(batch.into_iter().map(Event64::from));
+            return Ok(n);
+        }
+
+        let keys64 = self.keys64.lock().unwrap_or_else(|e| e.into_inner());
+        events.extend(batch.into_iter().map(|ev| Event64 {
+            key: keys64.get(&ev.key).map_or(ev.key as u64, |&(_, key)| key),
+            readable: ev.readable,
+            writable: ev.writable,
+        }));
+        Ok(n)
+    }
+}
+
+/// The event passed to the operating system for `interest`.
+fn event(key: usize, interest: Event64) -> Event {
+    Event::new(key, interest.readable, interest.writable)
+}
@@ -0,0 +1,43 @@
+//! Hooks for the fuzz targets in `fuzz/`.
+//!
+//! This module is only available with `--cfg fuzzing`, which `cargo fuzz` sets, and is not part
+//! of the public API.
+
+use crate::{platform, sys, Events, NOTIFY_KEY};
+
+/// Creates a list of events from raw events, as if they had been reported by the backend.
+pub fn events_from_raw(raw: Vec<sys::RawEvent>) -> Events {
+    Events {
+        events: platform::Events::from_raw(raw),
+        notify_key: NOTIFY_KEY,
+        woken: Vec::new(),
+        merged: None,
+        origins: Vec::new(),
+        notified: false,
+        interrupted: false,
+        notify_count: 0,
+    }
+}
+
+/// Creates a raw kqueue event from the fields of a `kevent`.
+#[cfg(any(
+    target_os = "macos",
+    target_os = "ios",
+    target_os = "tvos",
+    target_os = "watchos",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd",
+    target_os = "dragonfly",
+))]
+#[cfg(not(polling_test_poll_backend))]
+pub fn kevent(
+    ident: usize,
+    filter: i16,
+    flags: u16,
+    fflags: u32,
+    data: i64,
+    udata: usize,
+) -> sys::RawEvent {
+    sys::RawEvent::from_fields(ident, filter, flags, fflags, data, udata)
+}
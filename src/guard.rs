@@ -0,0 +1,78 @@
+//! Guards that remove a source from the poller when dropped.
+
+use std::fmt;
+use std::io;
+use std::ops::Deref;
+
+use crate::{Event, PollMode, Poller, RawSource, Source};
+
+/// A file descriptor or socket that is registered in a [`Poller`].
+///
+/// This is returned by [`Poller::add_watching()`] and [`Poller::register()`]. The source is
+/// removed from the poller when the guard is dropped, unless
+/// [`disarm()`][`WatchGuard::disarm()`] is called.
+///
+/// The guard dereferences to the source it was created with.
+pub struct WatchGuard<'a, S: Source> {
+    poller: &'a Poller,
+    source: S,
+    armed: bool,
+}
+
+impl<'a, S: Source> WatchGuard<'a, S> {
+    pub(crate) fn new(poller: &'a Poller, source: S) -> Self {
+        WatchGuard {
+            poller,
+            source,
+            armed: true,
+        }
+    }
+
+    /// The poller that the source is registered in.
+    pub fn poller(&self) -> &'a Poller {
+        self.poller
+    }
+
+    /// Modifies the interest in the source.
+    ///
+    /// This is identical to [`Poller::modify_with_mode()`] for the registered source.
+    pub fn modify(&self, interest: Event, mode: PollMode) -> io::Result<()> {
+        self.poller
+            .modify_with_mode(self.source.raw(), interest, mode)
+    }
+
+    /// Keeps the source registered in the poller and returns its raw file descriptor or socket.
+    ///
+    /// The source must then be removed with [`Poller::delete()`] once it is no longer used.
+    pub fn disarm(mut self) -> RawSource {
+        self.armed = false;
+        self.source.raw()
+    }
+}
+
+impl<S: Source> Deref for WatchGuard<'_, S> {
+    type Target = S;
+
+    fn deref(&self) -> &S {
+        &self.source
+    }
+}
+
+impl<S: Source> fmt::Debug for WatchGuard<'_, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WatchGuard")
+            .field("source", &self.source.raw())
+            .field("armed", &self.armed)
+            .finish()
+    }
+}
+
+impl<S: Source> Drop for WatchGuard<'_, S> {
+    fn drop(&mut self) {
+        if self.armed {
+            if let Err(err) = self.poller.delete(self.source.raw()) {
+                log::debug!("WatchGuard: failed to delete source: {}", err);
+            }
+        }
+    }
+}
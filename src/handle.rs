@@ -0,0 +1,444 @@
+//! Handles that change the sources of a poller from other threads.
+
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
+use std::sync::atomic::{self, AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex, Weak};
+
+use crate::split::{self, Splits};
+use crate::{
+    check_source, is_not_registered, platform, shut_down_error, Event, NotifyHook, PollMode,
+    Poller, RawSource, Registration, Source, Sources,
+};
+
+/// A handle to a [`Poller`] that can add, modify and remove sources and send notifications, but
+/// not wait.
+///
+/// Handles are created by [`Poller::handle()`]. They are cheap to clone and can be moved to
+/// other threads, so that the thread that waits can own the [`Poller`] without wrapping it in an
+/// [`Arc`]. Events can only be waited for through the [`Poller`] itself.
+///
+/// A handle doesn't keep the poller alive: once the [`Poller`] is dropped, or its instance is
+/// released with `into_raw_fd()`, the methods of its handles return an error of kind
+/// [`io::ErrorKind::NotConnected`].
+///
+/// # Examples
+///
+/// ```
+/// use polling::{Event, Poller};
+/// use std::net::TcpListener;
+/// use std::thread;
+///
+/// let poller = Poller::new()?;
+/// let handle = poller.handle();
+///
+/// thread::spawn(move || -> std::io::Result<()> {
+///     let socket = TcpListener::bind("127.0.0.1:0")?;
+///     socket.set_nonblocking(true)?;
+///     handle.add(&socket, Event::readable(7))?;
+///     handle.delete(&socket)?;
+///     handle.notify()
+/// })
+/// .join()
+/// .unwrap()?;
+///
+/// let mut events = Vec::new();
+/// poller.wait(&mut events, None)?;
+/// # std::io::Result::Ok(())
+/// ```
+#[derive(Clone)]
+pub struct PollerHandle {
+    poller: Weak<platform::Poller>,
+    notified: Arc<AtomicBool>,
+    notify_count: Option<Arc<AtomicUsize>>,
+    shut_down: Arc<AtomicBool>,
+    notify_key: usize,
+    sources: Option<Arc<Sources>>,
+    keys64: Arc<Mutex<HashMap<usize, (RawSource, u64)>>>,
+    has_keys64: Arc<AtomicBool>,
+    splits: Arc<Mutex<Splits>>,
+    has_splits: Arc<AtomicBool>,
+    woken: Arc<Mutex<Vec<(usize, usize)>>>,
+    notify_hook: Arc<Mutex<Option<Arc<NotifyHook>>>>,
+    release: Arc<Release>,
+}
+
+impl PollerHandle {
+    /// Adds a file descriptor or socket to the poller, in oneshot mode.
+    ///
+    /// This is identical to [`Poller::add()`].
+    pub fn add(&self, source: impl Source, interest: Event) -> io::Result<()> {
+        self.add_with_mode(source, interest, PollMode::Oneshot)
+    }
+
+    /// Adds a file descriptor or socket to the poller in the specified mode.
+    ///
+    /// This is identical to [`Poller::add_with_mode()`].
+    pub fn add_with_mode(
+        &self,
+        source: impl Source,
+        interest: Event,
+        mode: PollMode,
+    ) -> io::Result<()> {
+        self.with_registry(|registry| registry.add(source.raw(), interest, mode))
+    }
+
+    /// Modifies the interest in a file descriptor or socket, in oneshot mode.
+    ///
+    /// This is identical to [`Poller::modify()`].
+    pub fn modify(&self, source: impl Source, interest: Event) -> io::Result<()> {
+        self.modify_with_mode(source, interest, PollMode::Oneshot)
+    }
+
+    /// Modifies the interest in a file descriptor or socket in the specified mode.
+    ///
+    /// This is identical to [`Poller::modify_with_mode()`].
+    pub fn modify_with_mode(
+        &self,
+        source: impl Source,
+        interest: Event,
+        mode: PollMode,
+    ) -> io::Result<()> {
+        self.with_registry(|registry| registry.modify(source.raw(), interest, mode))
+    }
+
+    /// Removes a file descriptor or socket from the poller.
+    ///
+    /// This is identical to [`Poller::delete()`].
+    pub fn delete(&self, source: impl Source) -> io::Result<()> {
+        self.with_registry(|registry| registry.delete(source.raw()))
+    }
+
+    /// Shuts the poller down.
+    ///
+    /// This is identical to [`Poller::shutdown()`].
+    pub fn shutdown(&self) -> io::Result<()> {
+        log::trace!("PollerHandle::shutdown()");
+        self.with_registry(|registry| registry.shutdown())
+    }
+
+    /// Wakes up the current or the following wait of the poller.
+    ///
+    /// This is identical to [`Poller::notify()`].
+    pub fn notify(&self) -> io::Result<()> {
+        log::trace!("PollerHandle::notify()");
+        self.with_registry(|registry| registry.notify())
+    }
+
+    /// Reports a readable event with the given key from the current or the following wait of
+    /// the poller.
+    ///
+    /// This is identical to [`Poller::wake()`]. It can be used to wake up the thread that waits
+    /// for one of several reasons, each with its own key.
+    pub fn wake(&self, key: usize) -> io::Result<()> {
+        log::trace!("PollerHandle::wake({})", key);
+        self.with_registry(|registry| registry.wake(key))
+    }
+
+    /// Reports a readable event with the given key and value from the current or the following
+    /// wait of the poller.
+    ///
+    /// This is identical to [`Poller::post_value()`].
+    pub fn post_value(&self, key: usize, value: usize) -> io::Result<()> {
+        log::trace!("PollerHandle::post_value({}, {})", key, value);
+        self.with_registry(|registry| registry.post_value(key, value))
+    }
+
+    /// Runs `f` with the registry of the poller, unless the poller was dropped or released.
+    fn with_registry<T>(&self, f: impl FnOnce(&Registry<'_>) -> io::Result<T>) -> io::Result<T> {
+        let dropped = || io::Error::new(io::ErrorKind::NotConnected, "the poller was dropped");
+        if self.release.released.load(Ordering::SeqCst) {
+            return Err(dropped());
+        }
+        let poller = self.poller.upgrade().ok_or_else(dropped)?;
+
+        // The notify hook runs once the instance is no longer used, so that a hook that blocks
+        // doesn't block `into_raw_fd()`.
+        let hook = Cell::new(false);
+        let result = f(&Registry {
+            poller: &poller,
+            notified: &self.notified,
+            notify_count: self.notify_count.as_deref(),
+            shut_down: &self.shut_down,
+            notify_key: self.notify_key,
+            sources: self.sources.as_deref(),
+            keys64: &self.keys64,
+            has_keys64: &self.has_keys64,
+            splits: &self.splits,
+            has_splits: &self.has_splits,
+            woken: &self.woken,
+            notify_hook: &self.notify_hook,
+            hook_pending: Some(&hook),
+        });
+        drop(poller);
+
+        // Pairs with the fence in `into_raw_fd()`: either it sees that the reference was dropped,
+        // or this sees that the instance is being released.
+        atomic::fence(Ordering::SeqCst);
+        if self.release.released.load(Ordering::SeqCst) {
+            let _guard = self.release.lock.lock().unwrap_or_else(|e| e.into_inner());
+            self.release.unused.notify_all();
+        }
+        if hook.get() {
+            run_notify_hook(&self.notify_hook);
+        }
+        result
+    }
+}
+
+/// Lets `into_raw_fd()` wait for the handles that are using the instance of a poller.
+#[derive(Default)]
+pub(crate) struct Release {
+    /// Set when the instance is released, so that handles stop using it.
+    pub(crate) released: AtomicBool,
+    pub(crate) lock: Mutex<()>,
+    /// Notified when a handle stops using the instance after it was released.
+    pub(crate) unused: Condvar,
+}
+
+impl fmt::Debug for PollerHandle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PollerHandle")
+            .field("backend", &crate::BACKEND)
+            .field("notify_key", &self.notify_key)
+            .finish()
+    }
+}
+
+impl Poller {
+    /// Returns a handle that can add, modify and remove sources and notify this poller from other
+    /// threads.
+    ///
+    /// See [`PollerHandle`] for more information.
+    pub fn handle(&self) -> PollerHandle {
+        PollerHandle {
+            poller: Arc::downgrade(&self.poller),
+            notified: self.notified.clone(),
+            notify_count: self.notify_count.clone(),
+            shut_down: self.shut_down.clone(),
+            notify_key: self.notify_key,
+            sources: self.sources.clone(),
+            keys64: self.keys64.clone(),
+            has_keys64: self.has_keys64.clone(),
+            splits: self.splits.clone(),
+            has_splits: self.has_splits.clone(),
+            woken: self.woken.clone(),
+            notify_hook: self.notify_hook.clone(),
+            release: self.release.clone(),
+        }
+    }
+
+    pub(crate) fn registry(&self) -> Registry<'_> {
+        Registry {
+            poller: &self.poller,
+            notified: &self.notified,
+            notify_count: self.notify_count.as_deref(),
+            shut_down: &self.shut_down,
+            notify_key: self.notify_key,
+            sources: self.sources.as_deref(),
+            keys64: &self.keys64,
+            has_keys64: &self.has_keys64,
+            splits: &self.splits,
+            has_splits: &self.has_splits,
+            woken: &self.woken,
+            notify_hook: &self.notify_hook,
+            hook_pending: None,
+        }
+    }
+}
+
+/// The parts of a poller that are shared with its handles.
+pub(crate) struct Registry<'a> {
+    poller: &'a platform::Poller,
+    notified: &'a AtomicBool,
+    notify_count: Option<&'a AtomicUsize>,
+    shut_down: &'a AtomicBool,
+    notify_key: usize,
+    sources: Option<&'a Sources>,
+    keys64: &'a Mutex<HashMap<usize, (RawSource, u64)>>,
+    has_keys64: &'a AtomicBool,
+    splits: &'a Mutex<Splits>,
+    has_splits: &'a AtomicBool,
+    woken: &'a Mutex<Vec<(usize, usize)>>,
+    notify_hook: &'a Mutex<Option<Arc<NotifyHook>>>,
+    hook_pending: Option<&'a Cell<bool>>,
+}
+
+impl Registry<'_> {
+    pub(crate) fn add(
+        &self,
+        fd: RawSource,
+        interest: impl Into<Registration>,
+        mode: PollMode,
+    ) -> io::Result<()> {
+        let interest = interest.into();
+        if self.shut_down.load(Ordering::SeqCst) {
+            return Err(shut_down_error());
+        }
+        self.check_key(interest)?;
+        check_source(fd)?;
+        self.poller.add(fd, interest, mode)?;
+        self.record(fd, interest, mode);
+        Ok(())
+    }
+
+    /// Adds several sources, after checking all of them, and returns the first error.
+    pub(crate) fn add_many(&self, entries: &[(RawSource, Event, PollMode)]) -> io::Result<()> {
+        if self.shut_down.load(Ordering::SeqCst) {
+            return Err(shut_down_error());
+        }
+        for &(fd, interest, _) in entries {
+            self.check_key(interest.into())?;
+            check_source(fd)?;
+        }
+        self.poller.add_many(entries, |i| {
+            let (fd, interest, mode) = entries[i];
+            self.record(fd, interest.into(), mode);
+        })
+    }
+
+    pub(crate) fn modify(
+        &self,
+        fd: RawSource,
+        interest: impl Into<Registration>,
+        mode: PollMode,
+    ) -> io::Result<()> {
+        let interest = interest.into();
+        self.check_key(interest)?;
+        check_source(fd)?;
+        self.poller.modify(fd, interest, mode)?;
+        self.record(fd, interest, mode);
+        Ok(())
+    }
+
+    pub(crate) fn delete(&self, fd: RawSource) -> io::Result<()> {
+        check_source(fd)?;
+        let res = self.poller.delete(fd);
+
+        // Only forget the source once it's no longer registered, which is also the case if it
+        // was never added or already closed.
+        match &res {
+            Err(err) if !is_not_registered(err) => {}
+            _ => self.forget(fd),
+        }
+        res
+    }
+
+    /// Records the interest and mode of a source, if sources are tracked.
+    pub(crate) fn record(&self, fd: RawSource, interest: Registration, mode: PollMode) {
+        if let Some(sources) = self.sources {
+            sources
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .insert(fd, (interest, mode));
+        }
+    }
+
+    /// Forgets everything that is known about a removed source.
+    fn forget(&self, fd: RawSource) {
+        if let Some(sources) = self.sources {
+            sources
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .remove(&fd);
+        }
+
+        // Forget the full key of a source added with `add64()`.
+        if self.has_keys64.load(Ordering::Relaxed) {
+            self.keys64
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .retain(|_, &mut (source, _)| source != fd);
+        }
+
+        // Forget the keys of a source added with `add_split()`.
+        if self.has_splits.load(Ordering::Relaxed) {
+            split::forget(
+                &mut self.splits.lock().unwrap_or_else(|e| e.into_inner()),
+                fd,
+            );
+        }
+    }
+
+    pub(crate) fn wake(&self, key: usize) -> io::Result<()> {
+        self.post_value(key, 0)
+    }
+
+    pub(crate) fn post_value(&self, key: usize, value: usize) -> io::Result<()> {
+        if key == self.notify_key {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "the key is reserved for notifications",
+            ));
+        }
+
+        self.woken
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push((key, value));
+        self.notify()
+    }
+
+    pub(crate) fn notify(&self) -> io::Result<()> {
+        self.count_notification();
+        if self
+            .notified
+            .compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst)
+            .is_ok()
+        {
+            self.poller.notify()?;
+            self.run_notify_hook();
+        }
+        Ok(())
+    }
+
+    pub(crate) fn shutdown(&self) -> io::Result<()> {
+        if !self.shut_down.swap(true, Ordering::SeqCst) {
+            // Always send a notification, even if one is pending, like `Poller::notify_one()`.
+            self.notified.store(true, Ordering::SeqCst);
+            self.poller.notify()?;
+            self.run_notify_hook();
+        }
+        Ok(())
+    }
+
+    /// Counts a notification, if counting is enabled with
+    /// [`PollerBuilder::count_notifications()`][`crate::PollerBuilder::count_notifications()`].
+    pub(crate) fn count_notification(&self) {
+        if let Some(count) = self.notify_count {
+            count.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    /// Invokes the callback set with [`Poller::set_notify_hook()`].
+    pub(crate) fn run_notify_hook(&self) {
+        match self.hook_pending {
+            Some(pending) => pending.set(true),
+            None => run_notify_hook(self.notify_hook),
+        }
+    }
+
+    fn check_key(&self, interest: Registration) -> io::Result<()> {
+        if interest.key == self.notify_key {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "the key is reserved for notifications",
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Invokes the callback set with [`Poller::set_notify_hook()`].
+fn run_notify_hook(notify_hook: &Mutex<Option<Arc<NotifyHook>>>) {
+    // Don't hold the lock while the hook runs, so that it can replace itself.
+    let hook = notify_hook
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone();
+    if let Some(hook) = hook {
+        hook();
+    }
+}
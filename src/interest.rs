@@ -0,0 +1,245 @@
+//! Interest in readiness, separate from the key of a source.
+
+use std::io;
+use std::ops::BitOr;
+
+use crate::{Event, PollMode, Poller, Source};
+
+/// The kinds of readiness a source is polled for.
+///
+/// Unlike [`Event`], this doesn't include a key, so it can be decided on before the key of the
+/// source is known. It is combined with a key by [`Event::from_interest()`] or
+/// [`Poller::add_with_interest()`].
+///
+/// It can also express interest in priority data and hangups, which [`Event`] can't. The
+/// conditions that are only ever reported by the poller, like
+/// [`EventDetails::is_err()`][`crate::EventDetails::is_err()`], have no interest.
+///
+/// # Examples
+///
+/// ```
+/// use polling::{Event, Interest};
+///
+/// let interest = Interest::READABLE | Interest::WRITABLE;
+/// assert_eq!(interest, Interest::READ_WRITE);
+/// assert_eq!(Event::from_interest(7, interest), Event::all(7));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Interest {
+    /// Interest in read operations that don't block.
+    pub readable: bool,
+    /// Interest in write operations that don't block.
+    pub writable: bool,
+    /// Interest in priority data, such as TCP out-of-band data.
+    ///
+    /// This is waited for with `EPOLLPRI` with epoll, `POLLPRI` with `poll()` and event ports,
+    /// `EVFILT_EXCEPT` with kqueue and the expedited receive condition with IOCP. The kqueue
+    /// backends of FreeBSD and NetBSD don't have `EVFILT_EXCEPT` and wait for readability
+    /// instead. Priority data is reported as a readable event whose details have
+    /// [`EventDetails::is_pri()`][`crate::EventDetails::is_pri()`] set.
+    pub priority: bool,
+    /// Interest in hangups, such as the peer shutting down its side of the connection.
+    ///
+    /// A hangup is reported as a readable event whose details have
+    /// [`EventDetails::is_hup()`][`crate::EventDetails::is_hup()`] set, even without interest in
+    /// readability. This waits for `EPOLLRDHUP` with epoll, `POLLRDHUP` with `poll()` on Linux and
+    /// Android and with event ports on illumos, `EV_EOF` of the read filter with kqueue and the
+    /// disconnect condition with IOCP.
+    ///
+    /// With kqueue, the read filter is added with a low-water mark that data doesn't reach, which
+    /// macOS still reports once the receive buffer is full. `poll()` on other platforms and event
+    /// ports on Solaris only report hangups of both directions, with `POLLHUP`.
+    pub hup: bool,
+}
+
+impl Interest {
+    /// Interest in readability.
+    pub const READABLE: Interest = Interest {
+        readable: true,
+        writable: false,
+        priority: false,
+        hup: false,
+    };
+
+    /// Interest in writability.
+    pub const WRITABLE: Interest = Interest {
+        readable: false,
+        writable: true,
+        priority: false,
+        hup: false,
+    };
+
+    /// Interest in readability and writability.
+    pub const READ_WRITE: Interest = Interest {
+        readable: true,
+        writable: true,
+        priority: false,
+        hup: false,
+    };
+
+    /// Interest in priority data.
+    pub const PRIORITY: Interest = Interest {
+        readable: false,
+        writable: false,
+        priority: true,
+        hup: false,
+    };
+
+    /// Interest in hangups.
+    pub const HUP: Interest = Interest {
+        readable: false,
+        writable: false,
+        priority: false,
+        hup: true,
+    };
+
+    /// Interest in all kinds of readiness.
+    pub fn all() -> Interest {
+        Interest {
+            readable: true,
+            writable: true,
+            priority: true,
+            hup: true,
+        }
+    }
+}
+
+impl From<Event> for Interest {
+    fn from(event: Event) -> Interest {
+        event.interest()
+    }
+}
+
+impl BitOr for Interest {
+    type Output = Interest;
+
+    fn bitor(self, other: Interest) -> Interest {
+        Interest {
+            readable: self.readable || other.readable,
+            writable: self.writable || other.writable,
+            priority: self.priority || other.priority,
+            hup: self.hup || other.hup,
+        }
+    }
+}
+
+impl Event {
+    /// Creates an event with the given key from the readability and writability in `interest`.
+    ///
+    /// Interest in priority data and hangups can't be expressed by an [`Event`]. Add the source
+    /// with [`Poller::add_with_interest()`] to wait for them.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use polling::{Event, Interest};
+    ///
+    /// assert_eq!(Event::from_interest(1, Interest::WRITABLE), Event::writable(1));
+    /// assert_eq!(Event::from_interest(1, Interest::PRIORITY), Event::none(1));
+    /// ```
+    pub fn from_interest(key: usize, interest: Interest) -> Event {
+        Event::new(key, interest.readable, interest.writable)
+    }
+
+    /// The readability and writability of this event, without its key.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use polling::{Event, Interest};
+    ///
+    /// assert_eq!(Event::all(1).interest(), Interest::READ_WRITE);
+    /// assert_eq!(Event::none(1).interest(), Interest::default());
+    /// ```
+    pub fn interest(&self) -> Interest {
+        Interest {
+            readable: self.readable,
+            writable: self.writable,
+            ..Interest::default()
+        }
+    }
+}
+
+/// The key of a source and the readiness it is polled for, as passed to the backends.
+///
+/// This is an [`Event`] together with the interest that only [`Interest`] can express.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Registration {
+    pub(crate) key: usize,
+    pub(crate) readable: bool,
+    pub(crate) writable: bool,
+    pub(crate) priority: bool,
+    pub(crate) hup: bool,
+}
+
+impl Registration {
+    /// Creates a registration with the given key and interest.
+    pub(crate) fn new(key: usize, interest: Interest) -> Registration {
+        Registration {
+            key,
+            readable: interest.readable,
+            writable: interest.writable,
+            priority: interest.priority,
+            hup: interest.hup,
+        }
+    }
+
+    /// A registration with the given key and no interest.
+    pub(crate) fn none(key: usize) -> Registration {
+        Registration::new(key, Interest::default())
+    }
+}
+
+impl From<Event> for Registration {
+    fn from(ev: Event) -> Registration {
+        Registration::new(ev.key, ev.interest())
+    }
+}
+
+impl Poller {
+    /// Adds a file descriptor or socket to the poller with the given key and interest.
+    ///
+    /// This is identical to [`add_with_mode()`][`Poller::add_with_mode()`], but can also wait for
+    /// priority data and hangups.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use polling::{Interest, PollMode, Poller};
+    /// use std::net::TcpListener;
+    ///
+    /// let socket = TcpListener::bind("127.0.0.1:0")?;
+    /// socket.set_nonblocking(true)?;
+    ///
+    /// let poller = Poller::new()?;
+    /// poller.add_with_interest(&socket, 7, Interest::READABLE, PollMode::Oneshot)?;
+    /// # poller.delete(&socket)?;
+    /// # std::io::Result::Ok(())
+    /// ```
+    pub fn add_with_interest(
+        &self,
+        source: impl Source,
+        key: usize,
+        interest: Interest,
+        mode: PollMode,
+    ) -> io::Result<()> {
+        self.registry()
+            .add(source.raw(), Registration::new(key, interest), mode)
+    }
+
+    /// Modifies the key and interest of a file descriptor or socket in the poller.
+    ///
+    /// This is identical to [`modify_with_mode()`][`Poller::modify_with_mode()`], but can also
+    /// wait for priority data and hangups.
+    pub fn modify_with_interest(
+        &self,
+        source: impl Source,
+        key: usize,
+        interest: Interest,
+        mode: PollMode,
+    ) -> io::Result<()> {
+        self.registry()
+            .modify(source.raw(), Registration::new(key, interest), mode)
+    }
+}
@@ -24,15 +24,22 @@
 //!
 //! Previously, this crate used the `wepoll` library for polling. `wepoll` uses a similar
 //! AFD-based strategy for polling.
+//!
+//! Other handles, like events and processes, can't be polled through AFD. Instead, the thread
+//! pool waits for them to be signaled and then posts a packet to the IOCP. Such a wait only
+//! fires once, so level-triggered mode is emulated by starting a new wait every time an event
+//! has been reported.
 
 mod afd;
 mod port;
+mod wait;
 
 use afd::{base_socket, Afd, AfdPollInfo, AfdPollMask, HasAfdInfo, IoStatusBlock};
 use port::{IoCompletionPort, OverlappedEntry};
+use wait::{WaitHandle, WaitableTimer};
 use windows_sys::Win32::Foundation::{ERROR_INVALID_HANDLE, ERROR_IO_PENDING, STATUS_CANCELLED};
 
-use crate::{Event, PollMode};
+use crate::{BackendKind, Event, EventDetails, PollMode, PollerBuilder, Registration};
 
 use concurrent_queue::ConcurrentQueue;
 use pin_project_lite::pin_project;
@@ -44,6 +51,7 @@ use std::io;
 use std::marker::PhantomPinned;
 use std::os::windows::io::{AsRawHandle, RawHandle, RawSocket};
 use std::pin::Pin;
+use std::process::Child;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex, MutexGuard, RwLock, Weak};
 use std::time::{Duration, Instant};
@@ -58,6 +66,9 @@ macro_rules! lock {
     }};
 }
 
+/// The backend implemented by this module.
+pub(crate) const BACKEND: BackendKind = BackendKind::Iocp;
+
 /// Interface to I/O completion ports.
 #[derive(Debug)]
 pub(super) struct Poller {
@@ -73,6 +84,12 @@ pub(super) struct Poller {
     /// The state of the sources registered with this poller.
     sources: RwLock<HashMap<RawSocket, Packet>>,
 
+    /// The state of the waitable handles registered with this poller.
+    waitables: Mutex<HashMap<RawHandle, Packet>>,
+
+    /// The timers added with `add_timer()`, by key.
+    timers: Mutex<HashMap<usize, WaitableTimer>>,
+
     /// Sockets with pending updates.
     pending_updates: ConcurrentQueue<Packet>,
 
@@ -91,7 +108,7 @@ unsafe impl Sync for Poller {}
 
 impl Poller {
     /// Creates a new poller.
-    pub(super) fn new() -> io::Result<Self> {
+    pub(super) fn new(builder: &PollerBuilder) -> io::Result<Self> {
         // Make sure AFD is able to be used.
         if let Err(e) = afd::NtdllImports::force_load() {
             return Err(crate::unsupported_error(format!(
@@ -106,7 +123,7 @@ impl Poller {
             e,
         )))?;
 
-        let port = IoCompletionPort::new(0)?;
+        let port = IoCompletionPort::new(builder.iocp_concurrent_threads as usize)?;
 
         log::trace!("new: handle={:?}", &port);
 
@@ -114,6 +131,8 @@ impl Poller {
             port,
             afd: Mutex::new(vec![]),
             sources: RwLock::new(HashMap::new()),
+            waitables: Mutex::new(HashMap::new()),
+            timers: Mutex::new(HashMap::new()),
             pending_updates: ConcurrentQueue::bounded(1024),
             polling: AtomicBool::new(false),
             packets: Mutex::new(Vec::with_capacity(1024)),
@@ -126,6 +145,21 @@ impl Poller {
         })
     }
 
+    /// Create a new poller that shares the completion port of this one.
+    pub(super) fn try_clone(&self) -> io::Result<Poller> {
+        Err(crate::unsupported_error(
+            "cloning the poller is not supported with IOCP",
+        ))
+    }
+
+    /// Prepares the poller for several threads waiting on it at the same time.
+    pub(super) fn enable_concurrent_wait(&self) -> io::Result<()> {
+        // The completion packets are locked for the duration of a wait.
+        Err(crate::unsupported_error(
+            "concurrent waits are not supported with IOCP",
+        ))
+    }
+
     /// Whether this poller supports level-triggered events.
     pub(super) fn supports_level(&self) -> bool {
         true
@@ -136,8 +170,30 @@ impl Poller {
         false
     }
 
+    /// Whether the poller wakes up only one poller for exclusive registrations.
+    pub(super) fn supports_exclusive(&self) -> bool {
+        false
+    }
+
+    /// The precision of the timeouts of `wait()`.
+    pub(super) fn timeout_granularity(&self) -> Duration {
+        Duration::from_millis(1)
+    }
+
+    /// Checks the internal state of this poller.
+    pub(super) fn check_invariants(&self, _registered: Option<usize>) -> io::Result<()> {
+        // The completion port has no state that could be checked from here, and sockets are
+        // dropped from `sources` as soon as they are closed, so the count can't be compared.
+        Ok(())
+    }
+
     /// Add a new source to the poller.
-    pub(super) fn add(&self, socket: RawSocket, interest: Event, mode: PollMode) -> io::Result<()> {
+    pub(super) fn add(
+        &self,
+        socket: RawSocket,
+        interest: Registration,
+        mode: PollMode,
+    ) -> io::Result<()> {
         log::trace!(
             "add: handle={:?}, sock={}, ev={:?}",
             self.port,
@@ -147,8 +203,7 @@ impl Poller {
 
         // We don't support edge-triggered events.
         if matches!(mode, PollMode::Edge | PollMode::EdgeOneshot) {
-            return Err(io::Error::new(
-                io::ErrorKind::InvalidInput,
+            return Err(crate::unsupported_error(
                 "edge-triggered events are not supported",
             ));
         }
@@ -191,11 +246,56 @@ impl Poller {
         self.update_packet(socket_state)
     }
 
+    /// Add several sockets, returning the result for each of them.
+    pub(super) fn add_batch(
+        &self,
+        sockets: &[(RawSocket, Event, PollMode)],
+    ) -> Vec<io::Result<()>> {
+        sockets
+            .iter()
+            .map(|&(socket, ev, mode)| self.add(socket, ev.into(), mode))
+            .collect()
+    }
+
+    /// Add several sockets, returning the first error.
+    ///
+    /// A failure to add one doesn't stop the others from being added. `added` is called with the
+    /// index of each socket that was added.
+    pub(super) fn add_many(
+        &self,
+        sockets: &[(RawSocket, Event, PollMode)],
+        mut added: impl FnMut(usize),
+    ) -> io::Result<()> {
+        let mut result = Ok(());
+        for (i, &(socket, ev, mode)) in sockets.iter().enumerate() {
+            match self.add(socket, ev.into(), mode) {
+                Ok(()) => added(i),
+                Err(err) => {
+                    if result.is_ok() {
+                        result = Err(err);
+                    }
+                }
+            }
+        }
+        result
+    }
+
+    /// Update several sources in the poller, returning the result for each of them.
+    pub(super) fn modify_batch(
+        &self,
+        sockets: &[(RawSocket, Event, PollMode)],
+    ) -> Vec<io::Result<()>> {
+        sockets
+            .iter()
+            .map(|&(socket, ev, mode)| self.modify(socket, ev.into(), mode))
+            .collect()
+    }
+
     /// Update a source in the poller.
     pub(super) fn modify(
         &self,
         socket: RawSocket,
-        interest: Event,
+        interest: Registration,
         mode: PollMode,
     ) -> io::Result<()> {
         log::trace!(
@@ -207,8 +307,7 @@ impl Poller {
 
         // We don't support edge-triggered events.
         if matches!(mode, PollMode::Edge | PollMode::EdgeOneshot) {
-            return Err(io::Error::new(
-                io::ErrorKind::InvalidInput,
+            return Err(crate::unsupported_error(
                 "edge-triggered events are not supported",
             ));
         }
@@ -231,6 +330,27 @@ impl Poller {
         Ok(())
     }
 
+    /// Stop delivering events for a source without removing it.
+    pub(super) fn disable(
+        &self,
+        socket: RawSocket,
+        interest: Registration,
+        mode: PollMode,
+    ) -> io::Result<()> {
+        // Emulated by clearing the interest.
+        self.modify(socket, Registration::none(interest.key), mode)
+    }
+
+    /// Resume delivering events for a disabled source.
+    pub(super) fn enable(
+        &self,
+        socket: RawSocket,
+        interest: Registration,
+        mode: PollMode,
+    ) -> io::Result<()> {
+        self.modify(socket, interest, mode)
+    }
+
     /// Delete a source from the poller.
     pub(super) fn delete(&self, socket: RawSocket) -> io::Result<()> {
         log::trace!("remove: handle={:?}, sock={}", self.port, socket);
@@ -253,8 +373,192 @@ impl Poller {
         source.begin_delete()
     }
 
-    /// Wait for events.
-    pub(super) fn wait(&self, events: &mut Events, timeout: Option<Duration>) -> io::Result<()> {
+    /// Add a handle that can be waited on to the poller.
+    pub(super) fn add_waitable(
+        &self,
+        handle: RawHandle,
+        interest: Event,
+        mode: PollMode,
+    ) -> io::Result<()> {
+        log::trace!(
+            "add_waitable: handle={:?}, waitable={:?}, ev={:?}",
+            self.port,
+            handle,
+            interest
+        );
+
+        // We don't support edge-triggered events.
+        if matches!(mode, PollMode::Edge | PollMode::EdgeOneshot) {
+            return Err(crate::unsupported_error(
+                "edge-triggered events are not supported",
+            ));
+        }
+
+        let packet: Packet = Arc::pin(IoStatusBlock::from(PacketInner::Waitable {
+            waitable: Mutex::new(WaitableState {
+                handle,
+                interest,
+                mode,
+                wait: None,
+            }),
+        }));
+
+        let mut waitables = lock!(self.waitables.lock());
+        match waitables.entry(handle) {
+            Entry::Vacant(v) => {
+                packet.update_wait(self)?;
+                v.insert(packet);
+                Ok(())
+            }
+            Entry::Occupied(_) => Err(io::Error::from(io::ErrorKind::AlreadyExists)),
+        }
+    }
+
+    /// Update a waitable handle in the poller.
+    pub(super) fn modify_waitable(
+        &self,
+        handle: RawHandle,
+        interest: Event,
+        mode: PollMode,
+    ) -> io::Result<()> {
+        log::trace!(
+            "modify_waitable: handle={:?}, waitable={:?}, ev={:?}",
+            self.port,
+            handle,
+            interest
+        );
+
+        // We don't support edge-triggered events.
+        if matches!(mode, PollMode::Edge | PollMode::EdgeOneshot) {
+            return Err(crate::unsupported_error(
+                "edge-triggered events are not supported",
+            ));
+        }
+
+        let packet = lock!(self.waitables.lock())
+            .get(&handle)
+            .cloned()
+            .ok_or_else(|| io::Error::from(io::ErrorKind::NotFound))?;
+
+        if let Some(mut state) = packet.as_ref().waitable_state() {
+            state.interest = interest;
+            state.mode = mode;
+        }
+        packet.update_wait(self)
+    }
+
+    /// Delete a waitable handle from the poller.
+    pub(super) fn delete_waitable(&self, handle: RawHandle) -> io::Result<()> {
+        log::trace!(
+            "delete_waitable: handle={:?}, waitable={:?}",
+            self.port,
+            handle
+        );
+
+        let packet = lock!(self.waitables.lock())
+            .remove(&handle)
+            .ok_or_else(|| io::Error::from(io::ErrorKind::NotFound))?;
+
+        // Unregister the wait. A packet that was already posted is ignored by `feed_event()`.
+        if let Some(mut state) = packet.as_ref().waitable_state() {
+            state.interest = Event::none(state.interest.key);
+            state.wait = None;
+        }
+        Ok(())
+    }
+
+    /// Set the number of bytes that must be buffered before a read event is reported.
+    pub(super) fn set_low_water_mark(&self, socket: RawSocket, bytes: usize) -> io::Result<()> {
+        let _ = (socket, bytes);
+        Err(crate::unsupported_error(
+            "low-water marks are not supported with IOCP",
+        ))
+    }
+
+    /// Add a child process that reports a readable event with `key` when it exits.
+    ///
+    /// Process handles are signaled when the process exits, so this waits for the handle.
+    pub(super) fn add_process(&self, child: &mut Child, key: usize) -> io::Result<()> {
+        self.add_waitable(
+            child.as_raw_handle(),
+            Event::readable(key),
+            PollMode::Oneshot,
+        )
+    }
+
+    /// Remove a child process added with `add_process()`.
+    pub(super) fn delete_process(&self, child: &Child) -> io::Result<()> {
+        match self.delete_waitable(child.as_raw_handle()) {
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(()),
+            res => res,
+        }
+    }
+
+    /// Add a signal that reports a readable event with `key` when it is delivered.
+    pub(super) fn add_signal(&self, signum: i32, key: usize) -> io::Result<()> {
+        let _ = (signum, key);
+        Err(crate::unsupported_error(
+            "signals are not supported with IOCP",
+        ))
+    }
+
+    /// Remove a signal added with `add_signal()`.
+    pub(super) fn delete_signal(&self, signum: i32) -> io::Result<()> {
+        let _ = signum;
+        Err(crate::unsupported_error(
+            "signals are not supported with IOCP",
+        ))
+    }
+
+    /// Add a timer that reports a readable event with `key` after `interval`, and then every
+    /// `interval` if `repeat` is set.
+    ///
+    /// This is a waitable timer, which is waited for in level-triggered mode if it repeats.
+    pub(super) fn add_timer(&self, key: usize, interval: Duration, repeat: bool) -> io::Result<()> {
+        log::trace!(
+            "add_timer: handle={:?}, key={}, interval={:?}, repeat={}",
+            self.port,
+            key,
+            interval,
+            repeat
+        );
+
+        let timer = WaitableTimer::new(interval, repeat)?;
+        let mode = if repeat {
+            PollMode::Level
+        } else {
+            PollMode::Oneshot
+        };
+        self.add_waitable(timer.as_raw_handle(), Event::readable(key), mode)?;
+
+        // Replace the previous timer with this key.
+        let old = lock!(self.timers.lock()).insert(key, timer);
+        if let Some(old) = old {
+            self.delete_waitable(old.as_raw_handle())?;
+        }
+        Ok(())
+    }
+
+    /// Remove a timer added with `add_timer()`.
+    pub(super) fn delete_timer(&self, key: usize) -> io::Result<()> {
+        log::trace!("delete_timer: handle={:?}, key={}", self.port, key);
+
+        let timer = lock!(self.timers.lock()).remove(&key);
+        match timer {
+            Some(timer) => self.delete_waitable(timer.as_raw_handle()),
+            None => Ok(()),
+        }
+    }
+
+    /// Wait for events, storing at most `max` of them.
+    ///
+    /// `max` is at most the capacity of `events`.
+    pub(super) fn wait(
+        &self,
+        events: &mut Events,
+        max: usize,
+        timeout: Option<Duration>,
+    ) -> io::Result<()> {
         log::trace!("wait: handle={:?}, timeout={:?}", self.port, timeout);
 
         let deadline = timeout.and_then(|timeout| Instant::now().checked_add(timeout));
@@ -281,7 +585,7 @@ impl Poller {
             let timeout = deadline.map(|t| t.saturating_duration_since(Instant::now()));
 
             // Wait for I/O events.
-            let len = self.port.wait(&mut packets, timeout)?;
+            let len = self.port.wait(&mut packets, max, timeout)?;
             log::trace!("new events: handle={:?}, len={}", self.port, len);
 
             // We are no longer polling.
@@ -294,8 +598,9 @@ impl Poller {
                 // Feed the event into the packet.
                 match packet.feed_event(self)? {
                     FeedEventResult::NoEvent => {}
-                    FeedEventResult::Event(event) => {
+                    FeedEventResult::Event(event, details) => {
                         events.packets.push(event);
+                        events.details.push(details);
                         new_events += 1;
                     }
                     FeedEventResult::Notified => {
@@ -413,6 +718,18 @@ impl Poller {
     }
 }
 
+impl Drop for Poller {
+    fn drop(&mut self) {
+        // The waits hold a reference to their packet and post to the port, so they have to be
+        // unregistered before the port is closed.
+        for packet in lock!(self.waitables.lock()).values() {
+            if let Some(mut state) = packet.as_ref().waitable_state() {
+                state.wait = None;
+            }
+        }
+    }
+}
+
 impl AsRawHandle for Poller {
     fn as_raw_handle(&self) -> RawHandle {
         self.port.as_raw_handle()
@@ -430,6 +747,9 @@ impl AsHandle for Poller {
 pub(super) struct Events {
     /// List of IOCP packets.
     packets: Vec<Event>,
+
+    /// The conditions reported alongside each packet.
+    details: Vec<EventDetails>,
 }
 
 unsafe impl Send for Events {}
@@ -437,14 +757,59 @@ unsafe impl Send for Events {}
 impl Events {
     /// Creates an empty list of events.
     pub(super) fn new() -> Events {
+        Events::with_capacity(1024)
+    }
+
+    /// Creates an empty list that holds at most `capacity` events per wait.
+    pub(super) fn with_capacity(capacity: usize) -> Events {
+        Events {
+            packets: Vec::with_capacity(capacity),
+            details: Vec::with_capacity(capacity),
+        }
+    }
+
+    /// Creates a list from raw events.
+    #[cfg(fuzzing)]
+    pub(super) fn from_raw(list: Vec<Event>) -> Events {
         Events {
-            packets: Vec::with_capacity(1024),
+            details: vec![EventDetails::default(); list.len()],
+            packets: list,
         }
     }
 
     /// Iterate over I/O events.
-    pub(super) fn iter(&self) -> impl Iterator<Item = Event> + '_ {
-        self.packets.iter().copied()
+    pub(super) fn iter(&self) -> impl Iterator<Item = (Event, EventDetails)> + '_ {
+        self.packets
+            .iter()
+            .copied()
+            .zip(self.details.iter().copied())
+    }
+
+    /// Iterate over the keys of I/O events.
+    pub(super) fn keys(&self) -> impl Iterator<Item = usize> + '_ {
+        self.packets.iter().map(|ev| ev.key)
+    }
+
+    /// Clear the list.
+    pub(super) fn clear(&mut self) {
+        self.packets.clear();
+        self.details.clear();
+    }
+
+    /// The maximum number of events that a single call to `wait` stores.
+    pub(super) fn capacity(&self) -> usize {
+        self.packets.capacity()
+    }
+
+    /// Move the events of another list to the end of this one, leaving the other one empty.
+    pub(super) fn append(&mut self, other: &mut Events) {
+        self.packets.append(&mut other.packets);
+        self.details.append(&mut other.details);
+    }
+
+    /// The events that were reported.
+    pub(super) fn as_raw_slice(&self) -> &[Event] {
+        &self.packets
     }
 }
 
@@ -488,6 +853,11 @@ pin_project! {
             socket: Mutex<SocketState>
         },
 
+        // A packet for a handle that is waited on by the thread pool.
+        Waitable {
+            waitable: Mutex<WaitableState>
+        },
+
         /// A custom event sent by the user.
         Custom {
             event: Event,
@@ -503,6 +873,10 @@ impl fmt::Debug for PacketInner {
         match self {
             Self::Wakeup { .. } => f.write_str("Wakeup { .. }"),
             Self::Custom { event } => f.debug_struct("Custom").field("event", event).finish(),
+            Self::Waitable { waitable } => f
+                .debug_struct("Waitable")
+                .field("waitable", waitable)
+                .finish(),
             Self::Socket { socket, .. } => f
                 .debug_struct("Socket")
                 .field("packet", &"..")
@@ -525,7 +899,7 @@ impl PacketUnwrapped {
     /// Set the new events that this socket is waiting on.
     ///
     /// Returns `true` if we need to be updated.
-    fn set_events(self: Pin<&Self>, interest: Event, mode: PollMode) -> bool {
+    fn set_events(self: Pin<&Self>, interest: Registration, mode: PollMode) -> bool {
         let mut socket = match self.socket_state() {
             Some(s) => s,
             None => return false,
@@ -536,8 +910,16 @@ impl PacketUnwrapped {
         socket.interest_error = true;
 
         match socket.status {
-            SocketStatus::Polling { readable, writable } => {
-                (interest.readable && !readable) || (interest.writable && !writable)
+            SocketStatus::Polling {
+                readable,
+                writable,
+                priority,
+                hup,
+            } => {
+                (interest.readable && !readable)
+                    || (interest.writable && !writable)
+                    || (interest.priority && !priority)
+                    || (interest.hup && !hup)
             }
             _ => true,
         }
@@ -557,11 +939,18 @@ impl PacketUnwrapped {
 
         // Check the current status.
         match socket.status {
-            SocketStatus::Polling { readable, writable } => {
+            SocketStatus::Polling {
+                readable,
+                writable,
+                priority,
+                hup,
+            } => {
                 // If we need to poll for events aside from what we are currently polling, we need
                 // to update the packet. Cancel the ongoing poll.
                 if (socket.interest.readable && !readable)
                     || (socket.interest.writable && !writable)
+                    || (socket.interest.priority && !priority)
+                    || (socket.interest.hup && !hup)
                 {
                     return self.cancel(socket);
                 }
@@ -584,6 +973,8 @@ impl PacketUnwrapped {
                     event_to_afd_mask(
                         socket.interest.readable,
                         socket.interest.writable,
+                        socket.interest.priority,
+                        socket.interest.hup,
                         socket.interest_error,
                     ),
                 );
@@ -610,6 +1001,8 @@ impl PacketUnwrapped {
                 socket.status = SocketStatus::Polling {
                     readable: socket.interest.readable,
                     writable: socket.interest.writable,
+                    priority: socket.interest.priority,
+                    hup: socket.interest.hup,
                 };
 
                 Ok(())
@@ -625,7 +1018,10 @@ impl PacketUnwrapped {
             PacketInnerProj::Socket { packet, socket } => (packet, socket),
             PacketInnerProj::Custom { event } => {
                 // This is a custom event.
-                return Ok(FeedEventResult::Event(*event));
+                return Ok(FeedEventResult::Event(*event, EventDetails::default()));
+            }
+            PacketInnerProj::Waitable { .. } => {
+                return self.feed_waitable_event(poller);
             }
             PacketInnerProj::Wakeup { .. } => {
                 // The poller was notified.
@@ -635,6 +1031,7 @@ impl PacketUnwrapped {
 
         let mut socket_state = lock!(socket.lock());
         let mut event = Event::none(socket_state.interest.key);
+        let mut details = EventDetails::default();
 
         // Put ourselves into the idle state.
         socket_state.status = SocketStatus::Idle;
@@ -658,6 +1055,7 @@ impl PacketUnwrapped {
                     // There was an error, so we signal both ends.
                     event.readable = true;
                     event.writable = true;
+                    details = EventDetails::new(true, false, false).with_write_closed(true);
                 }
 
                 _ => {
@@ -679,25 +1077,37 @@ impl PacketUnwrapped {
                         let (readable, writable) = afd_mask_to_event(events);
                         event.readable = readable;
                         event.writable = writable;
+                        details = EventDetails::new(
+                            events.contains(AfdPollMask::CONNECT_FAIL),
+                            events.intersects(AfdPollMask::DISCONNECT | AfdPollMask::ABORT),
+                            events.contains(AfdPollMask::RECEIVE_EXPEDITED),
+                        )
+                        .with_eof(events.contains(AfdPollMask::DISCONNECT))
+                        .with_write_closed(
+                            events.intersects(AfdPollMask::ABORT | AfdPollMask::CONNECT_FAIL),
+                        );
                     }
                 }
             }
         }
 
-        // Filter out events that the user didn't ask for.
-        event.readable &= socket_state.interest.readable;
+        // Filter out events that the user didn't ask for. Priority data and hangups are also
+        // reported as readability.
+        let priority = details.is_pri() && socket_state.interest.priority;
+        let hup = details.is_hup() && socket_state.interest.hup;
+        event.readable &= socket_state.interest.readable || priority || hup;
         event.writable &= socket_state.interest.writable;
 
         // If this event doesn't have anything that interests us, don't return or
         // update the oneshot state.
         let return_value = if event.readable || event.writable {
             // If we are in oneshot mode, remove the interest.
-            if matches!(socket_state.mode, PollMode::Oneshot) {
-                socket_state.interest = Event::none(socket_state.interest.key);
+            if matches!(socket_state.mode, PollMode::Oneshot | PollMode::Dispatch) {
+                socket_state.interest = Registration::none(socket_state.interest.key);
                 socket_state.interest_error = false;
             }
 
-            FeedEventResult::Event(event)
+            FeedEventResult::Event(event, details)
         } else {
             FeedEventResult::NoEvent
         };
@@ -744,6 +1154,66 @@ impl PacketUnwrapped {
         Ok(())
     }
 
+    /// The handle of this waitable state was signaled.
+    fn feed_waitable_event(self: Pin<Arc<Self>>, poller: &Poller) -> io::Result<FeedEventResult> {
+        let (interest, mode) = match self.as_ref().waitable_state() {
+            Some(mut state) => {
+                // The wait has fired, so release it.
+                state.wait = None;
+                let registered = (state.interest, state.mode);
+
+                // If we are in oneshot mode, remove the interest.
+                if matches!(state.mode, PollMode::Oneshot | PollMode::Dispatch) {
+                    state.interest = Event::none(state.interest.key);
+                }
+                registered
+            }
+            None => return Ok(FeedEventResult::NoEvent),
+        };
+
+        // The handle was deleted, or the interest was cleared after the packet was posted.
+        if !interest.readable {
+            return Ok(FeedEventResult::NoEvent);
+        }
+
+        // In level-triggered mode, wait for the handle again. If it is still signaled, the
+        // event is reported again by the following wait.
+        if !matches!(mode, PollMode::Oneshot | PollMode::Dispatch) {
+            self.update_wait(poller)?;
+        }
+
+        Ok(FeedEventResult::Event(
+            Event::readable(interest.key),
+            EventDetails::default(),
+        ))
+    }
+
+    /// Start waiting for the handle if we are interested in it, replacing the current wait.
+    fn update_wait(self: &Pin<Arc<Self>>, poller: &Poller) -> io::Result<()> {
+        let mut state = match self.as_ref().waitable_state() {
+            Some(s) => s,
+            None => return Err(io::Error::new(io::ErrorKind::Other, "invalid handle state")),
+        };
+
+        state.wait = None;
+        if state.interest.readable {
+            // SAFETY: The wait is dropped before the port, in `Poller::drop()`, and the handle
+            // must stay open while it is registered.
+            let wait = unsafe {
+                WaitHandle::new(state.handle, poller.port.as_raw_handle(), self.clone())?
+            };
+            state.wait = Some(wait);
+        }
+        Ok(())
+    }
+
+    fn waitable_state(self: Pin<&Self>) -> Option<MutexGuard<'_, WaitableState>> {
+        match self.data().project_ref() {
+            PacketInnerProj::Waitable { waitable } => Some(lock!(waitable.lock())),
+            _ => None,
+        }
+    }
+
     fn socket_state(self: Pin<&Self>) -> Option<MutexGuard<'_, SocketState>> {
         let inner = self.data().project_ref();
 
@@ -766,7 +1236,7 @@ struct SocketState {
     base_socket: RawSocket,
 
     /// The event that this socket is currently waiting on.
-    interest: Event,
+    interest: Registration,
 
     /// Whether to listen for error events.
     interest_error: bool,
@@ -784,6 +1254,22 @@ struct SocketState {
     status: SocketStatus,
 }
 
+/// Per-handle state of a waitable handle.
+#[derive(Debug)]
+struct WaitableState {
+    /// The raw handle.
+    handle: RawHandle,
+
+    /// The event that this handle is currently waiting on.
+    interest: Event,
+
+    /// The current poll mode.
+    mode: PollMode,
+
+    /// The wait registered with the thread pool, if any.
+    wait: Option<WaitHandle<Packet>>,
+}
+
 /// The mode that a socket can be in.
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 enum SocketStatus {
@@ -797,6 +1283,12 @@ enum SocketStatus {
 
         /// We are currently polling for writable events.
         writable: bool,
+
+        /// We are currently polling for priority data.
+        priority: bool,
+
+        /// We are currently polling for hangups.
+        hup: bool,
     },
 
     /// The last poll operation was cancelled, and we're waiting for it to
@@ -811,18 +1303,24 @@ enum FeedEventResult {
     NoEvent,
 
     /// An event was yielded.
-    Event(Event),
+    Event(Event, EventDetails),
 
     /// The poller has been notified.
     Notified,
 }
 
-fn event_to_afd_mask(readable: bool, writable: bool, error: bool) -> afd::AfdPollMask {
+fn event_to_afd_mask(
+    readable: bool,
+    writable: bool,
+    priority: bool,
+    hup: bool,
+    error: bool,
+) -> afd::AfdPollMask {
     use afd::AfdPollMask as AfdPoll;
 
     let mut mask = AfdPoll::empty();
 
-    if error || readable || writable {
+    if error || readable || writable || priority || hup {
         mask |= AfdPoll::ABORT | AfdPoll::CONNECT_FAIL;
     }
 
@@ -835,6 +1333,14 @@ fn event_to_afd_mask(readable: bool, writable: bool, error: bool) -> afd::AfdPol
         mask |= AfdPoll::SEND;
     }
 
+    if priority {
+        mask |= AfdPoll::RECEIVE_EXPEDITED;
+    }
+
+    if hup {
+        mask |= AfdPoll::DISCONNECT;
+    }
+
     mask
 }
 
@@ -213,10 +213,11 @@ impl<T: CompletionHandle> IoCompletionPort<T> {
         }
     }
 
-    /// Wait for completion packets to arrive.
+    /// Wait for at most `limit` completion packets to arrive.
     pub(super) fn wait(
         &self,
         packets: &mut Vec<OverlappedEntry<T>>,
+        limit: usize,
         timeout: Option<Duration>,
     ) -> io::Result<usize> {
         // Drop the current packets.
@@ -229,7 +230,11 @@ impl<T: CompletionHandle> IoCompletionPort<T> {
             GetQueuedCompletionStatusEx(
                 self.handle,
                 packets.as_mut_ptr() as _,
-                packets.capacity().try_into().expect("too many packets"),
+                packets
+                    .capacity()
+                    .min(limit)
+                    .try_into()
+                    .expect("too many packets"),
                 count.as_mut_ptr(),
                 timeout,
                 0,
@@ -0,0 +1,153 @@
+//! Waiting for handles to be signaled with the thread pool.
+
+use std::convert::TryFrom;
+use std::ffi::c_void;
+use std::fmt;
+use std::io;
+use std::os::windows::io::{AsRawHandle, RawHandle};
+use std::ptr;
+use std::time::Duration;
+
+use super::port::CompletionHandle;
+
+use windows_sys::Win32::Foundation::{CloseHandle, BOOLEAN, HANDLE, INVALID_HANDLE_VALUE};
+use windows_sys::Win32::System::Threading::{
+    CreateWaitableTimerW, RegisterWaitForSingleObject, SetWaitableTimer, UnregisterWaitEx,
+    INFINITE, WT_EXECUTEINWAITTHREAD, WT_EXECUTEONLYONCE,
+};
+use windows_sys::Win32::System::IO::PostQueuedCompletionStatus;
+
+/// A wait for a handle, which posts a completion packet to a port once the handle is signaled.
+///
+/// The packet is posted at most once. Dropping the wait unregisters it, and blocks until the
+/// callback has returned if it is running.
+pub(super) struct WaitHandle<T: CompletionHandle + Clone> {
+    /// The wait object returned by the thread pool.
+    wait: HANDLE,
+
+    /// The data passed to the callback.
+    context: *mut WaitContext<T>,
+}
+
+struct WaitContext<T> {
+    /// The I/O completion port to post the packet to.
+    port: HANDLE,
+
+    /// The packet to post.
+    packet: T,
+}
+
+impl<T: CompletionHandle + Clone> WaitHandle<T> {
+    /// Waits for `handle` to be signaled, and then posts `packet` to `port`.
+    ///
+    /// # Safety
+    ///
+    /// `handle` and `port` must stay open until the wait is dropped.
+    pub(super) unsafe fn new(handle: RawHandle, port: RawHandle, packet: T) -> io::Result<Self> {
+        let context = Box::into_raw(Box::new(WaitContext {
+            port: port as HANDLE,
+            packet,
+        }));
+
+        let mut wait = 0;
+        let result = RegisterWaitForSingleObject(
+            &mut wait,
+            handle as HANDLE,
+            Some(callback::<T>),
+            context as *const c_void,
+            INFINITE,
+            WT_EXECUTEONLYONCE | WT_EXECUTEINWAITTHREAD,
+        );
+
+        if result == 0 {
+            let err = io::Error::last_os_error();
+            drop(Box::from_raw(context));
+            return Err(err);
+        }
+
+        Ok(WaitHandle { wait, context })
+    }
+}
+
+impl<T: CompletionHandle + Clone> Drop for WaitHandle<T> {
+    fn drop(&mut self) {
+        unsafe {
+            // Passing `INVALID_HANDLE_VALUE` waits for the callback to return, so the context
+            // can be freed afterwards.
+            UnregisterWaitEx(self.wait, INVALID_HANDLE_VALUE);
+            drop(Box::from_raw(self.context));
+        }
+    }
+}
+
+impl<T: CompletionHandle + Clone> fmt::Debug for WaitHandle<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WaitHandle")
+            .field("wait", &self.wait)
+            .finish()
+    }
+}
+
+/// A waitable timer, which is signaled once or periodically.
+#[derive(Debug)]
+pub(super) struct WaitableTimer(HANDLE);
+
+impl WaitableTimer {
+    /// Creates a timer that is signaled after `interval`, and then every `interval` if `repeat`
+    /// is set.
+    ///
+    /// The timer resets itself when a wait for it returns. Periods are rounded up to a whole
+    /// millisecond.
+    pub(super) fn new(interval: Duration, repeat: bool) -> io::Result<Self> {
+        let handle = unsafe { CreateWaitableTimerW(ptr::null(), 0, ptr::null()) };
+        if handle == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let timer = WaitableTimer(handle);
+
+        // The due time is relative when it is negative, in units of 100 nanoseconds.
+        let due = i64::try_from((interval.as_nanos() + 99) / 100).unwrap_or(i64::MAX);
+        let period = if repeat {
+            let millis = (interval.as_nanos() + 999_999) / 1_000_000;
+            i32::try_from(millis.max(1)).unwrap_or(i32::MAX)
+        } else {
+            0
+        };
+
+        let result =
+            unsafe { SetWaitableTimer(timer.0, &-due.max(1), period, None, ptr::null(), 0) };
+        if result == 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(timer)
+    }
+}
+
+impl AsRawHandle for WaitableTimer {
+    fn as_raw_handle(&self) -> RawHandle {
+        self.0 as _
+    }
+}
+
+impl Drop for WaitableTimer {
+    fn drop(&mut self) {
+        unsafe {
+            CloseHandle(self.0);
+        }
+    }
+}
+
+unsafe extern "system" fn callback<T: CompletionHandle + Clone>(
+    context: *mut c_void,
+    _timed_out: BOOLEAN,
+) {
+    // SAFETY: The context lives until the wait is unregistered, which waits for this callback.
+    let context = &*(context as *const WaitContext<T>);
+    let packet = T::into_ptr(context.packet.clone());
+
+    if PostQueuedCompletionStatus(context.port, 0, 0, packet) == 0 {
+        // The port was closed, so nobody is waiting for the packet.
+        drop(T::from_ptr(packet));
+    }
+}
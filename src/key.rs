@@ -0,0 +1,96 @@
+//! Allocation of unique keys.
+
+use crate::Poller;
+
+/// An allocator of unique keys for the sources of a [`Poller`].
+///
+/// Keys are handed out from zero upwards, and freed keys are reused before new ones. The
+/// notification key of the poller is never handed out, even if it was set to a small value
+/// with [`PollerBuilder::notify_key()`][`crate::PollerBuilder::notify_key()`].
+///
+/// # Examples
+///
+/// ```
+/// use polling::{Event, KeyAllocator, Poller};
+/// use std::net::TcpListener;
+///
+/// let poller = Poller::new()?;
+/// let mut keys = KeyAllocator::new(&poller);
+///
+/// let socket = TcpListener::bind("127.0.0.1:0")?;
+/// socket.set_nonblocking(true)?;
+///
+/// let key = keys.allocate();
+/// poller.add(&socket, Event::readable(key))?;
+///
+/// poller.delete(&socket)?;
+/// keys.free(key);
+/// # std::io::Result::Ok(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct KeyAllocator {
+    /// The key that is reserved for notifications.
+    reserved: usize,
+
+    /// The smallest key that was never handed out.
+    next: usize,
+
+    /// The keys that were freed and can be handed out again.
+    free: Vec<usize>,
+}
+
+impl KeyAllocator {
+    /// Creates an allocator for the keys of `poller`.
+    pub fn new(poller: &Poller) -> KeyAllocator {
+        KeyAllocator {
+            reserved: poller.notify_key(),
+            next: 0,
+            free: Vec::new(),
+        }
+    }
+
+    /// Returns a key that is not in use.
+    ///
+    /// # Panics
+    ///
+    /// Panics if all keys have been handed out, which can only happen after `usize::MAX` calls.
+    pub fn allocate(&mut self) -> usize {
+        if let Some(key) = self.free.pop() {
+            return key;
+        }
+
+        if self.next == self.reserved {
+            self.next = self.next.checked_add(1).expect("all keys are in use");
+        }
+        let key = self.next;
+        self.next = key.checked_add(1).expect("all keys are in use");
+        key
+    }
+
+    /// Makes `key` available to be handed out again.
+    ///
+    /// The key must have been returned by [`allocate()`][`KeyAllocator::allocate()`] and not
+    /// been freed since. Otherwise, it may be handed out twice.
+    pub fn free(&mut self, key: usize) {
+        debug_assert_ne!(
+            key, self.reserved,
+            "the notification key is never allocated"
+        );
+        self.free.push(key);
+    }
+
+    /// The number of keys that are in use.
+    pub fn len(&self) -> usize {
+        let handed_out = if self.next > self.reserved {
+            self.next - 1
+        } else {
+            self.next
+        };
+        handed_out - self.free.len()
+    }
+
+    /// Returns `true` if no keys are in use.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
@@ -1,16 +1,27 @@
 //! Bindings to kqueue (macOS, iOS, tvOS, watchOS, FreeBSD, NetBSD, OpenBSD, DragonFly BSD).
 
+use std::collections::{HashMap, HashSet};
+use std::convert::{TryFrom, TryInto};
+use std::fmt;
 use std::io;
-use std::os::unix::io::{AsRawFd, RawFd};
+use std::mem::ManuallyDrop;
+use std::os::unix::io::{AsRawFd, FromRawFd, IntoRawFd, RawFd};
+use std::process::Child;
+use std::ptr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
 use std::time::Duration;
 
 #[cfg(not(polling_no_io_safety))]
 use std::os::unix::io::{AsFd, BorrowedFd};
 
 use rustix::fd::OwnedFd;
-use rustix::io::{fcntl_setfd, kqueue, Errno, FdFlags};
+use rustix::io::{fcntl_getfd, fcntl_setfd, kqueue, Errno, FdFlags};
 
-use crate::{Event, PollMode};
+use crate::{BackendKind, Event, EventDetails, PollMode, PollerBuilder, Registration};
+
+/// The backend implemented by this module.
+pub(crate) const BACKEND: BackendKind = BackendKind::Kqueue;
 
 /// Interface to kqueue.
 #[derive(Debug)]
@@ -23,18 +34,58 @@ pub struct Poller {
     /// On platforms that support `EVFILT_USER`, this uses that to wake up the poller. Otherwise, it
     /// uses a pipe.
     notify: notify::Notify,
+
+    /// The key used for notifications.
+    notify_key: usize,
+
+    /// Whether a read filter with `EV_EOF` is also reported as writable.
+    writable_on_eof: bool,
+
+    /// The low-water marks set with `set_low_water_mark()`, by file descriptor.
+    ///
+    /// Adding a read filter again resets its low-water mark, so it is applied by every change
+    /// that adds one.
+    low_water_marks: Mutex<HashMap<RawFd, usize>>,
+
+    /// Whether a low-water mark was ever set, so that sources without one skip the lock.
+    has_low_water_marks: AtomicBool,
+
+    /// The file descriptors that have an `EVFILT_EXCEPT` filter for priority data.
+    ///
+    /// The filter is only added for sources that are interested in priority data, so this tells
+    /// which ones need to delete it when their interest changes.
+    priority_fds: Mutex<HashSet<RawFd>>,
+
+    /// Whether `priority_fds` was ever used, so that other sources don't have to lock it.
+    has_priority_fds: AtomicBool,
+
+    /// The changes waiting to be submitted with the next wait, if changes are deferred.
+    deferred: Option<Deferred>,
+
+    /// The quality-of-service class that changes and waits are submitted at.
+    qos_class: Option<crate::QosClass>,
 }
 
 impl Poller {
     /// Creates a new poller.
-    pub fn new() -> io::Result<Poller> {
+    pub fn new(builder: &PollerBuilder) -> io::Result<Poller> {
         // Create a kqueue instance.
         let kqueue_fd = kqueue::kqueue()?;
-        fcntl_setfd(&kqueue_fd, FdFlags::CLOEXEC)?;
+        if builder.close_on_exec {
+            fcntl_setfd(&kqueue_fd, FdFlags::CLOEXEC)?;
+        }
 
         let poller = Poller {
             kqueue_fd,
             notify: notify::Notify::new()?,
+            notify_key: builder.notify_key,
+            writable_on_eof: builder.writable_on_eof,
+            low_water_marks: Mutex::new(HashMap::new()),
+            has_low_water_marks: AtomicBool::new(false),
+            priority_fds: Mutex::new(HashSet::new()),
+            has_priority_fds: AtomicBool::new(false),
+            deferred: Deferred::new(builder),
+            qos_class: builder.qos_class,
         };
 
         // Register the notification pipe.
@@ -44,6 +95,77 @@ impl Poller {
         Ok(poller)
     }
 
+    /// Creates a poller from an existing kqueue instance.
+    ///
+    /// If `fd` is not a kqueue instance, an error is returned and `fd` is left open.
+    ///
+    /// # Safety
+    ///
+    /// `fd` must be an open file descriptor owned by the caller.
+    pub unsafe fn from_raw_fd(fd: RawFd, builder: &PollerBuilder) -> io::Result<Poller> {
+        // Check that this is a kqueue instance without taking any of its events.
+        kqueue::kevent(
+            rustix::fd::BorrowedFd::borrow_raw(fd),
+            &[],
+            &mut Vec::new(),
+            Some(Duration::from_secs(0)),
+        )?;
+        let notify = notify::Notify::new()?;
+
+        let poller = Poller {
+            kqueue_fd: OwnedFd::from_raw_fd(fd),
+            notify,
+            notify_key: builder.notify_key,
+            writable_on_eof: builder.writable_on_eof,
+            low_water_marks: Mutex::new(HashMap::new()),
+            has_low_water_marks: AtomicBool::new(false),
+            priority_fds: Mutex::new(HashSet::new()),
+            has_priority_fds: AtomicBool::new(false),
+            deferred: Deferred::new(builder),
+            qos_class: builder.qos_class,
+        };
+        if let Err(e) = poller.notify.register(&poller) {
+            poller.into_raw_fd();
+            return Err(e);
+        }
+
+        if builder.close_on_exec {
+            if let Err(e) = set_cloexec(&poller.kqueue_fd) {
+                poller.into_raw_fd();
+                return Err(e);
+            }
+        }
+
+        log::trace!("from_raw_fd: kqueue_fd={:?}", poller.kqueue_fd);
+        Ok(poller)
+    }
+
+    /// Releases the kqueue instance without closing it.
+    pub fn into_raw_fd(self) -> RawFd {
+        let mut poller = ManuallyDrop::new(self);
+        let _ = poller.flush_deferred();
+        let _ = poller.notify.deregister(&poller);
+
+        // SAFETY: `poller` is never used or dropped again.
+        unsafe {
+            ptr::drop_in_place(&mut poller.notify);
+            ptr::read(&poller.kqueue_fd).into_raw_fd()
+        }
+    }
+
+    /// Creates a new poller that shares the kqueue instance of this one.
+    pub fn try_clone(&self) -> io::Result<Poller> {
+        Err(crate::unsupported_error(
+            "cloning the poller is not supported with kqueue",
+        ))
+    }
+
+    /// Prepares the poller for several threads waiting on it at the same time.
+    pub fn enable_concurrent_wait(&self) -> io::Result<()> {
+        // Waiting doesn't modify any state that other waiters depend on.
+        Ok(())
+    }
+
     /// Whether this poller supports level-triggered events.
     pub fn supports_level(&self) -> bool {
         true
@@ -54,14 +176,30 @@ impl Poller {
         true
     }
 
+    /// Whether the poller wakes up only one poller for exclusive registrations.
+    pub fn supports_exclusive(&self) -> bool {
+        false
+    }
+
+    /// The precision of the timeouts of `wait()`.
+    pub fn timeout_granularity(&self) -> Duration {
+        Duration::from_nanos(1)
+    }
+
+    /// Checks that the file descriptors of this poller are still open.
+    pub fn check_invariants(&self, _registered: Option<usize>) -> io::Result<()> {
+        fcntl_getfd(&self.kqueue_fd)?;
+        self.notify.check_invariants()
+    }
+
     /// Adds a new file descriptor.
-    pub fn add(&self, fd: RawFd, ev: Event, mode: PollMode) -> io::Result<()> {
+    pub fn add(&self, fd: RawFd, ev: Registration, mode: PollMode) -> io::Result<()> {
         // File descriptors don't need to be added explicitly, so just modify the interest.
         self.modify(fd, ev, mode)
     }
 
     /// Modifies an existing file descriptor.
-    pub fn modify(&self, fd: RawFd, ev: Event, mode: PollMode) -> io::Result<()> {
+    pub fn modify(&self, fd: RawFd, ev: Registration, mode: PollMode) -> io::Result<()> {
         if !self.notify.has_fd(fd) {
             log::trace!(
                 "add: kqueue_fd={:?}, fd={}, ev={:?}",
@@ -71,91 +209,485 @@ impl Poller {
             );
         }
 
-        let mode_flags = mode_to_flags(mode);
+        // The notification pipe is registered before the first wait, so it is never deferred.
+        if let (Some(deferred), false) = (&self.deferred, self.notify.has_fd(fd)) {
+            let mut deferred = deferred.0.lock().unwrap_or_else(|e| e.into_inner());
+            for mut change in self.changes(fd, ev, mode).as_ref().iter().copied() {
+                // Without `EV_RECEIPT`, only the changes that fail are reported by the wait.
+                as_raw_mut(&mut change).flags &= !libc::EV_RECEIPT;
+                deferred.push(change);
+            }
+            return Ok(());
+        }
+
+        // Apply changes.
+        self.submit_changes(self.changes(fd, ev, mode))
+    }
+
+    /// Takes the changes waiting to be submitted with the next wait.
+    fn take_deferred(&self) -> Vec<kqueue::Event> {
+        match &self.deferred {
+            Some(deferred) => {
+                std::mem::take(&mut *deferred.0.lock().unwrap_or_else(|e| e.into_inner()))
+            }
+            None => Vec::new(),
+        }
+    }
+
+    /// Submits the deferred changes on their own, so that they apply before the changes that
+    /// follow them.
+    fn flush_deferred(&self) -> io::Result<()> {
+        let mut changelist = self.take_deferred();
+        if changelist.is_empty() {
+            return Ok(());
+        }
 
-        let read_flags = if ev.readable {
-            kqueue::EventFlags::ADD | mode_flags
+        for change in &mut changelist {
+            as_raw_mut(change).flags |= libc::EV_RECEIPT;
+        }
+        let mut eventlist = changelist.clone();
+        let received = self.kevent_changes(&changelist, &mut eventlist)?;
+        for ev in &eventlist[..received] {
+            if let Some(err) = change_error(ev) {
+                return Err(err);
+            }
+        }
+        Ok(())
+    }
+
+    /// The changes that set the interest of a file descriptor, with its low-water mark.
+    fn changes(&self, fd: RawFd, ev: Registration, mode: PollMode) -> Changes {
+        let mut changes = changes(fd, ev, mode, self.had_priority(fd, ev.priority));
+        self.apply_low_water_mark(fd, &mut changes.list[0]);
+        changes
+    }
+
+    /// Records whether `fd` is interested in priority data, and returns whether it was before.
+    fn had_priority(&self, fd: RawFd, priority: bool) -> bool {
+        if !HAS_EXCEPT {
+            return false;
+        }
+        if priority {
+            self.has_priority_fds.store(true, Ordering::Relaxed);
+        } else if !self.has_priority_fds.load(Ordering::Relaxed) {
+            return false;
+        }
+
+        let mut priority_fds = self.priority_fds.lock().unwrap_or_else(|e| e.into_inner());
+        if priority {
+            !priority_fds.insert(fd)
         } else {
-            kqueue::EventFlags::DELETE
-        };
-        let write_flags = if ev.writable {
-            kqueue::EventFlags::ADD | mode_flags
+            priority_fds.remove(&fd)
+        }
+    }
+
+    /// Sets `NOTE_LOWAT` on a change that adds the read filter of `fd`, if it has a low-water
+    /// mark.
+    fn apply_low_water_mark(&self, fd: RawFd, change: &mut kqueue::Event) {
+        // The read filter of a source that is only interested in hangups has its own mark.
+        if !change.flags().contains(kqueue::EventFlags::ADD)
+            || as_raw(change).fflags & libc::NOTE_LOWAT != 0
+            || !self.has_low_water_marks.load(Ordering::Relaxed)
+        {
+            return;
+        }
+
+        let low_water_marks = self
+            .low_water_marks
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        if let Some(&bytes) = low_water_marks.get(&fd) {
+            let raw = as_raw_mut(change);
+            raw.fflags |= libc::NOTE_LOWAT;
+            raw.data = bytes.min(std::isize::MAX as usize) as _;
+        }
+    }
+
+    /// Sets the number of bytes that must be buffered before a read event is reported for `fd`.
+    ///
+    /// This is applied as `NOTE_LOWAT` to the read filter the next time it is added.
+    pub fn set_low_water_mark(&self, fd: RawFd, bytes: usize) -> io::Result<()> {
+        log::trace!(
+            "set_low_water_mark: kqueue_fd={:?}, fd={}, bytes={}",
+            self.kqueue_fd,
+            fd,
+            bytes
+        );
+
+        let mut low_water_marks = self
+            .low_water_marks
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        if bytes > 1 {
+            self.has_low_water_marks.store(true, Ordering::Relaxed);
+            low_water_marks.insert(fd, bytes);
+        } else {
+            low_water_marks.remove(&fd);
+        }
+        Ok(())
+    }
+
+    /// Adds several file descriptors with a single system call, returning the result for each.
+    pub fn add_batch(&self, fds: &[(RawFd, Event, PollMode)]) -> Vec<io::Result<()>> {
+        let mut results = Vec::with_capacity(fds.len());
+        match self.submit_batch(fds, |_, result| results.push(result)) {
+            Ok(()) => results,
+            Err(err) => fds.iter().map(|_| Err(copy_error(&err))).collect(),
+        }
+    }
+
+    /// Adds several file descriptors with a single system call, returning the first error.
+    ///
+    /// `added` is called with the index of each file descriptor that was added.
+    pub fn add_many(
+        &self,
+        fds: &[(RawFd, Event, PollMode)],
+        mut added: impl FnMut(usize),
+    ) -> io::Result<()> {
+        let mut first = Ok(());
+        self.submit_batch(fds, |i, result| match result {
+            Ok(()) => added(i),
+            Err(err) => {
+                if first.is_ok() {
+                    first = Err(err);
+                }
+            }
+        })?;
+        first
+    }
+
+    /// Submits the changes for several file descriptors with a single system call, and passes
+    /// the result for each of them to `report`, in order.
+    ///
+    /// Every change has `EV_RECEIPT` set, so the kernel reports the result of each change instead
+    /// of stopping at the first error. An error is returned if the changes couldn't be submitted
+    /// at all.
+    fn submit_batch(
+        &self,
+        fds: &[(RawFd, Event, PollMode)],
+        mut report: impl FnMut(usize, io::Result<()>),
+    ) -> io::Result<()> {
+        log::trace!(
+            "submit_batch: kqueue_fd={:?}, {} file descriptors",
+            self.kqueue_fd,
+            fds.len()
+        );
+
+        // Build the changes and receive their receipts on the stack for small batches.
+        let max = CHANGES * fds.len();
+        let filler = changes(-1, Registration::none(0), PollMode::Oneshot, false).list[0];
+        let mut stack = [filler; CHANGES * STACK_BATCH];
+        let mut stack_receipts = stack;
+        let mut stack_ends = [0; STACK_BATCH];
+        let mut heap = Vec::new();
+        let mut heap_receipts = Vec::new();
+        let mut heap_ends = Vec::new();
+        let (changelist, eventlist, ends) = if fds.len() <= STACK_BATCH {
+            (
+                &mut stack[..max],
+                &mut stack_receipts[..max],
+                &mut stack_ends[..fds.len()],
+            )
         } else {
-            kqueue::EventFlags::DELETE
+            heap.resize(max, filler);
+            heap_receipts.resize(max, filler);
+            heap_ends.resize(fds.len(), 0);
+            (&mut heap[..], &mut heap_receipts[..], &mut heap_ends[..])
         };
 
-        // A list of changes for kqueue.
-        let changelist = [
-            kqueue::Event::new(
-                kqueue::EventFilter::Read(fd),
-                read_flags | kqueue::EventFlags::RECEIPT,
-                ev.key as _,
-            ),
-            kqueue::Event::new(
-                kqueue::EventFilter::Write(fd),
-                write_flags | kqueue::EventFlags::RECEIPT,
-                ev.key as _,
-            ),
-        ];
+        // Each file descriptor has a varying number of changes, so remember where they end.
+        let mut len = 0;
+        for (&(fd, ev, mode), end) in fds.iter().zip(ends.iter_mut()) {
+            let changes = self.changes(fd, ev.into(), mode);
+            let changes = changes.as_ref();
+            changelist[len..len + changes.len()].copy_from_slice(changes);
+            len += changes.len();
+            *end = len;
+        }
 
-        // Apply changes.
-        self.submit_changes(changelist)
+        let received = self.submit_with_receipts(&changelist[..len], &mut eventlist[..len])?;
+
+        // The receipts are reported in the order of the changes.
+        let mut start = 0;
+        for (i, &end) in ends.iter().enumerate() {
+            let receipts = &eventlist[start.min(received)..end.min(received)];
+            report(
+                i,
+                receipts.iter().find_map(change_error).map_or(Ok(()), Err),
+            );
+            start = end;
+        }
+        Ok(())
+    }
+
+    /// Modifies several file descriptors with a single system call.
+    pub fn modify_batch(&self, fds: &[(RawFd, Event, PollMode)]) -> Vec<io::Result<()>> {
+        // File descriptors don't need to be added explicitly, so the changes are the same.
+        self.add_batch(fds)
+    }
+
+    /// Stops delivering events for a file descriptor without removing it.
+    pub fn disable(&self, fd: RawFd, ev: Registration, _mode: PollMode) -> io::Result<()> {
+        self.set_enabled(fd, ev, kqueue::EventFlags::DISABLE)
+    }
+
+    /// Resumes delivering events for a disabled file descriptor.
+    pub fn enable(&self, fd: RawFd, ev: Registration, mode: PollMode) -> io::Result<()> {
+        // Oneshot filters are removed once they fire, so add them back as well.
+        self.set_enabled(
+            fd,
+            ev,
+            kqueue::EventFlags::ADD | kqueue::EventFlags::ENABLE | mode_to_flags(mode),
+        )
+    }
+
+    /// Applies `flags` to the filters that `ev` is interested in.
+    fn set_enabled(
+        &self,
+        fd: RawFd,
+        ev: Registration,
+        flags: kqueue::EventFlags,
+    ) -> io::Result<()> {
+        log::trace!(
+            "set_enabled: kqueue_fd={:?}, fd={}, ev={:?}, flags={:?}",
+            self.kqueue_fd,
+            fd,
+            ev,
+            flags
+        );
+
+        let flags = flags | kqueue::EventFlags::RECEIPT;
+        let mut read = kqueue::Event::new(kqueue::EventFilter::Read(fd), flags, ev.key as _);
+        self.apply_low_water_mark(fd, &mut read);
+        let write = kqueue::Event::new(kqueue::EventFilter::Write(fd), flags, ev.key as _);
+
+        match (ev.readable, ev.writable) {
+            (true, true) => self.submit_changes([read, write]),
+            (true, false) => self.submit_changes([read]),
+            (false, true) => self.submit_changes([write]),
+            (false, false) => Ok(()),
+        }
     }
 
     /// Submit one or more changes to the kernel queue and check to see if they succeeded.
+    ///
+    /// This doesn't allocate: the receipts are written into a copy of `changelist`.
     pub(crate) fn submit_changes<A>(&self, changelist: A) -> io::Result<()>
     where
         A: Copy + AsRef<[kqueue::Event]> + AsMut<[kqueue::Event]>,
     {
-        let mut eventlist = Vec::with_capacity(changelist.as_ref().len());
-
         // Apply changes.
-        {
-            let changelist = changelist.as_ref();
-
-            unsafe {
-                kqueue::kevent(&self.kqueue_fd, changelist, &mut eventlist, None)?;
-            }
-        }
+        let mut eventlist = changelist;
+        let received = self.submit_with_receipts(changelist.as_ref(), eventlist.as_mut())?;
 
         // Check for errors.
-        for &ev in &eventlist {
-            // TODO: Once the data field is exposed in rustix, use that.
-            let data = unsafe { (*(&ev as *const kqueue::Event as *const libc::kevent)).data };
-
-            // Explanation for ignoring EPIPE: https://github.com/tokio-rs/mio/issues/582
-            if (ev.flags().contains(kqueue::EventFlags::ERROR))
-                && data != 0
-                && data != Errno::NOENT.raw_os_error() as _
-                && data != Errno::PIPE.raw_os_error() as _
-            {
-                return Err(io::Error::from_raw_os_error(data as _));
+        for ev in &eventlist.as_ref()[..received] {
+            if let Some(err) = change_error(ev) {
+                return Err(err);
             }
         }
 
         Ok(())
     }
 
+    /// Submits changes and writes up to `eventlist.len()` of their receipts into `eventlist`.
+    ///
+    /// Returns the number of receipts. Unlike `kqueue::kevent()`, this doesn't need a `Vec`.
+    fn submit_with_receipts(
+        &self,
+        changelist: &[kqueue::Event],
+        eventlist: &mut [kqueue::Event],
+    ) -> io::Result<usize> {
+        self.flush_deferred()?;
+        self.kevent_changes(changelist, eventlist)
+    }
+
+    /// Submits changes without waiting, and writes their receipts into `eventlist`.
+    fn kevent_changes(
+        &self,
+        changelist: &[kqueue::Event],
+        eventlist: &mut [kqueue::Event],
+    ) -> io::Result<usize> {
+        let kq = self.kqueue_fd.as_raw_fd();
+        if let Some(res) = qos::submit(kq, self.qos_class, changelist, eventlist) {
+            return res;
+        }
+
+        // SAFETY: `kqueue::Event` is a transparent wrapper around `libc::kevent`, and the
+        // lengths are those of the slices.
+        let res = unsafe {
+            libc::kevent(
+                kq,
+                changelist.as_ptr() as *const libc::kevent,
+                changelist.len() as _,
+                eventlist.as_mut_ptr() as *mut libc::kevent,
+                eventlist.len() as _,
+                ptr::null(),
+            )
+        };
+        if res == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(res as usize)
+    }
+
     /// Deletes a file descriptor.
     pub fn delete(&self, fd: RawFd) -> io::Result<()> {
+        if self.has_low_water_marks.load(Ordering::Relaxed) {
+            self.low_water_marks
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .remove(&fd);
+        }
+
         // Simply delete interest in the file descriptor.
-        self.modify(fd, Event::none(0), PollMode::Oneshot)
+        self.modify(fd, Registration::none(0), PollMode::Oneshot)
     }
 
-    /// Waits for I/O events with an optional timeout.
-    pub fn wait(&self, events: &mut Events, timeout: Option<Duration>) -> io::Result<()> {
+    /// Adds a child process that reports a readable event with `key` when it exits.
+    ///
+    /// This is an `EVFILT_PROC` filter for `NOTE_EXIT`, which is removed once it fires.
+    pub fn add_process(&self, child: &mut Child, key: usize) -> io::Result<()> {
+        log::trace!(
+            "add_process: kqueue_fd={:?}, pid={}, key={}",
+            self.kqueue_fd,
+            child.id(),
+            key
+        );
+
+        // The process ID of a child that was reaped may belong to another process by now.
+        if child.try_wait()?.is_some() {
+            return Err(io::Error::from_raw_os_error(libc::ESRCH));
+        }
+
+        self.submit_changes([process_change(
+            child,
+            key,
+            kqueue::EventFlags::ADD | kqueue::EventFlags::ONESHOT,
+        )])
+    }
+
+    /// Removes a child process added with `add_process()`.
+    pub fn delete_process(&self, child: &Child) -> io::Result<()> {
+        log::trace!(
+            "delete_process: kqueue_fd={:?}, pid={}",
+            self.kqueue_fd,
+            child.id()
+        );
+
+        // The filter is already gone once the process exited, which is not an error.
+        match self.submit_changes([process_change(child, 0, kqueue::EventFlags::DELETE)]) {
+            Err(err) if err.raw_os_error() == Some(Errno::SRCH.raw_os_error()) => Ok(()),
+            res => res,
+        }
+    }
+
+    /// Adds a signal that reports a readable event with `key` when it is delivered.
+    ///
+    /// This is an `EVFILT_SIGNAL` filter, which records the signal without changing its
+    /// disposition.
+    pub fn add_signal(&self, signum: i32, key: usize) -> io::Result<()> {
+        log::trace!(
+            "add_signal: kqueue_fd={:?}, signum={}, key={}",
+            self.kqueue_fd,
+            signum,
+            key
+        );
+
+        // Adding a filter that exists already replaces its key.
+        self.submit_changes([signal_change(signum, key, kqueue::EventFlags::ADD)?])
+    }
+
+    /// Removes a signal added with `add_signal()`.
+    pub fn delete_signal(&self, signum: i32) -> io::Result<()> {
+        log::trace!(
+            "delete_signal: kqueue_fd={:?}, signum={}",
+            self.kqueue_fd,
+            signum
+        );
+
+        self.submit_changes([signal_change(signum, 0, kqueue::EventFlags::DELETE)?])
+    }
+
+    /// Adds a timer that reports a readable event with `key` after `interval`, and then every
+    /// `interval` if `repeat` is set.
+    ///
+    /// The timer is an `EVFILT_TIMER` filter with `key` as its identifier.
+    pub fn add_timer(&self, key: usize, interval: Duration, repeat: bool) -> io::Result<()> {
+        log::trace!(
+            "add_timer: kqueue_fd={:?}, key={}, interval={:?}, repeat={}",
+            self.kqueue_fd,
+            key,
+            interval,
+            repeat
+        );
+
+        // Adding a timer with the identifier of an existing one replaces it.
+        let mut flags = kqueue::EventFlags::ADD | kqueue::EventFlags::RECEIPT;
+        if !repeat {
+            flags |= kqueue::EventFlags::ONESHOT;
+        }
+        self.submit_changes([kqueue::Event::new(
+            kqueue::EventFilter::Timer {
+                ident: key as _,
+                timer: Some(interval.max(Duration::from_nanos(1))),
+            },
+            flags,
+            key as _,
+        )])
+    }
+
+    /// Removes a timer added with `add_timer()`.
+    pub fn delete_timer(&self, key: usize) -> io::Result<()> {
+        log::trace!("delete_timer: kqueue_fd={:?}, key={}", self.kqueue_fd, key);
+
+        // Oneshot timers are already gone once they fired, which is not an error.
+        self.submit_changes([kqueue::Event::new(
+            kqueue::EventFilter::Timer {
+                ident: key as _,
+                timer: None,
+            },
+            kqueue::EventFlags::DELETE | kqueue::EventFlags::RECEIPT,
+            key as _,
+        )])
+    }
+
+    /// Waits for I/O events with an optional timeout, storing at most `max` of them.
+    ///
+    /// `max` is at most the capacity of `events`.
+    pub fn wait(
+        &self,
+        events: &mut Events,
+        max: usize,
+        timeout: Option<Duration>,
+    ) -> io::Result<()> {
         log::trace!(
             "wait: kqueue_fd={:?}, timeout={:?}",
             self.kqueue_fd,
             timeout
         );
 
-        // Wait for I/O events.
-        let changelist = [];
-        let eventlist = &mut events.list;
-        let res = unsafe { kqueue::kevent(&self.kqueue_fd, &changelist, eventlist, timeout)? };
+        let timeout = timeout.map(|t| libc::timespec {
+            tv_sec: t.as_secs().min(libc::time_t::max_value() as u64) as libc::time_t,
+            tv_nsec: t.subsec_nanos() as _,
+        });
+
+        events.list.clear();
+        events.writable_on_eof = self.writable_on_eof;
 
-        log::trace!("new events: kqueue_fd={:?}, res={}", self.kqueue_fd, res);
+        // Submit the deferred changes along with the wait.
+        let changes = self.take_deferred();
+        let mut res = self.kevent_wait(&changes, events, max, timeout.as_ref());
+        if !changes.is_empty() {
+            res = self.finish_deferred(&changes, res, events, max, timeout.as_ref());
+        }
+        res?;
+
+        log::trace!(
+            "new events: kqueue_fd={:?}, res={}",
+            self.kqueue_fd,
+            events.list.len()
+        );
 
         // Clear the notification (if received) and re-register interest in it.
         self.notify.reregister(self)?;
@@ -163,6 +695,101 @@ impl Poller {
         Ok(())
     }
 
+    /// Submits `changelist` and waits for I/O events.
+    fn kevent_wait(
+        &self,
+        changelist: &[kqueue::Event],
+        events: &mut Events,
+        max: usize,
+        timeout: Option<&libc::timespec>,
+    ) -> io::Result<()> {
+        let kq = self.kqueue_fd.as_raw_fd();
+        if let Some(res) = qos::wait(
+            kq,
+            self.qos_class,
+            changelist,
+            &mut events.list,
+            &mut events.buffer,
+            max,
+            timeout,
+        ) {
+            return res;
+        }
+
+        // This calls `kevent` directly so that fewer events than fit in the list can be
+        // requested.
+        let res = unsafe {
+            libc::kevent(
+                kq,
+                changelist.as_ptr() as *const libc::kevent,
+                changelist.len() as _,
+                events.list.as_mut_ptr() as *mut libc::kevent,
+                max.try_into().unwrap_or(libc::c_int::MAX) as _,
+                timeout.map_or(ptr::null(), |t| t as *const libc::timespec),
+            )
+        };
+        if res == -1 {
+            return Err(io::Error::last_os_error());
+        }
+
+        // SAFETY: `kqueue::Event` is a transparent wrapper around `libc::kevent`, and the kernel
+        // initialized `res` of them.
+        unsafe {
+            events.list.set_len(res as usize);
+        }
+        Ok(())
+    }
+
+    /// Handles the result of a wait that submitted deferred changes.
+    ///
+    /// The kernel reports the changes that fail as `EV_ERROR` events and returns them without
+    /// waiting, or fails the whole call if there is no room for them in the list.
+    fn finish_deferred(
+        &self,
+        changes: &[kqueue::Event],
+        res: io::Result<()>,
+        events: &mut Events,
+        max: usize,
+        timeout: Option<&libc::timespec>,
+    ) -> io::Result<()> {
+        match res {
+            // The changes are applied before the wait is interrupted.
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => Err(err),
+
+            // Some changes may not have been applied, so submit them again on their own. Applying
+            // a change twice has the same effect as applying it once.
+            Err(err) => {
+                log::trace!(
+                    "wait: kqueue_fd={:?}, deferred changes failed: {}",
+                    self.kqueue_fd,
+                    err
+                );
+                if let Some(deferred) = &self.deferred {
+                    let mut deferred = deferred.0.lock().unwrap_or_else(|e| e.into_inner());
+                    deferred.splice(0..0, changes.iter().copied());
+                }
+                self.flush_deferred()?;
+                self.kevent_wait(&[], events, max, timeout)
+            }
+
+            Ok(())
+                if events
+                    .list
+                    .iter()
+                    .any(|ev| ev.flags().contains(kqueue::EventFlags::ERROR)) =>
+            {
+                let err = events.list.iter().find_map(change_error);
+                events.list.clear();
+                match err {
+                    Some(err) => Err(err),
+                    None => self.kevent_wait(&[], events, max, timeout),
+                }
+            }
+
+            Ok(()) => Ok(()),
+        }
+    }
+
     /// Sends a notification to wake up the current or next `wait()` call.
     pub fn notify(&self) -> io::Result<()> {
         log::trace!("notify: kqueue_fd={:?}", self.kqueue_fd);
@@ -191,9 +818,320 @@ impl Drop for Poller {
     }
 }
 
+/// The changes waiting to be submitted with the next wait.
+struct Deferred(Mutex<Vec<kqueue::Event>>);
+
+// SAFETY: `kqueue::Event` is only `!Send` because its `udata` is a raw pointer. The changes are
+// built by this module, which stores the key of a source in `udata` and never dereferences it.
+// The list is only accessed through the mutex, so sharing it is as safe as sending it.
+unsafe impl Send for Deferred {}
+unsafe impl Sync for Deferred {}
+
+impl Deferred {
+    /// Creates an empty list, if the builder defers changes.
+    fn new(builder: &PollerBuilder) -> Option<Deferred> {
+        if builder.deferred_changes {
+            Some(Deferred(Mutex::new(Vec::new())))
+        } else {
+            None
+        }
+    }
+}
+
+impl fmt::Debug for Deferred {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let len = self.0.try_lock().map(|changes| changes.len()).ok();
+        f.debug_struct("Deferred").field("len", &len).finish()
+    }
+}
+
+/// The number of file descriptors up to which `submit_batch()` builds its changes on the stack.
+const STACK_BATCH: usize = 64;
+
+/// Sets the `CLOEXEC` flag of a file descriptor if it isn't set already.
+fn set_cloexec(fd: &OwnedFd) -> io::Result<()> {
+    let fd_flags = fcntl_getfd(fd)?;
+    if !fd_flags.contains(FdFlags::CLOEXEC) {
+        fcntl_setfd(fd, fd_flags | FdFlags::CLOEXEC)?;
+    }
+    Ok(())
+}
+
+/// The changes that set the interest of a file descriptor.
+///
+/// The `EVFILT_EXCEPT` filter is only added for interest in priority data, and only deleted if
+/// `had_priority` tells that it was added before.
+fn changes(fd: RawFd, ev: Registration, mode: PollMode, had_priority: bool) -> Changes {
+    let mode_flags = mode_to_flags(mode);
+
+    // Without `EVFILT_EXCEPT`, priority data can only be waited for as readability. Hangups are
+    // reported by the read filter with `EV_EOF`.
+    let read_data = ev.readable || (ev.priority && !HAS_EXCEPT);
+    let read_flags = if read_data || ev.hup {
+        kqueue::EventFlags::ADD | mode_flags
+    } else {
+        kqueue::EventFlags::DELETE
+    };
+    let write_flags = if ev.writable {
+        kqueue::EventFlags::ADD | mode_flags
+    } else {
+        kqueue::EventFlags::DELETE
+    };
+
+    let read = kqueue::Event::new(
+        kqueue::EventFilter::Read(fd),
+        read_flags | kqueue::EventFlags::RECEIPT,
+        ev.key as _,
+    );
+    let write = kqueue::Event::new(
+        kqueue::EventFilter::Write(fd),
+        write_flags | kqueue::EventFlags::RECEIPT,
+        ev.key as _,
+    );
+    let mut changes = Changes {
+        list: [read; CHANGES],
+        len: 2,
+    };
+    changes.list[1] = write;
+
+    // Without interest in data, the read filter only has to report `EV_EOF`, which it does
+    // regardless of the low-water mark.
+    if ev.hup && !read_data {
+        let raw = as_raw_mut(&mut changes.list[0]);
+        raw.fflags |= libc::NOTE_LOWAT;
+        raw.data = std::isize::MAX as _;
+    }
+
+    #[cfg(any(
+        target_os = "macos",
+        target_os = "ios",
+        target_os = "tvos",
+        target_os = "watchos",
+        target_os = "dragonfly",
+        target_os = "openbsd",
+    ))]
+    {
+        let except_flags = if ev.priority {
+            Some(kqueue::EventFlags::ADD | mode_flags)
+        } else if had_priority {
+            Some(kqueue::EventFlags::DELETE)
+        } else {
+            None
+        };
+        if let Some(flags) = except_flags {
+            changes.list[2] = except_change(fd, flags | kqueue::EventFlags::RECEIPT, ev.key);
+            changes.len = 3;
+        }
+    }
+    let _ = had_priority;
+
+    changes
+}
+
+/// The changes returned by `changes()`, of which the first `len` are used.
+#[derive(Clone, Copy)]
+struct Changes {
+    list: [kqueue::Event; CHANGES],
+    len: usize,
+}
+
+impl AsRef<[kqueue::Event]> for Changes {
+    fn as_ref(&self) -> &[kqueue::Event] {
+        &self.list[..self.len]
+    }
+}
+
+impl AsMut<[kqueue::Event]> for Changes {
+    fn as_mut(&mut self) -> &mut [kqueue::Event] {
+        &mut self.list[..self.len]
+    }
+}
+
+/// Whether `EVFILT_EXCEPT` is available for priority data.
+const HAS_EXCEPT: bool = cfg!(any(
+    target_os = "macos",
+    target_os = "ios",
+    target_os = "tvos",
+    target_os = "watchos",
+    target_os = "dragonfly",
+    target_os = "openbsd",
+));
+
+/// The maximum number of changes that `changes()` returns for each file descriptor.
+const CHANGES: usize = 2 + HAS_EXCEPT as usize;
+
+/// Creates an `EVFILT_EXCEPT` change for out-of-band data.
+#[cfg(any(
+    target_os = "macos",
+    target_os = "ios",
+    target_os = "tvos",
+    target_os = "watchos",
+    target_os = "dragonfly",
+    target_os = "openbsd",
+))]
+fn except_change(fd: RawFd, flags: kqueue::EventFlags, key: usize) -> kqueue::Event {
+    // rustix doesn't know about `EVFILT_EXCEPT`, so change the filter of a read change.
+    let mut change = kqueue::Event::new(kqueue::EventFilter::Read(fd), flags, key as _);
+    let raw = as_raw_mut(&mut change);
+    raw.filter = libc::EVFILT_EXCEPT;
+    raw.fflags = libc::NOTE_OOB;
+    change
+}
+
+/// Whether `ev` was reported by the `EVFILT_EXCEPT` filter.
+fn is_except(ev: &kqueue::Event) -> bool {
+    #[cfg(any(
+        target_os = "macos",
+        target_os = "ios",
+        target_os = "tvos",
+        target_os = "watchos",
+        target_os = "dragonfly",
+        target_os = "openbsd",
+    ))]
+    {
+        if as_raw(ev).filter == libc::EVFILT_EXCEPT {
+            return true;
+        }
+    }
+
+    let _ = ev;
+    false
+}
+
+/// Copies an error that is reported for several changes.
+fn copy_error(err: &io::Error) -> io::Error {
+    match err.raw_os_error() {
+        Some(code) => io::Error::from_raw_os_error(code),
+        None => io::Error::new(err.kind(), err.to_string()),
+    }
+}
+
+/// The error of a change, as reported by its receipt.
+fn change_error(ev: &kqueue::Event) -> Option<io::Error> {
+    let data = event_data(ev);
+
+    // Explanation for ignoring EPIPE: https://github.com/tokio-rs/mio/issues/582
+    if (ev.flags().contains(kqueue::EventFlags::ERROR))
+        && data != 0
+        && data != Errno::NOENT.raw_os_error() as _
+        && data != Errno::PIPE.raw_os_error() as _
+    {
+        Some(io::Error::from_raw_os_error(data as _))
+    } else {
+        None
+    }
+}
+
+/// Returns the `data` field of an event.
+pub(crate) fn event_data(ev: &kqueue::Event) -> i64 {
+    // TODO: Once the data field is exposed in rustix, use that.
+    as_raw(ev).data as i64
+}
+
+/// Returns the raw `kevent` of a change, to set the fields that rustix doesn't expose.
+pub(crate) fn as_raw_mut(ev: &mut kqueue::Event) -> &mut libc::kevent {
+    // SAFETY: `kqueue::Event` is a transparent wrapper around `libc::kevent`.
+    unsafe { &mut *(ev as *mut kqueue::Event as *mut libc::kevent) }
+}
+
+/// Returns the raw `kevent` of an event, for the fields that rustix doesn't expose.
+pub(crate) fn as_raw(ev: &kqueue::Event) -> &libc::kevent {
+    // SAFETY: `kqueue::Event` is a transparent wrapper around `libc::kevent`.
+    unsafe { &*(ev as *const kqueue::Event as *const libc::kevent) }
+}
+
+/// Converts a raw event into an `Event` and its details.
+fn to_event(ev: &kqueue::Event, writable_on_eof: bool) -> (Event, EventDetails) {
+    // On some platforms, closing the read end of a pipe wakes up writers, but the
+    // event is reported as EVFILT_READ with the EV_EOF flag. Unless this is turned off with
+    // `PollerBuilder::writable_on_eof()`, such events are also reported as writable.
+    //
+    // https://github.com/golang/go/commit/23aad448b1e3f7c3b4ba2af90120bde91ac865b4
+    // Like with the other backends, priority data is also reported as readability.
+    let readable = is_except(ev)
+        || crate::os::kqueue::is_machport(ev)
+        || matches!(
+            ev.filter(),
+            kqueue::EventFilter::Read(..)
+                | kqueue::EventFilter::Vnode { .. }
+                | kqueue::EventFilter::Proc { .. }
+                | kqueue::EventFilter::Signal { .. }
+                | kqueue::EventFilter::Timer { .. }
+        );
+    let writable = matches!(ev.filter(), kqueue::EventFilter::Write(..))
+        || (writable_on_eof
+            && matches!(ev.filter(), kqueue::EventFilter::Read(..))
+            && (ev.flags().intersects(kqueue::EventFlags::EOF)));
+
+    let event = Event::new(ev.udata() as usize, readable, writable);
+    let mut details = EventDetails::new(
+        ev.flags().contains(kqueue::EventFlags::ERROR),
+        ev.flags().contains(kqueue::EventFlags::EOF),
+        is_except(ev),
+    )
+    .with_eof(
+        matches!(ev.filter(), kqueue::EventFilter::Read(..))
+            && ev.flags().contains(kqueue::EventFlags::EOF),
+    )
+    .with_write_closed(
+        matches!(ev.filter(), kqueue::EventFilter::Write(..))
+            && ev.flags().contains(kqueue::EventFlags::EOF),
+    );
+
+    // For reads, `data` is the number of bytes to read, or the listen backlog. The other
+    // filters tell what happened in `fflags`.
+    match ev.filter() {
+        kqueue::EventFilter::Read(..) => {
+            details.available = Some(usize::try_from(event_data(ev)).unwrap_or(0));
+        }
+        kqueue::EventFilter::Write(..) => {}
+        _ if as_raw(ev).fflags != 0 => details.backend_flags = Some(as_raw(ev).fflags),
+        _ => {}
+    }
+    (event, details)
+}
+
+/// The change for the `EVFILT_SIGNAL` filter of a signal.
+fn signal_change(signum: i32, key: usize, flags: kqueue::EventFlags) -> io::Result<kqueue::Event> {
+    let signal = rustix::process::Signal::from_raw(signum)
+        .ok_or_else(|| io::Error::from(io::ErrorKind::InvalidInput))?;
+    Ok(kqueue::Event::new(
+        kqueue::EventFilter::Signal { signal, times: 0 },
+        flags | kqueue::EventFlags::RECEIPT,
+        key as _,
+    ))
+}
+
+/// The change for the `NOTE_EXIT` filter of a child process.
+fn process_change(child: &Child, key: usize, flags: kqueue::EventFlags) -> kqueue::Event {
+    kqueue::Event::new(
+        kqueue::EventFilter::Proc {
+            pid: rustix::process::Pid::from_child(child),
+            flags: kqueue::ProcessEvents::EXIT,
+        },
+        flags | kqueue::EventFlags::RECEIPT,
+        key as _,
+    )
+}
+
+/// The file descriptor of an `EVFILT_READ`, `EVFILT_WRITE` or `EVFILT_EXCEPT` event.
+fn io_fd(ev: &kqueue::Event) -> Option<RawFd> {
+    match ev.filter() {
+        kqueue::EventFilter::Read(fd) | kqueue::EventFilter::Write(fd) => Some(fd),
+        _ if is_except(ev) => Some(as_raw(ev).ident as RawFd),
+        _ => None,
+    }
+}
+
 /// A list of reported I/O events.
 pub struct Events {
     list: Vec<kqueue::Event>,
+
+    /// Whether a read filter with `EV_EOF` is also reported as writable, set by the poller.
+    writable_on_eof: bool,
+
+    /// The buffer that waits at a quality-of-service class use.
+    buffer: qos::Buffer,
 }
 
 unsafe impl Send for Events {}
@@ -201,32 +1139,84 @@ unsafe impl Send for Events {}
 impl Events {
     /// Creates an empty list.
     pub fn new() -> Events {
+        Events::with_capacity(1024)
+    }
+
+    /// Creates an empty list that holds at most `capacity` events per wait.
+    pub fn with_capacity(capacity: usize) -> Events {
+        Events {
+            list: Vec::with_capacity(capacity),
+            writable_on_eof: true,
+            buffer: Default::default(),
+        }
+    }
+
+    /// Creates a list from raw events.
+    #[cfg(fuzzing)]
+    pub fn from_raw(list: Vec<crate::sys::RawEvent>) -> Events {
         Events {
-            list: Vec::with_capacity(1024),
+            list: list
+                .into_iter()
+                .map(crate::sys::RawEvent::into_inner)
+                .collect(),
+            writable_on_eof: true,
+            buffer: Default::default(),
         }
     }
 
     /// Iterates over I/O events.
-    pub fn iter(&self) -> impl Iterator<Item = Event> + '_ {
-        // On some platforms, closing the read end of a pipe wakes up writers, but the
-        // event is reported as EVFILT_READ with the EV_EOF flag.
-        //
-        // https://github.com/golang/go/commit/23aad448b1e3f7c3b4ba2af90120bde91ac865b4
-        self.list.iter().map(|ev| Event {
-            key: ev.udata() as usize,
-            readable: matches!(
-                ev.filter(),
-                kqueue::EventFilter::Read(..)
-                    | kqueue::EventFilter::Vnode { .. }
-                    | kqueue::EventFilter::Proc { .. }
-                    | kqueue::EventFilter::Signal { .. }
-                    | kqueue::EventFilter::Timer { .. }
-            ),
-            writable: matches!(ev.filter(), kqueue::EventFilter::Write(..))
-                || (matches!(ev.filter(), kqueue::EventFilter::Read(..))
-                    && (ev.flags().intersects(kqueue::EventFlags::EOF))),
+    ///
+    /// kqueue reports the `EVFILT_READ`, `EVFILT_WRITE` and `EVFILT_EXCEPT` filters of a file
+    /// descriptor as separate events. When they are next to each other in the list, they are
+    /// merged into a single event, like the other backends report them.
+    pub fn iter(&self) -> impl Iterator<Item = (Event, EventDetails)> + '_ {
+        let mut list = self.list.iter().peekable();
+        std::iter::from_fn(move || {
+            let ev = list.next()?;
+            let (mut event, mut details) = to_event(ev, self.writable_on_eof);
+
+            if let Some(fd) = io_fd(ev) {
+                while let Some(next) = list.peek() {
+                    if io_fd(next) != Some(fd) || next.udata() != ev.udata() {
+                        break;
+                    }
+
+                    let (next_event, next_details) = to_event(next, self.writable_on_eof);
+                    event.merge(next_event);
+                    details.merge(next_details);
+                    list.next();
+                }
+            }
+
+            Some((event, details))
         })
     }
+
+    /// Iterates over the keys of the I/O events.
+    pub fn keys(&self) -> impl Iterator<Item = usize> + '_ {
+        self.iter().map(|(ev, _)| ev.key)
+    }
+
+    /// Clears the list.
+    pub fn clear(&mut self) {
+        self.list.clear();
+    }
+
+    /// The maximum number of events that a single call to `wait` stores.
+    pub fn capacity(&self) -> usize {
+        self.list.capacity()
+    }
+
+    /// Moves the events of another list to the end of this one, leaving the other one empty.
+    pub fn append(&mut self, other: &mut Events) {
+        self.list.append(&mut other.list);
+        self.writable_on_eof = other.writable_on_eof;
+    }
+
+    /// The raw events reported by kqueue.
+    pub fn as_raw_slice(&self) -> &[crate::sys::RawEvent] {
+        crate::sys::RawEvent::from_slice(&self.list)
+    }
 }
 
 pub(crate) fn mode_to_flags(mode: PollMode) -> kqueue::EventFlags {
@@ -237,6 +1227,10 @@ pub(crate) fn mode_to_flags(mode: PollMode) -> kqueue::EventFlags {
         PollMode::Level => EV::empty(),
         PollMode::Edge => EV::CLEAR,
         PollMode::EdgeOneshot => EV::ONESHOT | EV::CLEAR,
+        // kqueue has no exclusive wakeups, so this is level-triggered.
+        PollMode::Exclusive => EV::empty(),
+        // SAFETY: `EV_DISPATCH` is a valid flag, it's only missing from rustix.
+        PollMode::Dispatch => unsafe { EV::from_bits_unchecked(libc::EV_DISPATCH as _) },
     }
 }
 
@@ -276,7 +1270,7 @@ mod notify {
                     user_flags: kqueue::UserDefinedFlags::new(0),
                 },
                 kqueue::EventFlags::ADD | kqueue::EventFlags::RECEIPT | kqueue::EventFlags::CLEAR,
-                crate::NOTIFY_KEY as _,
+                poller.notify_key as _,
             )])
         }
 
@@ -296,7 +1290,7 @@ mod notify {
                     user_flags: kqueue::UserDefinedFlags::new(0),
                 },
                 kqueue::EventFlags::ADD | kqueue::EventFlags::RECEIPT,
-                crate::NOTIFY_KEY as _,
+                poller.notify_key as _,
             )])?;
 
             Ok(())
@@ -312,7 +1306,7 @@ mod notify {
                     user_flags: kqueue::UserDefinedFlags::new(0),
                 },
                 kqueue::EventFlags::DELETE | kqueue::EventFlags::RECEIPT,
-                crate::NOTIFY_KEY as _,
+                poller.notify_key as _,
             )])
         }
 
@@ -320,6 +1314,12 @@ mod notify {
         pub(super) fn has_fd(&self, _fd: RawFd) -> bool {
             false
         }
+
+        /// Checks that the notification is still usable.
+        pub(super) fn check_invariants(&self) -> io::Result<()> {
+            // `EVFILT_USER` has no file descriptor that could be closed.
+            Ok(())
+        }
     }
 }
 
@@ -333,7 +1333,7 @@ mod notify {
 )))]
 mod notify {
     use super::Poller;
-    use crate::{Event, PollMode, NOTIFY_KEY};
+    use crate::{Event, PollMode};
     use std::io::{self, prelude::*};
     use std::os::unix::{
         io::{AsRawFd, RawFd},
@@ -370,7 +1370,7 @@ mod notify {
             // Register the read end of this pipe.
             poller.add(
                 self.read_stream.as_raw_fd(),
-                Event::readable(NOTIFY_KEY),
+                Event::readable(poller.notify_key).into(),
                 PollMode::Oneshot,
             )
         }
@@ -383,7 +1383,7 @@ mod notify {
             // Reregister the read end of this pipe.
             poller.modify(
                 self.read_stream.as_raw_fd(),
-                Event::readable(NOTIFY_KEY),
+                Event::readable(poller.notify_key).into(),
                 PollMode::Oneshot,
             )
         }
@@ -407,5 +1407,284 @@ mod notify {
         pub(super) fn has_fd(&self, fd: RawFd) -> bool {
             self.read_stream.as_raw_fd() == fd
         }
+
+        /// Checks that both ends of the pipe are still open.
+        pub(super) fn check_invariants(&self) -> io::Result<()> {
+            rustix::io::fcntl_getfd(&self.read_stream)?;
+            rustix::io::fcntl_getfd(&self.write_stream)?;
+            Ok(())
+        }
+    }
+}
+
+/// Submitting changes and waiting with `kevent_qos()`, which passes a quality-of-service class.
+mod qos {
+    use std::io;
+    use std::os::unix::io::RawFd;
+
+    use rustix::io::kqueue;
+
+    use crate::QosClass;
+
+    cfg_if::cfg_if! {
+        if #[cfg(any(
+            target_os = "macos",
+            target_os = "ios",
+            target_os = "tvos",
+            target_os = "watchos",
+        ))] {
+            use std::convert::TryInto;
+            use std::os::raw::{c_int, c_uint, c_ulong, c_void};
+            use std::ptr;
+            use std::slice;
+            use std::sync::atomic::{AtomicBool, Ordering};
+
+            use super::as_raw;
+
+            // These are declared in `<sys/qos.h>`, which `libc` doesn't bind.
+            const QOS_CLASS_USER_INTERACTIVE: c_uint = 0x21;
+            const QOS_CLASS_USER_INITIATED: c_uint = 0x19;
+            const QOS_CLASS_DEFAULT: c_uint = 0x15;
+            const QOS_CLASS_UTILITY: c_uint = 0x11;
+            const QOS_CLASS_BACKGROUND: c_uint = 0x09;
+
+            /// `struct kevent_qos_s` from `<sys/event.h>`, which `libc` doesn't bind.
+            #[repr(C)]
+            #[derive(Clone, Copy)]
+            pub(super) struct KeventQos {
+                ident: u64,
+                filter: i16,
+                flags: u16,
+                qos: i32,
+                udata: u64,
+                fflags: u32,
+                xflags: u32,
+                data: i64,
+                ext: [u64; 4],
+            }
+
+            extern "C" {
+                fn kevent_qos(
+                    kq: c_int,
+                    changelist: *const KeventQos,
+                    nchanges: c_int,
+                    eventlist: *mut KeventQos,
+                    nevents: c_int,
+                    data_out: *mut c_void,
+                    data_available: *mut usize,
+                    flags: c_uint,
+                ) -> c_int;
+                fn _pthread_qos_class_encode(
+                    qos_class: c_uint,
+                    relative_priority: c_int,
+                    flags: c_ulong,
+                ) -> c_ulong;
+            }
+
+            /// The buffer that a wait converts its changes and events in, kept between waits.
+            pub(super) type Buffer = Vec<KeventQos>;
+
+            /// The number of changes that are converted on the stack at a time.
+            const CHUNK: usize = 32;
+
+            const EMPTY: KeventQos = KeventQos {
+                ident: 0,
+                filter: 0,
+                flags: 0,
+                qos: 0,
+                udata: 0,
+                fflags: 0,
+                xflags: 0,
+                data: 0,
+                ext: [0; 4],
+            };
+
+            /// Set once `kevent_qos()` fails because the kernel doesn't support it.
+            static UNSUPPORTED: AtomicBool = AtomicBool::new(false);
+
+            /// Submits changes at `class` without waiting, and writes their receipts into
+            /// `eventlist`.
+            ///
+            /// Returns `None` if `kevent()` has to be used instead.
+            pub(super) fn submit(
+                kq: RawFd,
+                class: Option<QosClass>,
+                changelist: &[kqueue::Event],
+                eventlist: &mut [kqueue::Event],
+            ) -> Option<io::Result<usize>> {
+                let priority = priority(class?)?;
+
+                // Every change has its own receipt, so submitting them in chunks has the same
+                // effect as submitting them at once.
+                let mut changes = [EMPTY; CHUNK];
+                let mut receipts = [EMPTY; CHUNK];
+                let mut received = 0;
+                for chunk in changelist.chunks(CHUNK) {
+                    for (change, ev) in changes.iter_mut().zip(chunk) {
+                        *change = to_qos(ev, priority);
+                    }
+                    let room = (eventlist.len() - received).min(CHUNK);
+                    let res = unsafe {
+                        kevent_qos(
+                            kq,
+                            changes.as_ptr(),
+                            chunk.len() as c_int,
+                            receipts.as_mut_ptr(),
+                            room as c_int,
+                            ptr::null_mut(),
+                            ptr::null_mut(),
+                            libc::KEVENT_FLAG_IMMEDIATE,
+                        )
+                    };
+                    if res == -1 {
+                        return fail(received == 0).map(Err);
+                    }
+                    let receipts = &receipts[..res as usize];
+                    for (ev, receipt) in eventlist[received..].iter_mut().zip(receipts) {
+                        *ev = from_qos(receipt);
+                    }
+                    received += res as usize;
+                }
+                Some(Ok(received))
+            }
+
+            /// Submits `changelist` at `class` and waits for at most `max` events, which are
+            /// appended to `list`.
+            ///
+            /// `kevent_qos()` can only wait without a timeout or not at all, so `None` is
+            /// returned for other timeouts, or if `kevent()` has to be used for another reason.
+            pub(super) fn wait(
+                kq: RawFd,
+                class: Option<QosClass>,
+                changelist: &[kqueue::Event],
+                list: &mut Vec<kqueue::Event>,
+                buffer: &mut Buffer,
+                max: usize,
+                timeout: Option<&libc::timespec>,
+            ) -> Option<io::Result<()>> {
+                let flags = match timeout {
+                    None => 0,
+                    Some(t) if t.tv_sec == 0 && t.tv_nsec == 0 => {
+                        libc::KEVENT_FLAG_IMMEDIATE
+                    }
+                    Some(_) => return None,
+                };
+                let priority = priority(class?)?;
+
+                // The events are received after the changes in the buffer.
+                buffer.clear();
+                buffer.reserve(changelist.len() + max);
+                buffer.extend(changelist.iter().map(|ev| to_qos(ev, priority)));
+                let base = buffer.as_mut_ptr();
+                let res = unsafe {
+                    kevent_qos(
+                        kq,
+                        base,
+                        changelist.len() as c_int,
+                        base.add(changelist.len()),
+                        max.try_into().unwrap_or(c_int::MAX),
+                        ptr::null_mut(),
+                        ptr::null_mut(),
+                        flags,
+                    )
+                };
+                if res == -1 {
+                    return fail(true).map(Err);
+                }
+
+                // SAFETY: The kernel initialized `res` events after the changes, and `list` has
+                // room for `max` of them.
+                let events = unsafe {
+                    slice::from_raw_parts(base.add(changelist.len()), res as usize)
+                };
+                list.extend(events.iter().map(from_qos));
+                Some(Ok(()))
+            }
+
+            /// Returns the error of a failed call to `kevent_qos()`, or `None` if the kernel
+            /// doesn't support it and nothing was submitted yet, so that `kevent()` is used.
+            fn fail(first: bool) -> Option<io::Error> {
+                let err = io::Error::last_os_error();
+                if first && err.raw_os_error() == Some(libc::ENOSYS) {
+                    log::trace!("kevent_qos is not supported, falling back to kevent");
+                    UNSUPPORTED.store(true, Ordering::Relaxed);
+                    return None;
+                }
+                Some(err)
+            }
+
+            /// The priority that `kevent_qos()` takes for `class`, unless it isn't supported.
+            fn priority(class: QosClass) -> Option<i32> {
+                if UNSUPPORTED.load(Ordering::Relaxed) {
+                    return None;
+                }
+                let class = match class {
+                    QosClass::UserInteractive => QOS_CLASS_USER_INTERACTIVE,
+                    QosClass::UserInitiated => QOS_CLASS_USER_INITIATED,
+                    QosClass::Default => QOS_CLASS_DEFAULT,
+                    QosClass::Utility => QOS_CLASS_UTILITY,
+                    QosClass::Background => QOS_CLASS_BACKGROUND,
+                };
+                Some(unsafe { _pthread_qos_class_encode(class, 0, 0) } as i32)
+            }
+
+            /// Converts a change into a `kevent_qos_s` at `priority`.
+            fn to_qos(ev: &kqueue::Event, priority: i32) -> KeventQos {
+                let ev = as_raw(ev);
+                KeventQos {
+                    ident: ev.ident as u64,
+                    filter: ev.filter,
+                    flags: ev.flags,
+                    qos: priority,
+                    udata: ev.udata as usize as u64,
+                    fflags: ev.fflags,
+                    xflags: 0,
+                    data: ev.data as i64,
+                    ext: [0; 4],
+                }
+            }
+
+            /// Converts an event reported by `kevent_qos()` back.
+            fn from_qos(ev: &KeventQos) -> kqueue::Event {
+                let ev = libc::kevent {
+                    ident: ev.ident as usize,
+                    filter: ev.filter,
+                    flags: ev.flags,
+                    fflags: ev.fflags,
+                    data: ev.data as isize,
+                    udata: ev.udata as usize as *mut c_void,
+                };
+                // SAFETY: `kqueue::Event` is a transparent wrapper around `libc::kevent`.
+                unsafe { std::mem::transmute::<libc::kevent, kqueue::Event>(ev) }
+            }
+        } else {
+            /// Nothing is buffered, as `kevent_qos()` only exists on Apple platforms.
+            pub(super) type Buffer = ();
+
+            /// Returns `None`, so that `kevent()` is used.
+            pub(super) fn submit(
+                kq: RawFd,
+                class: Option<QosClass>,
+                changelist: &[kqueue::Event],
+                eventlist: &mut [kqueue::Event],
+            ) -> Option<io::Result<usize>> {
+                let _ = (kq, class, changelist, eventlist);
+                None
+            }
+
+            /// Returns `None`, so that `kevent()` is used.
+            pub(super) fn wait(
+                kq: RawFd,
+                class: Option<QosClass>,
+                changelist: &[kqueue::Event],
+                list: &mut Vec<kqueue::Event>,
+                buffer: &mut Buffer,
+                max: usize,
+                timeout: Option<&libc::timespec>,
+            ) -> Option<io::Result<()>> {
+                let _ = (kq, class, changelist, list, buffer, max, timeout);
+                None
+            }
+        }
     }
 }
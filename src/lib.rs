@@ -55,11 +55,12 @@
 #![allow(clippy::useless_conversion, clippy::unnecessary_cast)]
 #![cfg_attr(docsrs, feature(doc_cfg))]
 
+use std::collections::{HashMap, HashSet};
 use std::fmt;
 use std::io;
-use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::Mutex;
-use std::time::Duration;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use std::usize;
 
 use cfg_if::cfg_if;
@@ -69,16 +70,21 @@ cfg_if! {
     // the backend that uses poll, and is not a public API.
     if #[cfg(polling_test_poll_backend)] {
         mod poll;
-        use poll as sys;
+        mod signal;
+        mod sockopt;
+        use poll as platform;
     } else if #[cfg(any(target_os = "linux", target_os = "android"))] {
         mod epoll;
-        use epoll as sys;
+        mod sockopt;
+        use epoll as platform;
     } else if #[cfg(any(
         target_os = "illumos",
         target_os = "solaris",
     ))] {
         mod port;
-        use port as sys;
+        mod signal;
+        mod sockopt;
+        use port as platform;
     } else if #[cfg(any(
         target_os = "macos",
         target_os = "ios",
@@ -90,7 +96,7 @@ cfg_if! {
         target_os = "dragonfly",
     ))] {
         mod kqueue;
-        use kqueue as sys;
+        use kqueue as platform;
     } else if #[cfg(any(
         target_os = "vxworks",
         target_os = "fuchsia",
@@ -98,22 +104,134 @@ cfg_if! {
         unix,
     ))] {
         mod poll;
-        use poll as sys;
+        mod signal;
+        mod sockopt;
+        use poll as platform;
     } else if #[cfg(target_os = "windows")] {
         mod iocp;
-        use iocp as sys;
+        use iocp as platform;
     } else {
         compile_error!("polling does not support this target OS");
     }
 }
 
+mod error;
+mod event64;
+mod guard;
+mod handle;
+mod interest;
+mod key;
+pub mod mock;
 pub mod os;
+mod sink;
+mod split;
+pub mod sys;
+pub mod timer;
+mod waiters;
+
+pub use error::PollerError;
+pub use event64::Event64;
+pub use guard::WatchGuard;
+pub use handle::PollerHandle;
+pub use interest::Interest;
+pub(crate) use interest::Registration;
+pub use key::KeyAllocator;
+pub use sink::WaitSink;
+use waiters::Waiters;
+
+#[cfg(any(
+    target_os = "linux",
+    target_os = "android",
+    all(
+        any(
+            target_os = "macos",
+            target_os = "ios",
+            target_os = "tvos",
+            target_os = "watchos",
+            target_os = "freebsd",
+            target_os = "netbsd",
+            target_os = "openbsd",
+            target_os = "dragonfly",
+        ),
+        not(polling_test_poll_backend),
+    ),
+))]
+mod watch;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub use watch::InotifyHandle;
+#[cfg(any(
+    target_os = "linux",
+    target_os = "android",
+    all(
+        any(
+            target_os = "macos",
+            target_os = "ios",
+            target_os = "tvos",
+            target_os = "watchos",
+            target_os = "freebsd",
+            target_os = "netbsd",
+            target_os = "openbsd",
+            target_os = "dragonfly",
+        ),
+        not(polling_test_poll_backend),
+    ),
+))]
+pub use watch::{PathWatch, WatchFlags};
+
+#[cfg(unix)]
+mod sigset;
+#[cfg(unix)]
+pub use sigset::SigSet;
+
+#[cfg(not(polling_no_io_safety))]
+mod pipe;
+#[cfg(not(polling_no_io_safety))]
+pub use pipe::pipe;
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+mod netlink;
+
+#[cfg(feature = "mio-compat")]
+#[cfg_attr(docsrs, doc(cfg(feature = "mio-compat")))]
+pub mod compat;
+
+#[cfg(fuzzing)]
+#[doc(hidden)]
+pub mod fuzz;
 
 /// Key associated with notifications.
-const NOTIFY_KEY: usize = std::usize::MAX;
+///
+/// This is the key that a [`Poller`] uses for its internal notifications unless a different one
+/// is set with [`PollerBuilder::notify_key()`]. Sources cannot be registered with this key.
+///
+/// [`Poller::wait()`] never reports events with this key, but events reported through
+/// [`Events::as_raw_slice()`] may use it. Use [`Poller::is_notify_event()`] to check for them
+/// if the poller may have been configured with a different key.
+pub const NOTIFY_KEY: usize = std::usize::MAX;
 
 /// Indicates that a file descriptor or socket can read or write without blocking.
+///
+/// The conditions that the poller reports in addition to readability and writability, such as
+/// errors and hangups, are returned separately as [`EventDetails`] by
+/// [`Events::iter_details()`].
+///
+/// # Layout
+///
+/// This type has the layout of the following C struct, which won't change before the next major
+/// version:
+///
+/// ```c
+/// struct event {
+///     uintptr_t key;  /* offset 0 */
+///     bool readable;  /* offset sizeof(uintptr_t) */
+///     bool writable;  /* offset sizeof(uintptr_t) + 1 */
+/// };
+/// ```
+///
+/// Its size is twice the size of a `usize`, and its alignment is that of a `usize`.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[repr(C)]
 pub struct Event {
     /// Key identifying the file descriptor or socket.
     pub key: usize,
@@ -123,8 +241,186 @@ pub struct Event {
     pub writable: bool,
 }
 
+// The layout of `Event` is part of the public API.
+const _: [(); 2 * std::mem::size_of::<usize>()] = [(); std::mem::size_of::<Event>()];
+const _: [(); std::mem::align_of::<usize>()] = [(); std::mem::align_of::<Event>()];
+
+/// The conditions that were reported with an [`Event`], in addition to its readiness.
+///
+/// These are returned next to each event by [`Events::iter_details()`]. Events that the poller
+/// doesn't report these conditions for, like the keys woken with [`Poller::wake()`], have the
+/// default details, in which none of them is set.
+///
+/// # Examples
+///
+/// ```
+/// use polling::{Event, Events, Poller};
+/// use std::net::{Shutdown, TcpListener, TcpStream};
+/// use std::time::Duration;
+///
+/// let listener = TcpListener::bind("127.0.0.1:0")?;
+/// let stream = TcpStream::connect(listener.local_addr()?)?;
+/// let (peer, _) = listener.accept()?;
+///
+/// let poller = Poller::new()?;
+/// poller.add(&stream, Event::readable(1))?;
+/// peer.shutdown(Shutdown::Write)?;
+///
+/// let mut events = Events::new();
+/// poller.wait_events(&mut events, Some(Duration::from_secs(10)))?;
+/// for (ev, details) in events.iter_details() {
+///     if details.is_eof() {
+///         // Read what is left of stream `ev.key`, then close it.
+///     }
+/// }
+/// # poller.delete(&stream)?;
+/// # std::io::Result::Ok(())
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct EventDetails {
+    err: bool,
+    hup: bool,
+    pri: bool,
+    eof: bool,
+    write_closed: bool,
+    available: Option<usize>,
+    backend_flags: Option<u32>,
+}
+
+impl EventDetails {
+    /// Creates the details of an event with the given conditions.
+    pub(crate) fn new(err: bool, hup: bool, pri: bool) -> EventDetails {
+        EventDetails {
+            err,
+            hup,
+            pri,
+            ..EventDetails::default()
+        }
+    }
+
+    /// Whether an error condition was reported, such as a failed connection.
+    ///
+    /// Sources with an error are also reported as readable and writable, so that the error can
+    /// be retrieved by the next I/O operation.
+    pub fn is_err(&self) -> bool {
+        self.err
+    }
+
+    /// Whether a hangup was reported, such as the peer shutting down its side of the connection.
+    ///
+    /// This is reported as `EPOLLHUP` or `EPOLLRDHUP` with epoll, `EV_EOF` with kqueue,
+    /// `POLLHUP` with `poll()` and event ports, and as an aborted or disconnected socket with
+    /// IOCP.
+    pub fn is_hup(&self) -> bool {
+        self.hup
+    }
+
+    /// Whether the end of the stream was reported, so that reads return no more data after what
+    /// is still buffered.
+    ///
+    /// This is reported as `EPOLLRDHUP` or `EPOLLHUP` with epoll, as a read filter with `EV_EOF`
+    /// with kqueue, `POLLHUP` with `poll()` and event ports, and as a gracefully disconnected
+    /// socket with IOCP. Unlike [`is_hup()`][`EventDetails::is_hup()`], it is not reported for
+    /// the write side of a source with kqueue, or for aborted connections with IOCP.
+    pub fn is_eof(&self) -> bool {
+        self.eof
+    }
+
+    /// Sets whether the end of the stream was reported.
+    pub(crate) fn with_eof(mut self, eof: bool) -> EventDetails {
+        self.eof = eof;
+        self
+    }
+
+    /// Whether the writing half of the source was closed, so that writes fail.
+    ///
+    /// This is reported when the peer has reset the connection or the read end of a pipe was
+    /// closed: as `EPOLLHUP` or `EPOLLERR` with epoll, as a write filter with `EV_EOF` with
+    /// kqueue, `POLLHUP` or `POLLERR` with `poll()` and event ports, and as an aborted or
+    /// failed connection with IOCP.
+    pub fn is_write_closed(&self) -> bool {
+        self.write_closed
+    }
+
+    /// Sets whether the writing half of the source was closed.
+    pub(crate) fn with_write_closed(mut self, closed: bool) -> EventDetails {
+        self.write_closed = closed;
+        self
+    }
+
+    /// Whether priority data was reported, such as TCP out-of-band data.
+    ///
+    /// Priority data is waited for with [`Interest::PRIORITY`].
+    pub fn is_pri(&self) -> bool {
+        self.pri
+    }
+
+    /// The amount of data that can be read, if the poller reported it.
+    ///
+    /// For a listening socket, this is the number of connections waiting to be accepted, and for
+    /// other sources, the number of bytes that can be read. It can be used to size reads and
+    /// accept loops without asking the operating system again, but it is only a hint: by the time
+    /// the source is read, more data may have arrived.
+    ///
+    /// This is only reported for readable events by kqueue. Other backends don't report it, so
+    /// this returns `None`.
+    pub fn available(&self) -> Option<usize> {
+        self.available
+    }
+
+    /// The flags that the backend reported with the event, if it reported any.
+    ///
+    /// With kqueue, these are the `fflags` of filters other than `EVFILT_READ` and
+    /// `EVFILT_WRITE`, which tell what happened: `NOTE_WRITE` or `NOTE_DELETE` for a path added
+    /// with [`Poller::add_path()`], `NOTE_EXIT` for a process, and so on. They are only reported
+    /// when some of them are set. Other backends don't report them, so this returns `None`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use polling::{Events, Poller};
+    /// use std::time::Duration;
+    ///
+    /// let poller = Poller::new()?;
+    /// let mut events = Events::new();
+    /// poller.wait_events(&mut events, Some(Duration::from_secs(0)))?;
+    ///
+    /// for (ev, details) in events.iter_details() {
+    ///     if let Some(flags) = details.backend_flags() {
+    ///         println!("key {} reported flags {:#x}", ev.key, flags);
+    ///     }
+    /// }
+    /// # std::io::Result::Ok(())
+    /// ```
+    pub fn backend_flags(&self) -> Option<u32> {
+        self.backend_flags
+    }
+
+    /// Adds the conditions of `other` to these details.
+    ///
+    /// The number of available bytes is kept if it is already known, and the backend flags of
+    /// both are combined.
+    pub fn merge(&mut self, other: EventDetails) {
+        self.err |= other.err;
+        self.hup |= other.hup;
+        self.pri |= other.pri;
+        self.eof |= other.eof;
+        self.write_closed |= other.write_closed;
+        self.available = self.available.or(other.available);
+        self.backend_flags = match (self.backend_flags, other.backend_flags) {
+            (Some(flags), Some(other_flags)) => Some(flags | other_flags),
+            (flags, other_flags) => flags.or(other_flags),
+        };
+    }
+}
+
 /// The mode in which the poller waits for I/O events.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "kebab-case")
+)]
 #[non_exhaustive]
 pub enum PollMode {
     /// Poll in oneshot mode.
@@ -165,207 +461,1894 @@ pub enum PollMode {
     /// this mode in an unsupported operating system will raise an error. You can check if
     /// the operating system supports this mode by calling `Poller::supports_edge`.
     EdgeOneshot,
+
+    /// Poll in level-triggered mode, waking up only one of the pollers that wait for the same
+    /// file descriptor.
+    ///
+    /// This avoids the thundering herd of several processes or pollers sharing a listening
+    /// socket, each accepting connections in its own loop. With epoll, this is `EPOLLEXCLUSIVE`,
+    /// which requires Linux 4.5 or newer. Interest in priority data and hangups is ignored in
+    /// this mode. You can check if the operating system supports exclusive wakeups by calling
+    /// `Poller::supports_exclusive`.
+    ///
+    /// Where exclusive wakeups are supported, a source can only be modified to this mode if it
+    /// was added in this mode. Otherwise, an error of kind [`io::ErrorKind::InvalidInput`] is
+    /// returned and the source keeps its registration.
+    ///
+    /// Other backends don't have exclusive wakeups. If they support level-triggered mode, this
+    /// behaves like `Level` and all pollers are woken up. Otherwise, registering a file
+    /// descriptor with this mode raises an error.
+    Exclusive,
+
+    /// Poll in dispatch mode.
+    ///
+    /// Like in the `Oneshot` mode, the poller delivers one event per file descriptor or socket
+    /// and then disables it, but the registration itself is kept, so that interest can be
+    /// re-enabled cheaply by calling `Poller::modify`. This lets a dispatcher hand a file
+    /// descriptor over to a worker thread, which re-enables it once it is done.
+    ///
+    /// With kqueue, this is `EV_DISPATCH`, while `Oneshot` uses `EV_ONESHOT`, which removes the
+    /// registration after the event. The other backends keep the registration in oneshot mode
+    /// anyway, so this behaves exactly like `Oneshot` on them.
+    Dispatch,
 }
 
 impl Event {
-    /// All kinds of events (readable and writable).
+    /// Creates an event with the given key and readiness.
     ///
-    /// Equivalent to: `Event { key, readable: true, writable: true }`
-    pub fn all(key: usize) -> Event {
+    /// # Examples
+    ///
+    /// ```
+    /// use polling::Event;
+    ///
+    /// let event = Event::new(7, true, false);
+    /// assert_eq!(event, Event::readable(7));
+    /// ```
+    pub fn new(key: usize, readable: bool, writable: bool) -> Event {
         Event {
             key,
-            readable: true,
-            writable: true,
+            readable,
+            writable,
         }
     }
 
+    /// All kinds of events (readable and writable).
+    ///
+    /// Equivalent to: `Event::new(key, true, true)`
+    pub fn all(key: usize) -> Event {
+        Event::new(key, true, true)
+    }
+
     /// Only the readable event.
     ///
-    /// Equivalent to: `Event { key, readable: true, writable: false }`
+    /// Equivalent to: `Event::new(key, true, false)`
     pub fn readable(key: usize) -> Event {
-        Event {
-            key,
-            readable: true,
-            writable: false,
-        }
+        Event::new(key, true, false)
     }
 
     /// Only the writable event.
     ///
-    /// Equivalent to: `Event { key, readable: false, writable: true }`
+    /// Equivalent to: `Event::new(key, false, true)`
     pub fn writable(key: usize) -> Event {
-        Event {
-            key,
-            readable: false,
-            writable: true,
-        }
+        Event::new(key, false, true)
     }
 
     /// No events.
     ///
-    /// Equivalent to: `Event { key, readable: false, writable: false }`
+    /// Equivalent to: `Event::new(key, false, false)`
     pub fn none(key: usize) -> Event {
-        Event {
-            key,
-            readable: false,
-            writable: false,
-        }
+        Event::new(key, false, false)
     }
-}
 
-/// Waits for I/O events.
-pub struct Poller {
-    poller: sys::Poller,
-    events: Mutex<sys::Events>,
-    notified: AtomicBool,
-}
-
-impl Poller {
-    /// Creates a new poller.
+    /// Adds interest in readability.
+    ///
+    /// Together with [`with_writable()`][`Event::with_writable()`], this composes an interest
+    /// without setting the fields one by one.
     ///
     /// # Examples
     ///
     /// ```
-    /// use polling::Poller;
+    /// use polling::Event;
     ///
-    /// let poller = Poller::new()?;
-    /// # std::io::Result::Ok(())
+    /// let interest = Event::none(7).with_readable();
+    /// assert!(interest.readable && !interest.writable);
     /// ```
-    pub fn new() -> io::Result<Poller> {
-        Ok(Poller {
-            poller: sys::Poller::new()?,
-            events: Mutex::new(sys::Events::new()),
-            notified: AtomicBool::new(false),
-        })
+    pub fn with_readable(mut self) -> Event {
+        self.readable = true;
+        self
     }
 
-    /// Tell whether or not this `Poller` supports level-triggered polling.
-    pub fn supports_level(&self) -> bool {
-        self.poller.supports_level()
+    /// Adds interest in writability.
+    pub fn with_writable(mut self) -> Event {
+        self.writable = true;
+        self
     }
 
-    /// Tell whether or not this `Poller` supports edge-triggered polling.
-    pub fn supports_edge(&self) -> bool {
-        self.poller.supports_edge()
+    /// Adds the readiness of `other` to this event.
+    ///
+    /// The key of this event is kept, so this is meant for events with the same key.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use polling::Event;
+    ///
+    /// let mut event = Event::readable(1);
+    /// event.merge(Event::writable(1));
+    /// assert_eq!(event, Event::all(1));
+    /// ```
+    pub fn merge(&mut self, other: Event) {
+        self.readable |= other.readable;
+        self.writable |= other.writable;
     }
 
-    /// Adds a file descriptor or socket to the poller.
+    /// Checks whether a file descriptor or socket is readable or writable right now.
     ///
-    /// A file descriptor or socket is considered readable or writable when a read or write
-    /// operation on it would not block. This doesn't mean the read or write operation will
-    /// succeed, it only means the operation will return immediately.
+    /// This doesn't block and doesn't involve a [`Poller`]. It uses `poll()` with a zero timeout,
+    /// or `WSAPoll()` on Windows. The returned event has the key `0`.
     ///
-    /// If interest is set in both readability and writability, the two kinds of events might be
-    /// delivered either separately or together.
+    /// This is useful for optimistic I/O: if the source is ready, the operation can be performed
+    /// right away, and otherwise the source can be added to a poller.
     ///
-    /// For example, interest in `Event { key: 7, readable: true, writable: true }` might result in
-    /// a single [`Event`] of the same form, or in two separate [`Event`]s:
-    /// - `Event { key: 7, readable: true, writable: false }`
-    /// - `Event { key: 7, readable: false, writable: true }`
+    /// # Examples
     ///
-    /// Note that interest in I/O events needs to be re-enabled using
-    /// [`modify()`][`Poller::modify()`] again after an event is delivered if we're interested in
-    /// the next event of the same kind.
+    /// ```
+    /// use polling::Event;
+    /// use std::net::{TcpListener, TcpStream};
     ///
-    /// Don't forget to [`delete()`][`Poller::delete()`] the file descriptor or socket when it is
-    /// no longer used!
+    /// let listener = TcpListener::bind("127.0.0.1:0")?;
+    /// let stream = TcpStream::connect(listener.local_addr()?)?;
     ///
-    /// # Errors
+    /// let event = Event::probe(&stream)?;
+    /// assert!(!event.readable);
+    /// # std::io::Result::Ok(())
+    /// ```
+    pub fn probe(source: impl Source) -> io::Result<Event> {
+        let (readable, writable) = probe_source(source.raw())?;
+        Ok(Event::new(0, readable, writable))
+    }
+}
+
+/// A list of I/O events.
+///
+/// This is filled by [`Poller::wait_events()`]. Unlike a [`Vec`] of [`Event`]s, it stores the
+/// events in the format reported by the operating system, which can be accessed with
+/// [`as_raw_slice()`][`Events::as_raw_slice()`].
+pub struct Events {
+    events: platform::Events,
+    notify_key: usize,
+    woken: Vec<(usize, usize)>,
+    merged: Option<Vec<(usize, Event, EventDetails)>>,
+    origins: Vec<Origin>,
+    notified: bool,
+    interrupted: bool,
+    notify_count: usize,
+}
+
+/// The events that one of the pollers passed to [`Poller::wait_multiple()`] stored in a list.
+#[derive(Debug, Clone, Copy)]
+struct Origin {
+    /// The index of the poller.
+    poller: usize,
+    /// The number of backend events in the list after those of this poller.
+    events_end: usize,
+    /// The number of woken keys in the list after those of this poller.
+    woken_end: usize,
+    /// Whether the poller consumed a notification.
+    notified: bool,
+}
+
+impl Events {
+    /// Creates an empty list of events.
     ///
-    /// This method returns an error in the following situations:
+    /// # Examples
     ///
-    /// * If `key` equals `usize::MAX` because that key is reserved for internal use.
-    /// * If an error is returned by the syscall.
+    /// ```
+    /// use polling::Events;
+    ///
+    /// let events = Events::new();
+    /// assert_eq!(events.iter().count(), 0);
+    /// ```
+    pub fn new() -> Events {
+        Events {
+            events: platform::Events::new(),
+            notify_key: NOTIFY_KEY,
+            woken: Vec::new(),
+            merged: None,
+            origins: Vec::new(),
+            notified: false,
+            interrupted: false,
+            notify_count: 0,
+        }
+    }
+
+    /// Creates an empty list that stores at most `capacity` events per wait.
+    ///
+    /// Events that don't fit are reported by the next wait. A capacity of zero is treated as a
+    /// capacity of one. [`new()`][`Events::new()`] uses a capacity of 1024, except with the
+    /// `poll()` backend, which reports every ready source at once by default.
     ///
     /// # Examples
     ///
-    /// Set interest in all events:
+    /// ```
+    /// use polling::Events;
     ///
-    /// ```no_run
-    /// use polling::{Event, Poller};
+    /// let events = Events::with_capacity(64);
+    /// assert!(events.capacity() >= 64);
+    /// ```
+    pub fn with_capacity(capacity: usize) -> Events {
+        Events {
+            events: platform::Events::with_capacity(capacity.max(1)),
+            notify_key: NOTIFY_KEY,
+            woken: Vec::new(),
+            merged: None,
+            origins: Vec::new(),
+            notified: false,
+            interrupted: false,
+            notify_count: 0,
+        }
+    }
+
+    /// The maximum number of events that a single wait stores.
     ///
-    /// let source = std::net::TcpListener::bind("127.0.0.1:0")?;
-    /// source.set_nonblocking(true)?;
-    /// let key = 7;
+    /// Keys woken with [`Poller::wake()`] are not limited by the capacity.
+    pub fn capacity(&self) -> usize {
+        self.events.capacity()
+    }
+
+    /// The number of I/O events in the list.
+    ///
+    /// This is the number of events returned by [`iter()`][`Events::iter()`]. Only the keys of
+    /// the events are inspected, so this is cheaper than counting the events of `iter()`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use polling::{Events, Poller};
+    /// use std::time::Duration;
     ///
     /// let poller = Poller::new()?;
-    /// poller.add(&source, Event::all(key))?;
+    /// poller.wake(1)?;
+    /// poller.wake(2)?;
+    ///
+    /// let mut events = Events::new();
+    /// poller.wait_events(&mut events, Some(Duration::from_secs(0)))?;
+    /// assert_eq!(events.len(), 2);
     /// # std::io::Result::Ok(())
     /// ```
-    pub fn add(&self, source: impl Source, interest: Event) -> io::Result<()> {
-        self.add_with_mode(source, interest, PollMode::Oneshot)
+    pub fn len(&self) -> usize {
+        match &self.merged {
+            Some(merged) => merged.len(),
+            None => self.iter_keys().count(),
+        }
     }
 
-    /// Adds a file descriptor or socket to the poller in the specified mode.
+    /// Returns `true` if there are no I/O events in the list.
+    pub fn is_empty(&self) -> bool {
+        self.iter_keys().next().is_none()
+    }
+
+    /// Iterates over the I/O events.
     ///
-    /// This is identical to the `add()` function, but allows specifying the
-    /// polling mode to use for this socket.
+    /// Internal notifications are not included. Keys woken with [`Poller::wake()`] are reported
+    /// as readable events after the I/O events.
+    pub fn iter(&self) -> impl Iterator<Item = Event> + '_ {
+        self.iter_details().map(|(ev, _)| ev)
+    }
+
+    /// Iterates over the I/O events and the conditions that were reported with them.
     ///
-    /// # Errors
+    /// This returns the same events as [`iter()`][`Events::iter()`], each with its
+    /// [`EventDetails`], such as whether an error or a hangup was reported.
     ///
-    /// If the operating system does not support the specified mode, this function
-    /// will return an error.
-    pub fn add_with_mode(
-        &self,
-        source: impl Source,
-        interest: Event,
-        mode: PollMode,
-    ) -> io::Result<()> {
-        if interest.key == NOTIFY_KEY {
-            return Err(io::Error::new(
-                io::ErrorKind::InvalidInput,
-                "the key is not allowed to be `usize::MAX`",
-            ));
-        }
-        self.poller.add(source.raw(), interest, mode)
+    /// # Examples
+    ///
+    /// ```
+    /// use polling::{Events, Poller};
+    /// use std::time::Duration;
+    ///
+    /// let poller = Poller::new()?;
+    /// let mut events = Events::new();
+    /// poller.wait_events(&mut events, Some(Duration::from_secs(0)))?;
+    ///
+    /// for (ev, details) in events.iter_details() {
+    ///     if details.is_err() || details.is_hup() {
+    ///         println!("key {} failed or hung up", ev.key);
+    ///     }
+    /// }
+    /// # std::io::Result::Ok(())
+    /// ```
+    pub fn iter_details(&self) -> impl Iterator<Item = (Event, EventDetails)> + '_ {
+        let notify_key = self.notify_key;
+        let reported = match self.merged {
+            Some(_) => None,
+            None => Some(
+                self.events
+                    .iter()
+                    .filter(move |(ev, _)| ev.key != notify_key)
+                    .chain(
+                        self.woken
+                            .iter()
+                            .map(|&(key, _)| (Event::readable(key), EventDetails::default())),
+                    ),
+            ),
+        };
+        self.merged
+            .iter()
+            .flatten()
+            .map(|&(_, ev, details)| (ev, details))
+            .chain(reported.into_iter().flatten())
     }
 
-    /// Modifies the interest in a file descriptor or socket.
+    /// Iterates over the I/O events with the index of the poller that reported each of them.
     ///
-    /// This method has the same behavior as [`add()`][`Poller::add()`] except it modifies the
-    /// interest of a previously added file descriptor or socket.
+    /// This returns the same events as [`iter_details()`][`Events::iter_details()`]. After
+    /// [`Poller::wait_multiple()`], the index is the position of the poller in the slice that was
+    /// passed to it, so that pollers that use the same keys can be told apart. Otherwise, it is
+    /// always 0.
     ///
-    /// To use this method with a file descriptor or socket, you must first add it using
-    /// [`add()`][`Poller::add()`].
+    /// # Examples
     ///
-    /// Note that interest in I/O events needs to be re-enabled using
-    /// [`modify()`][`Poller::modify()`] again after an event is delivered if we're interested in
-    /// the next event of the same kind.
+    /// ```
+    /// use polling::{Event, Events, Poller};
+    /// use std::time::Duration;
     ///
-    /// # Errors
+    /// let network = Poller::new()?;
+    /// let storage = Poller::new()?;
     ///
-    /// This method returns an error in the following situations:
+    /// # if polling::BACKEND == "epoll" || polling::BACKEND == "kqueue" {
+    /// storage.wake(1)?;
     ///
-    /// * If `key` equals `usize::MAX` because that key is reserved for internal use.
-    /// * If an error is returned by the syscall.
+    /// let mut events = Events::new();
+    /// Poller::wait_multiple(&[&network, &storage], &mut events, Some(Duration::from_secs(1)))?;
+    /// for (poller, ev, _) in events.iter_with_poller() {
+    ///     assert_eq!((poller, ev), (1, Event::readable(1)));
+    /// }
+    /// # }
+    /// # std::io::Result::Ok(())
+    /// ```
+    pub fn iter_with_poller(&self) -> impl Iterator<Item = (usize, Event, EventDetails)> + '_ {
+        let notify_key = self.notify_key;
+        let reported = match self.merged {
+            Some(_) => None,
+            None => Some(
+                self.events
+                    .iter()
+                    .enumerate()
+                    .filter(move |(_, (ev, _))| ev.key != notify_key)
+                    .map(move |(i, (ev, details))| (self.origin(|o| i < o.events_end), ev, details))
+                    .chain(self.woken.iter().enumerate().map(move |(i, &(key, _))| {
+                        (
+                            self.origin(|o| i < o.woken_end),
+                            Event::readable(key),
+                            EventDetails::default(),
+                        )
+                    })),
+            ),
+        };
+        self.merged
+            .iter()
+            .flatten()
+            .copied()
+            .chain(reported.into_iter().flatten())
+    }
+
+    /// The index of the first poller whose events match `ends_after`, or 0.
+    fn origin(&self, ends_after: impl Fn(&Origin) -> bool) -> usize {
+        self.origins
+            .iter()
+            .find(|o| ends_after(o))
+            .map_or(0, |o| o.poller)
+    }
+
+    /// Iterates over the indices of the pollers that consumed a notification in
+    /// [`Poller::wait_multiple()`].
+    ///
+    /// This tells which of the pollers were notified, which
+    /// [`is_notified()`][`Events::is_notified()`] doesn't. It is empty after other waits.
+    pub fn notified_pollers(&self) -> impl Iterator<Item = usize> + '_ {
+        self.origins.iter().filter(|o| o.notified).map(|o| o.poller)
+    }
+
+    /// Iterates over the keys of the I/O events.
+    ///
+    /// This is cheaper than [`iter()`][`Events::iter()`] when only the keys are needed, since the
+    /// rest of each event isn't decoded. Internal notifications are not included.
     ///
     /// # Examples
     ///
-    /// To enable interest in all events:
+    /// ```
+    /// use polling::{Events, Poller};
+    /// use std::time::Duration;
     ///
-    /// ```no_run
-    /// # use polling::{Event, Poller};
-    /// # let source = std::net::TcpListener::bind("127.0.0.1:0")?;
-    /// # let key = 7;
-    /// # let poller = Poller::new()?;
-    /// # poller.add(&source, Event::none(key))?;
-    /// poller.modify(&source, Event::all(key))?;
+    /// let poller = Poller::new()?;
+    /// let mut events = Events::new();
+    /// poller.wait_events(&mut events, Some(Duration::from_secs(0)))?;
+    ///
+    /// for key in events.iter_keys() {
+    ///     println!("key {} is ready", key);
+    /// }
     /// # std::io::Result::Ok(())
     /// ```
+    pub fn iter_keys(&self) -> impl Iterator<Item = usize> + '_ {
+        let notify_key = self.notify_key;
+        let reported = match self.merged {
+            Some(_) => None,
+            None => Some(
+                self.events
+                    .keys()
+                    .filter(move |&key| key != notify_key)
+                    .chain(self.woken.iter().map(|&(key, _)| key)),
+            ),
+        };
+        self.merged
+            .iter()
+            .flatten()
+            .map(|(_, ev, _)| ev.key)
+            .chain(reported.into_iter().flatten())
+    }
+
+    /// Iterates over the keys and values posted with [`Poller::post_value()`].
     ///
-    /// To enable interest in readable events and disable interest in writable events:
+    /// Keys woken with [`Poller::wake()`] are included with a value of 0. Their events are also
+    /// returned by [`iter()`][`Events::iter()`].
+    ///
+    /// # Examples
     ///
-    /// ```no_run
-    /// # use polling::{Event, Poller};
-    /// # let source = std::net::TcpListener::bind("127.0.0.1:0")?;
-    /// # let key = 7;
-    /// # let poller = Poller::new()?;
-    /// # poller.add(&source, Event::none(key))?;
-    /// poller.modify(&source, Event::readable(key))?;
-    /// # std::io::Result::Ok(())
     /// ```
+    /// use polling::{Events, Poller};
+    /// use std::time::Duration;
     ///
-    /// To disable interest in readable events and enable interest in writable events:
+    /// let poller = Poller::new()?;
+    /// poller.post_value(1, 10)?;
+    /// poller.wake(2)?;
+    ///
+    /// let mut events = Events::new();
+    /// poller.wait_events(&mut events, Some(Duration::from_secs(0)))?;
+    /// assert_eq!(events.posted().collect::<Vec<_>>(), [(1, 10), (2, 0)]);
+    /// # std::io::Result::Ok(())
+    /// ```
+    pub fn posted(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.woken.iter().copied()
+    }
+
+    /// Returns the first event with the given key, if any.
+    ///
+    /// This is a linear scan over the events, which is fine for the usual number of events
+    /// returned by a single call to [`Poller::wait_events()`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use polling::{Event, Events, Poller};
+    /// use std::net::TcpListener;
+    /// use std::time::Duration;
+    ///
+    /// let socket = TcpListener::bind("127.0.0.1:0")?;
+    /// socket.set_nonblocking(true)?;
+    ///
+    /// let poller = Poller::new()?;
+    /// poller.add(&socket, Event::readable(7))?;
+    ///
+    /// let mut events = Events::new();
+    /// poller.wait_events(&mut events, Some(Duration::from_secs(0)))?;
+    /// assert_eq!(events.find(7), None);
+    /// # poller.delete(&socket)?;
+    /// # std::io::Result::Ok(())
+    /// ```
+    pub fn find(&self, key: usize) -> Option<Event> {
+        self.iter().find(|ev| ev.key == key)
+    }
+
+    /// The raw events, as reported by the operating system.
+    ///
+    /// See the [`sys`] module for the type of the raw events on each platform. Unlike
+    /// [`iter()`][`Events::iter()`], this may include the events that the poller uses internally
+    /// for notifications, and it doesn't include the keys woken with [`Poller::wake()`].
+    pub fn as_raw_slice(&self) -> &[sys::RawEvent] {
+        self.events.as_raw_slice()
+    }
+
+    /// Merges the events with the same key into a single event.
+    ///
+    /// The kernel may report a key several times in a single wait, for example with kqueue,
+    /// which reports readability and writability separately, or with edge-triggered sources that
+    /// became ready again before the events were collected. After calling this, each key is
+    /// reported once by [`iter()`][`Events::iter()`], with the readiness of all of its events
+    /// combined by [`Event::merge()`], and their details by [`EventDetails::merge()`]. The
+    /// events are sorted by key. After [`Poller::wait_multiple()`], the events of different
+    /// pollers are not merged, and are sorted by poller first.
+    ///
+    /// This doesn't change the raw events returned by [`as_raw_slice()`][`Events::as_raw_slice()`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use polling::{Event, Events, Poller};
+    /// use std::time::Duration;
+    ///
+    /// let poller = Poller::new()?;
+    /// poller.wake(1)?;
+    /// poller.wake(1)?;
+    ///
+    /// let mut events = Events::new();
+    /// poller.wait_events(&mut events, Some(Duration::from_secs(0)))?;
+    /// assert_eq!(events.iter().count(), 2);
+    ///
+    /// events.dedup();
+    /// assert_eq!(events.iter().collect::<Vec<_>>(), [Event::readable(1)]);
+    /// # std::io::Result::Ok(())
+    /// ```
+    pub fn dedup(&mut self) {
+        let mut list: Vec<(usize, Event, EventDetails)> = self.iter_with_poller().collect();
+        list.sort_by_key(|&(poller, ev, _)| (poller, ev.key));
+        list.dedup_by(|(poller, ev, details), (prev_poller, prev, prev_details)| {
+            if poller == prev_poller && ev.key == prev.key {
+                prev.merge(*ev);
+                prev_details.merge(*details);
+                true
+            } else {
+                false
+            }
+        });
+        self.merged = Some(list);
+    }
+
+    /// Removes all events from the list.
+    ///
+    /// The memory of the list is kept, so it can be reused by the next wait. Every wait clears
+    /// the list before storing new events, so this is only needed to drop the events early.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use polling::{Events, Poller};
+    /// use std::time::Duration;
+    ///
+    /// let poller = Poller::new()?;
+    /// poller.wake(1)?;
+    ///
+    /// let mut events = Events::with_capacity(16);
+    /// poller.wait_events(&mut events, Some(Duration::from_secs(0)))?;
+    /// assert_eq!(events.iter().count(), 1);
+    ///
+    /// events.clear();
+    /// assert_eq!(events.iter().count(), 0);
+    /// # std::io::Result::Ok(())
+    /// ```
+    pub fn clear(&mut self) {
+        self.events.clear();
+        self.woken.clear();
+        self.merged = None;
+        self.origins.clear();
+        self.notified = false;
+        self.interrupted = false;
+        self.notify_count = 0;
+    }
+
+    /// Whether the wait that stored these events consumed a notification.
+    ///
+    /// This is `true` if [`Poller::notify()`], or one of the methods built on it like
+    /// [`Poller::wake()`], was called since the previous wait, so that a scheduler can tell
+    /// whether it was woken up to re-check its task queues. Notifications are never reported as
+    /// events, so this is the only way to tell them apart from a timeout or a spurious wakeup.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use polling::{Events, Poller};
+    /// use std::time::Duration;
+    ///
+    /// let poller = Poller::new()?;
+    /// let mut events = Events::new();
+    ///
+    /// poller.notify()?;
+    /// poller.wait_events(&mut events, None)?;
+    /// assert!(events.is_notified());
+    ///
+    /// poller.wait_events(&mut events, Some(Duration::from_secs(0)))?;
+    /// assert!(!events.is_notified());
+    /// # std::io::Result::Ok(())
+    /// ```
+    pub fn is_notified(&self) -> bool {
+        self.notified
+    }
+
+    /// The number of notifications that were sent since they were last reported.
+    ///
+    /// Notifications are coalesced into a single wakeup, so
+    /// [`is_notified()`][`Events::is_notified()`] can't tell how many there were. If counting is
+    /// enabled with [`PollerBuilder::count_notifications()`], this is the number of calls to
+    /// [`Poller::notify()`], [`Poller::notify_one()`], [`Poller::notify_all()`] and
+    /// [`Poller::wake()`], on the poller or its handles, since the previous wait that stored its
+    /// events in an [`Events`] list. A scheduler can use it to decide how many worker threads to
+    /// wake up. Otherwise, this is always zero.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use polling::{Events, PollerBuilder};
+    ///
+    /// let poller = PollerBuilder::new().count_notifications(true).build()?;
+    /// let mut events = Events::new();
+    ///
+    /// poller.notify()?;
+    /// poller.notify()?;
+    /// poller.wait_events(&mut events, None)?;
+    /// assert_eq!(events.notify_count(), 2);
+    /// # std::io::Result::Ok(())
+    /// ```
+    pub fn notify_count(&self) -> usize {
+        self.notify_count
+    }
+
+    /// Describes how the wait that stored these events ended.
+    ///
+    /// This tells a timer wheel whether its timeout expired without looking through the events.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use polling::{Events, Poller, WaitOutcome};
+    /// use std::time::Duration;
+    ///
+    /// let poller = Poller::new()?;
+    /// let mut events = Events::new();
+    ///
+    /// poller.wait_events(&mut events, Some(Duration::from_millis(1)))?;
+    /// assert_eq!(events.outcome(), WaitOutcome::TimedOut);
+    ///
+    /// poller.notify()?;
+    /// poller.wait_events(&mut events, None)?;
+    /// assert_eq!(events.outcome(), WaitOutcome::Notified);
+    ///
+    /// poller.wake(7)?;
+    /// poller.wait_events(&mut events, None)?;
+    /// assert_eq!(events.outcome(), WaitOutcome::Events(1));
+    /// # std::io::Result::Ok(())
+    /// ```
+    pub fn outcome(&self) -> WaitOutcome {
+        match self.len() {
+            0 if self.notified => WaitOutcome::Notified,
+            0 if self.interrupted => WaitOutcome::Interrupted,
+            0 => WaitOutcome::TimedOut,
+            n => WaitOutcome::Events(n),
+        }
+    }
+}
+
+impl Default for Events {
+    fn default() -> Events {
+        Events::new()
+    }
+}
+
+impl AsMut<Events> for Events {
+    fn as_mut(&mut self) -> &mut Events {
+        self
+    }
+}
+
+impl fmt::Debug for Events {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+/// How a wait ended, as returned by [`Events::outcome()`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum WaitOutcome {
+    /// The given number of events was delivered.
+    Events(usize),
+
+    /// No events were delivered, but the wait was woken up by a notification, such as a call to
+    /// [`Poller::notify()`].
+    Notified,
+
+    /// No events were delivered, there was no notification, and the timeout expired.
+    TimedOut,
+
+    /// No events were delivered and there was no notification, but the wait ended before its
+    /// timeout expired.
+    ///
+    /// This happens when another thread is already waiting, when a wait wakes up early without a
+    /// notification, for example through the pipe of `new_piped()`, or when
+    /// [`try_wait()`][`Poller::try_wait()`] is interrupted by a signal.
+    Interrupted,
+}
+
+/// A hint for which backend a [`Poller`] should use.
+///
+/// Only one backend is compiled in for each platform, so this is treated as a preference. If the
+/// preferred backend is not available, the default backend for the platform is used instead.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum BackendHint {
+    /// Use the default backend for the current platform.
+    Auto,
+
+    /// Use `epoll` (Linux, Android).
+    Epoll,
+
+    /// Use `io_uring` (Linux).
+    ///
+    /// This backend is not implemented yet.
+    IoUring,
+
+    /// Use `kqueue` (macOS, iOS, tvOS, watchOS, FreeBSD, NetBSD, OpenBSD, DragonFly BSD).
+    Kqueue,
+
+    /// Use event ports (illumos, Solaris).
+    Port,
+
+    /// Use `/dev/poll` (illumos, Solaris).
+    ///
+    /// This backend is not implemented yet.
+    DevPoll,
+
+    /// Use `poll`.
+    Poll,
+
+    /// Use I/O completion ports (Windows).
+    Iocp,
+}
+
+impl Default for BackendHint {
+    fn default() -> Self {
+        BackendHint::Auto
+    }
+}
+
+impl BackendHint {
+    /// Whether this hint is satisfied by the given backend.
+    fn allows(self, kind: BackendKind) -> bool {
+        match self {
+            BackendHint::Auto => true,
+            BackendHint::Epoll => kind == BackendKind::Epoll,
+            BackendHint::IoUring => kind == BackendKind::IoUring,
+            BackendHint::Kqueue => kind == BackendKind::Kqueue,
+            BackendHint::Port => kind == BackendKind::Port,
+            BackendHint::DevPoll => false,
+            BackendHint::Poll => kind == BackendKind::Poll,
+            BackendHint::Iocp => kind == BackendKind::Iocp,
+        }
+    }
+}
+
+/// The kind of backend that a [`Poller`] uses.
+///
+/// The backend used on the current platform is [`BACKEND_KIND`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum BackendKind {
+    /// `kqueue` (macOS, iOS, tvOS, watchOS, FreeBSD, NetBSD, OpenBSD, DragonFly BSD).
+    Kqueue,
+
+    /// `epoll` (Linux, Android).
+    Epoll,
+
+    /// I/O completion ports (Windows).
+    Iocp,
+
+    /// Event ports (illumos, Solaris).
+    Port,
+
+    /// `poll` (VxWorks, Fuchsia, other Unix systems).
+    Poll,
+
+    /// `io_uring` (Linux).
+    IoUring,
+}
+
+impl BackendKind {
+    /// The name of this backend, such as `"epoll"` or `"kqueue"`.
+    pub const fn name(self) -> &'static str {
+        match self {
+            BackendKind::Kqueue => "kqueue",
+            BackendKind::Epoll => "epoll",
+            BackendKind::Iocp => "iocp",
+            BackendKind::Port => "port",
+            BackendKind::Poll => "poll",
+            BackendKind::IoUring => "io_uring",
+        }
+    }
+}
+
+impl fmt::Display for BackendKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// The kind of backend used on the current platform.
+pub const BACKEND_KIND: BackendKind = platform::BACKEND;
+
+/// The name of the backend used on the current platform.
+///
+/// # Examples
+///
+/// ```
+/// println!("Using the {} backend", polling::BACKEND);
+/// ```
+pub const BACKEND: &str = BACKEND_KIND.name();
+
+/// A quality-of-service class that a [`Poller`] waits at on Apple platforms.
+///
+/// The system schedules the thread that wakes up from a wait according to its class, so that
+/// background work doesn't use the resources, and the energy, of work the user is waiting for.
+/// See [`PollerBuilder::qos_class()`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum QosClass {
+    /// Work that the user interacts with, such as updating the user interface.
+    UserInteractive,
+
+    /// Work that the user started and is waiting for.
+    UserInitiated,
+
+    /// The class of threads that weren't given one.
+    Default,
+
+    /// Work that the user isn't waiting for right away, like downloads.
+    Utility,
+
+    /// Work that the user isn't aware of, like indexing or backups.
+    Background,
+}
+
+/// A builder for a [`Poller`] with non-default configuration.
+///
+/// # Examples
+///
+/// ```
+/// use polling::PollerBuilder;
+///
+/// let poller = PollerBuilder::new()
+///     .close_on_exec(true)
+///     .retry_on_eintr(true)
+///     .build()?;
+/// # std::io::Result::Ok(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct PollerBuilder {
+    /// The key used for internal notifications.
+    notify_key: usize,
+
+    /// Whether the internal file descriptors are created with `CLOEXEC` set.
+    close_on_exec: bool,
+
+    /// The number of threads that IOCP allows to run concurrently.
+    iocp_concurrent_threads: u32,
+
+    /// The preferred backend.
+    backend: BackendHint,
+
+    /// Whether `wait()` retries when it is interrupted by a signal.
+    retry_on_eintr: bool,
+
+    /// The maximum number of events that a single wait stores.
+    max_events: Option<usize>,
+
+    /// Whether kqueue reports read filters with `EV_EOF` as writable.
+    writable_on_eof: bool,
+
+    /// Whether the number of notifications is counted.
+    count_notifications: bool,
+
+    /// Whether the interest and mode of the added sources are recorded.
+    track_sources: bool,
+
+    /// Whether kqueue submits interest changes with the next wait.
+    deferred_changes: bool,
+
+    /// The quality-of-service class that waits run at on Apple platforms.
+    qos_class: Option<QosClass>,
+
+    /// Whether epoll waits with `epoll_pwait2()` on Linux.
+    epoll_pwait2: bool,
+}
+
+impl Default for PollerBuilder {
+    fn default() -> Self {
+        PollerBuilder {
+            notify_key: NOTIFY_KEY,
+            close_on_exec: true,
+            iocp_concurrent_threads: 0,
+            backend: BackendHint::Auto,
+            retry_on_eintr: false,
+            max_events: None,
+            writable_on_eof: true,
+            count_notifications: false,
+            track_sources: false,
+            deferred_changes: false,
+            qos_class: None,
+            epoll_pwait2: false,
+        }
+    }
+}
+
+impl PollerBuilder {
+    /// Creates a new builder with the default configuration.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the key used internally to deliver notifications.
+    ///
+    /// Sources cannot be registered with this key. By default, this is `usize::MAX`.
+    pub fn notify_key(mut self, key: usize) -> Self {
+        self.notify_key = key;
+        self
+    }
+
+    /// Sets whether the file descriptors created by the poller have `CLOEXEC` set.
+    ///
+    /// This is `true` by default. It has no effect on Windows.
+    pub fn close_on_exec(mut self, close_on_exec: bool) -> Self {
+        self.close_on_exec = close_on_exec;
+        self
+    }
+
+    /// Sets the number of threads that the I/O completion port allows to run concurrently.
+    ///
+    /// Zero, the default, allows as many threads as there are processors. This only has an
+    /// effect on Windows.
+    pub fn iocp_concurrent_threads(mut self, threads: u32) -> Self {
+        self.iocp_concurrent_threads = threads;
+        self
+    }
+
+    /// Sets the backend that the poller should prefer.
+    ///
+    /// See [`BackendHint`] for more information.
+    pub fn prefer_backend(mut self, backend: BackendHint) -> Self {
+        self.backend = backend;
+        self
+    }
+
+    /// Sets whether [`Poller::wait()`] retries when it is interrupted by a signal.
+    ///
+    /// If this is `true`, `wait()` is restarted with the remaining timeout when the underlying
+    /// system call fails with `EINTR`. Otherwise, the default, an error of kind
+    /// [`io::ErrorKind::Interrupted`] is returned, like [`Poller::new()`] does, so that the
+    /// caller can handle the signal. This can be changed later with
+    /// [`Poller::set_retry_on_eintr()`].
+    pub fn retry_on_eintr(mut self, retry: bool) -> Self {
+        self.retry_on_eintr = retry;
+        self
+    }
+
+    /// Sets the maximum number of events that a single wait stores.
+    ///
+    /// Events that don't fit are reported by the next wait. This sizes the list that
+    /// [`Poller::wait()`] collects into, and also limits [`Poller::wait_events()`], whatever the
+    /// capacity of its [`Events`] list, to bound the work done per iteration by
+    /// latency-sensitive event loops. The limit is passed to the operating system, as the length
+    /// of the event list of `kevent()` and `port_getn()`, the `maxevents` of `epoll_wait()` and
+    /// the `ulCount` of `GetQueuedCompletionStatusEx()`. A value of zero is treated as one.
+    ///
+    /// By default, `wait()` collects up to 1024 events, except with the `poll()` backend, which
+    /// reports every ready source at once, and `wait_events()` stores as many as its list holds.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use polling::{Events, PollerBuilder};
+    /// use std::time::Duration;
+    ///
+    /// let poller = PollerBuilder::new().max_events(16).build()?;
+    /// let mut events = Events::with_capacity(1024);
+    /// poller.wait_events(&mut events, Some(Duration::from_secs(0)))?;
+    /// assert!(events.len() <= 16);
+    /// # std::io::Result::Ok(())
+    /// ```
+    pub fn max_events(mut self, max: usize) -> Self {
+        self.max_events = Some(max.max(1));
+        self
+    }
+
+    /// Sets whether a source whose read side reached the end of the stream is also reported as
+    /// writable.
+    ///
+    /// kqueue reports the read end of a pipe being closed to the writer as a read filter with
+    /// `EV_EOF`. If this is `true`, the default, such events are also reported as writable, like Go
+    /// does, so that the next write fails and reports the error. Otherwise, events are reported as
+    /// kqueue delivers them, and [`EventDetails::is_eof()`] tells whether the end of the stream was
+    /// reached. This only has an effect with kqueue; the other backends report a closed pipe as
+    /// an error, which is also writable.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use polling::PollerBuilder;
+    ///
+    /// let poller = PollerBuilder::new().writable_on_eof(false).build()?;
+    /// # std::io::Result::Ok(())
+    /// ```
+    pub fn writable_on_eof(mut self, enabled: bool) -> Self {
+        self.writable_on_eof = enabled;
+        self
+    }
+
+    /// Sets whether the poller counts the notifications it receives.
+    ///
+    /// Notifications that are sent before the poller wakes up are coalesced into one wakeup. If
+    /// this is `true`, every notification is also counted, and the count is reported by
+    /// [`Events::notify_count()`]. This is `false` by default.
+    pub fn count_notifications(mut self, enabled: bool) -> Self {
+        self.count_notifications = enabled;
+        self
+    }
+
+    /// Sets whether the poller records the interest and [`PollMode`] of the sources it adds.
+    ///
+    /// This is needed by [`Poller::set_interest()`], [`Poller::disable()`],
+    /// [`Poller::enable()`] and [`Poller::rearm()`], which reuse what a source was last
+    /// registered with. The record is only updated once the operating system accepted a change,
+    /// and an entry is only removed by [`Poller::delete()`], so sources must be deleted before
+    /// they are closed. Keeping it costs a lock and a hash map update for every
+    /// [`add()`][`Poller::add()`], [`modify()`][`Poller::modify()`] and
+    /// [`delete()`][`Poller::delete()`], so this is `false` by default.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use polling::{Event, PollerBuilder};
+    /// use std::net::TcpListener;
+    ///
+    /// let socket = TcpListener::bind("127.0.0.1:0")?;
+    /// socket.set_nonblocking(true)?;
+    ///
+    /// let poller = PollerBuilder::new().track_sources(true).build()?;
+    /// poller.add(&socket, Event::readable(7))?;
+    /// poller.set_interest(&socket, true, true)?;
+    /// # poller.delete(&socket)?;
+    /// # std::io::Result::Ok(())
+    /// ```
+    pub fn track_sources(mut self, enabled: bool) -> Self {
+        self.track_sources = enabled;
+        self
+    }
+
+    /// Sets whether kqueue submits the changes made by [`Poller::add()`], [`Poller::modify()`]
+    /// and [`Poller::delete()`] with the next wait instead of one at a time.
+    ///
+    /// kqueue accepts a list of changes along with every wait, so deferring them saves a system
+    /// call for every change, which adds up when many oneshot sources are re-armed between waits.
+    /// In return, these methods can't report errors. A change that fails makes the next call that
+    /// submits changes return the error instead: the next [`Poller::wait()`], or a method that
+    /// isn't deferred, such as [`Poller::add_many_from_slice()`] or [`Poller::add_timer()`]. The
+    /// failed change is discarded once its error is returned. A change made while another thread
+    /// is blocked in `wait()` only applies once that wait returns, which [`Poller::notify()`] can
+    /// force.
+    ///
+    /// This is `false` by default. It only has an effect with kqueue.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use polling::PollerBuilder;
+    ///
+    /// let poller = PollerBuilder::new().deferred_changes(true).build()?;
+    /// # std::io::Result::Ok(())
+    /// ```
+    pub fn deferred_changes(mut self, enabled: bool) -> Self {
+        self.deferred_changes = enabled;
+        self
+    }
+
+    /// Sets the quality-of-service class that [`Poller::wait()`] runs at.
+    ///
+    /// Changes and waits are submitted to the kernel with `kevent_qos()` at this class, so that
+    /// the kernel wakes the thread up at that class instead of the class of the thread, without
+    /// changing the class of the thread. Apple platforms penalize apps that wake up at a higher
+    /// class than their work needs. By default, waits run at the class of the thread that calls
+    /// them.
+    ///
+    /// `kevent_qos()` can't wait with a timeout, so waits with a timeout other than zero use
+    /// `kevent()` and run at the class of the thread. If the kernel doesn't support
+    /// `kevent_qos()`, `kevent()` is used for everything.
+    ///
+    /// This only has an effect on macOS, iOS, tvOS and watchOS.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use polling::{PollerBuilder, QosClass};
+    ///
+    /// let poller = PollerBuilder::new().qos_class(QosClass::Utility).build()?;
+    /// # std::io::Result::Ok(())
+    /// ```
+    pub fn qos_class(mut self, class: QosClass) -> Self {
+        self.qos_class = Some(class);
+        self
+    }
+
+    /// Sets whether epoll waits with `epoll_pwait2()`, which takes timeouts in nanoseconds.
+    ///
+    /// Otherwise, timeouts finer than a millisecond need a timerfd that is rearmed before every
+    /// wait, and pollers created by [`Poller::try_clone()`] round timeouts up to a whole
+    /// millisecond. The poller checks whether the kernel supports `epoll_pwait2()`, which was
+    /// added in Linux 5.11, when it is created, and falls back to `epoll_wait()` if it doesn't.
+    ///
+    /// This is `false` by default, because seccomp filters that don't know about the system call
+    /// may kill the process instead of making it fail. It only has an effect with epoll on Linux;
+    /// it is ignored on Android, which kills processes that make system calls it doesn't allow.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use polling::PollerBuilder;
+    ///
+    /// let poller = PollerBuilder::new().epoll_pwait2(true).build()?;
+    /// # std::io::Result::Ok(())
+    /// ```
+    pub fn epoll_pwait2(mut self, enabled: bool) -> Self {
+        self.epoll_pwait2 = enabled;
+        self
+    }
+
+    /// Creates a new [`Poller`] with this configuration.
+    pub fn build(self) -> io::Result<Poller> {
+        if !self.backend.allows(BACKEND_KIND) {
+            log::debug!(
+                "build: backend {:?} is not available, using {:?}",
+                self.backend,
+                BACKEND_KIND
+            );
+        }
+
+        let poller = platform::Poller::new(&self)?;
+        Ok(self.wrap(poller))
+    }
+
+    /// Creates a [`Poller`] around a backend poller that was configured by this builder.
+    fn wrap(&self, poller: platform::Poller) -> Poller {
+        Poller {
+            poller: Arc::new(poller),
+            events: Mutex::new(new_events(self.max_events)),
+            notified: Arc::new(AtomicBool::new(false)),
+            notify_count: if self.count_notifications {
+                Some(Arc::new(AtomicUsize::new(0)))
+            } else {
+                None
+            },
+            shut_down: Arc::new(AtomicBool::new(false)),
+            notify_key: self.notify_key,
+            retry_on_eintr: AtomicBool::new(self.retry_on_eintr),
+            max_events: self.max_events,
+            sources: if self.track_sources {
+                Some(Arc::new(Mutex::new(HashMap::new())))
+            } else {
+                None
+            },
+            keys64: Arc::new(Mutex::new(HashMap::new())),
+            has_keys64: Arc::new(AtomicBool::new(false)),
+            splits: Arc::new(Mutex::new(split::Splits::default())),
+            has_splits: Arc::new(AtomicBool::new(false)),
+            woken: Arc::new(Mutex::new(Vec::new())),
+            waiters: Waiters::new(),
+            notify_hook: Arc::new(Mutex::new(None)),
+            release: Arc::new(handle::Release::default()),
+            #[cfg(all(unix, not(polling_no_io_safety)))]
+            wake_pipe: None,
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            netlink: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+}
+
+/// Waits for I/O events.
+pub struct Poller {
+    poller: Arc<platform::Poller>,
+    events: Mutex<platform::Events>,
+    notified: Arc<AtomicBool>,
+    notify_count: Option<Arc<AtomicUsize>>,
+    shut_down: Arc<AtomicBool>,
+    notify_key: usize,
+    retry_on_eintr: AtomicBool,
+    max_events: Option<usize>,
+    sources: Option<Arc<Sources>>,
+    keys64: Arc<Mutex<HashMap<usize, (RawSource, u64)>>>,
+    has_keys64: Arc<AtomicBool>,
+    splits: Arc<Mutex<split::Splits>>,
+    has_splits: Arc<AtomicBool>,
+    woken: Arc<Mutex<Vec<(usize, usize)>>>,
+    waiters: Waiters,
+    notify_hook: Arc<Mutex<Option<Arc<NotifyHook>>>>,
+    release: Arc<handle::Release>,
+    #[cfg(all(unix, not(polling_no_io_safety)))]
+    wake_pipe: Option<Arc<std::os::unix::io::OwnedFd>>,
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    netlink: Arc<Mutex<HashMap<usize, std::os::unix::io::RawFd>>>,
+}
+
+/// The registered sources and their interest, kept with `PollerBuilder::track_sources()`.
+type Sources = Mutex<HashMap<RawSource, (Registration, PollMode)>>;
+
+/// A callback that is invoked when a [`Poller`] is notified.
+type NotifyHook = dyn Fn() + Send + Sync;
+
+impl Poller {
+    /// Creates a new poller.
+    ///
+    /// This is equivalent to `PollerBuilder::default().build()`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use polling::Poller;
+    ///
+    /// let poller = Poller::new()?;
+    /// # std::io::Result::Ok(())
+    /// ```
+    pub fn new() -> io::Result<Poller> {
+        PollerBuilder::default().build()
+    }
+
+    /// Creates a new poller that uses the given backend.
+    ///
+    /// Unlike [`PollerBuilder::prefer_backend()`], this fails if the backend is not available on
+    /// the current platform. [`BackendHint::Auto`] always selects the default backend, so
+    /// `Poller::new_with_backend(BackendHint::Auto)` is equivalent to [`Poller::new()`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind [`io::ErrorKind::Unsupported`] if the backend is not available.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use polling::{BackendHint, Poller};
+    ///
+    /// let poller = Poller::new_with_backend(BackendHint::Auto)?;
+    /// assert!(Poller::new_with_backend(BackendHint::IoUring).is_err());
+    /// # std::io::Result::Ok(())
+    /// ```
+    pub fn new_with_backend(hint: BackendHint) -> io::Result<Poller> {
+        if !hint.allows(BACKEND_KIND) {
+            return Err(unsupported_error(format!(
+                "the {:?} backend is not available on this platform",
+                hint
+            )));
+        }
+
+        PollerBuilder::new().prefer_backend(hint).build()
+    }
+
+    /// Creates a new poller that uses `key` to deliver notifications.
+    ///
+    /// This is equivalent to `PollerBuilder::new().notify_key(key).build()`. Choosing a different
+    /// key is useful when keys are derived from pointers or other values that might collide with
+    /// [`NOTIFY_KEY`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use polling::{Event, Poller, NOTIFY_KEY};
+    ///
+    /// let poller = Poller::with_notify_key(usize::MAX - 1)?;
+    /// assert_eq!(poller.notify_key(), usize::MAX - 1);
+    /// assert!(!poller.is_notify_event(&Event::readable(NOTIFY_KEY)));
+    /// # std::io::Result::Ok(())
+    /// ```
+    pub fn with_notify_key(key: usize) -> io::Result<Poller> {
+        PollerBuilder::new().notify_key(key).build()
+    }
+
+    /// Returns the key used by this `Poller` to deliver notifications.
+    ///
+    /// Sources cannot be registered with this key.
+    pub fn notify_key(&self) -> usize {
+        self.notify_key
+    }
+
+    /// Returns whether waits are restarted when they are interrupted by a signal.
+    ///
+    /// This is configured with [`PollerBuilder::retry_on_eintr()`], and is `false` by default.
+    pub fn retry_on_eintr(&self) -> bool {
+        self.retry_on_eintr.load(Ordering::Relaxed)
+    }
+
+    /// Sets whether waits are restarted when they are interrupted by a signal.
+    ///
+    /// If this is `true`, a wait that fails with `EINTR` is restarted with the time that remains
+    /// until its deadline, so it only returns once events are delivered, the poller is notified
+    /// or the timeout expires. Otherwise, an error of kind
+    /// [`io::ErrorKind::Interrupted`] is returned, and the caller can check for the signal it
+    /// is expecting. This applies to the waits that start after the call, on every backend.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use polling::Poller;
+    ///
+    /// let poller = Poller::new()?;
+    /// assert!(!poller.retry_on_eintr());
+    ///
+    /// poller.set_retry_on_eintr(true);
+    /// assert!(poller.retry_on_eintr());
+    /// # std::io::Result::Ok(())
+    /// ```
+    pub fn set_retry_on_eintr(&self, retry: bool) {
+        self.retry_on_eintr.store(retry, Ordering::Relaxed);
+    }
+
+    /// Creates a new poller that shares the interest set of this one.
+    ///
+    /// The new poller uses a duplicate of the underlying file descriptor, so both pollers see the
+    /// same registered sources: a source added to one of them is reported by both, and must only
+    /// be added once. The duplicate keeps the `CLOEXEC` flag of the original, which makes it
+    /// possible to share the interest set with a child process.
+    ///
+    /// Each poller has its own notification mechanism and can be [notified][`Poller::notify()`]
+    /// independently. However, since the notifications are delivered through the shared
+    /// interest set, a notification may also be consumed by the other poller if both are
+    /// waiting at the same time.
+    ///
+    /// # Errors
+    ///
+    /// This is currently only supported with epoll. On other platforms, an error of kind
+    /// [`io::ErrorKind::Unsupported`] is returned.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use polling::Poller;
+    ///
+    /// let poller = Poller::new()?;
+    /// # if cfg!(any(target_os = "linux", target_os = "android")) && polling::BACKEND == "epoll" {
+    /// let clone = poller.try_clone()?;
+    /// clone.notify()?;
+    /// # }
+    /// # std::io::Result::Ok(())
+    /// ```
+    pub fn try_clone(&self) -> io::Result<Poller> {
+        Ok(Poller {
+            poller: Arc::new(self.poller.try_clone()?),
+            events: Mutex::new(new_events(self.max_events)),
+            notified: Arc::new(AtomicBool::new(false)),
+            notify_count: self
+                .notify_count
+                .as_ref()
+                .map(|_| Arc::new(AtomicUsize::new(0))),
+            shut_down: self.shut_down.clone(),
+            notify_key: self.notify_key,
+            retry_on_eintr: AtomicBool::new(self.retry_on_eintr()),
+            max_events: self.max_events,
+            sources: self.sources.clone(),
+            keys64: self.keys64.clone(),
+            has_keys64: self.has_keys64.clone(),
+            splits: self.splits.clone(),
+            has_splits: self.has_splits.clone(),
+            woken: Arc::new(Mutex::new(Vec::new())),
+            waiters: Waiters::new(),
+            notify_hook: Arc::new(Mutex::new(None)),
+            release: Arc::new(handle::Release::default()),
+            #[cfg(all(unix, not(polling_no_io_safety)))]
+            wake_pipe: self.wake_pipe.clone(),
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            netlink: self.netlink.clone(),
+        })
+    }
+
+    /// Tell whether or not this event is an internal notification of this `Poller`.
+    ///
+    /// This compares the key of the event with the notification key of this `Poller`, which is
+    /// [`NOTIFY_KEY`] unless it was configured with [`PollerBuilder::notify_key()`] or
+    /// [`Poller::with_notify_key()`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use polling::{Event, Poller, NOTIFY_KEY};
+    ///
+    /// let poller = Poller::new()?;
+    /// assert!(poller.is_notify_event(&Event::readable(NOTIFY_KEY)));
+    /// assert!(!poller.is_notify_event(&Event::readable(7)));
+    /// # std::io::Result::Ok(())
+    /// ```
+    pub fn is_notify_event(&self, ev: &Event) -> bool {
+        ev.key == self.notify_key
+    }
+
+    /// Tell whether or not this `Poller` supports level-triggered polling.
+    pub fn supports_level(&self) -> bool {
+        self.poller.supports_level()
+    }
+
+    /// Tell whether or not this `Poller` supports edge-triggered polling.
+    pub fn supports_edge(&self) -> bool {
+        self.poller.supports_edge()
+    }
+
+    /// Tell whether or not this `Poller` wakes up only one poller for file descriptors added in
+    /// [`PollMode::Exclusive`].
+    ///
+    /// With epoll, this is `false` on kernels older than Linux 4.5.
+    pub fn supports_exclusive(&self) -> bool {
+        self.poller.supports_exclusive()
+    }
+
+    /// Tell whether or not several threads can wait on this `Poller` at the same time with
+    /// [`wait_shared()`][`Poller::wait_shared()`].
+    pub fn supports_concurrent_wait(&self) -> bool {
+        matches!(
+            BACKEND_KIND,
+            BackendKind::Epoll | BackendKind::Kqueue | BackendKind::Port
+        )
+    }
+
+    /// The kind of backend that this `Poller` uses.
+    ///
+    /// This is always [`BACKEND_KIND`], but it saves libraries that adapt to the backend from
+    /// duplicating the `cfg` conditions that select it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use polling::{BackendKind, Poller};
+    ///
+    /// let poller = Poller::new()?;
+    /// if poller.backend() == BackendKind::Epoll {
+    ///     println!("timeouts are precise: {:?}", poller.timeout_granularity());
+    /// }
+    /// # std::io::Result::Ok(())
+    /// ```
+    pub fn backend(&self) -> BackendKind {
+        BACKEND_KIND
+    }
+
+    /// The precision of the timeouts passed to [`wait()`][`Poller::wait()`].
+    ///
+    /// This is one nanosecond with kqueue, event ports, and epoll when a timerfd is available,
+    /// and one millisecond otherwise. Timeouts are rounded up to a multiple of the granularity, so
+    /// a wait never ends before its timeout, but it may last up to one granularity longer.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use polling::Poller;
+    /// use std::time::Duration;
+    ///
+    /// let poller = Poller::new()?;
+    /// assert!(poller.timeout_granularity() <= Duration::from_millis(1));
+    /// # std::io::Result::Ok(())
+    /// ```
+    pub fn timeout_granularity(&self) -> Duration {
+        self.poller.timeout_granularity()
+    }
+
+    /// The maximum number of file descriptors that can be open in this process, if there is one.
+    ///
+    /// On Unix platforms, this is the soft limit of `RLIMIT_NOFILE`, which applies to the sources
+    /// added to the poller as well as to the file descriptors of the poller itself. Returns `None`
+    /// if the limit is infinite, or on platforms without such a limit, like Windows.
+    ///
+    /// This can be used to stop accepting new connections before running out of file
+    /// descriptors.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use polling::Poller;
+    ///
+    /// let poller = Poller::new()?;
+    /// if let Some(max) = poller.max_fds() {
+    ///     println!("at most {} file descriptors can be open", max);
+    /// }
+    /// # std::io::Result::Ok(())
+    /// ```
+    pub fn max_fds(&self) -> Option<usize> {
+        #[cfg(all(unix, not(target_os = "fuchsia"), not(target_os = "vxworks")))]
+        {
+            use rustix::process::{getrlimit, Resource};
+            use std::convert::TryInto;
+
+            getrlimit(Resource::Nofile)
+                .current
+                .map(|limit| limit.try_into().unwrap_or(usize::MAX))
+        }
+
+        #[cfg(not(all(unix, not(target_os = "fuchsia"), not(target_os = "vxworks"))))]
+        {
+            None
+        }
+    }
+
+    /// Checks the internal state of the poller for consistency.
+    ///
+    /// This verifies that the file descriptors of the poller, including the one used by
+    /// [`notify()`][`Poller::notify()`], are still open. With the `poll` backend, it also checks
+    /// that the notification pipe is polled and, if the sources are tracked with
+    /// [`PollerBuilder::track_sources()`], that the polled file descriptors match them. Returns an
+    /// error describing the first problem that was found.
+    ///
+    /// This is meant to be called from tests and is not part of the stable API.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use polling::{Event, Poller};
+    /// use std::net::TcpListener;
+    ///
+    /// let socket = TcpListener::bind("127.0.0.1:0")?;
+    /// let poller = Poller::new()?;
+    /// poller.add(&socket, Event::readable(7))?;
+    ///
+    /// poller.check_invariants()?;
+    /// # poller.delete(&socket)?;
+    /// # std::io::Result::Ok(())
+    /// ```
+    #[doc(hidden)]
+    pub fn check_invariants(&self) -> io::Result<()> {
+        let registered = self.registered_count();
+        // The pipe of `new_piped()` is registered in the backend, but it's not a source.
+        #[cfg(all(unix, not(polling_no_io_safety)))]
+        let registered = registered.map(|n| n + self.wake_pipe.is_some() as usize);
+        self.poller.check_invariants(registered)
+    }
+
+    /// Adds a file descriptor or socket to the poller.
+    ///
+    /// A file descriptor or socket is considered readable or writable when a read or write
+    /// operation on it would not block. This doesn't mean the read or write operation will
+    /// succeed, it only means the operation will return immediately.
+    ///
+    /// If interest is set in both readability and writability, the two kinds of events might be
+    /// delivered either separately or together.
+    ///
+    /// For example, interest in `Event { key: 7, readable: true, writable: true }` might result in
+    /// a single [`Event`] of the same form, or in two separate [`Event`]s:
+    /// - `Event { key: 7, readable: true, writable: false }`
+    /// - `Event { key: 7, readable: false, writable: true }`
+    ///
+    /// Note that interest in I/O events needs to be re-enabled using
+    /// [`modify()`][`Poller::modify()`] again after an event is delivered if we're interested in
+    /// the next event of the same kind.
+    ///
+    /// Don't forget to [`delete()`][`Poller::delete()`] the file descriptor or socket when it is
+    /// no longer used!
+    ///
+    /// The key is stored by the operating system alongside the registration, as the `u64` field
+    /// with epoll, as `udata` with kqueue and as the user value with event ports, and is
+    /// reported back unchanged. It can therefore serve as the user data of the registration, such
+    /// as an index into a slab or a pointer to the state of a connection, without a separate
+    /// lookup from key to connection.
+    ///
+    /// # Errors
+    ///
+    /// This method returns an error in the following situations:
+    ///
+    /// * If `key` equals the notification key (`usize::MAX` by default) because that key is
+    ///   reserved for internal use.
+    /// * If an error is returned by the syscall.
+    ///
+    /// # Examples
+    ///
+    /// Set interest in all events:
+    ///
+    /// ```no_run
+    /// use polling::{Event, Poller};
+    ///
+    /// let source = std::net::TcpListener::bind("127.0.0.1:0")?;
+    /// source.set_nonblocking(true)?;
+    /// let key = 7;
+    ///
+    /// let poller = Poller::new()?;
+    /// poller.add(&source, Event::all(key))?;
+    /// # std::io::Result::Ok(())
+    /// ```
+    pub fn add(&self, source: impl Source, interest: Event) -> io::Result<()> {
+        self.add_with_mode(source, interest, PollMode::Oneshot)
+    }
+
+    /// Adds a file descriptor or socket to the poller in the specified mode.
+    ///
+    /// This is identical to the `add()` function, but allows specifying the
+    /// polling mode to use for this socket.
+    ///
+    /// # Errors
+    ///
+    /// If the operating system does not support the specified mode, this function
+    /// will return an error.
+    pub fn add_with_mode(
+        &self,
+        source: impl Source,
+        interest: Event,
+        mode: PollMode,
+    ) -> io::Result<()> {
+        self.registry().add(source.raw(), interest, mode)
+    }
+
+    /// Adds several file descriptors or sockets to the poller, returning the result for each.
+    ///
+    /// This is like calling [`add_with_mode()`][`Poller::add_with_mode()`] for each source, but a
+    /// failure to add one source doesn't stop the others from being added. The returned [`Vec`]
+    /// has the same length as `sources`, and each result belongs to the source at the same index.
+    ///
+    /// With kqueue, all sources are added with a single system call.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use polling::{Event, PollMode, Poller, Source, NOTIFY_KEY};
+    /// use std::net::TcpListener;
+    ///
+    /// let a = TcpListener::bind("127.0.0.1:0")?;
+    /// let b = TcpListener::bind("127.0.0.1:0")?;
+    /// a.set_nonblocking(true)?;
+    /// b.set_nonblocking(true)?;
+    ///
+    /// let poller = Poller::new()?;
+    /// let results = poller.register_batch_with_errors(&[
+    ///     ((&a).raw(), Event::readable(1), PollMode::Oneshot),
+    ///     ((&b).raw(), Event::readable(NOTIFY_KEY), PollMode::Oneshot),
+    /// ]);
+    /// assert!(results[0].is_ok());
+    /// assert!(results[1].is_err());
+    /// # poller.delete(&a)?;
+    /// # std::io::Result::Ok(())
+    /// ```
+    pub fn register_batch_with_errors(
+        &self,
+        sources: &[(RawSource, Event, PollMode)],
+    ) -> Vec<io::Result<()>> {
+        log::trace!(
+            "Poller::register_batch_with_errors({} sources)",
+            sources.len()
+        );
+
+        if self.is_shut_down() {
+            return sources.iter().map(|_| Err(shut_down_error())).collect();
+        }
+        self.submit_batch(sources, |batch| self.poller.add_batch(batch))
+    }
+
+    /// Adds several file descriptors or sockets to the poller.
+    ///
+    /// This is like [`register_batch_with_errors()`][`Poller::register_batch_with_errors()`],
+    /// but only the first error is returned, and nothing is allocated for the results. Every
+    /// source is checked before any of them is added, so if one is invalid, for example because
+    /// it uses the notification key, none of them are added. Otherwise, the sources that were
+    /// added successfully stay added, even if an error is returned.
+    ///
+    /// With kqueue, all sources are added with a single system call, and the changes for up to
+    /// 64 sources are built on the stack without allocating.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use polling::{Event, PollMode, Poller, Source};
+    /// use std::net::TcpListener;
+    ///
+    /// let a = TcpListener::bind("127.0.0.1:0")?;
+    /// let b = TcpListener::bind("127.0.0.1:0")?;
+    /// a.set_nonblocking(true)?;
+    /// b.set_nonblocking(true)?;
+    ///
+    /// let poller = Poller::new()?;
+    /// poller.add_many_from_slice(&[
+    ///     ((&a).raw(), Event::readable(1), PollMode::Oneshot),
+    ///     ((&b).raw(), Event::readable(2), PollMode::Oneshot),
+    /// ])?;
+    /// # poller.delete(&a)?;
+    /// # poller.delete(&b)?;
+    /// # std::io::Result::Ok(())
+    /// ```
+    pub fn add_many_from_slice(&self, entries: &[(RawSource, Event, PollMode)]) -> io::Result<()> {
+        log::trace!("Poller::add_many_from_slice({} sources)", entries.len());
+        self.registry().add_many(entries)
+    }
+
+    /// Modifies several file descriptors or sockets in the poller.
+    ///
+    /// This is like calling [`modify_with_mode()`][`Poller::modify_with_mode()`] for each source,
+    /// but a failure to modify one source doesn't stop the others from being modified. Only the
+    /// first error is returned. To add several sources at once, use
+    /// [`add_many_from_slice()`][`Poller::add_many_from_slice()`].
+    ///
+    /// With kqueue, all sources are modified with a single system call.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use polling::{Event, PollMode, Poller, Source};
+    /// use std::net::TcpListener;
+    ///
+    /// let a = TcpListener::bind("127.0.0.1:0")?;
+    /// let b = TcpListener::bind("127.0.0.1:0")?;
+    /// a.set_nonblocking(true)?;
+    /// b.set_nonblocking(true)?;
+    ///
+    /// let poller = Poller::new()?;
+    /// poller.add_many_from_slice(&[
+    ///     ((&a).raw(), Event::readable(1), PollMode::Oneshot),
+    ///     ((&b).raw(), Event::readable(2), PollMode::Oneshot),
+    /// ])?;
+    /// poller.modify_batch(&[
+    ///     ((&a).raw(), Event::all(1), PollMode::Level),
+    ///     ((&b).raw(), Event::all(2), PollMode::Level),
+    /// ])?;
+    /// # poller.delete(&a)?;
+    /// # poller.delete(&b)?;
+    /// # std::io::Result::Ok(())
+    /// ```
+    pub fn modify_batch(&self, sources: &[(RawSource, Event, PollMode)]) -> io::Result<()> {
+        log::trace!("Poller::modify_batch({} sources)", sources.len());
+
+        self.submit_batch(sources, |batch| self.poller.modify_batch(batch))
+            .into_iter()
+            .collect()
+    }
+
+    /// Validates a batch of sources, passes the valid ones to `submit` and records the sources
+    /// that were registered successfully.
+    fn submit_batch(
+        &self,
+        sources: &[(RawSource, Event, PollMode)],
+        submit: impl FnOnce(&[(RawSource, Event, PollMode)]) -> Vec<io::Result<()>>,
+    ) -> Vec<io::Result<()>> {
+        // Reject the invalid sources before the rest reach the backend.
+        let mut results = Vec::with_capacity(sources.len());
+        let mut batch = Vec::with_capacity(sources.len());
+        for &(fd, interest, mode) in sources {
+            let result = if interest.key == self.notify_key {
+                Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "the key is reserved for notifications",
+                ))
+            } else {
+                check_source(fd)
+            };
+            if result.is_ok() {
+                batch.push((fd, interest, mode));
+            }
+            results.push(result);
+        }
+
+        let batch_results = submit(&batch);
+        debug_assert_eq!(batch_results.len(), batch.len());
+        let mut batch_results = batch_results.into_iter();
+        let mut registered = self
+            .sources
+            .as_ref()
+            .map(|sources| sources.lock().unwrap_or_else(|e| e.into_inner()));
+        for (result, &(fd, interest, mode)) in results.iter_mut().zip(sources) {
+            if result.is_ok() {
+                *result = batch_results.next().unwrap_or_else(|| {
+                    Err(io::Error::new(
+                        io::ErrorKind::Other,
+                        "the backend returned no result for the source",
+                    ))
+                });
+                if let (Ok(()), Some(registered)) = (&result, &mut registered) {
+                    registered.insert(fd, (interest.into(), mode));
+                }
+            }
+        }
+
+        results
+    }
+
+    /// Adds a file descriptor or socket to the poller and returns a guard that removes it again.
+    ///
+    /// This is identical to [`add_with_mode()`][`Poller::add_with_mode()`], but the source is
+    /// removed from the poller with [`delete()`][`Poller::delete()`] when the returned
+    /// [`WatchGuard`] is dropped. Errors from the removal are ignored. Use
+    /// [`WatchGuard::disarm()`] to keep the source registered.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use polling::{Event, PollMode, Poller};
+    /// use std::net::TcpListener;
+    ///
+    /// let socket = TcpListener::bind("127.0.0.1:0")?;
+    /// socket.set_nonblocking(true)?;
+    /// let key = 7;
+    ///
+    /// let poller = Poller::new()?;
+    /// {
+    ///     let socket = poller.add_watching(&socket, Event::readable(key), PollMode::Oneshot)?;
+    ///     println!("listening on {}", socket.local_addr()?);
+    /// } // `socket` is removed from the poller here.
+    /// # std::io::Result::Ok(())
+    /// ```
+    pub fn add_watching<S: Source>(
+        &self,
+        source: S,
+        interest: Event,
+        mode: PollMode,
+    ) -> io::Result<WatchGuard<'_, S>> {
+        self.add_with_mode(source.raw(), interest, mode)?;
+        Ok(WatchGuard::new(self, source))
+    }
+
+    /// Adds a borrowed file descriptor or socket to the poller and returns a [`WatchGuard`].
+    ///
+    /// This is identical to [`add_watching()`][`Poller::add_watching()`] with a reference to the
+    /// source. The source stays borrowed for as long as the guard exists, so it can't be closed
+    /// before it is removed from the poller.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use polling::{Event, PollMode, Poller};
+    /// use std::net::TcpListener;
+    ///
+    /// let socket = TcpListener::bind("127.0.0.1:0")?;
+    /// socket.set_nonblocking(true)?;
+    ///
+    /// let poller = Poller::new()?;
+    /// let registration = poller.register(&socket, Event::readable(7), PollMode::Oneshot)?;
+    /// registration.modify(Event::readable(7), PollMode::Level)?;
+    ///
+    /// // `socket` can't be dropped until `registration` is.
+    /// drop(registration);
+    /// drop(socket);
+    /// # std::io::Result::Ok(())
+    /// ```
+    pub fn register<'a, S>(
+        &'a self,
+        source: &'a S,
+        interest: Event,
+        mode: PollMode,
+    ) -> io::Result<WatchGuard<'a, &'a S>>
+    where
+        &'a S: Source,
+    {
+        self.add_watching(source, interest, mode)
+    }
+
+    /// Modifies the interest in a file descriptor or socket.
+    ///
+    /// This method has the same behavior as [`add()`][`Poller::add()`] except it modifies the
+    /// interest of a previously added file descriptor or socket.
+    ///
+    /// To use this method with a file descriptor or socket, you must first add it using
+    /// [`add()`][`Poller::add()`].
+    ///
+    /// Note that interest in I/O events needs to be re-enabled using
+    /// [`modify()`][`Poller::modify()`] again after an event is delivered if we're interested in
+    /// the next event of the same kind.
+    ///
+    /// # Errors
+    ///
+    /// This method returns an error in the following situations:
+    ///
+    /// * If `key` equals the notification key (`usize::MAX` by default) because that key is
+    ///   reserved for internal use.
+    /// * If an error is returned by the syscall.
+    ///
+    /// # Examples
+    ///
+    /// To enable interest in all events:
+    ///
+    /// ```no_run
+    /// # use polling::{Event, Poller};
+    /// # let source = std::net::TcpListener::bind("127.0.0.1:0")?;
+    /// # let key = 7;
+    /// # let poller = Poller::new()?;
+    /// # poller.add(&source, Event::none(key))?;
+    /// poller.modify(&source, Event::all(key))?;
+    /// # std::io::Result::Ok(())
+    /// ```
+    ///
+    /// To enable interest in readable events and disable interest in writable events:
+    ///
+    /// ```no_run
+    /// # use polling::{Event, Poller};
+    /// # let source = std::net::TcpListener::bind("127.0.0.1:0")?;
+    /// # let key = 7;
+    /// # let poller = Poller::new()?;
+    /// # poller.add(&source, Event::none(key))?;
+    /// poller.modify(&source, Event::readable(key))?;
+    /// # std::io::Result::Ok(())
+    /// ```
+    ///
+    /// To disable interest in readable events and enable interest in writable events:
     ///
     /// ```no_run
     /// # use polling::{Event, Poller};
@@ -377,129 +2360,1619 @@ impl Poller {
     /// # std::io::Result::Ok(())
     /// ```
     ///
-    /// To disable interest in all events:
+    /// To disable interest in all events:
+    ///
+    /// ```no_run
+    /// # use polling::{Event, Poller};
+    /// # let source = std::net::TcpListener::bind("127.0.0.1:0")?;
+    /// # let key = 7;
+    /// # let poller = Poller::new()?;
+    /// # poller.add(&source, Event::none(key))?;
+    /// poller.modify(&source, Event::none(key))?;
+    /// # std::io::Result::Ok(())
+    /// ```
+    pub fn modify(&self, source: impl Source, interest: Event) -> io::Result<()> {
+        self.modify_with_mode(source, interest, PollMode::Oneshot)
+    }
+
+    /// Modifies interest in a file descriptor or socket to the poller, but with the specified
+    /// mode.
+    ///
+    /// This is identical to the `modify()` function, but allows specifying the polling mode
+    /// to use for this socket.
+    ///
+    /// # Performance Notes
+    ///
+    /// This function can be used to change a source from one polling mode to another. However,
+    /// on some platforms, this switch can cause delays in the delivery of events.
+    ///
+    /// # Errors
+    ///
+    /// If the operating system does not support the specified mode, this function will return
+    /// an error.
+    pub fn modify_with_mode(
+        &self,
+        source: impl Source,
+        interest: Event,
+        mode: PollMode,
+    ) -> io::Result<()> {
+        self.registry().modify(source.raw(), interest, mode)
+    }
+
+    /// Modifies the interest in a file descriptor or socket, if it is added to the poller.
+    ///
+    /// This is identical to [`modify_with_mode()`][`Poller::modify_with_mode()`], but returns
+    /// `Ok(false)` instead of an error if the source is not added to this poller or was already
+    /// closed, and `Ok(true)` once it was modified. The decision is made by the error of the
+    /// modification itself, so no lock is held while it is made. The `poll()` backend doesn't
+    /// notice that an added file descriptor was closed, so it is still modified.
+    ///
+    /// kqueue and event ports add a file descriptor that they don't know when it is modified.
+    /// With them, this needs the sources to be tracked with
+    /// [`PollerBuilder::track_sources()`], and a source is only modified if it is recorded as
+    /// added. A source that is concurrently removed with [`delete()`][`Poller::delete()`] may
+    /// then still be added back.
+    ///
+    /// # Errors
+    ///
+    /// With kqueue and event ports, returns an error of kind [`io::ErrorKind::Unsupported`] if
+    /// the sources are not tracked.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use polling::{Event, PollMode, PollerBuilder};
+    /// use std::net::TcpListener;
+    ///
+    /// let socket = TcpListener::bind("127.0.0.1:0")?;
+    /// socket.set_nonblocking(true)?;
+    ///
+    /// let poller = PollerBuilder::new().track_sources(true).build()?;
+    /// assert!(!poller.modify_if_registered(&socket, Event::readable(7), PollMode::Oneshot)?);
+    ///
+    /// poller.add(&socket, Event::none(7))?;
+    /// assert!(poller.modify_if_registered(&socket, Event::readable(7), PollMode::Oneshot)?);
+    /// # poller.delete(&socket)?;
+    /// # std::io::Result::Ok(())
+    /// ```
+    pub fn modify_if_registered(
+        &self,
+        source: impl Source,
+        interest: Event,
+        mode: PollMode,
+    ) -> io::Result<bool> {
+        let fd = source.raw();
+
+        if matches!(BACKEND_KIND, BackendKind::Kqueue | BackendKind::Port) {
+            let sources = self.sources.as_ref().ok_or_else(|| {
+                unsupported_error(
+                    "`modify_if_registered()` needs `PollerBuilder::track_sources()` with this backend",
+                )
+            })?;
+            if !sources
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .contains_key(&fd)
+            {
+                return Ok(false);
+            }
+        }
+
+        match self.registry().modify(fd, interest, mode) {
+            Ok(()) => Ok(true),
+            Err(err) if is_not_registered(&err) => Ok(false),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Changes the readability and writability interest of a file descriptor or socket.
+    ///
+    /// This is identical to [`modify_with_mode()`][`Poller::modify_with_mode()`], but the key
+    /// and the [`PollMode`] that the source was last registered with are kept.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind [`io::ErrorKind::NotFound`] if the source was not added to this
+    /// poller, or if an error is returned by the syscall. Returns an error of kind
+    /// [`io::ErrorKind::Other`] if the poller wasn't built with
+    /// [`PollerBuilder::track_sources()`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use polling::{Event, PollMode, PollerBuilder};
+    /// use std::net::TcpListener;
+    ///
+    /// let socket = TcpListener::bind("127.0.0.1:0")?;
+    /// socket.set_nonblocking(true)?;
+    /// let key = 7;
+    ///
+    /// let poller = PollerBuilder::new().track_sources(true).build()?;
+    /// poller.add_with_mode(&socket, Event::readable(key), PollMode::Level)?;
+    ///
+    /// // Also wait for writability, still in level-triggered mode.
+    /// poller.set_interest(&socket, true, true)?;
+    /// # poller.delete(&socket)?;
+    /// # std::io::Result::Ok(())
+    /// ```
+    pub fn set_interest(
+        &self,
+        source: impl Source,
+        readable: bool,
+        writable: bool,
+    ) -> io::Result<()> {
+        let fd = source.raw();
+        let (interest, mode) = self.registration(fd)?;
+
+        let interest = Registration {
+            readable,
+            writable,
+            ..interest
+        };
+        self.registry().modify(fd, interest, mode)
+    }
+
+    /// Temporarily stops delivering events for a file descriptor or socket.
+    ///
+    /// Unlike [`delete()`][`Poller::delete()`], the source stays registered, and delivery of its
+    /// events can be resumed with [`enable()`][`Poller::enable()`]. This is meant for pausing a
+    /// source during backpressure without losing its registration in the kernel. On kqueue, this
+    /// uses `EV_DISABLE`. On other platforms, this is emulated by clearing the interest of the
+    /// source.
+    ///
+    /// Calling [`modify()`][`Poller::modify()`] or [`set_interest()`][`Poller::set_interest()`]
+    /// on a disabled source enables it again.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind [`io::ErrorKind::NotFound`] if the source was not added to this
+    /// poller. Returns an error of kind [`io::ErrorKind::Other`] if the poller wasn't built with
+    /// [`PollerBuilder::track_sources()`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use polling::{Event, PollerBuilder};
+    /// use std::net::TcpListener;
+    ///
+    /// let socket = TcpListener::bind("127.0.0.1:0")?;
+    /// socket.set_nonblocking(true)?;
+    ///
+    /// let poller = PollerBuilder::new().track_sources(true).build()?;
+    /// poller.add(&socket, Event::readable(7))?;
+    ///
+    /// # if cfg!(unix) {
+    /// poller.disable(&socket)?;
+    /// // No events are delivered for `socket` here.
+    /// poller.enable(&socket)?;
+    /// # }
+    /// # poller.delete(&socket)?;
+    /// # std::io::Result::Ok(())
+    /// ```
+    pub fn disable(&self, source: impl Source) -> io::Result<()> {
+        let fd = source.raw();
+        let (interest, mode) = self.registration(fd)?;
+        self.poller.disable(fd, interest, mode)
+    }
+
+    /// Resumes delivering events for a file descriptor or socket disabled with
+    /// [`disable()`][`Poller::disable()`].
+    ///
+    /// The source is re-armed with the interest and [`PollMode`] it was last registered with.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind [`io::ErrorKind::NotFound`] if the source was not added to this
+    /// poller. Returns an error of kind [`io::ErrorKind::Other`] if the poller wasn't built with
+    /// [`PollerBuilder::track_sources()`].
+    pub fn enable(&self, source: impl Source) -> io::Result<()> {
+        let fd = source.raw();
+        let (interest, mode) = self.registration(fd)?;
+        self.poller.enable(fd, interest, mode)
+    }
+
+    /// Re-enables a source in oneshot mode after one of its events was delivered.
+    ///
+    /// This is like [`modify()`][`Poller::modify()`], but the source keeps the [`PollMode`] it
+    /// was added with, and re-arming it with the same kinds of events takes as few system calls
+    /// as possible. With kqueue, only the filters of `interest` are added back, instead of also
+    /// deleting the others. The other backends re-arm the source like `modify()` does. A
+    /// different interest, or a source registered with interest in priority data or hangups,
+    /// falls back to `modify()`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind [`io::ErrorKind::NotFound`] if the source was not added to this
+    /// poller. Returns an error of kind [`io::ErrorKind::Other`] if the poller wasn't built with
+    /// [`PollerBuilder::track_sources()`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use polling::{Event, PollerBuilder};
+    /// use std::net::TcpListener;
+    /// use std::time::Duration;
+    ///
+    /// let socket = TcpListener::bind("127.0.0.1:0")?;
+    /// socket.set_nonblocking(true)?;
+    ///
+    /// let poller = PollerBuilder::new().track_sources(true).build()?;
+    /// poller.add(&socket, Event::readable(7))?;
+    ///
+    /// let mut events = Vec::new();
+    /// poller.wait(&mut events, Some(Duration::from_millis(10)))?;
+    /// for ev in &events {
+    ///     // Accept connections, then wait for more.
+    ///     poller.rearm(&socket, Event::readable(ev.key))?;
+    /// }
+    /// # poller.delete(&socket)?;
+    /// # std::io::Result::Ok(())
+    /// ```
+    pub fn rearm(&self, source: impl Source, interest: Event) -> io::Result<()> {
+        let fd = source.raw();
+        let (registered, mode) = self.registration(fd)?;
+
+        let same_filters = interest.readable == registered.readable
+            && interest.writable == registered.writable
+            && !registered.priority
+            && !registered.hup;
+        if !same_filters || interest.key == self.notify_key {
+            return self.modify_with_mode(fd, interest, mode);
+        }
+
+        let interest = Registration::from(interest);
+        self.poller.enable(fd, interest, mode)?;
+        if let Some(sources) = &self.sources {
+            sources
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .insert(fd, (interest, mode));
+        }
+        Ok(())
+    }
+
+    /// Sets the number of bytes that must be buffered before a read event is reported for a
+    /// socket.
+    ///
+    /// This lets a parser that needs a header of a fixed size sleep until the whole header
+    /// arrived, instead of being woken up for every few bytes. A value of 0 or 1 restores the
+    /// default. End of file and errors are still reported right away.
+    ///
+    /// With kqueue, this is `NOTE_LOWAT` on the read filter, which is applied the next time the
+    /// source is added or modified, so it is best called before [`add()`][`Poller::add()`]. It
+    /// is forgotten when the source is removed. The other Unix backends set `SO_RCVLOWAT` on the
+    /// socket right away, which also affects blocking reads from it. It is not restored when the
+    /// socket is removed from the poller; set the mark to 1 to restore the default.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind [`io::ErrorKind::Unsupported`] on Windows. Except with kqueue,
+    /// returns an error if the source is not a socket.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use polling::{Event, Poller};
+    /// use std::net::{TcpListener, TcpStream};
+    ///
+    /// let listener = TcpListener::bind("127.0.0.1:0")?;
+    /// let stream = TcpStream::connect(listener.local_addr()?)?;
+    /// stream.set_nonblocking(true)?;
+    ///
+    /// let poller = Poller::new()?;
+    /// # if polling::BACKEND != "iocp" {
+    /// // Wait for an 8-byte header.
+    /// poller.set_low_water_mark(&stream, 8)?;
+    /// # }
+    /// poller.add(&stream, Event::readable(1))?;
+    /// # poller.delete(&stream)?;
+    /// # std::io::Result::Ok(())
+    /// ```
+    pub fn set_low_water_mark(&self, source: impl Source, bytes: usize) -> io::Result<()> {
+        let fd = source.raw();
+        check_source(fd)?;
+        self.poller.set_low_water_mark(fd, bytes)
+    }
+
+    /// Returns the number of added sources, if they are tracked.
+    fn registered_count(&self) -> Option<usize> {
+        self.sources
+            .as_ref()
+            .map(|sources| sources.lock().unwrap_or_else(|e| e.into_inner()).len())
+    }
+
+    /// Looks up the interest and mode that a source was last registered with.
+    fn registration(&self, fd: RawSource) -> io::Result<(Registration, PollMode)> {
+        let sources = self.sources.as_ref().ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::Other,
+                "sources are only tracked with `PollerBuilder::track_sources()`",
+            )
+        })?;
+        sources
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(&fd)
+            .copied()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "the source is not registered"))
+    }
+
+    /// Removes a file descriptor or socket from the poller.
+    ///
+    /// Unlike [`add()`][`Poller::add()`], this method only removes the file descriptor or
+    /// socket from the poller without putting it back into blocking mode.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use polling::{Event, Poller};
+    /// use std::net::TcpListener;
+    ///
+    /// let socket = TcpListener::bind("127.0.0.1:0")?;
+    /// socket.set_nonblocking(true)?;
+    /// let key = 7;
+    ///
+    /// let poller = Poller::new()?;
+    /// poller.add(&socket, Event::all(key))?;
+    /// poller.delete(&socket)?;
+    /// # std::io::Result::Ok(())
+    /// ```
+    pub fn delete(&self, source: impl Source) -> io::Result<()> {
+        self.registry().delete(source.raw())
+    }
+
+    /// Removes a file descriptor from the poller and then closes it.
+    ///
+    /// Closing a file descriptor doesn't always remove it from the poller: with epoll, the
+    /// registration belongs to the open file description, so it survives as long as a duplicate
+    /// of the file descriptor, made by `dup()` or inherited by a child process, is still open.
+    /// Events keep being reported for it, under a key that may have been reused in the meantime.
+    /// This method removes the file descriptor first, so that can't happen.
+    ///
+    /// # Errors
+    ///
+    /// If the file descriptor could not be removed, for example because it was never added,
+    /// the error is returned. The file descriptor is closed in any case.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use polling::{Event, Poller};
+    /// use std::net::TcpListener;
+    /// use std::os::unix::io::OwnedFd;
+    ///
+    /// let socket = TcpListener::bind("127.0.0.1:0")?;
+    /// socket.set_nonblocking(true)?;
+    ///
+    /// let poller = Poller::new()?;
+    /// poller.add(&socket, Event::readable(7))?;
+    /// poller.delete_and_close(OwnedFd::from(socket))?;
+    /// # std::io::Result::Ok(())
+    /// ```
+    #[cfg(all(unix, not(polling_no_io_safety)))]
+    #[cfg_attr(docsrs, doc(cfg(unix)))]
+    pub fn delete_and_close(&self, fd: std::os::unix::io::OwnedFd) -> io::Result<()> {
+        use std::os::unix::io::AsRawFd;
+
+        let res = self.delete(fd.as_raw_fd());
+        drop(fd);
+        res
+    }
+
+    /// Waits for at least one I/O event and returns the number of new events.
+    ///
+    /// New events will be appended to `events`. If necessary, make sure to clear the [`Vec`]
+    /// before calling [`wait()`][`Poller::wait()`]!
+    ///
+    /// This method will return with no new events if a notification is delivered by the
+    /// [`notify()`] method, or the timeout is reached. Sometimes it may even return with no events
+    /// spuriously.
+    ///
+    /// Only one thread can wait on I/O. If another thread is already in [`wait()`], concurrent
+    /// calls to this method will return immediately with no new events.
+    ///
+    /// If the operating system is ready to deliver a large number of events at once, this method
+    /// may decide to deliver them in smaller batches.
+    ///
+    /// [`notify()`]: `Poller::notify()`
+    /// [`wait()`]: `Poller::wait()`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use polling::{Event, Poller};
+    /// use std::net::TcpListener;
+    /// use std::time::Duration;
+    ///
+    /// let socket = TcpListener::bind("127.0.0.1:0")?;
+    /// socket.set_nonblocking(true)?;
+    /// let key = 7;
+    ///
+    /// let poller = Poller::new()?;
+    /// poller.add(&socket, Event::all(key))?;
+    ///
+    /// let mut events = Vec::new();
+    /// let n = poller.wait(&mut events, Some(Duration::from_secs(1)))?;
+    /// # std::io::Result::Ok(())
+    /// ```
+    pub fn wait(&self, events: &mut Vec<Event>, timeout: Option<Duration>) -> io::Result<usize> {
+        log::trace!("Poller::wait(_, {:?})", timeout);
+
+        if let Ok(mut lock) = self.events.try_lock() {
+            // Wait for I/O events.
+            self.wait_inner(&mut lock, timeout)?;
+
+            // Collect events.
+            let len = events.len();
+            events.extend(
+                lock.iter()
+                    .map(|(ev, _)| ev)
+                    .filter(|ev| !self.is_notify_event(ev)),
+            );
+            let mut woken = Vec::new();
+            self.take_woken(&mut woken);
+            events.extend(woken.into_iter().map(|(key, _)| Event::readable(key)));
+            self.split_events(events, len);
+            Ok(events.len() - len)
+        } else {
+            log::trace!("wait: skipping because another thread is already waiting on I/O");
+            Ok(0)
+        }
+    }
+
+    /// Waits for at least one I/O event and stores the events in `events`.
+    ///
+    /// This is identical to [`wait()`][`Poller::wait()`], but the events are stored in an
+    /// [`Events`] list instead of being appended to a [`Vec`]. The list is cleared before the new
+    /// events are stored, and the raw events reported by the operating system can be accessed
+    /// through [`Events::as_raw_slice()`].
+    ///
+    /// Returns the number of new events.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use polling::{Event, Events, Poller};
+    /// use std::net::TcpListener;
+    /// use std::time::Duration;
+    ///
+    /// let socket = TcpListener::bind("127.0.0.1:0")?;
+    /// socket.set_nonblocking(true)?;
+    /// let key = 7;
+    ///
+    /// let poller = Poller::new()?;
+    /// poller.add(&socket, Event::all(key))?;
+    ///
+    /// let mut events = Events::new();
+    /// let n = poller.wait_events(&mut events, Some(Duration::from_secs(1)))?;
+    /// # std::io::Result::Ok(())
+    /// ```
+    pub fn wait_events(&self, events: &mut Events, timeout: Option<Duration>) -> io::Result<usize> {
+        log::trace!("Poller::wait_events(_, {:?})", timeout);
+
+        events.notify_key = self.notify_key;
+
+        // The lock is only used to make sure that one thread is waiting at a time.
+        if let Ok(_lock) = self.events.try_lock() {
+            events.clear();
+            // A wait that returns early without events was woken up, and didn't time out.
+            let start = Instant::now();
+            events.notified = self.wait_inner(&mut events.events, timeout)?;
+            events.interrupted = timeout.map_or(true, |t| start.elapsed() < t);
+            self.take_woken(&mut events.woken);
+            events.notify_count += self.take_notify_count();
+            Ok(events.iter().count())
+        } else {
+            log::trace!("wait_events: skipping because another thread is already waiting on I/O");
+            events.clear();
+            events.interrupted = true;
+            Ok(0)
+        }
+    }
+
+    /// Collects the I/O events that are ready without blocking and stores them in `events`.
+    ///
+    /// This is like [`wait_events()`][`Poller::wait_events()`] with a zero timeout, but it is
+    /// cheaper for event loops that poll once per iteration, such as once per frame: the backend
+    /// is polled once, and an interrupted poll is reported as no events instead of being retried.
+    ///
+    /// Returns the number of new events. If another thread is already waiting, `events` is
+    /// cleared and 0 is returned.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use polling::{Event, Events, Poller};
+    ///
+    /// let poller = Poller::new()?;
+    /// let mut events = Events::new();
+    /// assert_eq!(poller.try_wait(&mut events)?, 0);
+    ///
+    /// poller.wake(7)?;
+    /// assert_eq!(poller.try_wait(&mut events)?, 1);
+    /// assert_eq!(events.find(7), Some(Event::readable(7)));
+    /// # std::io::Result::Ok(())
+    /// ```
+    pub fn try_wait(&self, events: &mut Events) -> io::Result<usize> {
+        log::trace!("Poller::try_wait(_)");
+
+        events.notify_key = self.notify_key;
+        events.clear();
+
+        let _lock = match self.events.try_lock() {
+            Ok(lock) => lock,
+            Err(_) => {
+                log::trace!("try_wait: skipping because another thread is already waiting on I/O");
+                events.interrupted = true;
+                return Ok(0);
+            }
+        };
+
+        match self.wait_backend(&mut events.events, Some(Duration::from_secs(0))) {
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => events.interrupted = true,
+            res => res?,
+        }
+
+        events.notified = self.clear_notification(&events.events)? || self.is_shut_down();
+        self.take_woken(&mut events.woken);
+        events.notify_count += self.take_notify_count();
+
+        Ok(events.len())
+    }
+
+    /// Waits for at least one I/O event and returns an error if the timeout expires first.
+    ///
+    /// This is identical to [`wait_events()`][`Poller::wait_events()`], but an error of kind
+    /// [`io::ErrorKind::TimedOut`] is returned if `timeout` expires without any events. If the
+    /// wait ends early without events, for example because of a call to
+    /// [`notify()`][`Poller::notify()`] or because another thread is already waiting, `Ok(())`
+    /// is returned.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use polling::{Events, Poller};
+    /// use std::io;
+    /// use std::time::Duration;
+    ///
+    /// let poller = Poller::new()?;
+    /// let mut events = Events::new();
+    ///
+    /// match poller.wait_or_timeout(&mut events, Duration::from_millis(10)) {
+    ///     Ok(()) => println!("{} events", events.iter().count()),
+    ///     Err(e) if e.kind() == io::ErrorKind::TimedOut => println!("timed out"),
+    ///     Err(e) => return Err(e),
+    /// }
+    /// # std::io::Result::Ok(())
+    /// ```
+    pub fn wait_or_timeout(&self, events: &mut Events, timeout: Duration) -> io::Result<()> {
+        let deadline = Instant::now().checked_add(timeout);
+        let n = self.wait_events(events, Some(timeout))?;
+
+        if n == 0 && deadline.map_or(false, |d| Instant::now() >= d) {
+            return Err(io::Error::from(io::ErrorKind::TimedOut));
+        }
+
+        Ok(())
+    }
+
+    /// Waits for at least one I/O event for as long as `pred` returns `true`.
+    ///
+    /// This is like [`wait_events()`][`Poller::wait_events()`], but a wakeup without events, such
+    /// as a call to [`notify()`][`Poller::notify()`] or an interruption by a signal, doesn't end
+    /// the wait. Instead, `pred` is checked again and waiting continues if it still returns
+    /// `true`. `pred` is also checked before the first wait, so this returns right away if it
+    /// returns `false`.
+    ///
+    /// To stop a thread blocked in this method, make `pred` return `false` and then call
+    /// [`notify()`][`Poller::notify()`].
+    ///
+    /// This returns when there are events, when `pred` returns `false`, when `timeout` expires,
+    /// or right away if another thread is already waiting.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use polling::{Events, Poller};
+    /// use std::sync::atomic::{AtomicBool, Ordering};
+    /// use std::sync::Arc;
+    /// use std::thread;
+    ///
+    /// let poller = Arc::new(Poller::new()?);
+    /// let running = Arc::new(AtomicBool::new(true));
+    ///
+    /// let handle = thread::spawn({
+    ///     let poller = poller.clone();
+    ///     let running = running.clone();
+    ///     move || {
+    ///         let mut events = Events::new();
+    ///         poller.wait_while(&mut events, None, || running.load(Ordering::SeqCst))
+    ///     }
+    /// });
+    ///
+    /// // Shut the thread down.
+    /// running.store(false, Ordering::SeqCst);
+    /// poller.notify()?;
+    /// handle.join().unwrap()?;
+    /// # std::io::Result::Ok(())
+    /// ```
+    pub fn wait_while(
+        &self,
+        events: &mut Events,
+        timeout: Option<Duration>,
+        pred: impl Fn() -> bool,
+    ) -> io::Result<()> {
+        log::trace!("Poller::wait_while(_, {:?})", timeout);
+
+        events.notify_key = self.notify_key;
+        events.clear();
+
+        // The lock is only used to make sure that one thread is waiting at a time.
+        let _lock = match self.events.try_lock() {
+            Ok(lock) => lock,
+            Err(_) => {
+                log::trace!(
+                    "wait_while: skipping because another thread is already waiting on I/O"
+                );
+                events.interrupted = true;
+                return Ok(());
+            }
+        };
+
+        let deadline = timeout.and_then(|t| Instant::now().checked_add(t));
+        events.interrupted = true;
+        while pred() {
+            let timeout = if self.is_shut_down() {
+                Some(Duration::from_secs(0))
+            } else {
+                deadline.map(|d| d.saturating_duration_since(Instant::now()))
+            };
+            match self.wait_backend(&mut events.events, timeout) {
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {
+                    log::trace!("wait_while: interrupted");
+                }
+                res => res?,
+            }
+
+            events.notified |= self.clear_notification(&events.events)? || self.is_shut_down();
+            self.take_woken(&mut events.woken);
+            events.notify_count += self.take_notify_count();
+
+            events.interrupted = timeout != Some(Duration::from_secs(0));
+            if events.iter().next().is_some() || !events.interrupted {
+                break;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Waits for a single I/O event and returns it.
+    ///
+    /// At most one event is taken from the operating system, so no other events are lost when
+    /// sources are added in oneshot mode. Returns `None` if the timeout expires, if the wait is
+    /// interrupted by [`notify()`][`Poller::notify()`], or if another thread is already waiting.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use polling::{Event, Poller};
+    /// use std::net::TcpListener;
+    /// use std::time::Duration;
+    ///
+    /// let socket = TcpListener::bind("127.0.0.1:0")?;
+    /// socket.set_nonblocking(true)?;
+    ///
+    /// let poller = Poller::new()?;
+    /// poller.add(&socket, Event::readable(7))?;
+    ///
+    /// if let Some(event) = poller.wait_one(Some(Duration::from_millis(10)))? {
+    ///     println!("{:?}", event);
+    /// }
+    /// # poller.delete(&socket)?;
+    /// # std::io::Result::Ok(())
+    /// ```
+    pub fn wait_one(&self, timeout: Option<Duration>) -> io::Result<Option<Event>> {
+        let mut events = Events {
+            events: platform::Events::with_capacity(1),
+            notify_key: self.notify_key,
+            woken: Vec::new(),
+            merged: None,
+            origins: Vec::new(),
+            notified: false,
+            interrupted: false,
+            notify_count: 0,
+        };
+        self.wait_events(&mut events, timeout)?;
+
+        let event = events.iter().next();
+
+        // Keep the woken keys that aren't returned for the next wait. They come after the I/O
+        // event, if there is one.
+        let has_io_event = events.iter_keys().count() > events.woken.len();
+        let rest = match (has_io_event, events.woken.split_first()) {
+            (false, Some((_, rest))) => rest,
+            _ => &events.woken[..],
+        };
+        if !rest.is_empty() {
+            self.woken
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .extend_from_slice(rest);
+            self.notify()?;
+        }
+
+        Ok(event)
+    }
+
+    /// Waits for at least one I/O event until `deadline` and stores the events in `events`.
+    ///
+    /// This is identical to [`wait_events()`][`Poller::wait_events()`], but it takes the point in
+    /// time at which to stop waiting instead of a timeout. If `deadline` is `None`, this waits
+    /// indefinitely. If `deadline` has already passed, this doesn't block.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use polling::{Events, Poller};
+    /// use std::time::{Duration, Instant};
+    ///
+    /// let poller = Poller::new()?;
+    /// let mut events = Events::new();
+    ///
+    /// // Keep waiting after notifications, without recomputing the timeout.
+    /// let deadline = Instant::now() + Duration::from_millis(10);
+    /// while Instant::now() < deadline {
+    ///     poller.wait_deadline(&mut events, Some(deadline))?;
+    /// }
+    /// # std::io::Result::Ok(())
+    /// ```
+    pub fn wait_deadline(
+        &self,
+        events: &mut Events,
+        deadline: Option<Instant>,
+    ) -> io::Result<usize> {
+        let timeout = deadline.map(|d| d.saturating_duration_since(Instant::now()));
+        self.wait_events(events, timeout)
+    }
+
+    /// Waits for at least one I/O event with `sigmask` as the signal mask of the thread.
+    ///
+    /// This is identical to [`wait_events()`][`Poller::wait_events()`], but the signal mask of
+    /// the calling thread is atomically replaced with `sigmask` while waiting, like `pselect()`
+    /// does. This avoids the race between unblocking a signal and starting to wait, in which the
+    /// signal could be delivered without interrupting the wait: block the signals, check the
+    /// state that their handlers update, and then wait with a mask that unblocks them.
+    ///
+    /// This waits for the poller with `ppoll()`, or `pselect()` on macOS, iOS, tvOS, watchOS,
+    /// illumos and Solaris, and then collects the events without blocking.
+    ///
+    /// If a signal is delivered while waiting, this returns an error of kind
+    /// [`io::ErrorKind::Interrupted`], even if the poller retries on `EINTR` otherwise.
+    ///
+    /// # Errors
+    ///
+    /// This is only supported by the epoll, kqueue and event ports backends. Otherwise, an error
+    /// of kind [`io::ErrorKind::Unsupported`] is returned. This is also the case if the file
+    /// descriptor of the poller doesn't fit in an `fd_set` for `pselect()`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use polling::{Events, Poller, SigSet};
+    /// use std::time::Duration;
+    ///
+    /// let poller = Poller::new()?;
+    /// let mut events = Events::new();
+    ///
+    /// # if polling::BACKEND != "poll" {
+    /// // Allow all signals while waiting.
+    /// poller.wait_with_sigmask(&mut events, Some(Duration::from_millis(10)), &SigSet::empty())?;
+    /// # }
+    /// # std::io::Result::Ok(())
+    /// ```
+    #[cfg(unix)]
+    #[cfg_attr(docsrs, doc(cfg(unix)))]
+    pub fn wait_with_sigmask(
+        &self,
+        events: &mut Events,
+        timeout: Option<Duration>,
+        sigmask: &SigSet,
+    ) -> io::Result<usize> {
+        log::trace!("Poller::wait_with_sigmask(_, {:?}, {:?})", timeout, sigmask);
+
+        // The poll backend doesn't have a file descriptor to wait on.
+        #[cfg(not(all(
+            any(
+                target_os = "linux",
+                target_os = "android",
+                target_os = "illumos",
+                target_os = "solaris",
+                target_os = "macos",
+                target_os = "ios",
+                target_os = "tvos",
+                target_os = "watchos",
+                target_os = "freebsd",
+                target_os = "netbsd",
+                target_os = "openbsd",
+                target_os = "dragonfly",
+            ),
+            not(polling_test_poll_backend),
+        )))]
+        {
+            let _ = (events, timeout, sigmask);
+            Err(unsupported_error(
+                "waiting with a signal mask is not supported by the poll backend",
+            ))
+        }
+
+        #[cfg(all(
+            any(
+                target_os = "linux",
+                target_os = "android",
+                target_os = "illumos",
+                target_os = "solaris",
+                target_os = "macos",
+                target_os = "ios",
+                target_os = "tvos",
+                target_os = "watchos",
+                target_os = "freebsd",
+                target_os = "netbsd",
+                target_os = "openbsd",
+                target_os = "dragonfly",
+            ),
+            not(polling_test_poll_backend),
+        ))]
+        self.wait_with_sigmask_inner(events, timeout, sigmask)
+    }
+
+    /// Waits for the poller to become readable with `sigmask` applied.
+    #[cfg(all(
+        any(
+            target_os = "linux",
+            target_os = "android",
+            target_os = "illumos",
+            target_os = "solaris",
+            target_os = "macos",
+            target_os = "ios",
+            target_os = "tvos",
+            target_os = "watchos",
+            target_os = "freebsd",
+            target_os = "netbsd",
+            target_os = "openbsd",
+            target_os = "dragonfly",
+        ),
+        not(polling_test_poll_backend),
+    ))]
+    fn wait_with_sigmask_inner(
+        &self,
+        events: &mut Events,
+        timeout: Option<Duration>,
+        sigmask: &SigSet,
+    ) -> io::Result<usize> {
+        use std::os::unix::io::AsRawFd;
+
+        events.notify_key = self.notify_key;
+
+        let _lock = match self.events.try_lock() {
+            Ok(lock) => lock,
+            Err(_) => {
+                log::trace!(
+                    "wait_with_sigmask: skipping because another thread is already waiting on I/O"
+                );
+                events.clear();
+                events.interrupted = true;
+                return Ok(0);
+            }
+        };
+
+        // Wait for the poller itself to become readable with the signal mask applied, and then
+        // collect the events without blocking.
+        let timeout = if self.is_shut_down() {
+            Some(Duration::from_secs(0))
+        } else {
+            timeout
+        };
+        let timeout = timeout.map(|t| libc::timespec {
+            tv_sec: t.as_secs().min(libc::time_t::max_value() as u64) as libc::time_t,
+            tv_nsec: t.subsec_nanos() as _,
+        });
+        let timeout = timeout
+            .as_ref()
+            .map_or(std::ptr::null(), |t| t as *const libc::timespec);
+        let fd = self.poller.as_raw_fd();
+
+        #[cfg(not(any(
+            target_os = "illumos",
+            target_os = "solaris",
+            target_os = "macos",
+            target_os = "ios",
+            target_os = "tvos",
+            target_os = "watchos",
+        )))]
+        let res = unsafe {
+            let mut pollfd = libc::pollfd {
+                fd,
+                events: libc::POLLIN,
+                revents: 0,
+            };
+            libc::ppoll(&mut pollfd, 1, timeout, &sigmask.raw)
+        };
+
+        // These platforms don't have `ppoll()`.
+        #[cfg(any(
+            target_os = "illumos",
+            target_os = "solaris",
+            target_os = "macos",
+            target_os = "ios",
+            target_os = "tvos",
+            target_os = "watchos",
+        ))]
+        let res = unsafe {
+            if fd < 0 || fd as usize >= libc::FD_SETSIZE as usize {
+                events.clear();
+                return Err(unsupported_error(
+                    "the file descriptor of the poller is too large for pselect()",
+                ));
+            }
+            let mut readfds = std::mem::MaybeUninit::<libc::fd_set>::zeroed().assume_init();
+            libc::FD_ZERO(&mut readfds);
+            libc::FD_SET(fd, &mut readfds);
+            libc::pselect(
+                fd + 1,
+                &mut readfds,
+                std::ptr::null_mut(),
+                std::ptr::null_mut(),
+                timeout,
+                &sigmask.raw,
+            )
+        };
+
+        if res == -1 {
+            events.clear();
+            return Err(io::Error::last_os_error());
+        }
+
+        events.clear();
+        events.notified = self.wait_inner(&mut events.events, Some(Duration::from_secs(0)))?;
+        events.interrupted = res != 0;
+        self.take_woken(&mut events.woken);
+        events.notify_count += self.take_notify_count();
+        Ok(events.iter().count())
+    }
+
+    /// Collects all pending I/O events without blocking and returns the number of new events.
+    ///
+    /// This waits with a zero timeout repeatedly, appending the events to `events`, until a wait
+    /// returns fewer events than fit in a single batch, which means that no more events are
+    /// pending, or until at least `max` events have been collected. The last batch may take the
+    /// total over `max`.
+    ///
+    /// This is useful for event loops that want to process every pending event before sleeping
+    /// again, especially with level-triggered sources.
+    ///
+    /// Unlike [`wait_events()`][`Poller::wait_events()`], `events` is not cleared first.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use polling::{Event, Events, Poller};
+    /// use std::net::TcpListener;
+    ///
+    /// let socket = TcpListener::bind("127.0.0.1:0")?;
+    /// socket.set_nonblocking(true)?;
+    ///
+    /// let poller = Poller::new()?;
+    /// poller.add(&socket, Event::readable(7))?;
+    ///
+    /// let mut events = Events::new();
+    /// let n = poller.drain_all(&mut events, 4096)?;
+    /// assert_eq!(n, 0);
+    /// # poller.delete(&socket)?;
+    /// # std::io::Result::Ok(())
+    /// ```
+    pub fn drain_all(&self, events: &mut Events, max: usize) -> io::Result<usize> {
+        log::trace!("Poller::drain_all(_, {})", max);
+
+        events.notify_key = self.notify_key;
+
+        let _lock = match self.events.try_lock() {
+            Ok(lock) => lock,
+            Err(_) => {
+                log::trace!("drain_all: skipping because another thread is already waiting on I/O");
+                return Ok(0);
+            }
+        };
+
+        // New events are appended, so the list is no longer merged.
+        events.merged = None;
+
+        let mut batch = new_events(self.max_events);
+        let mut total = 0;
+        while total < max {
+            events.notified |= self.wait_inner(&mut batch, Some(Duration::from_secs(0)))?;
+            total += batch.keys().filter(|&key| key != self.notify_key).count();
+            let done = batch.as_raw_slice().len() < self.wait_len(&batch);
+            events.events.append(&mut batch);
+
+            if done {
+                break;
+            }
+        }
+
+        let len = events.woken.len();
+        self.take_woken(&mut events.woken);
+        events.notify_count += self.take_notify_count();
+        total += events.woken.len() - len;
+
+        Ok(total)
+    }
+
+    /// Discards all pending I/O events without blocking and returns how many were discarded.
+    ///
+    /// This is like [`drain_all()`][`Poller::drain_all()`], but the events are collected into
+    /// the poller's own buffer and dropped. The keys woken with [`wake()`][`Poller::wake()`] and a
+    /// pending notification are discarded as well. This is useful to flush stale readiness, for
+    /// example after reloading the configuration of an event loop.
+    ///
+    /// Sources in oneshot mode need to be re-armed with [`modify()`][`Poller::modify()`] after
+    /// their events were discarded. Sources in level-triggered mode are reported again by the
+    /// next wait if they are still ready, so this stops once a wait reports no sources that
+    /// weren't discarded already, and each source is counted once.
+    ///
+    /// If another thread is already waiting, nothing is discarded and 0 is returned.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use polling::{Event, Poller};
+    /// use std::time::Duration;
+    ///
+    /// let poller = Poller::new()?;
+    /// poller.wake(7)?;
+    /// assert_eq!(poller.drain()?, 1);
+    ///
+    /// let mut events = Vec::new();
+    /// poller.wait(&mut events, Some(Duration::from_secs(0)))?;
+    /// assert!(events.is_empty());
+    /// # std::io::Result::Ok(())
+    /// ```
+    pub fn drain(&self) -> io::Result<usize> {
+        log::trace!("Poller::drain()");
+
+        let mut lock = match self.events.try_lock() {
+            Ok(lock) => lock,
+            Err(_) => {
+                log::trace!("drain: skipping because another thread is already waiting on I/O");
+                return Ok(0);
+            }
+        };
+
+        let mut seen = HashSet::new();
+        loop {
+            // Some backends return on a notification before collecting the other events.
+            let notified = self.wait_inner(&mut lock, Some(Duration::from_secs(0)))?;
+            let new = lock
+                .keys()
+                .filter(|&key| key != self.notify_key && seen.insert(key))
+                .count();
+
+            // Level-triggered sources are reported again while they are ready, so stop once a
+            // wait finds no new keys.
+            if !notified && (lock.as_raw_slice().len() < self.wait_len(&lock) || new == 0) {
+                break;
+            }
+        }
+        let total = seen.len();
+
+        let mut woken = Vec::new();
+        self.take_woken(&mut woken);
+        Ok(total + woken.len())
+    }
+
+    /// Waits for at least one I/O event on any of the given pollers.
+    ///
+    /// This creates a temporary poller, registers the file descriptors of `pollers` in it and
+    /// waits on it. The events of each poller that became ready are then collected without
+    /// blocking, and stored in `events`. This is useful when independent pollers, such as one per
+    /// subsystem, need to be waited on together.
+    ///
+    /// Returns the number of new events. Pollers that another thread is already waiting on are
+    /// skipped. [`Events::iter_with_poller()`] tells which poller reported each event, and
+    /// [`Events::notified_pollers()`] which pollers were notified.
+    ///
+    /// Every call creates and closes the temporary poller, with its notification mechanism, and
+    /// makes one system call for each poller in `pollers` to register it. To wait on the same
+    /// pollers repeatedly, for example in an event loop, register their file descriptors in a
+    /// long-lived [`Poller`] with [`add()`][`Poller::add()`] instead, and wait on that one.
+    ///
+    /// # Errors
+    ///
+    /// This is only supported by the epoll and kqueue backends, which allow a poller to be
+    /// registered in another one. Otherwise, an error of kind [`io::ErrorKind::Unsupported`] is
+    /// returned. An error of kind [`io::ErrorKind::InvalidInput`] is returned if the pollers don't
+    /// use the same notification key.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use polling::{Events, Poller};
+    /// use std::time::Duration;
+    ///
+    /// let network = Poller::new()?;
+    /// let storage = Poller::new()?;
+    ///
+    /// # if polling::BACKEND == "epoll" || polling::BACKEND == "kqueue" {
+    /// let mut events = Events::new();
+    /// storage.notify()?;
+    /// Poller::wait_multiple(&[&network, &storage], &mut events, Some(Duration::from_secs(1)))?;
+    /// # }
+    /// # std::io::Result::Ok(())
+    /// ```
+    pub fn wait_multiple(
+        pollers: &[&Poller],
+        events: &mut Events,
+        timeout: Option<Duration>,
+    ) -> io::Result<usize> {
+        log::trace!("Poller::wait_multiple({}, _, {:?})", pollers.len(), timeout);
+
+        #[cfg(any(
+            not(any(
+                target_os = "linux",
+                target_os = "android",
+                target_os = "macos",
+                target_os = "ios",
+                target_os = "tvos",
+                target_os = "watchos",
+                target_os = "freebsd",
+                target_os = "netbsd",
+                target_os = "openbsd",
+                target_os = "dragonfly",
+            )),
+            polling_test_poll_backend,
+        ))]
+        {
+            let _ = (pollers, events);
+            Err(unsupported_error(
+                "waiting on multiple pollers is not supported by this backend",
+            ))
+        }
+
+        #[cfg(all(
+            any(
+                target_os = "linux",
+                target_os = "android",
+                target_os = "macos",
+                target_os = "ios",
+                target_os = "tvos",
+                target_os = "watchos",
+                target_os = "freebsd",
+                target_os = "netbsd",
+                target_os = "openbsd",
+                target_os = "dragonfly",
+            ),
+            not(polling_test_poll_backend),
+        ))]
+        {
+            use std::os::unix::io::AsRawFd;
+
+            let notify_key = pollers.first().map_or(NOTIFY_KEY, |p| p.notify_key);
+            if pollers.iter().any(|p| p.notify_key != notify_key) {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "the pollers use different notification keys",
+                ));
+            }
+            events.notify_key = notify_key;
+            events.clear();
+
+            // Register every poller in a parent poller, keyed by its index.
+            let parent = Poller::new()?;
+            for (i, poller) in pollers.iter().enumerate() {
+                parent.add(poller.as_raw_fd(), Event::readable(i))?;
+            }
+
+            let mut ready = Vec::new();
+            let timeout = if pollers.iter().any(|p| p.is_shut_down()) {
+                Some(Duration::from_secs(0))
+            } else {
+                timeout
+            };
+            let start = Instant::now();
+            parent.wait(&mut ready, timeout)?;
+            events.interrupted = !ready.is_empty() || timeout.map_or(true, |t| start.elapsed() < t);
+
+            // Collect the events of the pollers that are ready.
+            let mut child_events = platform::Events::new();
+            //
+            // The events are appended, so remember where those of each poller end.
+            let mut events_end = 0;
+            for ev in &ready {
+                let poller = pollers[ev.key];
+                if let Ok(_lock) = poller.events.try_lock() {
+                    let notified =
+                        poller.wait_inner(&mut child_events, Some(Duration::from_secs(0)))?;
+                    events_end += child_events.iter().count();
+                    events.events.append(&mut child_events);
+                    poller.take_woken(&mut events.woken);
+                    events.notified |= notified;
+                    events.notify_count += poller.take_notify_count();
+                    events.origins.push(Origin {
+                        poller: ev.key,
+                        events_end,
+                        woken_end: events.woken.len(),
+                        notified,
+                    });
+                }
+            }
+
+            Ok(events.iter().count())
+        }
+    }
+
+    /// Waits for I/O events on the backend, restarting with the remaining time if interrupted.
+    ///
+    /// Returns whether a notification was consumed.
+    fn wait_inner(
+        &self,
+        events: &mut platform::Events,
+        timeout: Option<Duration>,
+    ) -> io::Result<bool> {
+        // Waits don't block anymore once the poller is shut down.
+        let mut timeout = if self.is_shut_down() {
+            Some(Duration::from_secs(0))
+        } else {
+            timeout
+        };
+        let deadline = timeout.and_then(|t| Instant::now().checked_add(t));
+        loop {
+            match self.wait_backend(events, timeout) {
+                Err(e) if e.kind() == io::ErrorKind::Interrupted && self.retry_on_eintr() => {
+                    log::trace!("wait: interrupted, retrying");
+                    timeout = deadline.map(|d| d.saturating_duration_since(Instant::now()));
+                }
+                res => break res?,
+            }
+        }
+
+        Ok(self.clear_notification(events)? || self.is_shut_down())
+    }
+
+    /// Waits for I/O events on the backend once, storing at most the configured number of them.
+    fn wait_backend(
+        &self,
+        events: &mut platform::Events,
+        timeout: Option<Duration>,
+    ) -> io::Result<()> {
+        let max = self.wait_len(events);
+        self.poller.wait(events, max, timeout)
+    }
+
+    /// The number of events that a single wait stores in `events` at most.
+    fn wait_len(&self, events: &platform::Events) -> usize {
+        events
+            .capacity()
+            .min(self.max_events.unwrap_or(std::usize::MAX))
+    }
+
+    /// Clears the notification, if any, after waiting for `events`.
+    ///
+    /// Returns whether there was a notification.
+    fn clear_notification(&self, events: &platform::Events) -> io::Result<bool> {
+        let notified = self.notified.swap(false, Ordering::SeqCst);
+
+        // Writes to the pipe of `new_piped()` are reported under the notification key.
+        #[cfg(all(unix, not(polling_no_io_safety)))]
+        if let Some(ref reader) = self.wake_pipe {
+            if events.keys().any(|key| key == self.notify_key) {
+                self.rearm_wake_pipe(reader)?;
+            }
+        }
+
+        #[cfg(not(all(unix, not(polling_no_io_safety))))]
+        let _ = events;
+
+        Ok(notified)
+    }
+
+    /// Takes the number of notifications that were counted since the last call.
+    ///
+    /// Like [`take_woken()`][`Poller::take_woken()`], this must be called after the notification
+    /// is cleared.
+    fn take_notify_count(&self) -> usize {
+        self.notify_count
+            .as_ref()
+            .map_or(0, |count| count.swap(0, Ordering::SeqCst))
+    }
+
+    /// Moves the keys woken with [`Poller::wake()`] and [`Poller::post_value()`] into `woken`,
+    /// with their values.
+    ///
+    /// This must be called after the notification is cleared, so that a concurrent call to
+    /// `wake()` either has its key taken here or notifies the poller again.
+    fn take_woken(&self, woken: &mut Vec<(usize, usize)>) {
+        let mut pending = self.woken.lock().unwrap_or_else(|e| e.into_inner());
+        woken.append(&mut pending);
+    }
+
+    /// Reports a readable event with the given key from the current or the following call to
+    /// [`wait()`][`Poller::wait()`].
+    ///
+    /// The key doesn't need to belong to a registered source. This is used for events that don't
+    /// come from the operating system, such as the timers of a
+    /// [`TimerWheel`][`timer::TimerWheel`]. Waking the same key several times before the next wait
+    /// reports it several times.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind [`io::ErrorKind::InvalidInput`] if `key` is the notification key.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use polling::{Event, Poller};
+    ///
+    /// let poller = Poller::new()?;
+    /// poller.wake(7)?;
+    ///
+    /// let mut events = Vec::new();
+    /// poller.wait(&mut events, None)?; // wakes up immediately
+    /// assert_eq!(events, [Event::readable(7)]);
+    /// # std::io::Result::Ok(())
+    /// ```
+    pub fn wake(&self, key: usize) -> io::Result<()> {
+        log::trace!("Poller::wake({})", key);
+        self.registry().wake(key)
+    }
+
+    /// Reports a readable event with the given key from the current or the following call to
+    /// [`wait()`][`Poller::wait()`], and stores `value` with it.
+    ///
+    /// This works like [`wake()`][`Poller::wake()`], which posts a value of 0. The values are
+    /// returned by [`Events::posted()`], so each queue of messages that is shared between
+    /// threads can have its own key, and a value such as the number of messages it received.
+    /// Posting the same key several times reports it several times, each with its value.
+    ///
+    /// Posted events are delivered by the notification of the backend: `EVFILT_USER` or a pipe
+    /// with kqueue, an eventfd with epoll, and `PostQueuedCompletionStatus()` with IOCP.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind [`io::ErrorKind::InvalidInput`] if `key` is the notification key.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use polling::{Events, Poller};
+    /// use std::sync::Arc;
+    /// use std::thread;
+    ///
+    /// let poller = Arc::new(Poller::new()?);
+    /// let sender = thread::spawn({
+    ///     let poller = poller.clone();
+    ///     move || poller.post_value(3, 42)
+    /// });
     ///
-    /// ```no_run
-    /// # use polling::{Event, Poller};
-    /// # let source = std::net::TcpListener::bind("127.0.0.1:0")?;
-    /// # let key = 7;
-    /// # let poller = Poller::new()?;
-    /// # poller.add(&source, Event::none(key))?;
-    /// poller.modify(&source, Event::none(key))?;
+    /// let mut events = Events::new();
+    /// poller.wait_events(&mut events, None)?;
+    /// assert_eq!(events.posted().collect::<Vec<_>>(), [(3, 42)]);
+    /// sender.join().unwrap()?;
     /// # std::io::Result::Ok(())
     /// ```
-    pub fn modify(&self, source: impl Source, interest: Event) -> io::Result<()> {
-        self.modify_with_mode(source, interest, PollMode::Oneshot)
+    pub fn post_value(&self, key: usize, value: usize) -> io::Result<()> {
+        log::trace!("Poller::post_value({}, {})", key, value);
+        self.registry().post_value(key, value)
     }
 
-    /// Modifies interest in a file descriptor or socket to the poller, but with the specified
-    /// mode.
-    ///
-    /// This is identical to the `modify()` function, but allows specifying the polling mode
-    /// to use for this socket.
+    /// Adds a timer that reports a readable event with `key` once `interval` has passed, and
+    /// then every `interval` if `repeat` is set.
     ///
-    /// # Performance Notes
+    /// The timer is kept by the operating system: an `EVFILT_TIMER` filter with kqueue, a
+    /// timerfd with epoll, and a waitable timer with IOCP. Expirations that happen before the
+    /// event is collected are reported once. Adding a timer with the key of an existing one
+    /// replaces it. Use [`delete_timer()`][`Poller::delete_timer()`] to cancel a timer, or to free
+    /// the resources of a timer that doesn't repeat after it fired.
     ///
-    /// This function can be used to change a source from one polling mode to another. However,
-    /// on some platforms, this switch can cause delays in the delivery of events.
+    /// Timer keys are not associated with sources, but events of timers and sources with the
+    /// same key can't be told apart. With epoll, a timer that doesn't repeat is freed once it
+    /// fires.
     ///
     /// # Errors
     ///
-    /// If the operating system does not support the specified mode, this function will return
-    /// an error.
-    pub fn modify_with_mode(
-        &self,
-        source: impl Source,
-        interest: Event,
-        mode: PollMode,
-    ) -> io::Result<()> {
-        if interest.key == NOTIFY_KEY {
+    /// Returns an error of kind [`io::ErrorKind::Unsupported`] with the `poll()` and event
+    /// ports backends, where [`timer::TimerWheel`] can be used instead. Returns an error of kind
+    /// [`io::ErrorKind::InvalidInput`] if `key` is the key used for notifications, the key of a
+    /// signal added with epoll, or the key of a source when
+    /// [sources are tracked][`PollerBuilder::track_sources()`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use polling::{Event, Poller};
+    /// use std::time::Duration;
+    ///
+    /// let poller = Poller::new()?;
+    /// # if polling::BACKEND == "epoll" || polling::BACKEND == "kqueue" {
+    /// poller.add_timer(7, Duration::from_millis(10), true)?;
+    ///
+    /// let mut events = Vec::new();
+    /// poller.wait(&mut events, None)?;
+    /// assert_eq!(events, [Event::readable(7)]);
+    /// poller.delete_timer(7)?;
+    /// # }
+    /// # std::io::Result::Ok(())
+    /// ```
+    pub fn add_timer(&self, key: usize, interval: Duration, repeat: bool) -> io::Result<()> {
+        log::trace!("Poller::add_timer({}, {:?}, {})", key, interval, repeat);
+        if self.is_shut_down() {
+            return Err(shut_down_error());
+        }
+        if key == self.notify_key {
             return Err(io::Error::new(
                 io::ErrorKind::InvalidInput,
-                "the key is not allowed to be `usize::MAX`",
+                "the key is reserved for notifications",
             ));
         }
-        self.poller.modify(source.raw(), interest, mode)
+        if let Some(sources) = &self.sources {
+            let sources = sources.lock().unwrap_or_else(|e| e.into_inner());
+            if sources
+                .values()
+                .any(|(registration, _)| registration.key == key)
+            {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "the key is used by a source",
+                ));
+            }
+        }
+        self.poller.add_timer(key, interval, repeat)
     }
 
-    /// Removes a file descriptor or socket from the poller.
+    /// Removes a timer added with [`add_timer()`][`Poller::add_timer()`].
     ///
-    /// Unlike [`add()`][`Poller::add()`], this method only removes the file descriptor or
-    /// socket from the poller without putting it back into blocking mode.
+    /// Removing a timer that doesn't exist does nothing.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind [`io::ErrorKind::Unsupported`] with the `poll()` and event
+    /// ports backends.
+    pub fn delete_timer(&self, key: usize) -> io::Result<()> {
+        log::trace!("Poller::delete_timer({})", key);
+        self.poller.delete_timer(key)
+    }
+
+    /// Adds a child process that reports a readable event with `key` once it exits.
+    ///
+    /// The event is reported once, and [`delete_process()`][`Poller::delete_process()`] frees the
+    /// resources used to watch the process afterwards. This uses a
+    /// pidfd with epoll, an `EVFILT_PROC` filter with kqueue, and a wait for the process handle
+    /// with IOCP. If the process already exited, the event is reported right away. With epoll
+    /// and kqueue, the process is watched by its ID, so
+    /// [`Child::try_wait()`][`std::process::Child::try_wait()`] is called first to make sure that
+    /// the child wasn't reaped and its ID reused, which reaps the child if it has exited.
+    /// Otherwise, the exit status is not collected, so
+    /// [`Child::wait()`][`std::process::Child::wait()`] still needs to be called to reap the
+    /// process.
+    ///
+    /// To watch a process that isn't a child of this one with kqueue, use
+    /// `polling::os::kqueue::Process` instead.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind [`io::ErrorKind::Unsupported`] with the `poll()` and event
+    /// ports backends, and with epoll on Android. Returns an error of kind
+    /// [`io::ErrorKind::InvalidInput`] if `key` is the key used for notifications.
     ///
     /// # Examples
     ///
-    /// ```
+    /// ```no_run
     /// use polling::{Event, Poller};
-    /// use std::net::TcpListener;
-    ///
-    /// let socket = TcpListener::bind("127.0.0.1:0")?;
-    /// socket.set_nonblocking(true)?;
-    /// let key = 7;
+    /// use std::process::Command;
     ///
     /// let poller = Poller::new()?;
-    /// poller.add(&socket, Event::all(key))?;
-    /// poller.delete(&socket)?;
+    /// let mut child = Command::new("true").spawn()?;
+    /// poller.add_process(&mut child, 7)?;
+    ///
+    /// let mut events = Vec::new();
+    /// poller.wait(&mut events, None)?;
+    /// assert_eq!(events, [Event::readable(7)]);
+    /// child.wait()?;
     /// # std::io::Result::Ok(())
     /// ```
-    pub fn delete(&self, source: impl Source) -> io::Result<()> {
-        self.poller.delete(source.raw())
+    pub fn add_process(&self, child: &mut std::process::Child, key: usize) -> io::Result<()> {
+        log::trace!("Poller::add_process({}, {})", child.id(), key);
+        if self.is_shut_down() {
+            return Err(shut_down_error());
+        }
+        if key == self.notify_key {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "the key is reserved for notifications",
+            ));
+        }
+        match self.poller.add_process(child, key) {
+            // The process was reaped already, so it can't be watched anymore.
+            #[cfg(unix)]
+            Err(err) if err.raw_os_error() == Some(rustix::io::Errno::SRCH.raw_os_error()) => {
+                self.wake(key)
+            }
+            res => res,
+        }
     }
 
-    /// Waits for at least one I/O event and returns the number of new events.
+    /// Stops watching a child process added with [`add_process()`][`Poller::add_process()`].
     ///
-    /// New events will be appended to `events`. If necessary, make sure to clear the [`Vec`]
-    /// before calling [`wait()`][`Poller::wait()`]!
+    /// Removing a process that isn't watched, or whose exit was already reported, does nothing.
     ///
-    /// This method will return with no new events if a notification is delivered by the
-    /// [`notify()`] method, or the timeout is reached. Sometimes it may even return with no events
-    /// spuriously.
+    /// # Errors
     ///
-    /// Only one thread can wait on I/O. If another thread is already in [`wait()`], concurrent
-    /// calls to this method will return immediately with no new events.
+    /// Returns an error of kind [`io::ErrorKind::Unsupported`] with the `poll()` and event
+    /// ports backends.
+    pub fn delete_process(&self, child: &std::process::Child) -> io::Result<()> {
+        log::trace!("Poller::delete_process({})", child.id());
+        self.poller.delete_process(child)
+    }
+
+    /// Adds a signal that reports a readable event with `key` when it is delivered to the
+    /// process.
     ///
-    /// If the operating system is ready to deliver a large number of events at once, this method
-    /// may decide to deliver them in smaller batches.
+    /// Deliveries that happen before the event is collected are reported once. Adding a signal
+    /// that was already added replaces its key.
     ///
-    /// [`notify()`]: `Poller::notify()`
-    /// [`wait()`]: `Poller::wait()`
+    /// How the signal is received depends on the backend:
+    ///
+    /// - With epoll, a signalfd receives the signal. It only receives signals that are blocked,
+    ///   so the signal has to be blocked in every thread, which is usually done with
+    ///   `pthread_sigmask()` on the main thread before any other thread is spawned.
+    /// - With kqueue, an `EVFILT_SIGNAL` filter records the signal. The disposition of the signal
+    ///   is not changed, so it should be blocked or ignored to stop its default action.
+    /// - With the `poll()` and event ports backends, a signal handler is installed, which writes
+    ///   to a pipe. The signal must not be blocked in every thread, and it can only be added to
+    ///   one poller at a time. The previous disposition is restored when the signal is removed.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind [`io::ErrorKind::Unsupported`] on Windows. Returns an error of
+    /// kind [`io::ErrorKind::InvalidInput`] if `key` is the key used for notifications, and
+    /// usually if `signum` is not a valid signal number.
     ///
     /// # Examples
     ///
-    /// ```
+    /// ```no_run
     /// use polling::{Event, Poller};
-    /// use std::net::TcpListener;
-    /// use std::time::Duration;
-    ///
-    /// let socket = TcpListener::bind("127.0.0.1:0")?;
-    /// socket.set_nonblocking(true)?;
-    /// let key = 7;
     ///
     /// let poller = Poller::new()?;
-    /// poller.add(&socket, Event::all(key))?;
+    /// poller.add_signal(libc::SIGTERM, 7)?;
     ///
     /// let mut events = Vec::new();
-    /// let n = poller.wait(&mut events, Some(Duration::from_secs(1)))?;
+    /// poller.wait(&mut events, None)?;
+    /// assert_eq!(events, [Event::readable(7)]);
     /// # std::io::Result::Ok(())
     /// ```
-    pub fn wait(&self, events: &mut Vec<Event>, timeout: Option<Duration>) -> io::Result<usize> {
-        log::trace!("Poller::wait(_, {:?})", timeout);
-
-        if let Ok(mut lock) = self.events.try_lock() {
-            // Wait for I/O events.
-            self.poller.wait(&mut lock, timeout)?;
-
-            // Clear the notification, if any.
-            self.notified.swap(false, Ordering::SeqCst);
-
-            // Collect events.
-            let len = events.len();
-            events.extend(lock.iter().filter(|ev| ev.key != usize::MAX));
-            Ok(events.len() - len)
-        } else {
-            log::trace!("wait: skipping because another thread is already waiting on I/O");
-            Ok(0)
+    pub fn add_signal(&self, signum: i32, key: usize) -> io::Result<()> {
+        log::trace!("Poller::add_signal({}, {})", signum, key);
+        if self.is_shut_down() {
+            return Err(shut_down_error());
+        }
+        if key == self.notify_key {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "the key is reserved for notifications",
+            ));
         }
+        self.poller.add_signal(signum, key)
+    }
+
+    /// Removes a signal added with [`add_signal()`][`Poller::add_signal()`].
+    ///
+    /// Removing a signal that wasn't added does nothing.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind [`io::ErrorKind::Unsupported`] on Windows.
+    pub fn delete_signal(&self, signum: i32) -> io::Result<()> {
+        log::trace!("Poller::delete_signal({})", signum);
+        self.poller.delete_signal(signum)
     }
 
     /// Wakes up the current or the following invocation of [`wait()`].
@@ -507,6 +3980,9 @@ impl Poller {
     /// If no thread is calling [`wait()`] right now, this method will cause the following call
     /// to wake up immediately.
     ///
+    /// Notifications are not reported as events. To tell the waiting thread why it was woken
+    /// up, use [`wake()`][`Poller::wake()`], which reports an event with a key of your choice.
+    ///
     /// [`wait()`]: `Poller::wait()`
     ///
     /// # Examples
@@ -526,14 +4002,167 @@ impl Poller {
     /// ```
     pub fn notify(&self) -> io::Result<()> {
         log::trace!("Poller::notify()");
-        if self
-            .notified
-            .compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst)
-            .is_ok()
-        {
-            self.poller.notify()?;
-        }
-        Ok(())
+        self.registry().notify()
+    }
+
+    /// Shuts the poller down, waking up all threads that are waiting and all following waits.
+    ///
+    /// Once the poller is shut down, waiting doesn't block anymore: pending events are still
+    /// collected, but every wait returns right away and reports a notification with
+    /// [`Events::is_notified()`]. Use [`is_shut_down()`][`Poller::is_shut_down()`] to tell the
+    /// shutdown apart from other notifications. Adding sources fails with an error of kind
+    /// [`io::ErrorKind::NotConnected`], while sources that are already registered can still be
+    /// modified and removed.
+    ///
+    /// Unlike an external flag followed by [`notify()`][`Poller::notify()`], this can't race with
+    /// a thread that checks the flag right before it starts waiting. Shutting down a poller that
+    /// is already shut down does nothing.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use polling::{Events, Poller};
+    /// use std::sync::Arc;
+    /// use std::thread;
+    ///
+    /// let poller = Arc::new(Poller::new()?);
+    ///
+    /// let event_loop = thread::spawn({
+    ///     let poller = poller.clone();
+    ///     move || -> std::io::Result<()> {
+    ///         let mut events = Events::new();
+    ///         while !poller.is_shut_down() {
+    ///             poller.wait_events(&mut events, None)?;
+    ///         }
+    ///         Ok(())
+    ///     }
+    /// });
+    ///
+    /// poller.shutdown()?;
+    /// event_loop.join().unwrap()?;
+    /// assert!(poller.add(0, polling::Event::readable(1)).is_err());
+    /// # std::io::Result::Ok(())
+    /// ```
+    pub fn shutdown(&self) -> io::Result<()> {
+        log::trace!("Poller::shutdown()");
+        self.registry().shutdown()
+    }
+
+    /// Whether [`shutdown()`][`Poller::shutdown()`] was called on this poller or one of its
+    /// handles.
+    pub fn is_shut_down(&self) -> bool {
+        self.shut_down.load(Ordering::SeqCst)
+    }
+
+    /// Sets a callback that is invoked whenever a notification is sent to this poller.
+    ///
+    /// The callback runs on the thread that calls [`notify()`][`Poller::notify()`],
+    /// [`notify_one()`][`Poller::notify_one()`] or [`notify_all()`][`Poller::notify_all()`], right
+    /// after the notification was sent. Calls to `notify()` that find a notification already
+    /// pending don't invoke it. This can be used to wake up a foreign event loop, like GLib or
+    /// libuv, that waits alongside this poller.
+    ///
+    /// The callback replaces the one that was set before, if any. It is not shared with the
+    /// pollers created by [`try_clone()`][`Poller::try_clone()`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use polling::Poller;
+    /// use std::sync::atomic::{AtomicUsize, Ordering};
+    /// use std::sync::Arc;
+    ///
+    /// let poller = Poller::new()?;
+    /// let count = Arc::new(AtomicUsize::new(0));
+    /// let hook_count = count.clone();
+    /// poller.set_notify_hook(Box::new(move || {
+    ///     hook_count.fetch_add(1, Ordering::SeqCst);
+    /// }));
+    ///
+    /// poller.notify()?;
+    /// assert_eq!(count.load(Ordering::SeqCst), 1);
+    /// # std::io::Result::Ok(())
+    /// ```
+    pub fn set_notify_hook(&self, hook: Box<dyn Fn() + Send + Sync>) {
+        *self.notify_hook.lock().unwrap_or_else(|e| e.into_inner()) = Some(Arc::from(hook));
+    }
+
+    /// Adds a file descriptor or socket to the poller, returning a [`PollerError`] on failure.
+    ///
+    /// This is identical to [`add_with_mode()`][`Poller::add_with_mode()`], but the error
+    /// contains the file descriptor or socket that failed to be registered.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use polling::{Event, PollMode, Poller, PollerError};
+    /// use std::net::TcpListener;
+    ///
+    /// let socket = TcpListener::bind("127.0.0.1:0")?;
+    /// socket.set_nonblocking(true)?;
+    ///
+    /// let poller = Poller::new()?;
+    /// let err = poller
+    ///     .add_detailed(&socket, Event::readable(polling::NOTIFY_KEY), PollMode::Oneshot)
+    ///     .unwrap_err();
+    /// assert!(matches!(err, PollerError::Register { .. }));
+    /// # std::io::Result::Ok(())
+    /// ```
+    pub fn add_detailed(
+        &self,
+        source: impl Source,
+        interest: Event,
+        mode: PollMode,
+    ) -> Result<(), PollerError> {
+        let fd = source.raw();
+        self.add_with_mode(fd, interest, mode)
+            .map_err(|source| PollerError::Register { fd, source })
+    }
+
+    /// Modifies the interest in a file descriptor or socket, returning a [`PollerError`] on
+    /// failure.
+    ///
+    /// This is identical to [`modify_with_mode()`][`Poller::modify_with_mode()`], but the error
+    /// contains the file descriptor or socket that failed to be registered.
+    pub fn modify_detailed(
+        &self,
+        source: impl Source,
+        interest: Event,
+        mode: PollMode,
+    ) -> Result<(), PollerError> {
+        let fd = source.raw();
+        self.modify_with_mode(fd, interest, mode)
+            .map_err(|source| PollerError::Register { fd, source })
+    }
+
+    /// Removes a file descriptor or socket from the poller, returning a [`PollerError`] on
+    /// failure.
+    ///
+    /// This is identical to [`delete()`][`Poller::delete()`], but the error contains the file
+    /// descriptor or socket that failed to be removed.
+    pub fn delete_detailed(&self, source: impl Source) -> Result<(), PollerError> {
+        let fd = source.raw();
+        self.delete(fd)
+            .map_err(|source| PollerError::Deregister { fd, source })
+    }
+
+    /// Waits for at least one I/O event, returning a [`PollerError`] on failure.
+    ///
+    /// This is identical to [`wait()`][`Poller::wait()`].
+    pub fn wait_detailed(
+        &self,
+        events: &mut Vec<Event>,
+        timeout: Option<Duration>,
+    ) -> Result<usize, PollerError> {
+        self.wait(events, timeout).map_err(PollerError::Wait)
+    }
+
+    /// Wakes up the current or the following invocation of [`wait()`][`Poller::wait()`],
+    /// returning a [`PollerError`] on failure.
+    ///
+    /// This is identical to [`notify()`][`Poller::notify()`].
+    pub fn notify_detailed(&self) -> Result<(), PollerError> {
+        self.notify().map_err(PollerError::Notify)
     }
 }
 
@@ -575,6 +4204,15 @@ mod raw_fd_impl {
     use crate::Poller;
     use std::os::unix::io::{AsRawFd, RawFd};
 
+    #[cfg(not(any(target_os = "illumos", target_os = "solaris")))]
+    use crate::{platform, PollerBuilder};
+    #[cfg(not(any(target_os = "illumos", target_os = "solaris")))]
+    use std::io;
+    #[cfg(not(any(target_os = "illumos", target_os = "solaris")))]
+    use std::sync::atomic::{self, Ordering};
+    #[cfg(not(any(target_os = "illumos", target_os = "solaris")))]
+    use std::sync::Arc;
+
     #[cfg(not(polling_no_io_safety))]
     use std::os::unix::io::{AsFd, BorrowedFd};
 
@@ -590,6 +4228,65 @@ mod raw_fd_impl {
             self.poller.as_fd()
         }
     }
+
+    #[cfg(not(any(target_os = "illumos", target_os = "solaris")))]
+    impl Poller {
+        /// Creates a poller from an existing epoll or kqueue instance.
+        ///
+        /// This is meant for instances inherited from another process, for example over a Unix
+        /// socket with `SCM_RIGHTS` or across `exec()`. A new notification mechanism is created
+        /// and registered, and the `CLOEXEC` flag is set on `fd` if it isn't already.
+        ///
+        /// The sources registered in the instance by its previous owner are still reported. The
+        /// new poller doesn't know their interest until they are passed to
+        /// [`modify()`][`Poller::modify()`], so [`disable()`][`Poller::disable()`] and
+        /// [`enable()`][`Poller::enable()`] don't work for them before that.
+        ///
+        /// # Errors
+        ///
+        /// An error is returned if `fd` is not an epoll instance on Linux and Android, or not a
+        /// kqueue instance on other platforms. In that case, `fd` is left open.
+        ///
+        /// # Safety
+        ///
+        /// `fd` must be an open file descriptor that is owned by the caller. If this succeeds,
+        /// the poller owns it and closes it when dropped.
+        pub unsafe fn from_raw_fd(fd: RawFd) -> io::Result<Poller> {
+            let builder = PollerBuilder::default();
+            let poller = platform::Poller::from_raw_fd(fd, &builder)?;
+            Ok(builder.wrap(poller))
+        }
+
+        /// Releases the epoll or kqueue instance of the poller without closing it.
+        ///
+        /// The notification mechanism of the poller is removed from the instance and closed.
+        /// The sources that were added stay registered. The
+        /// [`PollerHandle`][`crate::PollerHandle`]s of this poller stop working, and this blocks
+        /// until the ones that are using the instance finish their call. Notify hooks run by
+        /// handles don't count, since they run after the instance is no longer used.
+        pub fn into_raw_fd(self) -> RawFd {
+            let release = self.release;
+            release.released.store(true, Ordering::SeqCst);
+            atomic::fence(Ordering::SeqCst);
+
+            // Handles notify the condition variable under the lock after they drop their
+            // reference, so a wakeup can't be missed between `try_unwrap()` and `wait()`.
+            let mut guard = release.lock.lock().unwrap_or_else(|e| e.into_inner());
+            let mut poller = self.poller;
+            loop {
+                match Arc::try_unwrap(poller) {
+                    Ok(poller) => return poller.into_raw_fd(),
+                    Err(shared) => {
+                        poller = shared;
+                        guard = release
+                            .unused
+                            .wait(guard)
+                            .unwrap_or_else(|e| e.into_inner());
+                    }
+                }
+            }
+        }
+    }
 }
 
 #[cfg(windows)]
@@ -617,7 +4314,50 @@ mod raw_handle_impl {
 
 impl fmt::Debug for Poller {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        self.poller.fmt(f)
+        let registered = self.registered_count();
+
+        let mut s = f.debug_struct("Poller");
+        s.field("backend", &BACKEND);
+
+        // The poll() backend doesn't have a file descriptor of its own.
+        #[cfg(all(
+            any(
+                target_os = "linux",
+                target_os = "android",
+                target_os = "illumos",
+                target_os = "solaris",
+                target_os = "macos",
+                target_os = "ios",
+                target_os = "tvos",
+                target_os = "watchos",
+                target_os = "freebsd",
+                target_os = "netbsd",
+                target_os = "openbsd",
+                target_os = "dragonfly",
+            ),
+            not(polling_test_poll_backend),
+        ))]
+        {
+            use std::os::unix::io::AsRawFd;
+            s.field("fd", &self.as_raw_fd());
+        }
+
+        #[cfg(windows)]
+        {
+            use std::os::windows::io::AsRawHandle;
+            s.field("handle", &self.as_raw_handle());
+        }
+
+        // The sources are only counted if they are tracked.
+        if let Some(registered) = registered {
+            #[cfg(unix)]
+            s.field("registered_fds", &registered);
+            #[cfg(windows)]
+            s.field("registered_handles", &registered);
+        }
+
+        s.field("notify_armed", &self.notified.load(Ordering::SeqCst));
+        s.finish()
     }
 }
 
@@ -625,7 +4365,15 @@ cfg_if! {
     if #[cfg(unix)] {
         use std::os::unix::io::{AsRawFd, RawFd};
 
-        /// A [`RawFd`] or a reference to a type implementing [`AsRawFd`].
+        /// The raw file descriptor of a [`Source`].
+        pub type RawSource = RawFd;
+
+        /// A [`RawFd`], a `BorrowedFd`, or a reference to a type implementing [`AsRawFd`].
+        ///
+        /// References to `OwnedFd` and `BorrowedFd` are sources too. To have the compiler check
+        /// that a source isn't closed while it is added to the poller, add it with
+        /// [`Poller::register()`], which borrows it until the returned [`WatchGuard`] is
+        /// dropped.
         pub trait Source {
             /// Returns the [`RawFd`] for this I/O object.
             fn raw(&self) -> RawFd;
@@ -642,10 +4390,60 @@ cfg_if! {
                 self.as_raw_fd()
             }
         }
+
+        #[cfg(not(polling_no_io_safety))]
+        impl Source for std::os::unix::io::BorrowedFd<'_> {
+            fn raw(&self) -> RawFd {
+                self.as_raw_fd()
+            }
+        }
+
+        /// Rejects file descriptors that can never be valid before they reach the backend.
+        fn check_source(fd: RawFd) -> io::Result<()> {
+            if fd < 0 {
+                return Err(io::Error::from_raw_os_error(libc::EBADF));
+            }
+            Ok(())
+        }
+
+        /// Checks whether a file descriptor is readable and writable without blocking.
+        fn probe_source(fd: RawFd) -> io::Result<(bool, bool)> {
+            use rustix::fd::BorrowedFd;
+            use rustix::io::{poll, PollFd, PollFlags};
+
+            check_source(fd)?;
+            let fd = unsafe { BorrowedFd::borrow_raw(fd) };
+            let mut fds = [PollFd::from_borrowed_fd(
+                fd,
+                PollFlags::IN | PollFlags::OUT | PollFlags::PRI,
+            )];
+            poll(&mut fds, 0)?;
+
+            let revents = fds[0].revents();
+            if revents.contains(PollFlags::NVAL) {
+                return Err(io::Error::from_raw_os_error(libc::EBADF));
+            }
+
+            // Errors and hangups are reported as both readable and writable, like the backends do.
+            let error = revents.intersects(PollFlags::ERR | PollFlags::HUP);
+            Ok((
+                error || revents.intersects(PollFlags::IN | PollFlags::PRI),
+                error || revents.contains(PollFlags::OUT),
+            ))
+        }
     } else if #[cfg(windows)] {
         use std::os::windows::io::{AsRawSocket, RawSocket};
 
-        /// A [`RawSocket`] or a reference to a type implementing [`AsRawSocket`].
+        /// The raw socket of a [`Source`].
+        pub type RawSource = RawSocket;
+
+        /// A [`RawSocket`], a `BorrowedSocket`, or a reference to a type implementing
+        /// [`AsRawSocket`].
+        ///
+        /// References to `OwnedSocket` and `BorrowedSocket` are sources too. To have the
+        /// compiler check that a source isn't closed while it is added to the poller, add it
+        /// with [`Poller::register()`], which borrows it until the returned [`WatchGuard`] is
+        /// dropped.
         pub trait Source {
             /// Returns the [`RawSocket`] for this I/O object.
             fn raw(&self) -> RawSocket;
@@ -662,10 +4460,77 @@ cfg_if! {
                 self.as_raw_socket()
             }
         }
+
+        #[cfg(not(polling_no_io_safety))]
+        impl Source for std::os::windows::io::BorrowedSocket<'_> {
+            fn raw(&self) -> RawSocket {
+                self.as_raw_socket()
+            }
+        }
+
+        /// Rejects sockets that can never be valid before they reach the backend.
+        fn check_source(_socket: RawSocket) -> io::Result<()> {
+            // Invalid sockets are rejected when they are looked up by the backend.
+            Ok(())
+        }
+
+        /// Checks whether a socket is readable and writable without blocking.
+        fn probe_source(socket: RawSocket) -> io::Result<(bool, bool)> {
+            use windows_sys::Win32::Networking::WinSock::{
+                WSAGetLastError, WSAPoll, POLLERR, POLLHUP, POLLNVAL, POLLRDBAND, POLLRDNORM,
+                POLLWRNORM, SOCKET_ERROR, WSAENOTSOCK, WSAPOLLFD,
+            };
+
+            let mut fd = WSAPOLLFD {
+                fd: socket as _,
+                events: POLLRDNORM | POLLRDBAND | POLLWRNORM,
+                revents: 0,
+            };
+            if unsafe { WSAPoll(&mut fd, 1, 0) } == SOCKET_ERROR {
+                return Err(io::Error::from_raw_os_error(unsafe { WSAGetLastError() }));
+            }
+            if fd.revents & POLLNVAL != 0 {
+                return Err(io::Error::from_raw_os_error(WSAENOTSOCK as _));
+            }
+
+            // Errors and hangups are reported as both readable and writable, like the backend does.
+            let error = fd.revents & (POLLERR | POLLHUP) != 0;
+            Ok((
+                error || fd.revents & (POLLRDNORM | POLLRDBAND) != 0,
+                error || fd.revents & POLLWRNORM != 0,
+            ))
+        }
+    }
+}
+
+/// Creates the list of events that `wait()` collects into.
+fn new_events(max_events: Option<usize>) -> platform::Events {
+    match max_events {
+        Some(max) => platform::Events::with_capacity(max),
+        None => platform::Events::new(),
+    }
+}
+
+fn shut_down_error() -> io::Error {
+    io::Error::new(io::ErrorKind::NotConnected, "the poller is shut down")
+}
+
+/// Checks whether an error means that a source is not added to the poller, or was closed.
+fn is_not_registered(err: &io::Error) -> bool {
+    if err.kind() == io::ErrorKind::NotFound {
+        return true;
+    }
+
+    #[cfg(unix)]
+    {
+        matches!(err.raw_os_error(), Some(libc::ENOENT) | Some(libc::EBADF))
+    }
+    #[cfg(windows)]
+    {
+        false
     }
 }
 
-#[allow(unused)]
 fn unsupported_error(err: impl Into<String>) -> io::Error {
     io::Error::new(
         #[cfg(not(polling_no_unsupported_error_kind))]
@@ -0,0 +1,191 @@
+//! A poller that doesn't perform any I/O, for testing event loops.
+//!
+//! [`MockPoller`] has the same interface as [`Poller`], but events are only delivered when they
+//! are [injected][`MockPoller::inject()`]. This makes it possible to test how an event loop reacts
+//! to I/O events without setting up real sockets or files.
+//!
+//! # Examples
+//!
+//! ```
+//! use polling::mock::MockPoller;
+//! use polling::Event;
+//! use std::time::Duration;
+//!
+//! let poller = MockPoller::new();
+//! poller.add(7, Event::readable(1))?;
+//!
+//! // Pretend that the source became readable.
+//! poller.inject(1, true, false);
+//!
+//! let mut events = Vec::new();
+//! poller.wait(&mut events, Some(Duration::from_secs(1)))?;
+//! assert_eq!(events, [Event::readable(1)]);
+//! # std::io::Result::Ok(())
+//! ```
+//!
+//! [`Poller`]: crate::Poller
+
+use std::collections::HashMap;
+use std::io;
+use std::sync::{Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::{Event, PollMode, RawSource, Source, NOTIFY_KEY};
+
+/// A poller that only delivers injected events.
+///
+/// See the [module documentation](self) for more information.
+#[derive(Debug, Default)]
+pub struct MockPoller {
+    state: Mutex<State>,
+    cond: Condvar,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    /// The registered sources.
+    sources: HashMap<RawSource, (Event, PollMode)>,
+    /// Events that were injected but not yet delivered.
+    pending: Vec<Event>,
+    /// Whether `notify()` was called since the last `wait()`.
+    notified: bool,
+}
+
+impl MockPoller {
+    /// Creates a new mock poller.
+    pub fn new() -> MockPoller {
+        MockPoller::default()
+    }
+
+    /// Adds a file descriptor or socket to the poller.
+    ///
+    /// The source is only recorded, and is never used for I/O.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the key is [`NOTIFY_KEY`] or if the source was already added.
+    pub fn add(&self, source: impl Source, interest: Event) -> io::Result<()> {
+        self.add_with_mode(source, interest, PollMode::Oneshot)
+    }
+
+    /// Adds a file descriptor or socket to the poller in the specified mode.
+    pub fn add_with_mode(
+        &self,
+        source: impl Source,
+        interest: Event,
+        mode: PollMode,
+    ) -> io::Result<()> {
+        check_key(&interest)?;
+        let mut state = self.lock();
+        if state.sources.contains_key(&source.raw()) {
+            return Err(io::Error::from(io::ErrorKind::AlreadyExists));
+        }
+        state.sources.insert(source.raw(), (interest, mode));
+        Ok(())
+    }
+
+    /// Modifies the interest in a file descriptor or socket.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the key is [`NOTIFY_KEY`] or if the source was not added.
+    pub fn modify(&self, source: impl Source, interest: Event) -> io::Result<()> {
+        self.modify_with_mode(source, interest, PollMode::Oneshot)
+    }
+
+    /// Modifies the interest in a file descriptor or socket, with the specified mode.
+    pub fn modify_with_mode(
+        &self,
+        source: impl Source,
+        interest: Event,
+        mode: PollMode,
+    ) -> io::Result<()> {
+        check_key(&interest)?;
+        match self.lock().sources.get_mut(&source.raw()) {
+            Some(registration) => {
+                *registration = (interest, mode);
+                Ok(())
+            }
+            None => Err(io::Error::from(io::ErrorKind::NotFound)),
+        }
+    }
+
+    /// Removes a file descriptor or socket from the poller.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the source was not added.
+    pub fn delete(&self, source: impl Source) -> io::Result<()> {
+        match self.lock().sources.remove(&source.raw()) {
+            Some(_) => Ok(()),
+            None => Err(io::Error::from(io::ErrorKind::NotFound)),
+        }
+    }
+
+    /// Returns the interest and mode that a source is registered with.
+    pub fn registration(&self, source: impl Source) -> Option<(Event, PollMode)> {
+        self.lock().sources.get(&source.raw()).copied()
+    }
+
+    /// Injects an event, which is delivered by the current or next call to
+    /// [`wait()`][`MockPoller::wait()`].
+    ///
+    /// The event is delivered as is, whether or not a source is registered with the key.
+    pub fn inject(&self, key: usize, readable: bool, writable: bool) {
+        self.lock()
+            .pending
+            .push(Event::new(key, readable, writable));
+        self.cond.notify_all();
+    }
+
+    /// Waits for injected events and returns the number of new events.
+    ///
+    /// New events are appended to `events`. This blocks until an event is injected,
+    /// [`notify()`][`MockPoller::notify()`] is called or the timeout expires.
+    pub fn wait(&self, events: &mut Vec<Event>, timeout: Option<Duration>) -> io::Result<usize> {
+        let deadline = timeout.and_then(|t| Instant::now().checked_add(t));
+        let mut state = self.lock();
+
+        while state.pending.is_empty() && !state.notified {
+            state = match deadline {
+                None => self.cond.wait(state).unwrap_or_else(|e| e.into_inner()),
+                Some(deadline) => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        break;
+                    }
+                    self.cond
+                        .wait_timeout(state, deadline - now)
+                        .unwrap_or_else(|e| e.into_inner())
+                        .0
+                }
+            };
+        }
+
+        state.notified = false;
+        let len = state.pending.len();
+        events.append(&mut state.pending);
+        Ok(len)
+    }
+
+    /// Wakes up the current or the following invocation of [`wait()`][`MockPoller::wait()`].
+    pub fn notify(&self) -> io::Result<()> {
+        self.lock().notified = true;
+        self.cond.notify_all();
+        Ok(())
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+fn check_key(interest: &Event) -> io::Result<()> {
+    if interest.key == NOTIFY_KEY {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "the key is reserved for notifications",
+        ));
+    }
+    Ok(())
+}
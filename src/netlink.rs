@@ -0,0 +1,131 @@
+//! Netlink sockets on Linux.
+
+use std::io;
+use std::mem;
+use std::os::raw::c_int;
+use std::os::unix::io::RawFd;
+
+use crate::{Event, Poller};
+
+/// The receive buffer size requested for netlink sockets.
+///
+/// The kernel drops netlink messages that don't fit into the receive buffer, which easily happens
+/// with the default size when many routes or links change at once.
+const NETLINK_RCVBUF: c_int = 1 << 20;
+
+impl Poller {
+    /// Adds a netlink socket to the poller, interested in readability under `key`.
+    ///
+    /// The receive buffer of the socket is raised to 1 MiB if it is smaller, so that bursts of
+    /// messages, such as a full routing table dump, aren't dropped by the kernel. Like with
+    /// [`add()`][`Poller::add()`], the socket is added in oneshot mode.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind [`io::ErrorKind::InvalidInput`] if `socket` is not a netlink
+    /// socket.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use polling::Poller;
+    /// use std::time::Duration;
+    ///
+    /// let socket = unsafe {
+    ///     libc::socket(
+    ///         libc::AF_NETLINK,
+    ///         libc::SOCK_RAW | libc::SOCK_NONBLOCK | libc::SOCK_CLOEXEC,
+    ///         libc::NETLINK_ROUTE,
+    ///     )
+    /// };
+    /// assert!(socket >= 0);
+    ///
+    /// let poller = Poller::new()?;
+    /// poller.register_netlink(1, socket)?;
+    ///
+    /// let mut events = Vec::new();
+    /// poller.wait(&mut events, Some(Duration::from_millis(10)))?;
+    ///
+    /// poller.deregister_netlink(1)?;
+    /// unsafe { libc::close(socket) };
+    /// # std::io::Result::Ok(())
+    /// ```
+    pub fn register_netlink(&self, key: usize, socket: RawFd) -> io::Result<()> {
+        if getsockopt(socket, libc::SO_DOMAIN)? != libc::AF_NETLINK {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "not a netlink socket",
+            ));
+        }
+
+        // Linux reports twice the size that was set, to account for its bookkeeping.
+        if getsockopt(socket, libc::SO_RCVBUF)? < NETLINK_RCVBUF * 2 {
+            setsockopt(socket, libc::SO_RCVBUF, NETLINK_RCVBUF)?;
+        }
+
+        self.add(socket, Event::readable(key))?;
+        self.netlink
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(key, socket);
+        Ok(())
+    }
+
+    /// Removes the netlink socket that was added under `key` with
+    /// [`register_netlink()`][`Poller::register_netlink()`].
+    ///
+    /// Returns an error of kind [`io::ErrorKind::NotFound`] if no netlink socket is registered
+    /// under `key`. Other sources are never removed by this method.
+    pub fn deregister_netlink(&self, key: usize) -> io::Result<()> {
+        let socket = self
+            .netlink
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(&key)
+            .copied()
+            .ok_or(io::ErrorKind::NotFound)?;
+
+        self.delete(socket)?;
+        self.netlink
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(&key);
+        Ok(())
+    }
+}
+
+/// Reads an integer option of a socket.
+fn getsockopt(socket: RawFd, option: c_int) -> io::Result<c_int> {
+    let mut value: c_int = 0;
+    let mut len = mem::size_of::<c_int>() as libc::socklen_t;
+    let res = unsafe {
+        libc::getsockopt(
+            socket,
+            libc::SOL_SOCKET,
+            option,
+            &mut value as *mut c_int as *mut libc::c_void,
+            &mut len,
+        )
+    };
+    if res == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(value)
+}
+
+/// Sets an integer option of a socket.
+fn setsockopt(socket: RawFd, option: c_int, value: c_int) -> io::Result<()> {
+    let res = unsafe {
+        libc::setsockopt(
+            socket,
+            libc::SOL_SOCKET,
+            option,
+            &value as *const c_int as *const libc::c_void,
+            mem::size_of::<c_int>() as libc::socklen_t,
+        )
+    };
+    if res == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
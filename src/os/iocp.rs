@@ -1,10 +1,11 @@
 //! Functionality that is only availale for IOCP-based platforms.
 
-pub use crate::sys::CompletionPacket;
+pub use crate::platform::CompletionPacket;
 
 use super::__private::PollerSealed;
-use crate::Poller;
+use crate::{Event, PollMode, Poller};
 use std::io;
+use std::os::windows::io::RawHandle;
 
 /// Extension trait for the [`Poller`] type that provides functionality specific to IOCP-based
 /// platforms.
@@ -43,10 +44,80 @@ pub trait PollerIocpExt: PollerSealed {
     /// # Ok(()) }
     /// ```
     fn post(&self, packet: CompletionPacket) -> io::Result<()>;
+
+    /// Adds a handle that can be waited on, like an event or a process, to the poller.
+    ///
+    /// A readable event is reported once the handle is signaled. Only the readability of
+    /// `interest` is used. In oneshot mode, the handle has to be re-enabled with
+    /// [`modify_waitable()`][`PollerIocpExt::modify_waitable()`] after an event. In
+    /// level-triggered mode, it is waited for again after every event, so an event is reported
+    /// by every wait while the handle stays signaled. Edge-triggered modes are not supported.
+    ///
+    /// Waiting for an auto-reset event or a semaphore resets it, so these only report an event
+    /// once per signal even in level-triggered mode. Files and named pipes are not signaled when
+    /// they are ready for I/O; wait for the event of an overlapped operation on them instead.
+    ///
+    /// The handle must be removed with [`delete_waitable()`][`PollerIocpExt::delete_waitable()`]
+    /// before it is closed.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use polling::{Event, PollMode, Poller};
+    /// use polling::os::iocp::PollerIocpExt;
+    /// use std::os::windows::io::AsRawHandle;
+    /// use std::process::Command;
+    ///
+    /// let child = Command::new("cmd").args(&["/C", "exit"]).spawn()?;
+    /// let poller = Poller::new()?;
+    /// poller.add_waitable(child.as_raw_handle(), Event::readable(1), PollMode::Oneshot)?;
+    ///
+    /// // The child has exited once the event is reported.
+    /// let mut events = Vec::new();
+    /// poller.wait(&mut events, None)?;
+    /// poller.delete_waitable(child.as_raw_handle())?;
+    /// # std::io::Result::Ok(())
+    /// ```
+    fn add_waitable(&self, handle: RawHandle, interest: Event, mode: PollMode) -> io::Result<()>;
+
+    /// Modifies the interest in a handle added with
+    /// [`add_waitable()`][`PollerIocpExt::add_waitable()`].
+    ///
+    /// This re-enables a handle in oneshot mode after an event was reported for it.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind [`io::ErrorKind::NotFound`] if the handle was not added.
+    fn modify_waitable(&self, handle: RawHandle, interest: Event, mode: PollMode)
+        -> io::Result<()>;
+
+    /// Removes a handle added with [`add_waitable()`][`PollerIocpExt::add_waitable()`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind [`io::ErrorKind::NotFound`] if the handle was not added.
+    fn delete_waitable(&self, handle: RawHandle) -> io::Result<()>;
 }
 
 impl PollerIocpExt for Poller {
     fn post(&self, packet: CompletionPacket) -> io::Result<()> {
         self.poller.post(packet)
     }
+
+    fn add_waitable(&self, handle: RawHandle, interest: Event, mode: PollMode) -> io::Result<()> {
+        self.poller.add_waitable(handle, interest, mode)
+    }
+
+    fn modify_waitable(
+        &self,
+        handle: RawHandle,
+        interest: Event,
+        mode: PollMode,
+    ) -> io::Result<()> {
+        self.poller.modify_waitable(handle, interest, mode)
+    }
+
+    fn delete_waitable(&self, handle: RawHandle) -> io::Result<()> {
+        self.poller.delete_waitable(handle)
+    }
 }
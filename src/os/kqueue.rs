@@ -1,6 +1,6 @@
 //! Functionality that is only available for `kqueue`-based platforms.
 
-use crate::sys::mode_to_flags;
+use crate::platform::mode_to_flags;
 use crate::{PollMode, Poller};
 
 use std::io;
@@ -237,6 +237,28 @@ unsafe impl FilterSealed for Timer {
 
 impl Filter for Timer {}
 
+cfg_if::cfg_if! {
+    if #[cfg(all(
+        feature = "mach",
+        any(
+            target_os = "macos",
+            target_os = "ios",
+            target_os = "tvos",
+            target_os = "watchos",
+        )
+    ))] {
+        mod mach;
+
+        pub use mach::MachPort;
+        pub(crate) use mach::is_machport;
+    } else {
+        /// Whether `ev` was reported by the `EVFILT_MACHPORT` filter.
+        pub(crate) fn is_machport(_ev: &kqueue::Event) -> bool {
+            false
+        }
+    }
+}
+
 mod __private {
     use rustix::io::kqueue;
 
@@ -0,0 +1,55 @@
+//! Support for the `EVFILT_MACHPORT` filter of Apple platforms.
+
+use super::__private::FilterSealed;
+use super::Filter;
+
+use rustix::io::kqueue;
+
+/// Wait for a message to arrive on a Mach port or port set.
+///
+/// The port is the `mach_port_t` name of a receive right, such as the port of an XPC connection
+/// or of an `IONotificationPort`. The event only reports that a message can be received; the
+/// message itself has to be received with `mach_msg()`, which stops the port from being reported
+/// in level-triggered mode.
+///
+/// # Examples
+///
+/// ```no_run
+/// use polling::os::kqueue::{MachPort, PollerKqueueExt};
+/// use polling::{Event, PollMode, Poller};
+///
+/// # let port = 0;
+/// let poller = Poller::new()?;
+/// poller.add_filter(MachPort(port), 1, PollMode::Level)?;
+///
+/// let mut events = Vec::new();
+/// poller.wait(&mut events, None)?;
+/// assert_eq!(events, [Event::readable(1)]);
+/// # std::io::Result::Ok(())
+/// ```
+#[cfg_attr(docsrs, doc(cfg(feature = "mach")))]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct MachPort(pub u32);
+
+unsafe impl FilterSealed for MachPort {
+    fn filter(&self, flags: kqueue::EventFlags, key: usize) -> kqueue::Event {
+        change(self.0, flags | kqueue::EventFlags::RECEIPT, key)
+    }
+}
+
+impl Filter for MachPort {}
+
+/// Creates an `EVFILT_MACHPORT` change for messages arriving on a port.
+fn change(port: u32, flags: kqueue::EventFlags, key: usize) -> kqueue::Event {
+    // rustix doesn't know about `EVFILT_MACHPORT`, so change the filter of a read change.
+    let mut change = kqueue::Event::new(kqueue::EventFilter::Read(0), flags, key as _);
+    let raw = crate::platform::as_raw_mut(&mut change);
+    raw.ident = port as _;
+    raw.filter = libc::EVFILT_MACHPORT;
+    change
+}
+
+/// Whether `ev` was reported by the `EVFILT_MACHPORT` filter.
+pub(crate) fn is_machport(ev: &kqueue::Event) -> bool {
+    crate::platform::as_raw(ev).filter == libc::EVFILT_MACHPORT
+}
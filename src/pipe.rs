@@ -0,0 +1,167 @@
+//! Creating pipes that can be used as custom event sources.
+
+use std::io;
+
+#[cfg(unix)]
+use std::os::unix::io::{AsRawFd, OwnedFd};
+#[cfg(windows)]
+use std::os::windows::io::OwnedSocket;
+#[cfg(unix)]
+use std::sync::Arc;
+
+#[cfg(unix)]
+use crate::{Event, PollMode, Poller};
+
+/// Creates a pipe whose ends are non-blocking and closed on `exec`.
+///
+/// Returns the read end and the write end of the pipe, in that order. The read end can be added
+/// to a [`Poller`][`crate::Poller`] to be woken up by writing to the write end from another thread
+/// or from a signal handler.
+///
+/// On platforms without `pipe2()`, such as macOS, the flags are set after the pipe is created, so
+/// a concurrent `fork()` may inherit the file descriptors.
+///
+/// On Windows, where only sockets can be added to a poller, the two ends are a pair of connected
+/// TCP sockets on the loopback interface instead. They are non-blocking and not inherited by
+/// child processes.
+///
+/// # Examples
+///
+/// ```
+/// use polling::{Event, Poller};
+/// use std::io::Write;
+/// use std::time::Duration;
+///
+/// let (reader, writer) = polling::pipe()?;
+///
+/// let poller = Poller::new()?;
+/// poller.add(&reader, Event::readable(1))?;
+///
+/// # #[cfg(unix)]
+/// let mut writer = std::fs::File::from(writer);
+/// # #[cfg(windows)]
+/// # let mut writer = std::net::TcpStream::from(writer);
+/// writer.write_all(&[1])?;
+///
+/// let mut events = Vec::new();
+/// poller.wait(&mut events, Some(Duration::from_secs(1)))?;
+/// assert_eq!(events, [Event::readable(1)]);
+/// # poller.delete(&reader)?;
+/// # std::io::Result::Ok(())
+/// ```
+#[cfg(unix)]
+pub fn pipe() -> io::Result<(OwnedFd, OwnedFd)> {
+    cfg_if::cfg_if! {
+        if #[cfg(any(
+            target_os = "macos",
+            target_os = "ios",
+            target_os = "tvos",
+            target_os = "watchos",
+            target_os = "aix",
+            target_os = "haiku",
+        ))] {
+            use rustix::fs::{fcntl_getfl, fcntl_setfl, OFlags};
+            use rustix::io::{fcntl_getfd, fcntl_setfd, FdFlags};
+
+            let (reader, writer) = rustix::io::pipe()?;
+            for fd in &[&reader, &writer] {
+                fcntl_setfd(fd, fcntl_getfd(fd)? | FdFlags::CLOEXEC)?;
+                fcntl_setfl(fd, fcntl_getfl(fd)? | OFlags::NONBLOCK)?;
+            }
+        } else {
+            use rustix::io::PipeFlags;
+
+            let (reader, writer) = rustix::io::pipe_with(PipeFlags::CLOEXEC | PipeFlags::NONBLOCK)?;
+        }
+    }
+
+    Ok((reader, writer))
+}
+
+/// Creates a pair of connected sockets that are used like a pipe.
+///
+/// See the Unix version of this function for more information.
+#[cfg(windows)]
+pub fn pipe() -> io::Result<(OwnedSocket, OwnedSocket)> {
+    use std::net::{Ipv4Addr, TcpListener, TcpStream};
+
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))?;
+    let writer = TcpStream::connect(listener.local_addr()?)?;
+
+    // Another process may connect to the listener first, so only accept our own connection.
+    let reader = loop {
+        let (stream, peer) = listener.accept()?;
+        if peer == writer.local_addr()? {
+            break stream;
+        }
+    };
+
+    reader.set_nonblocking(true)?;
+    writer.set_nonblocking(true)?;
+    writer.set_nodelay(true)?;
+    Ok((reader.into(), writer.into()))
+}
+
+#[cfg(unix)]
+impl Poller {
+    /// Creates a poller that is woken up by writing to the returned file descriptor.
+    ///
+    /// The read end of a pipe is registered in the poller under its notification key, and the
+    /// write end is returned. Writing to it wakes up the current or the next call to
+    /// [`wait()`][`Poller::wait()`] like [`notify()`][`Poller::notify()`] does,
+    /// so no event is reported for it. The pipe is emptied by the poller.
+    ///
+    /// This is useful for code that can only wake the poller with a file descriptor, for example
+    /// from a signal handler or from another process.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use polling::Poller;
+    /// use std::fs::File;
+    /// use std::io::Write;
+    ///
+    /// let (poller, waker) = Poller::new_piped()?;
+    /// File::from(waker).write_all(&[1])?;
+    ///
+    /// // This returns right away.
+    /// let mut events = Vec::new();
+    /// poller.wait(&mut events, None)?;
+    /// assert!(events.is_empty());
+    /// # std::io::Result::Ok(())
+    /// ```
+    pub fn new_piped() -> io::Result<(Poller, OwnedFd)> {
+        let (reader, writer) = pipe()?;
+
+        let mut poller = Poller::new()?;
+        poller.poller.add(
+            reader.as_raw_fd(),
+            Event::readable(poller.notify_key).into(),
+            PollMode::Oneshot,
+        )?;
+        poller.wake_pipe = Some(Arc::new(reader));
+
+        Ok((poller, writer))
+    }
+
+    /// Empties the pipe of `new_piped()` and waits for the next write to it.
+    pub(crate) fn rearm_wake_pipe(&self, reader: &OwnedFd) -> io::Result<()> {
+        let mut buf = [0; 64];
+        loop {
+            match rustix::io::read(reader, &mut buf) {
+                // The write end was closed, so there is nothing to wait for.
+                Ok(0) => return Ok(()),
+                Ok(_) => {}
+                Err(e) if e == rustix::io::Errno::AGAIN => break,
+                Err(e) if e == rustix::io::Errno::INTR => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        self.poller.modify(
+            reader.as_raw_fd(),
+            Event::readable(self.notify_key).into(),
+            PollMode::Oneshot,
+        )
+    }
+}
@@ -3,6 +3,8 @@
 use std::collections::HashMap;
 use std::convert::TryInto;
 use std::io;
+use std::os::raw::c_int;
+use std::process::Child;
 use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::{Condvar, Mutex};
 use std::time::{Duration, Instant};
@@ -15,9 +17,12 @@ use rustix::io::{
 };
 
 // std::os::unix doesn't exist on Fuchsia
-type RawFd = std::os::raw::c_int;
+type RawFd = c_int;
 
-use crate::{Event, PollMode};
+use crate::{BackendKind, Event, EventDetails, PollMode, PollerBuilder, Registration};
+
+/// The backend implemented by this module.
+pub(crate) const BACKEND: BackendKind = BackendKind::Poll;
 
 /// Interface to poll.
 #[derive(Debug)]
@@ -46,6 +51,9 @@ pub struct Poller {
     ///
     /// This is used with the `fds` mutex.
     operations_complete: Condvar,
+
+    /// The signals added with `add_signal()`, with their keys and the read ends of their pipes.
+    signals: Mutex<HashMap<c_int, (usize, RawFd)>>,
 }
 
 /// The file descriptors to poll in a `Poller`.
@@ -74,17 +82,21 @@ struct FdData {
 
 impl Poller {
     /// Creates a new poller.
-    pub fn new() -> io::Result<Poller> {
+    pub fn new(builder: &PollerBuilder) -> io::Result<Poller> {
         // Create the notification pipe.
-        let (notify_read, notify_write) = pipe_with(PipeFlags::CLOEXEC).or_else(|_| {
-            let (notify_read, notify_write) = pipe()?;
-            fcntl_setfd(&notify_read, fcntl_getfd(&notify_read)? | FdFlags::CLOEXEC)?;
-            fcntl_setfd(
-                &notify_write,
-                fcntl_getfd(&notify_write)? | FdFlags::CLOEXEC,
-            )?;
-            io::Result::Ok((notify_read, notify_write))
-        })?;
+        let (notify_read, notify_write) = if builder.close_on_exec {
+            pipe_with(PipeFlags::CLOEXEC).or_else(|_| {
+                let (notify_read, notify_write) = pipe()?;
+                fcntl_setfd(&notify_read, fcntl_getfd(&notify_read)? | FdFlags::CLOEXEC)?;
+                fcntl_setfd(
+                    &notify_write,
+                    fcntl_getfd(&notify_write)? | FdFlags::CLOEXEC,
+                )?;
+                io::Result::Ok((notify_read, notify_write))
+            })?
+        } else {
+            pipe()?
+        };
 
         // Put the reading side into non-blocking mode.
         fcntl_setfl(&notify_read, fcntl_getfl(&notify_read)? | OFlags::NONBLOCK)?;
@@ -109,9 +121,25 @@ impl Poller {
             waiting_operations: AtomicUsize::new(0),
             operations_complete: Condvar::new(),
             notified: AtomicBool::new(false),
+            signals: Mutex::new(HashMap::new()),
         })
     }
 
+    /// Creates a new poller that shares the interest set of this one.
+    pub fn try_clone(&self) -> io::Result<Poller> {
+        Err(crate::unsupported_error(
+            "cloning the poller is not supported with poll",
+        ))
+    }
+
+    /// Prepares the poller for several threads waiting on it at the same time.
+    pub fn enable_concurrent_wait(&self) -> io::Result<()> {
+        // The file descriptors are locked for the duration of a wait.
+        Err(crate::unsupported_error(
+            "concurrent waits are not supported with poll",
+        ))
+    }
+
     /// Whether this poller supports level-triggered events.
     pub fn supports_level(&self) -> bool {
         true
@@ -122,8 +150,46 @@ impl Poller {
         false
     }
 
+    /// Whether the poller wakes up only one poller for exclusive registrations.
+    pub fn supports_exclusive(&self) -> bool {
+        false
+    }
+
+    /// The precision of the timeouts of `wait()`.
+    pub fn timeout_granularity(&self) -> Duration {
+        Duration::from_millis(1)
+    }
+
+    /// Checks that the notification pipe is still open and polled, and that the list of polled
+    /// file descriptors matches the `registered` sources.
+    pub fn check_invariants(&self, registered: Option<usize>) -> io::Result<()> {
+        fcntl_getfd(&self.notify_read)?;
+        fcntl_getfd(&self.notify_write)?;
+
+        let broken = |msg: &str| Err(io::Error::new(io::ErrorKind::Other, msg));
+        self.modify_fds(|fds| {
+            match fds.poll_fds.first() {
+                Some(poll_fd) if poll_fd.as_fd().as_raw_fd() == self.notify_read.as_raw_fd() => {}
+                _ => return broken("the notification pipe is not polled"),
+            }
+            if registered.map_or(false, |registered| fds.fd_data.len() != registered) {
+                return broken("the number of polled file descriptors doesn't match the sources");
+            }
+            if fds.poll_fds.len() != fds.fd_data.len() + 1 {
+                return broken("`poll_fds` and `fd_data` have different lengths");
+            }
+            for (&fd, data) in &fds.fd_data {
+                match fds.poll_fds.get(data.poll_fds_index) {
+                    Some(poll_fd) if poll_fd.as_fd().as_raw_fd() == fd => {}
+                    _ => return broken("`fd_data` points at the wrong entry of `poll_fds`"),
+                }
+            }
+            Ok(())
+        })
+    }
+
     /// Adds a new file descriptor.
-    pub fn add(&self, fd: RawFd, ev: Event, mode: PollMode) -> io::Result<()> {
+    pub fn add(&self, fd: RawFd, ev: Registration, mode: PollMode) -> io::Result<()> {
         if fd == self.notify_read.as_raw_fd() || fd == self.notify_write.as_raw_fd() {
             return Err(io::Error::from(io::ErrorKind::InvalidInput));
         }
@@ -160,8 +226,45 @@ impl Poller {
         })
     }
 
+    /// Adds several file descriptors, returning the result for each of them.
+    pub fn add_batch(&self, fds: &[(RawFd, Event, PollMode)]) -> Vec<io::Result<()>> {
+        fds.iter()
+            .map(|&(fd, ev, mode)| self.add(fd, ev.into(), mode))
+            .collect()
+    }
+
+    /// Adds several file descriptors, returning the first error.
+    ///
+    /// A failure to add one doesn't stop the others from being added. `added` is called with the
+    /// index of each file descriptor that was added.
+    pub fn add_many(
+        &self,
+        fds: &[(RawFd, Event, PollMode)],
+        mut added: impl FnMut(usize),
+    ) -> io::Result<()> {
+        let mut result = Ok(());
+        for (i, &(fd, ev, mode)) in fds.iter().enumerate() {
+            match self.add(fd, ev.into(), mode) {
+                Ok(()) => added(i),
+                Err(err) => {
+                    if result.is_ok() {
+                        result = Err(err);
+                    }
+                }
+            }
+        }
+        result
+    }
+
+    /// Modifies several file descriptors, returning the result for each of them.
+    pub fn modify_batch(&self, fds: &[(RawFd, Event, PollMode)]) -> Vec<io::Result<()>> {
+        fds.iter()
+            .map(|&(fd, ev, mode)| self.modify(fd, ev.into(), mode))
+            .collect()
+    }
+
     /// Modifies an existing file descriptor.
-    pub fn modify(&self, fd: RawFd, ev: Event, mode: PollMode) -> io::Result<()> {
+    pub fn modify(&self, fd: RawFd, ev: Registration, mode: PollMode) -> io::Result<()> {
         log::trace!(
             "modify: notify_read={:?}, fd={}, ev={:?}",
             self.notify_read,
@@ -181,6 +284,17 @@ impl Poller {
         })
     }
 
+    /// Stops delivering events for a file descriptor without removing it.
+    pub fn disable(&self, fd: RawFd, ev: Registration, mode: PollMode) -> io::Result<()> {
+        // Emulated by clearing the interest.
+        self.modify(fd, Registration::none(ev.key), mode)
+    }
+
+    /// Resumes delivering events for a disabled file descriptor.
+    pub fn enable(&self, fd: RawFd, ev: Registration, mode: PollMode) -> io::Result<()> {
+        self.modify(fd, ev, mode)
+    }
+
     /// Deletes a file descriptor.
     pub fn delete(&self, fd: RawFd) -> io::Result<()> {
         log::trace!("delete: notify_read={:?}, fd={}", self.notify_read, fd);
@@ -199,8 +313,109 @@ impl Poller {
         })
     }
 
-    /// Waits for I/O events with an optional timeout.
-    pub fn wait(&self, events: &mut Events, timeout: Option<Duration>) -> io::Result<()> {
+    /// Sets the number of bytes that must be buffered before a read event is reported for `fd`.
+    ///
+    /// This sets `SO_RCVLOWAT`, so it only works for sockets.
+    pub fn set_low_water_mark(&self, fd: RawFd, bytes: usize) -> io::Result<()> {
+        log::trace!(
+            "set_low_water_mark: notify_read={:?}, fd={}, bytes={}",
+            self.notify_read,
+            fd,
+            bytes
+        );
+
+        crate::sockopt::set_recv_low_water_mark(fd, bytes)
+    }
+
+    /// Adds a child process that reports a readable event with `key` when it exits.
+    pub fn add_process(&self, child: &mut Child, key: usize) -> io::Result<()> {
+        let _ = (child, key);
+        Err(crate::unsupported_error(
+            "watching processes is not supported with poll",
+        ))
+    }
+
+    /// Removes a child process added with `add_process()`.
+    pub fn delete_process(&self, child: &Child) -> io::Result<()> {
+        let _ = child;
+        Err(crate::unsupported_error(
+            "watching processes is not supported with poll",
+        ))
+    }
+
+    /// Adds a signal that reports a readable event with `key` when it is delivered.
+    ///
+    /// This installs a signal handler that writes to a pipe, which is read by `wait()` when it
+    /// reports the signal.
+    pub fn add_signal(&self, signum: c_int, key: usize) -> io::Result<()> {
+        log::trace!(
+            "add_signal: notify_read={:?}, signum={}, key={}",
+            self.notify_read,
+            signum,
+            key
+        );
+
+        let mut signals = self.signals.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(&mut (ref mut old_key, fd)) = signals.get_mut(&signum) {
+            self.modify(fd, Event::readable(key).into(), PollMode::Level)?;
+            *old_key = key;
+            return Ok(());
+        }
+
+        let fd = crate::signal::watch(signum)?;
+        if let Err(err) = self.add(fd, Event::readable(key).into(), PollMode::Level) {
+            crate::signal::unwatch(signum);
+            return Err(err);
+        }
+        signals.insert(signum, (key, fd));
+        Ok(())
+    }
+
+    /// Removes a signal added with `add_signal()`.
+    pub fn delete_signal(&self, signum: c_int) -> io::Result<()> {
+        log::trace!(
+            "delete_signal: notify_read={:?}, signum={}",
+            self.notify_read,
+            signum
+        );
+
+        let removed = self
+            .signals
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(&signum);
+        if let Some((_, fd)) = removed {
+            crate::signal::unwatch(signum);
+            self.delete(fd)?;
+        }
+        Ok(())
+    }
+
+    /// Adds a timer that reports a readable event with `key` after `interval`.
+    pub fn add_timer(&self, key: usize, interval: Duration, repeat: bool) -> io::Result<()> {
+        let _ = (key, interval, repeat);
+        Err(crate::unsupported_error(
+            "timers are not supported with poll",
+        ))
+    }
+
+    /// Removes a timer added with `add_timer()`.
+    pub fn delete_timer(&self, key: usize) -> io::Result<()> {
+        let _ = key;
+        Err(crate::unsupported_error(
+            "timers are not supported with poll",
+        ))
+    }
+
+    /// Waits for I/O events with an optional timeout, storing at most `max` of them.
+    ///
+    /// `max` is at most the capacity of `events`.
+    pub fn wait(
+        &self,
+        events: &mut Events,
+        max: usize,
+        timeout: Option<Duration>,
+    ) -> io::Result<()> {
         log::trace!(
             "wait: notify_read={:?}, timeout={:?}",
             self.notify_read,
@@ -209,7 +424,7 @@ impl Poller {
 
         let deadline = timeout.and_then(|t| Instant::now().checked_add(t));
 
-        events.inner.clear();
+        events.clear();
 
         let mut fds = self.fds.lock().unwrap();
 
@@ -218,8 +433,10 @@ impl Poller {
             loop {
                 if self.notified.swap(false, Ordering::SeqCst) {
                     // `notify` will have sent a notification in case we were polling. We weren't,
-                    // so remove it.
-                    return self.pop_notification();
+                    // so remove it. It may not have been written yet, in which case it is read
+                    // by the next call to `wait`.
+                    let _ = self.pop_notification();
+                    return Ok(());
                 } else if self.waiting_operations.load(Ordering::SeqCst) == 0 {
                     break;
                 }
@@ -267,16 +484,28 @@ impl Poller {
             if num_fd_events > 0 {
                 let fds = &mut *fds;
 
+                let num_fd_events = num_fd_events.min(max);
                 events.inner.reserve(num_fd_events);
+                events.details.reserve(num_fd_events);
                 for fd_data in fds.fd_data.values_mut() {
                     let poll_fd = &mut fds.poll_fds[fd_data.poll_fds_index];
                     if !poll_fd.revents().is_empty() {
                         // Store event
-                        events.inner.push(Event {
-                            key: fd_data.key,
-                            readable: poll_fd.revents().intersects(read_events()),
-                            writable: poll_fd.revents().intersects(write_events()),
-                        });
+                        let revents = poll_fd.revents();
+                        events.inner.push(Event::new(
+                            fd_data.key,
+                            revents.intersects(read_events()),
+                            revents.intersects(write_events()),
+                        ));
+                        events.details.push(
+                            EventDetails::new(
+                                revents.contains(PollFlags::ERR),
+                                revents.intersects(PollFlags::HUP | rdhup_flag()),
+                                revents.contains(PollFlags::PRI),
+                            )
+                            .with_eof(revents.intersects(PollFlags::HUP | rdhup_flag()))
+                            .with_write_closed(revents.intersects(PollFlags::HUP | PollFlags::ERR)),
+                        );
                         // Remove interest if necessary
                         if fd_data.remove {
                             *poll_fd = PollFd::from_borrowed_fd(
@@ -294,6 +523,15 @@ impl Poller {
 
             break;
         }
+        drop(fds);
+
+        // The pipes of signals stay readable until they are emptied.
+        let signals = self.signals.lock().unwrap_or_else(|e| e.into_inner());
+        for (&signum, &(key, _)) in signals.iter() {
+            if events.inner.iter().any(|ev| ev.key == key) {
+                crate::signal::drain(signum);
+            }
+        }
 
         Ok(())
     }
@@ -349,11 +587,16 @@ impl Poller {
 impl Drop for Poller {
     fn drop(&mut self) {
         log::trace!("drop: notify_read={:?}", self.notify_read);
+
+        let signals = self.signals.get_mut().unwrap_or_else(|e| e.into_inner());
+        for (&signum, _) in signals.iter() {
+            crate::signal::unwatch(signum);
+        }
     }
 }
 
 /// Get the input poll events for the given event.
-fn poll_events(ev: Event) -> PollFlags {
+fn poll_events(ev: Registration) -> PollFlags {
     (if ev.readable {
         PollFlags::IN | PollFlags::PRI
     } else {
@@ -362,12 +605,42 @@ fn poll_events(ev: Event) -> PollFlags {
         PollFlags::OUT | PollFlags::WRBAND
     } else {
         PollFlags::empty()
+    }) | (if ev.priority {
+        PollFlags::PRI
+    } else {
+        PollFlags::empty()
+    }) | (if ev.hup {
+        rdhup_flag()
+    } else {
+        PollFlags::empty()
     })
 }
 
 /// Returned poll events for reading.
 fn read_events() -> PollFlags {
-    PollFlags::IN | PollFlags::PRI | PollFlags::HUP | PollFlags::ERR
+    PollFlags::IN | PollFlags::PRI | PollFlags::HUP | PollFlags::ERR | rdhup_flag()
+}
+
+/// The poll flag for the peer shutting down its writing half.
+///
+/// Only Linux and Android have `POLLRDHUP`. Elsewhere, hangups are only reported once both
+/// directions are shut down, with `POLLHUP`.
+fn rdhup_flag() -> PollFlags {
+    #[cfg(all(
+        any(target_os = "linux", target_os = "android"),
+        not(any(target_arch = "sparc", target_arch = "sparc64"))
+    ))]
+    {
+        PollFlags::RDHUP
+    }
+
+    #[cfg(not(all(
+        any(target_os = "linux", target_os = "android"),
+        not(any(target_arch = "sparc", target_arch = "sparc64"))
+    )))]
+    {
+        PollFlags::empty()
+    }
 }
 
 /// Returned poll events for writing.
@@ -378,25 +651,82 @@ fn write_events() -> PollFlags {
 /// A list of reported I/O events.
 pub struct Events {
     inner: Vec<Event>,
+
+    /// The conditions reported alongside each event in `inner`.
+    details: Vec<EventDetails>,
+
+    /// The maximum number of events stored by a single wait.
+    limit: usize,
 }
 
 impl Events {
     /// Creates an empty list.
     pub fn new() -> Events {
-        Self { inner: Vec::new() }
+        Self {
+            inner: Vec::new(),
+            details: Vec::new(),
+            limit: usize::MAX,
+        }
+    }
+
+    /// Creates an empty list that holds at most `capacity` events per wait.
+    pub fn with_capacity(capacity: usize) -> Events {
+        Self {
+            inner: Vec::with_capacity(capacity),
+            details: Vec::with_capacity(capacity),
+            limit: capacity,
+        }
+    }
+
+    /// Creates a list from raw events.
+    #[cfg(fuzzing)]
+    pub fn from_raw(list: Vec<Event>) -> Events {
+        Events {
+            details: vec![EventDetails::default(); list.len()],
+            inner: list,
+            limit: usize::MAX,
+        }
     }
 
     /// Iterates over I/O events.
-    pub fn iter(&self) -> impl Iterator<Item = Event> + '_ {
-        self.inner.iter().copied()
+    pub fn iter(&self) -> impl Iterator<Item = (Event, EventDetails)> + '_ {
+        self.inner.iter().copied().zip(self.details.iter().copied())
+    }
+
+    /// Iterates over the keys of the I/O events.
+    pub fn keys(&self) -> impl Iterator<Item = usize> + '_ {
+        self.inner.iter().map(|ev| ev.key)
+    }
+
+    /// Clears the list.
+    pub fn clear(&mut self) {
+        self.inner.clear();
+        self.details.clear();
+    }
+
+    /// The maximum number of events that a single call to `wait` stores.
+    pub fn capacity(&self) -> usize {
+        // Unless limited, every ready file descriptor is reported at once.
+        self.limit
+    }
+
+    /// Moves the events of another list to the end of this one, leaving the other one empty.
+    pub fn append(&mut self, other: &mut Events) {
+        self.inner.append(&mut other.inner);
+        self.details.append(&mut other.details);
+    }
+
+    /// The events that were reported.
+    pub fn as_raw_slice(&self) -> &[Event] {
+        &self.inner
     }
 }
 
 fn cvt_mode_as_remove(mode: PollMode) -> io::Result<bool> {
     match mode {
-        PollMode::Oneshot => Ok(true),
-        PollMode::Level => Ok(false),
-        _ => Err(crate::unsupported_error(
+        PollMode::Oneshot | PollMode::Dispatch => Ok(true),
+        PollMode::Level | PollMode::Exclusive => Ok(false),
+        PollMode::Edge | PollMode::EdgeOneshot => Err(crate::unsupported_error(
             "edge-triggered I/O events are not supported in poll()",
         )),
     }
@@ -1,7 +1,13 @@
 //! Bindings to event port (illumos, Solaris).
 
+use std::collections::HashMap;
+use std::convert::TryInto;
 use std::io;
+use std::os::raw::c_int;
 use std::os::unix::io::{AsRawFd, RawFd};
+use std::process::Child;
+use std::ptr;
+use std::sync::Mutex;
 use std::time::Duration;
 
 #[cfg(not(polling_no_io_safety))]
@@ -10,23 +16,51 @@ use std::os::unix::io::{AsFd, BorrowedFd};
 use rustix::fd::OwnedFd;
 use rustix::io::{fcntl_getfd, fcntl_setfd, port, FdFlags, PollFlags};
 
-use crate::{Event, PollMode};
+use crate::{BackendKind, Event, EventDetails, PollMode, PollerBuilder, Registration};
+
+/// The backend implemented by this module.
+pub(crate) const BACKEND: BackendKind = BackendKind::Port;
 
 /// Interface to event ports.
 #[derive(Debug)]
 pub struct Poller {
     /// File descriptor for the port instance.
     port_fd: OwnedFd,
+
+    /// The key used for notifications.
+    notify_key: usize,
+
+    /// The signals added with `add_signal()`, with their keys and the read ends of their pipes.
+    signals: Mutex<HashMap<c_int, (usize, RawFd)>>,
 }
 
 impl Poller {
     /// Creates a new poller.
-    pub fn new() -> io::Result<Poller> {
+    pub fn new(builder: &PollerBuilder) -> io::Result<Poller> {
         let port_fd = port::port_create()?;
-        let flags = fcntl_getfd(&port_fd)?;
-        fcntl_setfd(&port_fd, flags | FdFlags::CLOEXEC)?;
+        if builder.close_on_exec {
+            let flags = fcntl_getfd(&port_fd)?;
+            fcntl_setfd(&port_fd, flags | FdFlags::CLOEXEC)?;
+        }
 
-        Ok(Poller { port_fd })
+        Ok(Poller {
+            port_fd,
+            notify_key: builder.notify_key,
+            signals: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Creates a new poller that shares the event port of this one.
+    pub fn try_clone(&self) -> io::Result<Poller> {
+        Err(crate::unsupported_error(
+            "cloning the poller is not supported with event ports",
+        ))
+    }
+
+    /// Prepares the poller for several threads waiting on it at the same time.
+    pub fn enable_concurrent_wait(&self) -> io::Result<()> {
+        // Waiting doesn't modify any state that other waiters depend on.
+        Ok(())
     }
 
     /// Whether this poller supports level-triggered events.
@@ -39,14 +73,67 @@ impl Poller {
         false
     }
 
+    /// Whether the poller wakes up only one poller for exclusive registrations.
+    pub fn supports_exclusive(&self) -> bool {
+        false
+    }
+
+    /// The precision of the timeouts of `wait()`.
+    pub fn timeout_granularity(&self) -> Duration {
+        Duration::from_nanos(1)
+    }
+
+    /// Checks that the event port of this poller is still open.
+    pub fn check_invariants(&self, _registered: Option<usize>) -> io::Result<()> {
+        fcntl_getfd(&self.port_fd)?;
+        Ok(())
+    }
+
     /// Adds a file descriptor.
-    pub fn add(&self, fd: RawFd, ev: Event, mode: PollMode) -> io::Result<()> {
+    pub fn add(&self, fd: RawFd, ev: Registration, mode: PollMode) -> io::Result<()> {
         // File descriptors don't need to be added explicitly, so just modify the interest.
         self.modify(fd, ev, mode)
     }
 
+    /// Adds several file descriptors, returning the result for each of them.
+    pub fn add_batch(&self, fds: &[(RawFd, Event, PollMode)]) -> Vec<io::Result<()>> {
+        fds.iter()
+            .map(|&(fd, ev, mode)| self.add(fd, ev.into(), mode))
+            .collect()
+    }
+
+    /// Adds several file descriptors, returning the first error.
+    ///
+    /// A failure to add one doesn't stop the others from being added. `added` is called with the
+    /// index of each file descriptor that was added.
+    pub fn add_many(
+        &self,
+        fds: &[(RawFd, Event, PollMode)],
+        mut added: impl FnMut(usize),
+    ) -> io::Result<()> {
+        let mut result = Ok(());
+        for (i, &(fd, ev, mode)) in fds.iter().enumerate() {
+            match self.add(fd, ev.into(), mode) {
+                Ok(()) => added(i),
+                Err(err) => {
+                    if result.is_ok() {
+                        result = Err(err);
+                    }
+                }
+            }
+        }
+        result
+    }
+
+    /// Modifies several file descriptors, returning the result for each of them.
+    pub fn modify_batch(&self, fds: &[(RawFd, Event, PollMode)]) -> Vec<io::Result<()>> {
+        fds.iter()
+            .map(|&(fd, ev, mode)| self.modify(fd, ev.into(), mode))
+            .collect()
+    }
+
     /// Modifies an existing file descriptor.
-    pub fn modify(&self, fd: RawFd, ev: Event, mode: PollMode) -> io::Result<()> {
+    pub fn modify(&self, fd: RawFd, ev: Registration, mode: PollMode) -> io::Result<()> {
         let mut flags = PollFlags::empty();
         if ev.readable {
             flags |= read_flags();
@@ -54,8 +141,15 @@ impl Poller {
         if ev.writable {
             flags |= write_flags();
         }
+        if ev.priority {
+            flags |= PollFlags::PRI;
+        }
+        if ev.hup {
+            flags |= rdhup_flag();
+        }
 
-        if mode != PollMode::Oneshot {
+        // Associations are always removed after an event, like in dispatch mode.
+        if !matches!(mode, PollMode::Oneshot | PollMode::Dispatch) {
             return Err(crate::unsupported_error(
                 "this kind of event is not supported with event ports",
             ));
@@ -68,6 +162,17 @@ impl Poller {
         Ok(())
     }
 
+    /// Stops delivering events for a file descriptor without removing it.
+    pub fn disable(&self, fd: RawFd, ev: Registration, mode: PollMode) -> io::Result<()> {
+        // Emulated by clearing the interest.
+        self.modify(fd, Registration::none(ev.key), mode)
+    }
+
+    /// Resumes delivering events for a disabled file descriptor.
+    pub fn enable(&self, fd: RawFd, ev: Registration, mode: PollMode) -> io::Result<()> {
+        self.modify(fd, ev, mode)
+    }
+
     /// Deletes a file descriptor.
     pub fn delete(&self, fd: RawFd) -> io::Result<()> {
         let result = unsafe { port::port_dissociate_fd(&self.port_fd, fd) };
@@ -81,18 +186,134 @@ impl Poller {
         Ok(())
     }
 
-    /// Waits for I/O events with an optional timeout.
-    pub fn wait(&self, events: &mut Events, timeout: Option<Duration>) -> io::Result<()> {
+    /// Sets the number of bytes that must be buffered before a read event is reported for `fd`.
+    ///
+    /// This sets `SO_RCVLOWAT`, so it only works for sockets.
+    pub fn set_low_water_mark(&self, fd: RawFd, bytes: usize) -> io::Result<()> {
+        crate::sockopt::set_recv_low_water_mark(fd, bytes)
+    }
+
+    /// Adds a child process that reports a readable event with `key` when it exits.
+    pub fn add_process(&self, child: &mut Child, key: usize) -> io::Result<()> {
+        let _ = (child, key);
+        Err(crate::unsupported_error(
+            "watching processes is not supported with event ports",
+        ))
+    }
+
+    /// Removes a child process added with `add_process()`.
+    pub fn delete_process(&self, child: &Child) -> io::Result<()> {
+        let _ = child;
+        Err(crate::unsupported_error(
+            "watching processes is not supported with event ports",
+        ))
+    }
+
+    /// Adds a signal that reports a readable event with `key` when it is delivered.
+    ///
+    /// This installs a signal handler that writes to a pipe, which is read and associated again
+    /// by `wait()` when it reports the signal.
+    pub fn add_signal(&self, signum: c_int, key: usize) -> io::Result<()> {
+        let mut signals = self.signals.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(&mut (ref mut old_key, fd)) = signals.get_mut(&signum) {
+            self.modify(fd, Event::readable(key).into(), PollMode::Oneshot)?;
+            *old_key = key;
+            return Ok(());
+        }
+
+        let fd = crate::signal::watch(signum)?;
+        if let Err(err) = self.add(fd, Event::readable(key).into(), PollMode::Oneshot) {
+            crate::signal::unwatch(signum);
+            return Err(err);
+        }
+        signals.insert(signum, (key, fd));
+        Ok(())
+    }
+
+    /// Removes a signal added with `add_signal()`.
+    pub fn delete_signal(&self, signum: c_int) -> io::Result<()> {
+        let removed = self
+            .signals
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(&signum);
+        if let Some((_, fd)) = removed {
+            crate::signal::unwatch(signum);
+            self.delete(fd)?;
+        }
+        Ok(())
+    }
+
+    /// Adds a timer that reports a readable event with `key` after `interval`.
+    pub fn add_timer(&self, key: usize, interval: Duration, repeat: bool) -> io::Result<()> {
+        let _ = (key, interval, repeat);
+        Err(crate::unsupported_error(
+            "timers are not supported with event ports",
+        ))
+    }
+
+    /// Removes a timer added with `add_timer()`.
+    pub fn delete_timer(&self, key: usize) -> io::Result<()> {
+        let _ = key;
+        Err(crate::unsupported_error(
+            "timers are not supported with event ports",
+        ))
+    }
+
+    /// Waits for I/O events with an optional timeout, storing at most `max` of them.
+    ///
+    /// `max` is at most the capacity of `events`.
+    pub fn wait(
+        &self,
+        events: &mut Events,
+        max: usize,
+        timeout: Option<Duration>,
+    ) -> io::Result<()> {
+        let mut timeout = timeout.map(|t| libc::timespec {
+            tv_sec: t.as_secs().min(libc::time_t::max_value() as u64) as libc::time_t,
+            tv_nsec: t.subsec_nanos() as _,
+        });
+
         // Wait for I/O events.
-        let res = port::port_getn(&self.port_fd, &mut events.list, 1, timeout);
+        //
+        // This calls `port_getn` directly so that fewer events than fit in the list can be
+        // requested.
+        events.list.clear();
+        let mut nget: libc::c_uint = 1;
+        let res = unsafe {
+            libc::port_getn(
+                self.port_fd.as_raw_fd(),
+                events.list.as_mut_ptr() as *mut libc::port_event,
+                max.try_into().unwrap_or(libc::c_uint::MAX),
+                &mut nget,
+                timeout
+                    .as_mut()
+                    .map_or(ptr::null_mut(), |t| t as *mut libc::timespec),
+            )
+        };
 
         // Event ports sets the return value to -1 and returns ETIME on timer expire. The number of
         // returned events is stored in nget, but in our case it should always be 0 since we set
         // nget to 1 initially.
-        if let Err(e) = res {
-            match e {
-                rustix::io::Errno::TIME => {}
-                _ => return Err(e.into()),
+        if res == -1 {
+            let err = io::Error::last_os_error();
+            if err.raw_os_error() != Some(libc::ETIME) {
+                return Err(err);
+            }
+        }
+
+        // SAFETY: `port::Event` is a transparent wrapper around `libc::port_event`, and the
+        // kernel initialized `nget` of them.
+        unsafe {
+            events.list.set_len(nget as usize);
+        }
+
+        // The pipes of signals are emptied and associated again after they were reported.
+        let signals = self.signals.lock().unwrap_or_else(|e| e.into_inner());
+        for (&signum, &(key, fd)) in signals.iter() {
+            if events.list.iter().any(|ev| ev.userdata() as usize == key) {
+                crate::signal::drain(signum);
+                self.modify(fd, Event::readable(key).into(), PollMode::Oneshot)?;
             }
         }
 
@@ -104,12 +325,21 @@ impl Poller {
         const PORT_SOURCE_USER: i32 = 3;
 
         // Use port_send to send a notification to the port.
-        port::port_send(&self.port_fd, PORT_SOURCE_USER, crate::NOTIFY_KEY as _)?;
+        port::port_send(&self.port_fd, PORT_SOURCE_USER, self.notify_key as _)?;
 
         Ok(())
     }
 }
 
+impl Drop for Poller {
+    fn drop(&mut self) {
+        let signals = self.signals.get_mut().unwrap_or_else(|e| e.into_inner());
+        for (&signum, _) in signals.iter() {
+            crate::signal::unwatch(signum);
+        }
+    }
+}
+
 impl AsRawFd for Poller {
     fn as_raw_fd(&self) -> RawFd {
         self.port_fd.as_raw_fd()
@@ -133,6 +363,23 @@ fn write_flags() -> PollFlags {
     PollFlags::OUT | PollFlags::HUP | PollFlags::ERR
 }
 
+/// The poll flag for the peer shutting down its writing half.
+///
+/// Only illumos has `POLLRDHUP`. On Solaris, hangups are only reported once both directions are
+/// shut down, with `POLLHUP`.
+fn rdhup_flag() -> PollFlags {
+    #[cfg(target_os = "illumos")]
+    {
+        // SAFETY: rustix doesn't know about `POLLRDHUP`, but event ports accept it.
+        unsafe { PollFlags::from_bits_unchecked(libc::POLLRDHUP as _) }
+    }
+
+    #[cfg(not(target_os = "illumos"))]
+    {
+        PollFlags::empty()
+    }
+}
+
 /// A list of reported I/O events.
 pub struct Events {
     list: Vec<port::Event>,
@@ -143,17 +390,71 @@ unsafe impl Send for Events {}
 impl Events {
     /// Creates an empty list.
     pub fn new() -> Events {
+        Events::with_capacity(1024)
+    }
+
+    /// Creates an empty list that holds at most `capacity` events per wait.
+    pub fn with_capacity(capacity: usize) -> Events {
+        Events {
+            list: Vec::with_capacity(capacity),
+        }
+    }
+
+    /// Creates a list from raw events.
+    #[cfg(fuzzing)]
+    pub fn from_raw(list: Vec<crate::sys::RawEvent>) -> Events {
         Events {
-            list: Vec::with_capacity(1024),
+            list: list
+                .into_iter()
+                .map(crate::sys::RawEvent::into_inner)
+                .collect(),
         }
     }
 
     /// Iterates over I/O events.
-    pub fn iter(&self) -> impl Iterator<Item = Event> + '_ {
-        self.list.iter().map(|ev| Event {
-            key: ev.userdata() as usize,
-            readable: PollFlags::from_bits_truncate(ev.events() as _).intersects(read_flags()),
-            writable: PollFlags::from_bits_truncate(ev.events() as _).intersects(write_flags()),
+    pub fn iter(&self) -> impl Iterator<Item = (Event, EventDetails)> + '_ {
+        self.list.iter().map(|ev| {
+            let flags = PollFlags::from_bits_truncate(ev.events() as _);
+            let rdhup = ev.events() as c_int & rdhup_flag().bits() as c_int != 0;
+            let event = Event::new(
+                ev.userdata() as usize,
+                flags.intersects(read_flags()) || rdhup,
+                flags.intersects(write_flags()),
+            );
+            let details = EventDetails::new(
+                flags.contains(PollFlags::ERR),
+                flags.contains(PollFlags::HUP) || rdhup,
+                flags.contains(PollFlags::PRI),
+            )
+            .with_eof(flags.contains(PollFlags::HUP) || rdhup)
+            .with_write_closed(flags.intersects(PollFlags::HUP | PollFlags::ERR));
+            (event, details)
         })
     }
+
+    /// Iterates over the keys of the I/O events.
+    pub fn keys(&self) -> impl Iterator<Item = usize> + '_ {
+        self.list.iter().map(|ev| ev.userdata() as usize)
+    }
+
+    /// Clears the list.
+    pub fn clear(&mut self) {
+        self.list.clear();
+    }
+
+    /// The maximum number of events that a single call to `wait` stores.
+    pub fn capacity(&self) -> usize {
+        self.list.capacity()
+    }
+
+    /// Moves the events of another list to the end of this one, leaving the other one empty.
+    pub fn append(&mut self, other: &mut Events) {
+        // `port::Event` doesn't implement `Clone`, so the events are moved.
+        self.list.append(&mut other.list);
+    }
+
+    /// The raw events reported by the event port.
+    pub fn as_raw_slice(&self) -> &[crate::sys::RawEvent] {
+        crate::sys::RawEvent::from_slice(&self.list)
+    }
 }
@@ -0,0 +1,221 @@
+//! Watching signals with a self-pipe, for backends that can't watch them natively.
+//!
+//! Each watched signal gets a pipe, and a signal handler writes a byte to it when the signal is
+//! delivered. The pipes and the state used by the handler are never freed, because the handler
+//! may still run on another thread after a signal stopped being watched.
+
+use std::collections::HashMap;
+use std::io;
+use std::mem::MaybeUninit;
+use std::os::raw::{c_int, c_void};
+use std::ptr;
+use std::sync::atomic::{AtomicBool, AtomicI32, AtomicPtr, Ordering};
+use std::sync::{Mutex, Once};
+
+use rustix::fd::{AsRawFd, IntoRawFd, OwnedFd};
+use rustix::fs::{fcntl_getfl, fcntl_setfl, OFlags};
+use rustix::io::{fcntl_getfd, fcntl_setfd, read, FdFlags};
+
+/// Signal numbers must be below this.
+const MAX_SIGNAL: usize = 128;
+
+/// The global state, which is allocated the first time a signal is watched.
+static SIGNALS: AtomicPtr<Signals> = AtomicPtr::new(ptr::null_mut());
+static SIGNALS_INIT: Once = Once::new();
+
+struct Signals {
+    /// The state of each signal that the handler uses, indexed by the signal number.
+    slots: Vec<Slot>,
+
+    /// The pipes of the signals that were watched, by signal number.
+    pipes: Mutex<HashMap<c_int, Pipe>>,
+}
+
+struct Slot {
+    /// The write end of the pipe, or -1 before it is created.
+    writer: AtomicI32,
+
+    /// Whether a byte was written to the pipe and not read yet.
+    pending: AtomicBool,
+}
+
+struct Pipe {
+    /// The read end of the pipe.
+    reader: OwnedFd,
+
+    /// The disposition of the signal before it was watched, while it is watched.
+    previous: Option<libc::sigaction>,
+}
+
+fn signals() -> &'static Signals {
+    SIGNALS_INIT.call_once(|| {
+        let signals = Box::new(Signals {
+            slots: (0..MAX_SIGNAL)
+                .map(|_| Slot {
+                    writer: AtomicI32::new(-1),
+                    pending: AtomicBool::new(false),
+                })
+                .collect(),
+            pipes: Mutex::new(HashMap::new()),
+        });
+        SIGNALS.store(Box::into_raw(signals), Ordering::Release);
+    });
+
+    // SAFETY: The state is initialized above and never freed.
+    unsafe { &*SIGNALS.load(Ordering::Acquire) }
+}
+
+/// Starts watching `signum`, and returns the read end of its pipe.
+///
+/// The read end becomes readable once the signal is delivered, and [`drain()`] must be called
+/// after that is reported. A signal can only be watched once at a time.
+pub(crate) fn watch(signum: c_int) -> io::Result<c_int> {
+    if signum <= 0 || signum as usize >= MAX_SIGNAL {
+        return Err(io::Error::from(io::ErrorKind::InvalidInput));
+    }
+
+    let signals = signals();
+    let slot = &signals.slots[signum as usize];
+    let mut pipes = signals.pipes.lock().unwrap_or_else(|e| e.into_inner());
+    let pipe = match pipes.get_mut(&signum) {
+        Some(pipe) if pipe.previous.is_some() => {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                "the signal is already watched by another poller",
+            ))
+        }
+        Some(pipe) => pipe,
+        None => {
+            let (reader, writer) = rustix::io::pipe()?;
+            for fd in &[&reader, &writer] {
+                fcntl_setfd(fd, fcntl_getfd(fd)? | FdFlags::CLOEXEC)?;
+                fcntl_setfl(fd, fcntl_getfl(fd)? | OFlags::NONBLOCK)?;
+            }
+            slot.writer.store(writer.into_raw_fd(), Ordering::Release);
+            pipes.entry(signum).or_insert(Pipe {
+                reader,
+                previous: None,
+            })
+        }
+    };
+
+    // Forget deliveries from the last time the signal was watched.
+    drain_pipe(pipe, slot);
+
+    unsafe {
+        let mut action: libc::sigaction = MaybeUninit::zeroed().assume_init();
+        action.sa_sigaction = handler as extern "C" fn(c_int) as libc::sighandler_t;
+        action.sa_flags = libc::SA_RESTART;
+        libc::sigemptyset(&mut action.sa_mask);
+
+        let mut previous: libc::sigaction = MaybeUninit::zeroed().assume_init();
+        if libc::sigaction(signum, &action, &mut previous) == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        pipe.previous = Some(previous);
+    }
+
+    Ok(pipe.reader.as_raw_fd())
+}
+
+/// Stops watching `signum`, and restores its previous disposition.
+pub(crate) fn unwatch(signum: c_int) {
+    let signals = signals();
+    let mut pipes = signals.pipes.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(previous) = pipes.get_mut(&signum).and_then(|pipe| pipe.previous.take()) {
+        unsafe {
+            libc::sigaction(signum, &previous, ptr::null_mut());
+        }
+    }
+}
+
+/// Empties the pipe of `signum` after it was reported as readable.
+pub(crate) fn drain(signum: c_int) {
+    let signals = signals();
+    let pipes = signals.pipes.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(pipe) = pipes.get(&signum) {
+        drain_pipe(pipe, &signals.slots[signum as usize]);
+    }
+}
+
+fn drain_pipe(pipe: &Pipe, slot: &Slot) {
+    while read(&pipe.reader, &mut [0; 8]).map_or(false, |n| n > 0) {}
+
+    // A signal delivered before this point is covered by the event that was just reported.
+    slot.pending.store(false, Ordering::SeqCst);
+}
+
+extern "C" fn handler(signum: c_int) {
+    let signals = SIGNALS.load(Ordering::Acquire);
+    if signals.is_null() {
+        return;
+    }
+
+    // SAFETY: The state is never freed once it is initialized.
+    let slot = match unsafe { &*signals }.slots.get(signum as usize) {
+        Some(slot) => slot,
+        None => return,
+    };
+
+    // Restore the `errno` of the interrupted code after writing.
+    let errno = errno_location();
+    // SAFETY: `errno_location()` returns the location of `errno` for this thread.
+    let saved = errno.map(|errno| unsafe { *errno });
+
+    // Only write to an empty pipe, so that it never fills up.
+    if !slot.pending.swap(true, Ordering::SeqCst) {
+        let writer = slot.writer.load(Ordering::Acquire);
+        if writer != -1 {
+            // SAFETY: The write end is never closed once it is stored.
+            unsafe {
+                libc::write(writer, [1u8].as_ptr() as *const c_void, 1);
+            }
+        }
+    }
+
+    if let (Some(errno), Some(saved)) = (errno, saved) {
+        // SAFETY: See above.
+        unsafe { *errno = saved };
+    }
+}
+
+/// The location of `errno` for the calling thread, if it is known for this platform.
+fn errno_location() -> Option<*mut c_int> {
+    cfg_if::cfg_if! {
+        if #[cfg(any(
+            target_os = "linux",
+            target_os = "emscripten",
+            target_os = "fuchsia",
+            target_os = "redox",
+            target_os = "hurd",
+            target_os = "dragonfly",
+        ))] {
+            Some(unsafe { libc::__errno_location() })
+        } else if #[cfg(any(
+            target_os = "android",
+            target_os = "netbsd",
+            target_os = "openbsd",
+            target_os = "horizon",
+        ))] {
+            Some(unsafe { libc::__errno() })
+        } else if #[cfg(any(target_os = "illumos", target_os = "solaris"))] {
+            Some(unsafe { libc::___errno() })
+        } else if #[cfg(any(
+            target_os = "macos",
+            target_os = "ios",
+            target_os = "tvos",
+            target_os = "watchos",
+            target_os = "freebsd",
+        ))] {
+            Some(unsafe { libc::__error() })
+        } else if #[cfg(target_os = "haiku")] {
+            Some(unsafe { libc::_errnop() })
+        } else if #[cfg(target_os = "aix")] {
+            Some(unsafe { libc::_Errno() })
+        } else if #[cfg(target_os = "nto")] {
+            Some(unsafe { libc::__get_errno_ptr() })
+        } else {
+            None
+        }
+    }
+}
@@ -0,0 +1,111 @@
+//! Sets of signals, used as the signal mask of a wait.
+
+use std::fmt;
+use std::io;
+use std::mem::MaybeUninit;
+use std::os::raw::c_int;
+use std::ptr;
+
+/// A set of signals.
+///
+/// This is used by [`Poller::wait_with_sigmask()`][`crate::Poller::wait_with_sigmask()`] as the
+/// signal mask of the thread while it waits.
+///
+/// # Examples
+///
+/// ```
+/// use polling::SigSet;
+///
+/// let mut set = SigSet::empty();
+/// set.add(libc::SIGUSR1)?;
+/// assert!(set.contains(libc::SIGUSR1));
+/// assert!(!set.contains(libc::SIGUSR2));
+/// # std::io::Result::Ok(())
+/// ```
+#[derive(Clone, Copy)]
+pub struct SigSet {
+    /// The set, which is passed to the system calls that wait.
+    pub(crate) raw: libc::sigset_t,
+}
+
+impl SigSet {
+    /// Creates a set without any signals.
+    pub fn empty() -> SigSet {
+        let mut raw = MaybeUninit::<libc::sigset_t>::uninit();
+        unsafe {
+            libc::sigemptyset(raw.as_mut_ptr());
+            SigSet {
+                raw: raw.assume_init(),
+            }
+        }
+    }
+
+    /// Creates a set with every signal.
+    pub fn all() -> SigSet {
+        let mut raw = MaybeUninit::<libc::sigset_t>::uninit();
+        unsafe {
+            libc::sigfillset(raw.as_mut_ptr());
+            SigSet {
+                raw: raw.assume_init(),
+            }
+        }
+    }
+
+    /// Returns the signals that are blocked on the current thread.
+    ///
+    /// Removing a signal from this set and waiting with it unblocks that signal only while the
+    /// thread waits.
+    pub fn current() -> io::Result<SigSet> {
+        let mut raw = MaybeUninit::<libc::sigset_t>::uninit();
+        unsafe {
+            libc::sigemptyset(raw.as_mut_ptr());
+            let res = libc::pthread_sigmask(libc::SIG_BLOCK, ptr::null(), raw.as_mut_ptr());
+            if res != 0 {
+                return Err(io::Error::from_raw_os_error(res));
+            }
+            Ok(SigSet {
+                raw: raw.assume_init(),
+            })
+        }
+    }
+
+    /// Adds a signal to the set.
+    ///
+    /// Returns an error of kind [`io::ErrorKind::InvalidInput`] if `signum` is not a valid signal.
+    pub fn add(&mut self, signum: c_int) -> io::Result<()> {
+        if unsafe { libc::sigaddset(&mut self.raw, signum) } == -1 {
+            return Err(io::Error::from(io::ErrorKind::InvalidInput));
+        }
+        Ok(())
+    }
+
+    /// Removes a signal from the set.
+    ///
+    /// Returns an error of kind [`io::ErrorKind::InvalidInput`] if `signum` is not a valid signal.
+    pub fn remove(&mut self, signum: c_int) -> io::Result<()> {
+        if unsafe { libc::sigdelset(&mut self.raw, signum) } == -1 {
+            return Err(io::Error::from(io::ErrorKind::InvalidInput));
+        }
+        Ok(())
+    }
+
+    /// Whether the set contains a signal.
+    pub fn contains(&self, signum: c_int) -> bool {
+        unsafe { libc::sigismember(&self.raw, signum) == 1 }
+    }
+}
+
+impl Default for SigSet {
+    fn default() -> SigSet {
+        SigSet::empty()
+    }
+}
+
+impl fmt::Debug for SigSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // No platform has more signals than this.
+        f.debug_set()
+            .entries((1..128).filter(|&signum| self.contains(signum)))
+            .finish()
+    }
+}
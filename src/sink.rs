@@ -0,0 +1,114 @@
+//! Generic collectors of events.
+
+use std::io;
+use std::time::Duration;
+
+use crate::{Events, Poller};
+
+/// A collector of events that is notified before and after each wait.
+///
+/// This is passed to [`Poller::wait_sink()`], which stores the events in the [`Events`] returned
+/// by [`as_mut()`][`AsMut::as_mut()`]. The hooks can be used for logging, for collecting
+/// metrics, or for post-processing the events, like with [`Events::dedup()`].
+///
+/// # Examples
+///
+/// ```
+/// use polling::{Events, Poller, WaitSink};
+/// use std::time::Duration;
+///
+/// #[derive(Default)]
+/// struct Counted {
+///     events: Events,
+///     waits: usize,
+///     total: usize,
+/// }
+///
+/// impl AsMut<Events> for Counted {
+///     fn as_mut(&mut self) -> &mut Events {
+///         &mut self.events
+///     }
+/// }
+///
+/// impl WaitSink for Counted {
+///     fn post_wait(&mut self, n: usize) {
+///         self.waits += 1;
+///         self.total += n;
+///     }
+/// }
+///
+/// let poller = Poller::new()?;
+/// let mut sink = Counted::default();
+/// poller.wait_sink(&mut sink, Some(Duration::from_secs(0)))?;
+/// assert_eq!(sink.waits, 1);
+/// # std::io::Result::Ok(())
+/// ```
+pub trait WaitSink: AsMut<Events> {
+    /// Called before waiting, with the maximum number of events a single wait stores.
+    fn pre_wait(&mut self, capacity: usize) {
+        let _ = capacity;
+    }
+
+    /// Called after a successful wait, with the number of new events.
+    fn post_wait(&mut self, n: usize) {
+        let _ = n;
+    }
+}
+
+impl WaitSink for Events {}
+
+impl Poller {
+    /// Waits for at least one I/O event and stores the events in `events`.
+    ///
+    /// This is identical to [`wait_events()`][`Poller::wait_events()`], but it accepts any type
+    /// that contains an [`Events`] list.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use polling::{Events, Poller};
+    /// use std::time::Duration;
+    ///
+    /// struct Reactor {
+    ///     events: Events,
+    /// }
+    ///
+    /// impl AsMut<Events> for Reactor {
+    ///     fn as_mut(&mut self) -> &mut Events {
+    ///         &mut self.events
+    ///     }
+    /// }
+    ///
+    /// let poller = Poller::new()?;
+    /// let mut reactor = Reactor { events: Events::new() };
+    /// poller.wait_generic(&mut reactor, Some(Duration::from_secs(0)))?;
+    /// # std::io::Result::Ok(())
+    /// ```
+    pub fn wait_generic<E: AsMut<Events>>(
+        &self,
+        events: &mut E,
+        timeout: Option<Duration>,
+    ) -> io::Result<()> {
+        self.wait_events(events.as_mut(), timeout)?;
+        Ok(())
+    }
+
+    /// Waits for at least one I/O event and stores the events in `sink`.
+    ///
+    /// This is identical to [`wait_events()`][`Poller::wait_events()`], but
+    /// [`WaitSink::pre_wait()`] is called before waiting and [`WaitSink::post_wait()`] is called
+    /// with the number of new events after waiting. `post_wait()` is not called if the wait
+    /// fails.
+    pub fn wait_sink<S: WaitSink>(
+        &self,
+        sink: &mut S,
+        timeout: Option<Duration>,
+    ) -> io::Result<()> {
+        let capacity = sink.as_mut().capacity();
+        sink.pre_wait(capacity);
+
+        let n = self.wait_events(sink.as_mut(), timeout)?;
+        sink.post_wait(n);
+        Ok(())
+    }
+}
@@ -0,0 +1,25 @@
+//! Socket options used by the backends that poll for readiness.
+
+use std::convert::TryInto;
+use std::io;
+use std::os::unix::io::RawFd;
+
+/// Sets the `SO_RCVLOWAT` option of a socket, which readiness of sockets follows.
+///
+/// The option stays set when the socket is removed from the poller.
+pub(crate) fn set_recv_low_water_mark(fd: RawFd, bytes: usize) -> io::Result<()> {
+    let bytes: libc::c_int = bytes.max(1).try_into().unwrap_or(libc::c_int::MAX);
+    let res = unsafe {
+        libc::setsockopt(
+            fd,
+            libc::SOL_SOCKET,
+            libc::SO_RCVLOWAT,
+            &bytes as *const libc::c_int as *const _,
+            std::mem::size_of::<libc::c_int>() as libc::socklen_t,
+        )
+    };
+    if res == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
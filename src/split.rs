@@ -0,0 +1,313 @@
+//! Sources registered under several keys.
+
+use std::collections::HashMap;
+use std::io;
+use std::sync::atomic::Ordering;
+
+use crate::{Event, PollMode, Poller, RawSource, Source};
+
+/// The sources registered with [`Poller::add_split()`].
+#[derive(Debug, Default)]
+pub(crate) struct Splits {
+    /// The sources, by file descriptor or socket.
+    sources: HashMap<RawSource, Split>,
+
+    /// The source registered with the operating system under each key.
+    keys: HashMap<usize, RawSource>,
+}
+
+/// A source registered with [`Poller::add_split()`].
+///
+/// The source is registered with the operating system once, under the key of one of its
+/// interests, with the union of all interests.
+#[derive(Debug)]
+struct Split {
+    /// The key that the source is registered under with the operating system.
+    key: usize,
+    mode: PollMode,
+    /// The interest of every key. In oneshot modes, the interest of a key is cleared once an
+    /// event has been reported for it.
+    interests: Vec<Event>,
+}
+
+impl Split {
+    /// The event passed to the operating system.
+    fn union(&self) -> Event {
+        let mut ev = Event::none(self.key);
+        for interest in &self.interests {
+            ev.readable |= interest.readable;
+            ev.writable |= interest.writable;
+        }
+        ev
+    }
+
+    fn is_oneshot(&self) -> bool {
+        matches!(
+            self.mode,
+            PollMode::Oneshot | PollMode::EdgeOneshot | PollMode::Dispatch
+        )
+    }
+}
+
+impl Poller {
+    /// Adds a file descriptor or socket to the poller under an additional key.
+    ///
+    /// Each key of a source has its own interest, and events are reported by
+    /// [`wait()`][`Poller::wait()`] once for every key that is interested in them. This allows
+    /// one task to own the read half of a socket and another one its write half without
+    /// duplicating the file descriptor. Operating systems only support one registration per
+    /// source, so the source is registered once with the union of all interests and the events
+    /// are split up when they are collected.
+    ///
+    /// All keys of a source must use the same mode. In oneshot mode, an event only disables the
+    /// keys it is reported for, and [`modify_split()`][`Poller::modify_split()`] re-enables a
+    /// single key.
+    ///
+    /// Sources added this way must be changed with [`modify_split()`][`Poller::modify_split()`]
+    /// and [`delete_split()`][`Poller::delete_split()`], or removed with all their keys by
+    /// [`delete()`][`Poller::delete()`]. Other methods that wait for events report them once,
+    /// under one of the keys.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind [`io::ErrorKind::AlreadyExists`] if the source is already
+    /// registered under the key, and an error of kind [`io::ErrorKind::InvalidInput`] if it is
+    /// registered in another mode.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use polling::{Event, PollMode, Poller};
+    /// use std::net::{TcpListener, TcpStream};
+    /// use std::time::Duration;
+    ///
+    /// let listener = TcpListener::bind("127.0.0.1:0")?;
+    /// let stream = TcpStream::connect(listener.local_addr()?)?;
+    /// stream.set_nonblocking(true)?;
+    ///
+    /// let poller = Poller::new()?;
+    /// poller.add_split(&stream, Event::readable(1), PollMode::Oneshot)?;
+    /// poller.add_split(&stream, Event::writable(2), PollMode::Oneshot)?;
+    ///
+    /// let mut events = Vec::new();
+    /// poller.wait(&mut events, Some(Duration::from_secs(1)))?;
+    /// assert_eq!(events, [Event::writable(2)]);
+    /// # poller.delete(&stream)?;
+    /// # std::io::Result::Ok(())
+    /// ```
+    pub fn add_split(
+        &self,
+        source: impl Source,
+        interest: Event,
+        mode: PollMode,
+    ) -> io::Result<()> {
+        let fd = source.raw();
+        if interest.key == self.notify_key {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "the key is reserved for notifications",
+            ));
+        }
+
+        let mut splits = self.splits.lock().unwrap_or_else(|e| e.into_inner());
+        let split = match splits.sources.get_mut(&fd) {
+            Some(split) => split,
+            None => {
+                self.add_with_mode(fd, interest, mode)?;
+                splits.sources.insert(
+                    fd,
+                    Split {
+                        key: interest.key,
+                        mode,
+                        interests: vec![interest],
+                    },
+                );
+                splits.keys.insert(interest.key, fd);
+                self.has_splits.store(true, Ordering::Relaxed);
+                return Ok(());
+            }
+        };
+
+        if split.mode != mode {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "the source is registered in another mode",
+            ));
+        }
+        if split.interests.iter().any(|ev| ev.key == interest.key) {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                "the source is already registered under the key",
+            ));
+        }
+
+        split.interests.push(interest);
+        let res = self.modify_with_mode(fd, split.union(), mode);
+        if res.is_err() {
+            split.interests.pop();
+        }
+        res
+    }
+
+    /// Modifies the interest of one key of a source added with
+    /// [`add_split()`][`Poller::add_split()`].
+    ///
+    /// The interest of the other keys of the source is left as it is.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind [`io::ErrorKind::NotFound`] if the source is not registered
+    /// under the key.
+    pub fn modify_split(&self, source: impl Source, interest: Event) -> io::Result<()> {
+        let fd = source.raw();
+        let mut splits = self.splits.lock().unwrap_or_else(|e| e.into_inner());
+        let split = splits.sources.get_mut(&fd).ok_or_else(not_found)?;
+        let slot = split
+            .interests
+            .iter_mut()
+            .find(|ev| ev.key == interest.key)
+            .ok_or_else(not_found)?;
+
+        let old = std::mem::replace(slot, interest);
+        let res = self.modify_with_mode(fd, split.union(), split.mode);
+        if res.is_err() {
+            if let Some(slot) = split.interests.iter_mut().find(|ev| ev.key == old.key) {
+                *slot = old;
+            }
+        }
+        res
+    }
+
+    /// Removes one key of a source added with [`add_split()`][`Poller::add_split()`].
+    ///
+    /// The source is removed from the poller together with its last key.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind [`io::ErrorKind::NotFound`] if the source is not registered
+    /// under the key.
+    pub fn delete_split(&self, source: impl Source, key: usize) -> io::Result<()> {
+        let fd = source.raw();
+        let mut splits = self.splits.lock().unwrap_or_else(|e| e.into_inner());
+        let split = splits.sources.get_mut(&fd).ok_or_else(not_found)?;
+        let index = split
+            .interests
+            .iter()
+            .position(|ev| ev.key == key)
+            .ok_or_else(not_found)?;
+        split.interests.remove(index);
+
+        if split.interests.is_empty() {
+            forget(&mut splits, fd);
+            drop(splits);
+            return self.delete(fd);
+        }
+
+        // Don't leave the source registered under a key that isn't its own anymore.
+        let old_key = split.key;
+        if old_key == key {
+            split.key = split.interests[0].key;
+        }
+        let res = self.modify_with_mode(fd, split.union(), split.mode);
+        if res.is_err() {
+            split.key = old_key;
+            return res;
+        }
+        if split.key != old_key {
+            let new_key = split.key;
+            splits.keys.remove(&old_key);
+            splits.keys.insert(new_key, fd);
+        }
+        Ok(())
+    }
+
+    /// Reports the events of sources added with [`Poller::add_split()`], starting at `start`,
+    /// under each of their keys.
+    ///
+    /// In oneshot modes, the keys that haven't received an event are enabled again once the
+    /// lock is released. If that fails, they are woken up with their interest, so that the
+    /// error is reported by their next I/O operation or by [`Poller::modify_split()`].
+    pub(crate) fn split_events(&self, events: &mut Vec<Event>, start: usize) {
+        if !self.has_splits.load(Ordering::Relaxed) {
+            return;
+        }
+
+        let mut rearm = Vec::new();
+        {
+            let mut splits = self.splits.lock().unwrap_or_else(|e| e.into_inner());
+            if splits.sources.is_empty() {
+                return;
+            }
+
+            let Splits { sources, keys } = &mut *splits;
+            let collected = events.split_off(start);
+            for ev in collected {
+                let (fd, split) = match keys.get(&ev.key).and_then(|fd| {
+                    let split = sources.get_mut(fd)?;
+                    Some((*fd, split))
+                }) {
+                    Some(entry) => entry,
+                    None => {
+                        events.push(ev);
+                        continue;
+                    }
+                };
+
+                let oneshot = split.is_oneshot();
+                for interest in &mut split.interests {
+                    let readable = ev.readable && interest.readable;
+                    let writable = ev.writable && interest.writable;
+                    if readable || writable {
+                        events.push(Event::new(interest.key, readable, writable));
+                        if oneshot {
+                            *interest = Event::none(interest.key);
+                        }
+                    }
+                }
+
+                // The operating system has disabled the whole source, so the keys that haven't
+                // received an event have to be enabled again.
+                if oneshot {
+                    let interest = split.union();
+                    if interest.readable || interest.writable {
+                        rearm.push((fd, interest, split.mode));
+                    }
+                }
+            }
+        }
+
+        for (fd, interest, mode) in rearm {
+            if let Err(err) = self.registry().modify(fd, interest, mode) {
+                log::trace!("split_events: failed to re-enable {:?}: {}", fd, err);
+                self.wake_split(fd, events);
+            }
+        }
+    }
+
+    /// Wakes up the keys of a split source that are still interested in events.
+    fn wake_split(&self, fd: RawSource, events: &mut Vec<Event>) {
+        let mut splits = self.splits.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(split) = splits.sources.get_mut(&fd) {
+            for interest in &mut split.interests {
+                if interest.readable || interest.writable {
+                    events.push(*interest);
+                    *interest = Event::none(interest.key);
+                }
+            }
+        }
+    }
+}
+
+fn not_found() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::NotFound,
+        "the source is not registered under the key",
+    )
+}
+
+/// Removes the source `fd` with all its keys.
+pub(crate) fn forget(splits: &mut Splits, fd: RawSource) {
+    if let Some(split) = splits.sources.remove(&fd) {
+        splits.keys.remove(&split.key);
+    }
+}
@@ -0,0 +1,233 @@
+//! Platform-specific types used by the backend.
+//!
+//! [`RawEvent`] is the type of the elements returned by [`Events::as_raw_slice()`]. It exposes
+//! the fields of the event reported by the operating system through methods, so that code which
+//! needs to inspect them doesn't have to depend on `libc` or `rustix`, and so that the types of
+//! these crates don't become part of the API of this one. The methods available depend on the
+//! backend that is used on the current platform. Backends that translate the operating system's
+//! events before they are stored use [`Event`] as their raw event type.
+//!
+//! [`Events::as_raw_slice()`]: crate::Events::as_raw_slice()
+//! [`Event`]: crate::Event
+
+use cfg_if::cfg_if;
+
+cfg_if! {
+    if #[cfg(polling_test_poll_backend)] {
+        /// The raw event type of the backend.
+        pub type RawEvent = crate::Event;
+    } else if #[cfg(any(target_os = "linux", target_os = "android"))] {
+        use std::fmt;
+
+        /// A raw event reported by `epoll_wait()`.
+        #[derive(Clone, Copy)]
+        #[repr(transparent)]
+        pub struct RawEvent(libc::epoll_event);
+
+        impl RawEvent {
+            /// The `events` field: the `EPOLL*` flags of the event.
+            pub fn events(&self) -> u32 {
+                self.0.events
+            }
+
+            /// The `data` field, which holds the key of the source.
+            pub fn data(&self) -> u64 {
+                self.0.u64
+            }
+
+            /// Converts the events stored by the backend.
+            pub(crate) fn from_slice(events: &[libc::epoll_event]) -> &[RawEvent] {
+                // SAFETY: `RawEvent` is a transparent wrapper around `epoll_event`.
+                unsafe { &*(events as *const [libc::epoll_event] as *const [RawEvent]) }
+            }
+
+            /// Unwraps the event stored by the backend.
+            #[cfg(fuzzing)]
+            pub(crate) fn into_inner(self) -> libc::epoll_event {
+                self.0
+            }
+        }
+
+        impl fmt::Debug for RawEvent {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.debug_struct("RawEvent")
+                    .field("events", &format_args!("{:#x}", self.events()))
+                    .field("data", &self.data())
+                    .finish()
+            }
+        }
+    } else if #[cfg(any(
+        target_os = "illumos",
+        target_os = "solaris",
+    ))] {
+        use rustix::io::port;
+        use std::fmt;
+
+        /// A raw event reported by `port_getn()`.
+        #[repr(transparent)]
+        pub struct RawEvent(port::Event);
+
+        impl RawEvent {
+            /// The `portev_events` field: the `POLL*` flags of the event.
+            pub fn events(&self) -> i32 {
+                self.0.events()
+            }
+
+            /// The `portev_object` field: the file descriptor of the event.
+            pub fn object(&self) -> usize {
+                self.0.object()
+            }
+
+            /// The `portev_user` field, which holds the key of the source.
+            pub fn user(&self) -> usize {
+                self.0.userdata() as usize
+            }
+
+            /// Converts the events stored by the backend.
+            pub(crate) fn from_slice(events: &[port::Event]) -> &[RawEvent] {
+                // SAFETY: `RawEvent` is a transparent wrapper around `port::Event`.
+                unsafe { &*(events as *const [port::Event] as *const [RawEvent]) }
+            }
+
+            /// Unwraps the event stored by the backend.
+            #[cfg(fuzzing)]
+            pub(crate) fn into_inner(self) -> port::Event {
+                self.0
+            }
+        }
+
+        impl fmt::Debug for RawEvent {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.debug_struct("RawEvent")
+                    .field("events", &format_args!("{:#x}", self.events()))
+                    .field("object", &self.object())
+                    .field("user", &self.user())
+                    .finish()
+            }
+        }
+    } else if #[cfg(any(
+        target_os = "macos",
+        target_os = "ios",
+        target_os = "tvos",
+        target_os = "watchos",
+        target_os = "freebsd",
+        target_os = "netbsd",
+        target_os = "openbsd",
+        target_os = "dragonfly",
+    ))] {
+        use rustix::io::kqueue;
+        use std::fmt;
+
+        /// A raw event reported by `kevent()`.
+        ///
+        /// # Examples
+        ///
+        /// ```
+        /// use polling::Events;
+        ///
+        /// let events = Events::new();
+        /// for ev in events.as_raw_slice() {
+        ///     println!("filter={} data={} fflags={:#x}", ev.filter(), ev.data(), ev.fflags());
+        /// }
+        /// ```
+        #[derive(Clone, Copy)]
+        #[repr(transparent)]
+        pub struct RawEvent(kqueue::Event);
+
+        impl RawEvent {
+            /// The `ident` field: the file descriptor, process ID, signal or timer of the event.
+            pub fn ident(&self) -> usize {
+                self.raw().ident as usize
+            }
+
+            /// The `filter` field: the `EVFILT_*` filter that reported the event.
+            pub fn filter(&self) -> i32 {
+                self.raw().filter as i32
+            }
+
+            /// The `flags` field: the `EV_*` flags of the event.
+            pub fn flags(&self) -> u32 {
+                self.raw().flags as u32
+            }
+
+            /// The `fflags` field: the `NOTE_*` flags of the filter.
+            pub fn fflags(&self) -> u32 {
+                self.raw().fflags as u32
+            }
+
+            /// The `data` field, which holds the number of bytes to read for `EVFILT_READ` and the
+            /// exit status for `EVFILT_PROC`.
+            pub fn data(&self) -> i64 {
+                crate::platform::event_data(&self.0)
+            }
+
+            /// The `udata` field, which holds the key of the source.
+            pub fn udata(&self) -> usize {
+                self.raw().udata as usize
+            }
+
+            fn raw(&self) -> &libc::kevent {
+                crate::platform::as_raw(&self.0)
+            }
+
+            /// Converts the events stored by the backend.
+            pub(crate) fn from_slice(events: &[kqueue::Event]) -> &[RawEvent] {
+                // SAFETY: `RawEvent` is a transparent wrapper around `kqueue::Event`.
+                unsafe { &*(events as *const [kqueue::Event] as *const [RawEvent]) }
+            }
+
+            /// Unwraps the event stored by the backend.
+            #[cfg(fuzzing)]
+            pub(crate) fn into_inner(self) -> kqueue::Event {
+                self.0
+            }
+
+            /// Creates an event from the fields of a `kevent`.
+            #[cfg(fuzzing)]
+            pub(crate) fn from_fields(
+                ident: usize,
+                filter: i16,
+                flags: u16,
+                fflags: u32,
+                data: i64,
+                udata: usize,
+            ) -> RawEvent {
+                // SAFETY: an all-zero `kevent` is valid.
+                let mut kev: libc::kevent = unsafe { std::mem::zeroed() };
+                kev.ident = ident as _;
+                kev.filter = filter as _;
+                kev.flags = flags as _;
+                kev.fflags = fflags as _;
+                kev.data = data as _;
+                kev.udata = udata as _;
+
+                // SAFETY: `kqueue::Event` is a transparent wrapper around `libc::kevent`.
+                RawEvent(unsafe { std::mem::transmute::<libc::kevent, kqueue::Event>(kev) })
+            }
+        }
+
+        impl fmt::Debug for RawEvent {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.debug_struct("RawEvent")
+                    .field("ident", &self.ident())
+                    .field("filter", &self.filter())
+                    .field("flags", &format_args!("{:#x}", self.flags()))
+                    .field("fflags", &format_args!("{:#x}", self.fflags()))
+                    .field("data", &self.data())
+                    .field("udata", &self.udata())
+                    .finish()
+            }
+        }
+    } else if #[cfg(any(
+        target_os = "vxworks",
+        target_os = "fuchsia",
+        target_os = "horizon",
+        unix,
+    ))] {
+        /// The raw event type of the backend.
+        pub type RawEvent = crate::Event;
+    } else if #[cfg(target_os = "windows")] {
+        /// The raw event type of the backend.
+        pub type RawEvent = crate::Event;
+    }
+}
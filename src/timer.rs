@@ -0,0 +1,289 @@
+//! Software timers that are reported through a [`Poller`].
+//!
+//! A [`TimerWheel`] keeps track of any number of timers in a hierarchical timing wheel with four
+//! levels of 256 slots and a resolution of one millisecond. When a timer expires, its key is
+//! reported as a readable event by the poller, through [`Poller::wake()`].
+//!
+//! Where the operating system provides timers, [`Poller::add_timer()`] can be used instead for
+//! timers that don't need a timing wheel.
+//!
+//! # Examples
+//!
+//! ```
+//! use polling::timer::TimerWheel;
+//! use polling::{Event, Events, Poller};
+//! use std::time::{Duration, Instant};
+//!
+//! let poller = Poller::new()?;
+//! let mut timers = TimerWheel::new(&poller);
+//! timers.insert(1, Instant::now() + Duration::from_millis(10));
+//!
+//! let mut events = Events::new();
+//! while events.iter().next().is_none() {
+//!     poller.wait_deadline(&mut events, timers.next_deadline())?;
+//!     timers.advance()?;
+//! }
+//! assert_eq!(events.iter().collect::<Vec<_>>(), [Event::readable(1)]);
+//! # std::io::Result::Ok(())
+//! ```
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io;
+use std::mem;
+use std::time::{Duration, Instant};
+
+use crate::Poller;
+
+/// The number of slots in each level of the wheel.
+const SLOTS: usize = 256;
+
+/// The number of bits of a tick that select the slot in a level.
+const SLOT_BITS: u32 = 8;
+
+/// The number of levels of the wheel.
+const LEVELS: usize = 4;
+
+/// The duration of a tick.
+const TICK: Duration = Duration::from_millis(1);
+
+/// A timer in the wheel.
+struct Timer {
+    /// The key that is woken when the timer expires.
+    key: usize,
+
+    /// The point in time at which the timer expires.
+    deadline: Instant,
+
+    /// The first tick at or after the deadline.
+    tick: u64,
+}
+
+/// A hierarchical timing wheel that reports expired timers through a [`Poller`].
+///
+/// Timers are scheduled with [`insert()`][`TimerWheel::insert()`] and fire when
+/// [`advance()`][`TimerWheel::advance()`] is called at or after their deadline. An event loop
+/// should use [`next_deadline()`][`TimerWheel::next_deadline()`] as the deadline of the next wait,
+/// and call `advance()` after every wait.
+///
+/// Timers never fire early, but they may fire up to a millisecond late in addition to the time it
+/// takes until `advance()` is called.
+pub struct TimerWheel<'a> {
+    /// The poller that the timers are reported through.
+    poller: &'a Poller,
+
+    /// The point in time of tick zero.
+    start: Instant,
+
+    /// The current tick. Every timer at or before this tick has fired.
+    now: u64,
+
+    /// The slots of each level, from the finest to the coarsest.
+    levels: Vec<Vec<Vec<Timer>>>,
+
+    /// Timers that were inserted with a deadline that already passed.
+    expired: Vec<Timer>,
+
+    /// The number of timers in `levels`.
+    len: usize,
+
+    /// The deadlines of the timers that haven't fired yet, with the number of timers for each.
+    deadlines: BTreeMap<Instant, usize>,
+}
+
+impl<'a> TimerWheel<'a> {
+    /// Creates an empty timer wheel that reports its timers through `poller`.
+    pub fn new(poller: &'a Poller) -> TimerWheel<'a> {
+        TimerWheel {
+            poller,
+            start: Instant::now(),
+            now: 0,
+            levels: (0..LEVELS)
+                .map(|_| (0..SLOTS).map(|_| Vec::new()).collect())
+                .collect(),
+            expired: Vec::new(),
+            len: 0,
+            deadlines: BTreeMap::new(),
+        }
+    }
+
+    /// The poller that the timers are reported through.
+    pub fn poller(&self) -> &'a Poller {
+        self.poller
+    }
+
+    /// The number of timers that haven't fired yet.
+    pub fn len(&self) -> usize {
+        self.len + self.expired.len()
+    }
+
+    /// Whether there are no timers that haven't fired yet.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Schedules a timer that reports a readable event with `key` at `deadline`.
+    ///
+    /// If `deadline` has already passed, the timer fires on the next call to
+    /// [`advance()`][`TimerWheel::advance()`]. Several timers may use the same key.
+    pub fn insert(&mut self, key: usize, deadline: Instant) {
+        let tick = self.tick_at_or_after(deadline);
+        *self.deadlines.entry(deadline).or_insert(0) += 1;
+        let timer = Timer {
+            key,
+            deadline,
+            tick,
+        };
+
+        if tick <= self.now {
+            self.expired.push(timer);
+        } else {
+            self.place(timer);
+            self.len += 1;
+        }
+    }
+
+    /// Cancels every timer with the given key that hasn't fired yet.
+    ///
+    /// Returns `true` if any timer was cancelled.
+    pub fn remove(&mut self, key: usize) -> bool {
+        let before = self.len();
+
+        let mut removed = Vec::new();
+        let mut keep = |timer: &Timer| {
+            if timer.key == key {
+                removed.push(timer.deadline);
+                false
+            } else {
+                true
+            }
+        };
+        self.expired.retain(&mut keep);
+        let mut len = 0;
+        for slot in self.levels.iter_mut().flatten() {
+            slot.retain(&mut keep);
+            len += slot.len();
+        }
+        self.len = len;
+
+        for deadline in removed {
+            self.forget_deadline(deadline);
+        }
+        self.len() < before
+    }
+
+    /// The earliest deadline of the timers that haven't fired yet.
+    ///
+    /// This is meant to be passed to [`Poller::wait_deadline()`]. Returns `None` if there are no
+    /// timers.
+    pub fn next_deadline(&self) -> Option<Instant> {
+        self.deadlines.keys().next().copied()
+    }
+
+    /// Fires every timer whose deadline has passed and returns the number of fired timers.
+    ///
+    /// The key of each fired timer is reported as a readable event by the poller, through
+    /// [`Poller::wake()`].
+    pub fn advance(&mut self) -> io::Result<usize> {
+        let target = self.tick_before(Instant::now());
+        let mut fired = mem::take(&mut self.expired);
+
+        while self.now < target {
+            if self.len == 0 {
+                self.now = target;
+                break;
+            }
+
+            // Skip the rest of the current rotation of the finest level if it is empty.
+            let end = (self.now | (SLOTS as u64 - 1)).min(target);
+            let level = &self.levels[0];
+            if (self.now + 1..=end).all(|tick| level[tick as usize % SLOTS].is_empty()) {
+                self.now = end;
+                if self.now == target {
+                    break;
+                }
+            }
+
+            self.now += 1;
+
+            // Move the timers of the coarser levels down when their slot comes up.
+            for level in (1..LEVELS).rev() {
+                let shift = SLOT_BITS * level as u32;
+                if self.now & ((1 << shift) - 1) == 0 {
+                    let slot = (self.now >> shift) as usize % SLOTS;
+                    for timer in mem::take(&mut self.levels[level][slot]) {
+                        self.place(timer);
+                    }
+                }
+            }
+
+            let slot = self.now as usize % SLOTS;
+            let expired = &mut self.levels[0][slot];
+            self.len -= expired.len();
+            fired.append(expired);
+        }
+
+        for timer in &fired {
+            self.forget_deadline(timer.deadline);
+        }
+        for timer in &fired {
+            self.poller.wake(timer.key)?;
+        }
+
+        log::trace!("TimerWheel::advance: fired {} timers", fired.len());
+        Ok(fired.len())
+    }
+
+    /// Removes one timer with `deadline` from the deadlines.
+    fn forget_deadline(&mut self, deadline: Instant) {
+        if let Some(count) = self.deadlines.get_mut(&deadline) {
+            *count -= 1;
+            if *count == 0 {
+                self.deadlines.remove(&deadline);
+            }
+        }
+    }
+
+    /// Puts a timer that expires after the current tick into its slot.
+    fn place(&mut self, timer: Timer) {
+        // The level is the coarsest one in which the tick differs from the current tick.
+        let diff = timer.tick ^ self.now;
+        let level = (0..LEVELS)
+            .find(|&level| diff >> (SLOT_BITS * (level as u32 + 1)) == 0)
+            .unwrap_or(LEVELS - 1);
+
+        let slot = if diff >> (SLOT_BITS * LEVELS as u32) == 0 {
+            (timer.tick >> (SLOT_BITS * level as u32)) as usize % SLOTS
+        } else {
+            // The timer is too far away for the wheel, so put it into the last slot that comes up
+            // and move it down from there.
+            ((self.now >> (SLOT_BITS * level as u32)) as usize + SLOTS - 1) % SLOTS
+        };
+
+        self.levels[level][slot].push(timer);
+    }
+
+    /// The first tick at or after `instant`.
+    fn tick_at_or_after(&self, instant: Instant) -> u64 {
+        let elapsed = instant.saturating_duration_since(self.start).as_nanos();
+        let tick = TICK.as_nanos();
+        let ticks = elapsed / tick + if elapsed % tick == 0 { 0 } else { 1 };
+        ticks.min(u64::max_value() as u128) as u64
+    }
+
+    /// The last tick at or before `instant`.
+    fn tick_before(&self, instant: Instant) -> u64 {
+        let elapsed = instant.saturating_duration_since(self.start).as_nanos();
+        (elapsed / TICK.as_nanos()).min(u64::max_value() as u128) as u64
+    }
+}
+
+impl fmt::Debug for TimerWheel<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TimerWheel")
+            .field("poller", &self.poller)
+            .field("len", &self.len())
+            .field("next_deadline", &self.next_deadline())
+            .finish()
+    }
+}
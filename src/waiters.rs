@@ -0,0 +1,164 @@
+//! Several threads waiting on the same poller.
+
+use std::io;
+use std::sync::atomic::Ordering;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::{Events, Poller};
+
+/// The threads that are waiting in [`Poller::wait_shared()`].
+#[derive(Debug, Default)]
+pub(crate) struct Waiters {
+    state: Mutex<State>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    /// The number of threads that are waiting.
+    waiting: usize,
+
+    /// Incremented by every call to `notify_all()` that finds waiting threads.
+    epoch: u64,
+
+    /// The number of threads that started waiting before the last call to `notify_all()` and
+    /// haven't returned yet.
+    to_wake: usize,
+}
+
+impl Waiters {
+    pub(crate) fn new() -> Waiters {
+        Waiters::default()
+    }
+
+    /// Registers a waiting thread and returns the epoch that it started waiting in.
+    fn enter(&self) -> u64 {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.waiting += 1;
+        state.epoch
+    }
+
+    /// Unregisters a waiting thread, and passes the wakeup of `notify_all()` on to the next
+    /// thread if some are still waiting.
+    ///
+    /// A thread that started waiting after `notify_all()` may have taken the wakeup meant for
+    /// another one, so every thread passes it on while some haven't returned yet.
+    fn exit(&self, epoch: u64, notify: impl FnOnce() -> io::Result<()>) -> io::Result<()> {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.waiting -= 1;
+
+        if state.to_wake > 0 {
+            if epoch < state.epoch {
+                state.to_wake -= 1;
+            }
+            if state.to_wake > 0 {
+                drop(state);
+                return notify();
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Marks all waiting threads to be woken up.
+    fn broadcast(&self) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        if state.waiting > 0 {
+            state.epoch += 1;
+            state.to_wake = state.waiting;
+        }
+    }
+}
+
+impl Poller {
+    /// Waits for at least one I/O event, allowing other threads to wait at the same time.
+    ///
+    /// Unlike [`wait_events()`][`Poller::wait_events()`], which returns right away if another
+    /// thread is already waiting, any number of threads can wait with this method at once. Each
+    /// event is reported to one of them. Use [`notify_one()`][`Poller::notify_one()`] and
+    /// [`notify_all()`][`Poller::notify_all()`] to wake them up.
+    ///
+    /// Returns the number of new events.
+    ///
+    /// # Errors
+    ///
+    /// This is supported by the epoll, kqueue and event ports backends. Otherwise, an error of
+    /// kind [`io::ErrorKind::Unsupported`] is returned. With epoll, the first call switches the
+    /// poller to the mode of [`try_clone()`][`Poller::try_clone()`], in which timeouts have a
+    /// granularity of a millisecond.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use polling::{Events, Poller};
+    /// use std::sync::Arc;
+    /// use std::thread;
+    ///
+    /// let poller = Arc::new(Poller::new()?);
+    /// # if polling::BACKEND == "epoll" || polling::BACKEND == "kqueue" {
+    /// let threads = (0..4)
+    ///     .map(|_| {
+    ///         let poller = poller.clone();
+    ///         thread::spawn(move || {
+    ///             let mut events = Events::new();
+    ///             poller.wait_shared(&mut events, None)
+    ///         })
+    ///     })
+    ///     .collect::<Vec<_>>();
+    ///
+    /// # thread::sleep(std::time::Duration::from_millis(100));
+    /// poller.notify_all()?;
+    /// for thread in threads {
+    ///     thread.join().unwrap()?;
+    /// }
+    /// # }
+    /// # std::io::Result::Ok(())
+    /// ```
+    pub fn wait_shared(&self, events: &mut Events, timeout: Option<Duration>) -> io::Result<usize> {
+        log::trace!("Poller::wait_shared(_, {:?})", timeout);
+
+        self.poller.enable_concurrent_wait()?;
+        events.notify_key = self.notify_key;
+        events.clear();
+
+        let epoch = self.waiters.enter();
+        let res = self.wait_inner(&mut events.events, timeout);
+        self.waiters.exit(epoch, || self.poller.notify())?;
+
+        // Pass the shutdown on to the next thread that is still waiting.
+        if self.is_shut_down() {
+            self.poller.notify()?;
+        }
+        events.notified = res?;
+
+        self.take_woken(&mut events.woken);
+
+        events.notify_count += self.take_notify_count();
+        Ok(events.len())
+    }
+
+    /// Wakes up at least one thread that is waiting, or the following wait.
+    ///
+    /// Unlike [`notify()`][`Poller::notify()`], which does nothing while a notification is
+    /// already pending, every call sends a notification, so that calling this `n` times wakes
+    /// up `n` threads waiting in [`wait_shared()`][`Poller::wait_shared()`].
+    pub fn notify_one(&self) -> io::Result<()> {
+        log::trace!("Poller::notify_one()");
+        self.registry().count_notification();
+        self.notified.store(true, Ordering::SeqCst);
+        self.poller.notify()?;
+        self.registry().run_notify_hook();
+        Ok(())
+    }
+
+    /// Wakes up all threads that are waiting in [`wait_shared()`][`Poller::wait_shared()`].
+    ///
+    /// The threads are woken up one after the other: each thread that returns wakes up the next
+    /// one. If no thread is waiting, this wakes up the following wait like
+    /// [`notify()`][`Poller::notify()`].
+    pub fn notify_all(&self) -> io::Result<()> {
+        log::trace!("Poller::notify_all()");
+        self.waiters.broadcast();
+        self.notify_one()
+    }
+}
@@ -0,0 +1,479 @@
+//! Watching files and directories for changes.
+
+use std::fmt;
+use std::io;
+use std::ops::{BitAnd, BitOr, BitOrAssign};
+use std::path::Path;
+
+#[cfg(all(
+    any(target_os = "linux", target_os = "android"),
+    not(polling_no_io_safety)
+))]
+use std::os::unix::io::{AsFd, BorrowedFd};
+use std::os::unix::io::{AsRawFd, RawFd};
+
+use crate::Poller;
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+use crate::{Event, PollMode};
+
+/// An inotify instance that watches a path and is registered in a [`Poller`].
+///
+/// This is returned by [`Poller::register_inotify()`]. When the inotify instance is readable,
+/// `inotify_event` structures can be read from it with [`read()`][`InotifyHandle::read()`].
+///
+/// The inotify instance must be removed from the poller with [`Poller::delete()`] before it is
+/// dropped.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+#[derive(Debug)]
+pub struct InotifyHandle {
+    /// The inotify instance.
+    fd: rustix::fd::OwnedFd,
+
+    /// The watch descriptor of the watched path.
+    watch: i32,
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+impl InotifyHandle {
+    /// The watch descriptor of the watched path, as found in the `wd` field of `inotify_event`.
+    pub fn watch_descriptor(&self) -> i32 {
+        self.watch
+    }
+
+    /// Reads `inotify_event` structures into `buf` and returns the number of bytes read.
+    ///
+    /// Returns an error of kind [`io::ErrorKind::WouldBlock`] if there are no events.
+    pub fn read(&self, buf: &mut [u8]) -> io::Result<usize> {
+        Ok(rustix::io::read(&self.fd, buf)?)
+    }
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+impl AsRawFd for InotifyHandle {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd.as_raw_fd()
+    }
+}
+
+#[cfg(all(
+    any(target_os = "linux", target_os = "android"),
+    not(polling_no_io_safety)
+))]
+impl AsFd for InotifyHandle {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.fd.as_fd()
+    }
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+impl Poller {
+    /// Watches `path` with inotify and registers the inotify instance as readable under `key`.
+    ///
+    /// `mask` is the set of `IN_*` events to watch for, as passed to `inotify_add_watch()`. The
+    /// inotify instance is added in oneshot mode, like with [`add()`][`Poller::add()`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use polling::{Event, Poller};
+    /// use std::time::Duration;
+    ///
+    /// let dir = std::env::temp_dir();
+    ///
+    /// let poller = Poller::new()?;
+    /// let inotify = poller.register_inotify(1, &dir, libc::IN_CREATE)?;
+    ///
+    /// let mut events = Vec::new();
+    /// poller.wait(&mut events, Some(Duration::from_millis(10)))?;
+    ///
+    /// poller.delete(&inotify)?;
+    /// # std::io::Result::Ok(())
+    /// ```
+    pub fn register_inotify(
+        &self,
+        key: usize,
+        path: &Path,
+        mask: u32,
+    ) -> io::Result<InotifyHandle> {
+        let handle = inotify(path, mask)?;
+        self.add(&handle, Event::readable(key))?;
+        Ok(handle)
+    }
+}
+
+/// Creates an inotify instance that watches `path`.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn inotify(path: &Path, mask: u32) -> io::Result<InotifyHandle> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+    use std::os::unix::io::FromRawFd;
+
+    let fd = unsafe { libc::inotify_init1(libc::IN_NONBLOCK | libc::IN_CLOEXEC) };
+    if fd == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    let fd = unsafe { rustix::fd::OwnedFd::from_raw_fd(fd) };
+
+    let path = CString::new(path.as_os_str().as_bytes())?;
+    let watch = unsafe { libc::inotify_add_watch(fd.as_raw_fd(), path.as_ptr(), mask) };
+    if watch == -1 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(InotifyHandle { fd, watch })
+}
+
+/// The kinds of changes a file or directory is watched for.
+///
+/// This is passed to [`Poller::add_path()`].
+///
+/// # Examples
+///
+/// ```
+/// use polling::WatchFlags;
+///
+/// let flags = WatchFlags::MODIFY | WatchFlags::REMOVE;
+/// assert!(flags.contains(WatchFlags::MODIFY) && !flags.contains(WatchFlags::RENAME));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct WatchFlags(u8);
+
+impl WatchFlags {
+    /// Writes to the file, or to the directory itself.
+    pub const MODIFY: WatchFlags = WatchFlags(1 << 0);
+
+    /// Changes of the permissions, ownership, timestamps or link count.
+    pub const ATTRIBUTES: WatchFlags = WatchFlags(1 << 1);
+
+    /// Removal of the file or directory.
+    pub const REMOVE: WatchFlags = WatchFlags(1 << 2);
+
+    /// Renaming of the file or directory.
+    pub const RENAME: WatchFlags = WatchFlags(1 << 3);
+
+    /// Entries being created, removed or renamed in a directory.
+    ///
+    /// With kqueue, this is reported as a write to the directory, so it can't be told apart from
+    /// [`MODIFY`][`WatchFlags::MODIFY`].
+    pub const CHILDREN: WatchFlags = WatchFlags(1 << 4);
+
+    /// No changes.
+    pub const fn empty() -> WatchFlags {
+        WatchFlags(0)
+    }
+
+    /// All kinds of changes.
+    pub const fn all() -> WatchFlags {
+        WatchFlags(
+            WatchFlags::MODIFY.0
+                | WatchFlags::ATTRIBUTES.0
+                | WatchFlags::REMOVE.0
+                | WatchFlags::RENAME.0
+                | WatchFlags::CHILDREN.0,
+        )
+    }
+
+    /// Returns the raw bits of the flags.
+    pub const fn bits(self) -> u8 {
+        self.0
+    }
+
+    /// Returns the flags for `bits`, or `None` if it contains unknown bits.
+    pub const fn from_bits(bits: u8) -> Option<WatchFlags> {
+        if bits & !WatchFlags::all().0 == 0 {
+            Some(WatchFlags(bits))
+        } else {
+            None
+        }
+    }
+
+    /// Returns `true` if no flags are set.
+    pub const fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    /// Returns `true` if all flags in `other` are set.
+    pub const fn contains(self, other: WatchFlags) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Returns `true` if any flag in `other` is set.
+    pub const fn intersects(self, other: WatchFlags) -> bool {
+        self.0 & other.0 != 0
+    }
+
+    /// The `IN_*` mask for `inotify_add_watch()`.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    fn inotify_mask(self) -> u32 {
+        let mut mask = 0;
+        if self.contains(WatchFlags::MODIFY) {
+            mask |= libc::IN_MODIFY | libc::IN_CLOSE_WRITE;
+        }
+        if self.contains(WatchFlags::ATTRIBUTES) {
+            mask |= libc::IN_ATTRIB;
+        }
+        if self.contains(WatchFlags::REMOVE) {
+            mask |= libc::IN_DELETE_SELF;
+        }
+        if self.contains(WatchFlags::RENAME) {
+            mask |= libc::IN_MOVE_SELF;
+        }
+        if self.contains(WatchFlags::CHILDREN) {
+            mask |= libc::IN_CREATE | libc::IN_DELETE | libc::IN_MOVED_FROM | libc::IN_MOVED_TO;
+        }
+        mask
+    }
+
+    /// The `NOTE_*` flags for `EVFILT_VNODE`.
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    fn vnode_events(self) -> rustix::io::kqueue::VnodeEvents {
+        use rustix::io::kqueue::VnodeEvents;
+
+        let mut events = VnodeEvents::empty();
+        if self.intersects(WatchFlags::MODIFY | WatchFlags::CHILDREN) {
+            events |= VnodeEvents::WRITE | VnodeEvents::EXTEND;
+        }
+        if self.contains(WatchFlags::ATTRIBUTES) {
+            events |= VnodeEvents::ATTRIBUTES | VnodeEvents::LINK;
+        }
+        if self.contains(WatchFlags::REMOVE) {
+            events |= VnodeEvents::DELETE;
+        }
+        if self.contains(WatchFlags::RENAME) {
+            events |= VnodeEvents::RENAME;
+        }
+        events
+    }
+}
+
+impl BitOr for WatchFlags {
+    type Output = WatchFlags;
+
+    fn bitor(self, other: WatchFlags) -> WatchFlags {
+        WatchFlags(self.0 | other.0)
+    }
+}
+
+impl BitOrAssign for WatchFlags {
+    fn bitor_assign(&mut self, other: WatchFlags) {
+        self.0 |= other.0;
+    }
+}
+
+impl BitAnd for WatchFlags {
+    type Output = WatchFlags;
+
+    fn bitand(self, other: WatchFlags) -> WatchFlags {
+        WatchFlags(self.0 & other.0)
+    }
+}
+
+/// A file or directory that is watched for changes.
+///
+/// This is returned by [`Poller::add_path()`] and [`Poller::register_path()`]. The path is no
+/// longer watched once this is dropped.
+pub struct PathWatch<'a> {
+    /// The poller that reports the changes.
+    poller: &'a Poller,
+
+    /// The inotify instance that watches the path.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    inotify: InotifyHandle,
+
+    /// The watched file or directory, opened for `EVFILT_VNODE`.
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    file: std::fs::File,
+}
+
+impl PathWatch<'_> {
+    /// Acknowledges the changes that were reported so far.
+    ///
+    /// This must be called after the watch is reported, or it is reported again by the next
+    /// wait.
+    pub fn clear(&self) -> io::Result<()> {
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        {
+            let mut buf = [0; 4096];
+            loop {
+                match self.inotify.read(&mut buf) {
+                    Ok(_) => {}
+                    Err(e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(()),
+                    Err(e) => return Err(e),
+                }
+            }
+        }
+
+        // `EVFILT_VNODE` is registered with `EV_CLEAR`, so the kernel clears it when it is
+        // reported.
+        #[cfg(not(any(target_os = "linux", target_os = "android")))]
+        {
+            Ok(())
+        }
+    }
+}
+
+impl AsRawFd for PathWatch<'_> {
+    fn as_raw_fd(&self) -> RawFd {
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        {
+            self.inotify.as_raw_fd()
+        }
+
+        #[cfg(not(any(target_os = "linux", target_os = "android")))]
+        {
+            self.file.as_raw_fd()
+        }
+    }
+}
+
+impl fmt::Debug for PathWatch<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PathWatch")
+            .field("poller", &self.poller)
+            .field("fd", &self.as_raw_fd())
+            .finish()
+    }
+}
+
+impl Drop for PathWatch<'_> {
+    fn drop(&mut self) {
+        // With kqueue, closing the file removes the filter.
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        if let Err(e) = self.poller.delete(&self.inotify) {
+            log::debug!("PathWatch: failed to remove the inotify instance: {}", e);
+        }
+    }
+}
+
+impl Poller {
+    /// Watches a file or directory for changes and reports them as readable under `key`.
+    ///
+    /// This uses inotify on Linux and Android, and `EVFILT_VNODE` with kqueue. The watch is
+    /// reported until [`PathWatch::clear()`] is called, and it is removed when the returned
+    /// [`PathWatch`] is dropped.
+    ///
+    /// Writes to the file, changes of its attributes, and its removal and renaming are reported.
+    /// For directories, entries being created, removed or renamed are reported as well.
+    ///
+    /// This is identical to [`add_path()`][`Poller::add_path()`] with [`WatchFlags::all()`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use polling::Poller;
+    /// use std::time::Duration;
+    ///
+    /// let dir = std::env::temp_dir();
+    ///
+    /// let poller = Poller::new()?;
+    /// let watch = poller.register_path(&dir, 1)?;
+    ///
+    /// let mut events = Vec::new();
+    /// poller.wait(&mut events, Some(Duration::from_millis(10)))?;
+    /// if !events.is_empty() {
+    ///     println!("{} changed", dir.display());
+    ///     watch.clear()?;
+    /// }
+    /// # std::io::Result::Ok(())
+    /// ```
+    pub fn register_path(&self, path: &Path, key: usize) -> io::Result<PathWatch<'_>> {
+        self.add_path(path, WatchFlags::all(), key)
+    }
+
+    /// Watches a file or directory for the changes in `flags` and reports them as readable under
+    /// `key`.
+    ///
+    /// This uses inotify on Linux and Android, and `EVFILT_VNODE` with kqueue. The watch is
+    /// reported until [`PathWatch::clear()`] is called, and it is removed when the returned
+    /// [`PathWatch`] is dropped.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind [`io::ErrorKind::InvalidInput`] if `flags` is empty.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use polling::{Poller, WatchFlags};
+    /// use std::time::Duration;
+    ///
+    /// let config = std::env::temp_dir().join("polling-add-path-example.toml");
+    /// std::fs::write(&config, "")?;
+    ///
+    /// let poller = Poller::new()?;
+    /// let watch = poller.add_path(&config, WatchFlags::MODIFY, 1)?;
+    ///
+    /// let mut events = Vec::new();
+    /// poller.wait(&mut events, Some(Duration::from_millis(10)))?;
+    /// if !events.is_empty() {
+    ///     println!("reloading {}", config.display());
+    ///     watch.clear()?;
+    /// }
+    /// # std::io::Result::Ok(())
+    /// ```
+    pub fn add_path(
+        &self,
+        path: &Path,
+        flags: WatchFlags,
+        key: usize,
+    ) -> io::Result<PathWatch<'_>> {
+        if flags.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "no changes to watch for",
+            ));
+        }
+
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        {
+            let inotify = inotify(path, flags.inotify_mask())?;
+            self.add_with_mode(&inotify, Event::readable(key), PollMode::Level)?;
+
+            Ok(PathWatch {
+                poller: self,
+                inotify,
+            })
+        }
+
+        #[cfg(not(any(target_os = "linux", target_os = "android")))]
+        {
+            use rustix::io::kqueue;
+            use std::fs::OpenOptions;
+            use std::os::unix::fs::OpenOptionsExt;
+
+            // Don't keep the file system busy just because the file is watched.
+            #[cfg(any(
+                target_os = "macos",
+                target_os = "ios",
+                target_os = "tvos",
+                target_os = "watchos",
+            ))]
+            let open_flags = libc::O_EVTONLY;
+            #[cfg(not(any(
+                target_os = "macos",
+                target_os = "ios",
+                target_os = "tvos",
+                target_os = "watchos",
+            )))]
+            let open_flags = 0;
+
+            let file = OpenOptions::new()
+                .read(true)
+                .custom_flags(open_flags)
+                .open(path)?;
+
+            self.poller.submit_changes([kqueue::Event::new(
+                kqueue::EventFilter::Vnode {
+                    vnode: file.as_raw_fd(),
+                    flags: flags.vnode_events(),
+                },
+                kqueue::EventFlags::ADD | kqueue::EventFlags::CLEAR | kqueue::EventFlags::RECEIPT,
+                key as _,
+            )])?;
+
+            Ok(PathWatch { poller: self, file })
+        }
+    }
+}
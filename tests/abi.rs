@@ -0,0 +1,23 @@
+//! Tests that the layout of `Event` matches its documentation.
+
+use std::mem;
+
+use polling::Event;
+
+/// The offset of a field of `ev`, in bytes.
+fn offset_of<T>(ev: &Event, field: &T) -> usize {
+    field as *const T as usize - ev as *const Event as usize
+}
+
+#[test]
+fn event_layout() {
+    let ev = Event::all(7);
+    let word = mem::size_of::<usize>();
+
+    assert_eq!(mem::size_of::<Event>(), 2 * word);
+    assert_eq!(mem::align_of::<Event>(), mem::align_of::<usize>());
+
+    assert_eq!(offset_of(&ev, &ev.key), 0);
+    assert_eq!(offset_of(&ev, &ev.readable), word);
+    assert_eq!(offset_of(&ev, &ev.writable), word + 1);
+}
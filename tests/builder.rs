@@ -0,0 +1,331 @@
+//! Tests for `PollerBuilder`.
+
+mod common;
+
+use std::io::Write;
+use std::time::{Duration, Instant};
+
+use polling::{BackendHint, Event, Events, Poller, PollerBuilder, QosClass};
+
+#[test]
+fn custom_notify_key() {
+    let poller = PollerBuilder::new().notify_key(0).build().unwrap();
+    let (read, mut write) = common::tcp_pair().unwrap();
+
+    assert!(poller.is_notify_event(&Event::readable(0)));
+    assert!(!poller.is_notify_event(&Event::readable(polling::NOTIFY_KEY)));
+
+    // The notify key is reserved, but `usize::MAX` is now usable.
+    assert!(poller.add(&read, Event::readable(0)).is_err());
+    poller.add(&read, Event::readable(usize::MAX)).unwrap();
+
+    // Notifications are not reported as events.
+    let mut events = Vec::new();
+    poller.notify().unwrap();
+    poller
+        .wait(&mut events, Some(Duration::from_secs(1)))
+        .unwrap();
+    assert!(events.is_empty());
+
+    write.write_all(&[1]).unwrap();
+    poller
+        .wait(&mut events, Some(Duration::from_secs(1)))
+        .unwrap();
+    assert_eq!(events, [Event::readable(usize::MAX)]);
+
+    poller.delete(&read).unwrap();
+}
+
+#[test]
+fn with_notify_key() {
+    let key = usize::MAX - 1;
+    let poller = Poller::with_notify_key(key).unwrap();
+    let (read, mut write) = common::tcp_pair().unwrap();
+
+    assert_eq!(poller.notify_key(), key);
+    assert!(poller.add(&read, Event::readable(key)).is_err());
+    poller.add(&read, Event::readable(usize::MAX)).unwrap();
+
+    let mut events = Vec::new();
+    write.write_all(&[1]).unwrap();
+    poller
+        .wait(&mut events, Some(Duration::from_secs(1)))
+        .unwrap();
+    assert_eq!(events, [Event::readable(usize::MAX)]);
+
+    poller.delete(&read).unwrap();
+}
+
+#[test]
+fn without_cloexec() {
+    let poller = PollerBuilder::new()
+        .close_on_exec(false)
+        .iocp_concurrent_threads(1)
+        .build()
+        .unwrap();
+
+    let mut events = Vec::new();
+    poller.notify().unwrap();
+    poller.wait(&mut events, None).unwrap();
+    assert!(events.is_empty());
+}
+
+#[test]
+fn explicit_backend() {
+    Poller::new_with_backend(BackendHint::Auto).unwrap();
+
+    let err = Poller::new_with_backend(BackendHint::IoUring).unwrap_err();
+    assert!(common::is_unsupported(&err));
+
+    // Preferring an unavailable backend falls back to the default one.
+    PollerBuilder::new()
+        .prefer_backend(BackendHint::IoUring)
+        .build()
+        .unwrap();
+}
+
+#[test]
+fn backend_name() {
+    let expected = if cfg!(polling_test_poll_backend) {
+        "poll"
+    } else if cfg!(any(target_os = "linux", target_os = "android")) {
+        "epoll"
+    } else if cfg!(any(target_os = "illumos", target_os = "solaris")) {
+        "port"
+    } else if cfg!(any(
+        target_os = "macos",
+        target_os = "ios",
+        target_os = "tvos",
+        target_os = "watchos",
+        target_os = "freebsd",
+        target_os = "netbsd",
+        target_os = "openbsd",
+        target_os = "dragonfly",
+    )) {
+        "kqueue"
+    } else if cfg!(windows) {
+        "iocp"
+    } else {
+        "poll"
+    };
+
+    assert_eq!(polling::BACKEND, expected);
+    assert_eq!(polling::BACKEND_KIND.to_string(), expected);
+}
+
+#[test]
+fn backend() {
+    let poller = Poller::new().unwrap();
+    assert_eq!(poller.backend(), polling::BACKEND_KIND);
+    assert_eq!(poller.backend().name(), polling::BACKEND);
+
+    // `wait_shared()` fails exactly when concurrent waits aren't supported.
+    let mut events = Events::new();
+    let res = poller.wait_shared(&mut events, Some(Duration::from_secs(0)));
+    assert_eq!(res.is_ok(), poller.supports_concurrent_wait());
+}
+
+#[test]
+fn max_events() {
+    let poller = PollerBuilder::new().max_events(1).build().unwrap();
+    let pairs = (0..2)
+        .map(|_| common::tcp_pair().unwrap())
+        .collect::<Vec<_>>();
+    for (i, (read, write)) in pairs.iter().enumerate() {
+        poller.add(read, Event::readable(i)).unwrap();
+        (&*write).write_all(&[1]).unwrap();
+    }
+
+    // Each wait reports a single event.
+    let mut events = Vec::new();
+    for _ in 0..2 {
+        assert_eq!(
+            poller
+                .wait(&mut events, Some(Duration::from_secs(1)))
+                .unwrap(),
+            1
+        );
+    }
+    events.sort_by_key(|ev| ev.key);
+    assert_eq!(events, [Event::readable(0), Event::readable(1)]);
+
+    for (read, _) in &pairs {
+        poller.delete(read).unwrap();
+    }
+}
+
+#[test]
+fn max_events_limits_wait_events() {
+    let poller = PollerBuilder::new().max_events(1).build().unwrap();
+    let pairs = (0..2)
+        .map(|_| common::tcp_pair().unwrap())
+        .collect::<Vec<_>>();
+    for (i, (read, write)) in pairs.iter().enumerate() {
+        poller.add(read, Event::readable(i)).unwrap();
+        (&*write).write_all(&[1]).unwrap();
+    }
+    std::thread::sleep(Duration::from_millis(100));
+
+    // Each wait reports a single event, whatever the capacity of the list.
+    let mut events = Events::with_capacity(1024);
+    let mut keys = Vec::new();
+    for _ in 0..2 {
+        assert_eq!(
+            poller
+                .wait_events(&mut events, Some(Duration::from_secs(1)))
+                .unwrap(),
+            1
+        );
+        keys.extend(events.iter_keys());
+    }
+    keys.sort_unstable();
+    assert_eq!(keys, [0, 1]);
+
+    for (read, _) in &pairs {
+        poller.delete(read).unwrap();
+    }
+}
+
+#[test]
+fn deferred_changes() {
+    let poller = PollerBuilder::new().deferred_changes(true).build().unwrap();
+    let (read, write) = common::tcp_pair().unwrap();
+    poller.add(&read, Event::readable(1)).unwrap();
+    (&write).write_all(&[1]).unwrap();
+
+    // Re-arming the oneshot source applies with each wait.
+    let mut events = Vec::new();
+    for _ in 0..3 {
+        events.clear();
+        poller
+            .wait(&mut events, Some(Duration::from_secs(1)))
+            .unwrap();
+        assert_eq!(events, [Event::readable(1)]);
+        poller.modify(&read, Event::readable(1)).unwrap();
+    }
+
+    // Deleting filters that already fired is not an error, and doesn't end the wait early.
+    events.clear();
+    poller
+        .wait(&mut events, Some(Duration::from_secs(1)))
+        .unwrap();
+    assert_eq!(events, [Event::readable(1)]);
+    poller.delete(&read).unwrap();
+
+    events.clear();
+    let start = Instant::now();
+    poller
+        .wait(&mut events, Some(Duration::from_millis(100)))
+        .unwrap();
+    assert!(events.is_empty());
+    assert!(start.elapsed() >= Duration::from_millis(100));
+}
+
+#[cfg(unix)]
+#[test]
+fn deferred_changes_error() {
+    use polling::PollMode;
+    use std::os::unix::io::{AsRawFd, RawFd};
+
+    // Only kqueue defers changes.
+    if polling::BACKEND != "kqueue" {
+        return;
+    }
+
+    let poller = PollerBuilder::new().deferred_changes(true).build().unwrap();
+    let (read, write) = common::tcp_pair().unwrap();
+    let bad = RawFd::MAX;
+
+    // A failing change is reported by the next wait, which then forgets it.
+    poller.add(bad, Event::readable(1)).unwrap();
+    let mut events = Vec::new();
+    let err = poller
+        .wait(&mut events, Some(Duration::from_secs(0)))
+        .unwrap_err();
+    assert_eq!(err.raw_os_error(), Some(libc::EBADF));
+    poller
+        .wait(&mut events, Some(Duration::from_secs(0)))
+        .unwrap();
+
+    // It is also reported by the next change that is submitted right away.
+    poller.add(bad, Event::readable(1)).unwrap();
+    let err = poller
+        .add_many_from_slice(&[(read.as_raw_fd(), Event::readable(2), PollMode::Oneshot)])
+        .unwrap_err();
+    assert_eq!(err.raw_os_error(), Some(libc::EBADF));
+
+    poller
+        .add_many_from_slice(&[(read.as_raw_fd(), Event::readable(2), PollMode::Oneshot)])
+        .unwrap();
+    (&write).write_all(&[1]).unwrap();
+    events.clear();
+    poller
+        .wait(&mut events, Some(Duration::from_secs(1)))
+        .unwrap();
+    assert_eq!(events, [Event::readable(2)]);
+    poller.delete(&read).unwrap();
+}
+
+#[test]
+fn qos_class() {
+    let poller = PollerBuilder::new()
+        .qos_class(QosClass::Utility)
+        .build()
+        .unwrap();
+    let (read, write) = common::tcp_pair().unwrap();
+    poller.add(&read, Event::readable(1)).unwrap();
+    (&write).write_all(&[1]).unwrap();
+
+    let mut events = Vec::new();
+    poller
+        .wait(&mut events, Some(Duration::from_secs(1)))
+        .unwrap();
+    assert_eq!(events, [Event::readable(1)]);
+
+    poller.delete(&read).unwrap();
+}
+
+#[test]
+fn qos_class_waits() {
+    use polling::{PollMode, Source};
+    use std::sync::Arc;
+    use std::thread;
+
+    let poller = Arc::new(
+        PollerBuilder::new()
+            .qos_class(QosClass::Utility)
+            .deferred_changes(true)
+            .build()
+            .unwrap(),
+    );
+    let (read, write) = common::tcp_pair().unwrap();
+    poller
+        .add_many_from_slice(&[((&read).raw(), Event::readable(1), PollMode::Oneshot)])
+        .unwrap();
+
+    // Waits without a timeout and without blocking are both submitted at the class.
+    let mut events = Vec::new();
+    poller
+        .wait(&mut events, Some(Duration::from_secs(0)))
+        .unwrap();
+    assert!(events.is_empty());
+
+    let notifier = thread::spawn({
+        let poller = poller.clone();
+        move || {
+            thread::sleep(Duration::from_millis(100));
+            poller.notify()
+        }
+    });
+    poller.wait(&mut events, None).unwrap();
+    assert!(events.is_empty());
+    notifier.join().unwrap().unwrap();
+
+    // Deferred changes are submitted with the wait.
+    (&write).write_all(&[1]).unwrap();
+    poller.modify(&read, Event::readable(2)).unwrap();
+    poller.wait(&mut events, None).unwrap();
+    assert_eq!(events, [Event::readable(2)]);
+
+    poller.delete(&read).unwrap();
+}
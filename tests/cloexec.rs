@@ -0,0 +1,68 @@
+//! Tests that the file descriptors created by the poller have `CLOEXEC` set.
+
+#![cfg(unix)]
+
+use std::collections::HashSet;
+use std::os::unix::io::RawFd;
+use std::os::unix::net::UnixStream;
+use std::time::Duration;
+
+use polling::{Event, Poller};
+
+// This is the only test in this file, so that no other file descriptors are created concurrently.
+#[test]
+fn internal_fds_have_cloexec() {
+    let before = open_fds();
+    let poller = Poller::new().unwrap();
+    check_new_fds(&before);
+
+    // Adding a source and waiting doesn't create file descriptors without `CLOEXEC` either.
+    let (a, b) = UnixStream::pair().unwrap();
+    let before = open_fds();
+    poller.add(&a, Event::readable(1)).unwrap();
+    poller.notify().unwrap();
+    poller
+        .wait(&mut Vec::new(), Some(Duration::from_secs(1)))
+        .unwrap();
+    check_new_fds(&before);
+
+    poller.delete(&a).unwrap();
+    drop((a, b));
+}
+
+/// Asserts that every file descriptor opened since `before` has `CLOEXEC` set.
+fn check_new_fds(before: &HashSet<RawFd>) {
+    let new = open_fds()
+        .into_iter()
+        .filter(|fd| !before.contains(fd))
+        .collect::<Vec<_>>();
+
+    for fd in new {
+        let flags = unsafe { libc::fcntl(fd, libc::F_GETFD) };
+        assert!(flags != -1, "fd {} was closed", fd);
+        assert!(flags & libc::FD_CLOEXEC != 0, "fd {} lacks CLOEXEC", fd);
+    }
+}
+
+/// Lists the open file descriptors of this process.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn open_fds() -> HashSet<RawFd> {
+    // The directory itself is an open file descriptor while it is being read, so collect the
+    // entries first and then check which ones are still open.
+    let fds = std::fs::read_dir("/proc/self/fd")
+        .unwrap()
+        .filter_map(|entry| entry.ok()?.file_name().to_str()?.parse().ok())
+        .collect::<Vec<RawFd>>();
+    fds.into_iter().filter(|&fd| is_open(fd)).collect()
+}
+
+/// Lists the open file descriptors of this process.
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+fn open_fds() -> HashSet<RawFd> {
+    let max = unsafe { libc::getdtablesize() };
+    (0..max).filter(|&fd| is_open(fd)).collect()
+}
+
+fn is_open(fd: RawFd) -> bool {
+    unsafe { libc::fcntl(fd, libc::F_GETFD) != -1 }
+}
@@ -0,0 +1,60 @@
+//! Tests for `Poller::try_clone()`.
+
+#![cfg(any(target_os = "linux", target_os = "android"))]
+#![cfg(not(polling_test_poll_backend))]
+
+mod common;
+
+use std::io::Write;
+use std::time::Duration;
+
+use polling::{Event, Poller, PollerBuilder};
+
+#[test]
+fn shared_interest() {
+    let poller = PollerBuilder::new().track_sources(true).build().unwrap();
+    let clone = poller.try_clone().unwrap();
+    let (read, mut write) = common::tcp_pair().unwrap();
+
+    // A source added to one poller is reported by the other.
+    poller.add(&read, Event::readable(1)).unwrap();
+    write.write_all(&[1]).unwrap();
+
+    let mut events = Vec::new();
+    clone
+        .wait(&mut events, Some(Duration::from_secs(1)))
+        .unwrap();
+    assert_eq!(events, [Event::readable(1)]);
+
+    clone.set_interest(&read, true, false).unwrap();
+    clone.delete(&read).unwrap();
+}
+
+#[test]
+fn independent_notify() {
+    let poller = Poller::new().unwrap();
+    let clone = poller.try_clone().unwrap();
+
+    // Both pollers can be notified repeatedly.
+    let mut events = Vec::new();
+    for _ in 0..3 {
+        for poller in &[&poller, &clone] {
+            poller.notify().unwrap();
+            poller
+                .wait(&mut events, Some(Duration::from_secs(1)))
+                .unwrap();
+            assert!(events.is_empty());
+        }
+    }
+
+    // Timeouts still work after the timerfd is no longer used.
+    let start = std::time::Instant::now();
+    poller
+        .wait(&mut events, Some(Duration::from_millis(50)))
+        .unwrap();
+    assert!(start.elapsed() >= Duration::from_millis(50));
+    drop(poller);
+
+    clone.notify().unwrap();
+    clone.wait(&mut events, None).unwrap();
+}
@@ -0,0 +1,28 @@
+//! Helpers shared by the integration tests.
+
+#![allow(dead_code)]
+
+use std::io;
+use std::net::{TcpListener, TcpStream};
+
+/// Checks whether an error is the one returned for unsupported operations.
+///
+/// This is `ErrorKind::Unsupported`, or `ErrorKind::Other` on Rust versions that don't have it.
+pub fn is_unsupported(err: &io::Error) -> bool {
+    #[cfg(not(polling_no_unsupported_error_kind))]
+    {
+        err.kind() == io::ErrorKind::Unsupported
+    }
+    #[cfg(polling_no_unsupported_error_kind)]
+    {
+        err.kind() == io::ErrorKind::Other
+    }
+}
+
+/// Creates a pair of connected TCP sockets.
+pub fn tcp_pair() -> io::Result<(TcpStream, TcpStream)> {
+    let listener = TcpListener::bind("127.0.0.1:0")?;
+    let a = TcpStream::connect(listener.local_addr()?)?;
+    let (b, _) = listener.accept()?;
+    Ok((a, b))
+}
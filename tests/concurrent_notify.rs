@@ -0,0 +1,47 @@
+//! Stress test for calling `notify()` from many threads at once.
+
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use polling::Poller;
+
+const THREADS: usize = 16;
+const NOTIFICATIONS: usize = 1000;
+
+#[test]
+fn many_notifiers() {
+    let poller = Arc::new(Poller::new().unwrap());
+    let mut events = Vec::new();
+
+    let handles = (0..THREADS)
+        .map(|_| {
+            let poller = poller.clone();
+            thread::spawn(move || {
+                for _ in 0..NOTIFICATIONS {
+                    poller.notify().unwrap();
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+
+    // The main thread wakes up while the other threads are notifying.
+    poller.wait(&mut events, None).unwrap();
+    assert!(events.is_empty());
+
+    for handle in handles {
+        handle.join().unwrap();
+    }
+
+    // A notification may still be pending from the threads that finished after the wait above.
+    poller
+        .wait(&mut events, Some(Duration::from_secs(0)))
+        .unwrap();
+
+    // There are no leftover notifications.
+    let timeout = Duration::from_millis(100);
+    let start = Instant::now();
+    poller.wait(&mut events, Some(timeout)).unwrap();
+    assert!(start.elapsed() >= timeout);
+    assert!(events.is_empty());
+}
@@ -0,0 +1,129 @@
+//! Stress test for adding and deleting sources from many threads while another thread waits.
+
+mod common;
+
+use std::collections::HashSet;
+use std::io::Write;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use polling::{Event, PollMode, Poller};
+
+const THREADS: usize = 8;
+const ITERATIONS: usize = 10_000;
+
+/// Keys used for the final registration of each thread.
+const FINAL_KEY: usize = 1000;
+
+// This is the only test in this file, so that the open file descriptors can be counted.
+#[test]
+fn add_delete_while_waiting() {
+    let fds_before = open_fds();
+
+    let poller = Arc::new(Poller::new().unwrap());
+    let stop = Arc::new(AtomicBool::new(false));
+    let seen = Arc::new(Mutex::new(HashSet::new()));
+
+    let waiter = thread::spawn({
+        let poller = poller.clone();
+        let stop = stop.clone();
+        let seen = seen.clone();
+        move || {
+            let mut events = Vec::new();
+            while !stop.load(Ordering::SeqCst) {
+                events.clear();
+                poller
+                    .wait(&mut events, Some(Duration::from_millis(10)))
+                    .unwrap();
+                seen.lock().unwrap().extend(events.iter().map(|ev| ev.key));
+            }
+        }
+    });
+
+    let modes = supported_modes(&poller);
+    let workers = (0..THREADS)
+        .map(|i| {
+            let poller = poller.clone();
+            let modes = modes.clone();
+            thread::spawn(move || {
+                // The source is always readable.
+                let (read, mut write) = common::tcp_pair().unwrap();
+                write.write_all(&[1]).unwrap();
+
+                let rng = fastrand::Rng::with_seed(i as u64);
+                for _ in 0..ITERATIONS {
+                    let mode = modes[rng.usize(..modes.len())];
+                    poller
+                        .add_with_mode(&read, Event::readable(i), mode)
+                        .unwrap();
+                    poller.delete(&read).unwrap();
+                }
+
+                // Events for a source that stays registered still arrive.
+                poller.add(&read, Event::readable(FINAL_KEY + i)).unwrap();
+                poller.notify().unwrap();
+                (read, write)
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let sockets = workers
+        .into_iter()
+        .map(|worker| worker.join().unwrap())
+        .collect::<Vec<_>>();
+
+    let deadline = Instant::now() + Duration::from_secs(10);
+    loop {
+        let missing = (0..THREADS)
+            .filter(|i| !seen.lock().unwrap().contains(&(FINAL_KEY + i)))
+            .count();
+        if missing == 0 {
+            break;
+        }
+        assert!(
+            Instant::now() < deadline,
+            "{} events did not arrive",
+            missing
+        );
+        thread::sleep(Duration::from_millis(10));
+    }
+
+    stop.store(true, Ordering::SeqCst);
+    poller.notify().unwrap();
+    waiter.join().unwrap();
+
+    for (read, _) in &sockets {
+        poller.delete(read).unwrap();
+    }
+    drop(sockets);
+    drop(poller);
+
+    // No file descriptors were leaked.
+    assert_eq!(open_fds(), fds_before);
+}
+
+fn supported_modes(poller: &Poller) -> Vec<PollMode> {
+    let mut modes = vec![PollMode::Oneshot];
+    if poller.supports_level() {
+        modes.push(PollMode::Level);
+    }
+    if poller.supports_edge() {
+        modes.push(PollMode::Edge);
+        modes.push(PollMode::EdgeOneshot);
+    }
+    modes
+}
+
+/// Counts the open file descriptors of this process, where this is possible.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn open_fds() -> Option<usize> {
+    Some(std::fs::read_dir("/proc/self/fd").ok()?.count())
+}
+
+/// Counts the open file descriptors of this process, where this is possible.
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+fn open_fds() -> Option<usize> {
+    None
+}
@@ -0,0 +1,141 @@
+//! Tests for edge-triggered mode.
+//!
+//! These are skipped on backends that don't support edge-triggered mode.
+
+mod common;
+
+use std::io::prelude::*;
+use std::net::TcpStream;
+use std::thread;
+use std::time::Duration;
+
+use polling::{Event, PollMode, Poller};
+
+const KEY: usize = 1;
+
+#[test]
+fn one_event_per_write() {
+    let (poller, (reader, mut writer)) = match setup(PollMode::Edge) {
+        Some(setup) => setup,
+        None => return,
+    };
+
+    writer.write_all(&[1, 2, 3]).unwrap();
+    assert_eq!(
+        wait(&poller, Duration::from_secs(10)),
+        [Event::readable(KEY)]
+    );
+
+    // The data was not read, but the event doesn't fire again without a new edge.
+    assert_eq!(wait(&poller, Duration::from_millis(100)), []);
+
+    poller.delete(&reader).unwrap();
+}
+
+#[test]
+fn writes_between_waits() {
+    let (poller, (mut reader, mut writer)) = match setup(PollMode::Edge) {
+        Some(setup) => setup,
+        None => return,
+    };
+
+    writer.write_all(&[1]).unwrap();
+    assert_eq!(
+        wait(&poller, Duration::from_secs(10)),
+        [Event::readable(KEY)]
+    );
+    reader.read_exact(&mut [0; 1]).unwrap();
+
+    // Data written between two calls to `wait()` produces a single event.
+    writer.write_all(&[2]).unwrap();
+    writer.write_all(&[3]).unwrap();
+    settle();
+    assert_eq!(
+        wait(&poller, Duration::from_secs(10)),
+        [Event::readable(KEY)]
+    );
+    assert_eq!(wait(&poller, Duration::from_millis(100)), []);
+
+    poller.delete(&reader).unwrap();
+}
+
+#[test]
+fn edge_oneshot_requires_rearm() {
+    let (poller, (mut reader, mut writer)) = match setup(PollMode::EdgeOneshot) {
+        Some(setup) => setup,
+        None => return,
+    };
+
+    writer.write_all(&[1]).unwrap();
+    assert_eq!(
+        wait(&poller, Duration::from_secs(10)),
+        [Event::readable(KEY)]
+    );
+    reader.read_exact(&mut [0; 1]).unwrap();
+
+    // New edges are not reported until the source is re-armed.
+    writer.write_all(&[2]).unwrap();
+    assert_eq!(wait(&poller, Duration::from_millis(100)), []);
+
+    poller
+        .modify_with_mode(&reader, Event::readable(KEY), PollMode::EdgeOneshot)
+        .unwrap();
+    writer.write_all(&[3]).unwrap();
+    assert_eq!(
+        wait(&poller, Duration::from_secs(10)),
+        [Event::readable(KEY)]
+    );
+
+    poller.delete(&reader).unwrap();
+}
+
+#[test]
+fn fast_producer() {
+    let (poller, (mut reader, mut writer)) = match setup(PollMode::Edge) {
+        Some(setup) => setup,
+        None => return,
+    };
+
+    for round in 0..3u8 {
+        // Several writes before the consumer waits only produce one event.
+        for i in 0..10 {
+            writer.write_all(&[round * 10 + i]).unwrap();
+        }
+        settle();
+        assert_eq!(
+            wait(&poller, Duration::from_secs(10)),
+            [Event::readable(KEY)]
+        );
+        assert_eq!(wait(&poller, Duration::from_millis(50)), []);
+
+        // Drain the socket before the next round.
+        reader.read_exact(&mut [0; 10]).unwrap();
+    }
+
+    poller.delete(&reader).unwrap();
+}
+
+/// Creates a poller with a reader registered in `mode`, or `None` if the mode is not supported.
+fn setup(mode: PollMode) -> Option<(Poller, (TcpStream, TcpStream))> {
+    let poller = Poller::new().unwrap();
+    if !poller.supports_edge() {
+        return None;
+    }
+
+    let (reader, writer) = common::tcp_pair().unwrap();
+    poller
+        .add_with_mode(&reader, Event::readable(KEY), mode)
+        .unwrap();
+    Some((poller, (reader, writer)))
+}
+
+fn wait(poller: &Poller, timeout: Duration) -> Vec<Event> {
+    let mut events = Vec::new();
+    poller.wait(&mut events, Some(timeout)).unwrap();
+    events
+}
+
+/// Gives the written data time to arrive at the reader.
+fn settle() {
+    thread::sleep(Duration::from_millis(50));
+}
@@ -0,0 +1,88 @@
+#![cfg(unix)]
+
+use polling::{Events, PollerBuilder};
+use std::io;
+use std::os::raw::c_int;
+use std::thread;
+use std::time::{Duration, Instant};
+
+extern "C" fn on_signal(_: c_int) {}
+
+/// Interrupts the calling thread with `SIGUSR1` after `delay`.
+fn interrupt_after(delay: Duration) -> thread::JoinHandle<()> {
+    unsafe {
+        let mut action: libc::sigaction = std::mem::zeroed();
+        action.sa_sigaction = on_signal as extern "C" fn(c_int) as libc::sighandler_t;
+        libc::sigemptyset(&mut action.sa_mask);
+        assert_eq!(
+            libc::sigaction(libc::SIGUSR1, &action, std::ptr::null_mut()),
+            0
+        );
+    }
+
+    let target = unsafe { libc::pthread_self() } as usize;
+    thread::spawn(move || {
+        thread::sleep(delay);
+        unsafe {
+            libc::pthread_kill(target as libc::pthread_t, libc::SIGUSR1);
+        }
+    })
+}
+
+#[test]
+fn no_retry() {
+    let poller = PollerBuilder::new().retry_on_eintr(false).build().unwrap();
+    let mut events = Events::new();
+
+    let interrupter = interrupt_after(Duration::from_millis(100));
+    let err = poller
+        .wait_events(&mut events, Some(Duration::from_secs(10)))
+        .unwrap_err();
+    interrupter.join().unwrap();
+
+    assert_eq!(err.kind(), io::ErrorKind::Interrupted);
+}
+
+#[test]
+fn retry_with_remaining_timeout() {
+    let poller = PollerBuilder::new().retry_on_eintr(true).build().unwrap();
+    let mut events = Events::new();
+
+    let start = Instant::now();
+    let interrupter = interrupt_after(Duration::from_millis(100));
+    let n = poller
+        .wait_events(&mut events, Some(Duration::from_millis(500)))
+        .unwrap();
+    interrupter.join().unwrap();
+
+    assert_eq!(n, 0);
+    let elapsed = start.elapsed();
+    assert!(elapsed >= Duration::from_millis(500));
+    assert!(elapsed < Duration::from_millis(1500));
+}
+
+#[test]
+fn set_retry_on_eintr() {
+    let poller = PollerBuilder::new().retry_on_eintr(true).build().unwrap();
+    let mut events = Events::new();
+
+    // Turning retries off applies to the next wait.
+    poller.set_retry_on_eintr(false);
+    let interrupter = interrupt_after(Duration::from_millis(100));
+    let err = poller
+        .wait_events(&mut events, Some(Duration::from_secs(10)))
+        .unwrap_err();
+    interrupter.join().unwrap();
+    assert_eq!(err.kind(), io::ErrorKind::Interrupted);
+
+    // And turning them back on restores the deadline accounting.
+    poller.set_retry_on_eintr(true);
+    let start = Instant::now();
+    let interrupter = interrupt_after(Duration::from_millis(100));
+    let n = poller
+        .wait_events(&mut events, Some(Duration::from_millis(300)))
+        .unwrap();
+    interrupter.join().unwrap();
+    assert_eq!(n, 0);
+    assert!(start.elapsed() >= Duration::from_millis(300));
+}
@@ -0,0 +1,70 @@
+//! Tests for `PollerError`.
+
+use std::error::Error;
+use std::io;
+use std::net::TcpListener;
+
+use polling::{Event, PollMode, Poller, PollerError};
+
+#[test]
+fn register_error() {
+    let poller = Poller::new().unwrap();
+    let socket = TcpListener::bind("127.0.0.1:0").unwrap();
+
+    let err = poller
+        .add_detailed(
+            &socket,
+            Event::readable(polling::NOTIFY_KEY),
+            PollMode::Oneshot,
+        )
+        .unwrap_err();
+    match &err {
+        PollerError::Register { fd, source } => {
+            assert_eq!(*fd, raw(&socket));
+            assert_eq!(source.kind(), io::ErrorKind::InvalidInput);
+        }
+        err => panic!("unexpected error: {:?}", err),
+    }
+
+    assert!(err.source().is_some());
+    let err: io::Error = err.into();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+}
+
+#[test]
+fn deregister_error() {
+    let poller = Poller::new().unwrap();
+    let socket = TcpListener::bind("127.0.0.1:0").unwrap();
+
+    // The socket was never added.
+    let err = poller.delete_detailed(&socket).unwrap_err();
+    assert!(matches!(err, PollerError::Deregister { .. }));
+    assert!(err.to_string().starts_with("failed to deregister"));
+}
+
+#[test]
+fn success() {
+    let poller = Poller::new().unwrap();
+    let socket = TcpListener::bind("127.0.0.1:0").unwrap();
+    socket.set_nonblocking(true).unwrap();
+
+    poller
+        .add_detailed(&socket, Event::readable(1), PollMode::Oneshot)
+        .unwrap();
+    poller
+        .modify_detailed(&socket, Event::none(1), PollMode::Oneshot)
+        .unwrap();
+    poller.notify_detailed().unwrap();
+    poller.wait_detailed(&mut Vec::new(), None).unwrap();
+    poller.delete_detailed(&socket).unwrap();
+}
+
+#[cfg(unix)]
+fn raw(socket: &TcpListener) -> polling::RawSource {
+    std::os::unix::io::AsRawFd::as_raw_fd(socket)
+}
+
+#[cfg(windows)]
+fn raw(socket: &TcpListener) -> polling::RawSource {
+    std::os::windows::io::AsRawSocket::as_raw_socket(socket)
+}
@@ -0,0 +1,70 @@
+//! Tests for `WatchGuard`.
+
+mod common;
+
+use std::io::Write;
+use std::time::Duration;
+
+use polling::{Event, PollMode, Poller};
+
+#[test]
+fn delete_on_drop() {
+    let poller = Poller::new().unwrap();
+    let (read, _write) = common::tcp_pair().unwrap();
+
+    let guard = poller
+        .add_watching(&read, Event::readable(1), PollMode::Oneshot)
+        .unwrap();
+    assert!(guard.peer_addr().is_ok());
+    drop(guard);
+
+    // The source was deleted, so it can be added again.
+    poller.add(&read, Event::readable(1)).unwrap();
+    poller.delete(&read).unwrap();
+}
+
+#[test]
+fn disarm() {
+    let poller = Poller::new().unwrap();
+    let (read, mut write) = common::tcp_pair().unwrap();
+
+    let guard = poller
+        .add_watching(&read, Event::readable(1), PollMode::Oneshot)
+        .unwrap();
+    let raw = guard.disarm();
+
+    // The source is still registered.
+    let mut events = Vec::new();
+    write.write_all(&[1]).unwrap();
+    poller
+        .wait(&mut events, Some(Duration::from_secs(1)))
+        .unwrap();
+    assert_eq!(events, [Event::readable(1)]);
+
+    poller.delete(raw).unwrap();
+}
+
+#[test]
+fn registration() {
+    let poller = Poller::new().unwrap();
+    let (read, mut write) = common::tcp_pair().unwrap();
+
+    let registration = poller
+        .register(&read, Event::none(1), PollMode::Oneshot)
+        .unwrap();
+    registration
+        .modify(Event::readable(2), PollMode::Oneshot)
+        .unwrap();
+
+    let mut events = Vec::new();
+    write.write_all(&[1]).unwrap();
+    poller
+        .wait(&mut events, Some(Duration::from_secs(1)))
+        .unwrap();
+    assert_eq!(events, [Event::readable(2)]);
+    drop(registration);
+
+    // The source was deleted, so it can be added again.
+    poller.add(&read, Event::readable(1)).unwrap();
+    poller.delete(&read).unwrap();
+}
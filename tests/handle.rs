@@ -0,0 +1,73 @@
+//! Tests for `PollerHandle`.
+
+mod common;
+
+use std::io::{self, Write};
+use std::thread;
+use std::time::Duration;
+
+use polling::{Event, Poller, PollerHandle};
+
+#[test]
+fn register_from_another_thread() {
+    let poller = Poller::new().unwrap();
+    let handle = poller.handle();
+    let (read, mut write) = common::tcp_pair().unwrap();
+
+    let read = thread::spawn(move || {
+        handle.add(&read, Event::none(1)).unwrap();
+        handle.modify(&read, Event::readable(1)).unwrap();
+        read
+    })
+    .join()
+    .unwrap();
+
+    write.write_all(&[1]).unwrap();
+    let mut events = Vec::new();
+    poller
+        .wait(&mut events, Some(Duration::from_secs(1)))
+        .unwrap();
+    assert_eq!(events, [Event::readable(1)]);
+
+    // The handle and the poller share their sources.
+    poller.handle().delete(&read).unwrap();
+    assert!(poller.delete(&read).is_err());
+}
+
+#[test]
+fn notify() {
+    let poller = Poller::new().unwrap();
+    let handle = poller.handle();
+    assert!(handle.add(0, Event::readable(polling::NOTIFY_KEY)).is_err());
+
+    let mut events = Vec::new();
+    let waiter = thread::spawn(move || {
+        poller.wait(&mut events, None).unwrap();
+        events.is_empty()
+    });
+    thread::sleep(Duration::from_millis(100));
+    handle.notify().unwrap();
+    assert!(waiter.join().unwrap());
+
+    // The handle doesn't keep the poller alive.
+    let err = handle.notify().unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::NotConnected);
+    fn is_send_sync<T: Send + Sync + Clone>(_: &T) {}
+    is_send_sync::<PollerHandle>(&handle);
+}
+
+#[test]
+fn wake() {
+    let poller = Poller::new().unwrap();
+    let handle = poller.handle();
+    assert!(handle.wake(polling::NOTIFY_KEY).is_err());
+
+    let waiter = thread::spawn(move || {
+        let mut events = Vec::new();
+        poller.wait(&mut events, None).unwrap();
+        events
+    });
+    thread::sleep(Duration::from_millis(100));
+    handle.wake(3).unwrap();
+    assert_eq!(waiter.join().unwrap(), [Event::readable(3)]);
+}
@@ -0,0 +1,56 @@
+//! Tests for `Poller::check_invariants()`.
+
+use std::io::{self, Write};
+use std::net::{TcpListener, TcpStream};
+use std::time::Duration;
+
+use polling::{Event, Poller, PollerBuilder};
+
+fn check_around_registration(poller: &Poller) -> io::Result<()> {
+    let listener = TcpListener::bind("127.0.0.1:0")?;
+    let mut writer = TcpStream::connect(listener.local_addr()?)?;
+    let (reader, _) = listener.accept()?;
+    reader.set_nonblocking(true)?;
+    poller.check_invariants()?;
+
+    poller.add(&reader, Event::readable(1))?;
+    poller.check_invariants()?;
+
+    writer.write_all(&[1])?;
+    let mut events = Vec::new();
+    poller.wait(&mut events, Some(Duration::from_secs(1)))?;
+    assert_eq!(events, [Event::readable(1)]);
+    poller.check_invariants()?;
+
+    poller.notify()?;
+    poller.wait(&mut events, Some(Duration::from_secs(1)))?;
+    poller.check_invariants()?;
+
+    poller.delete(&reader)?;
+    poller.check_invariants()?;
+    Ok(())
+}
+
+#[test]
+fn untracked() -> io::Result<()> {
+    let poller = Poller::new()?;
+    check_around_registration(&poller)
+}
+
+#[test]
+fn tracked() -> io::Result<()> {
+    let poller = PollerBuilder::new().track_sources(true).build()?;
+    check_around_registration(&poller)
+}
+
+#[cfg(all(
+    any(target_os = "linux", target_os = "android"),
+    not(polling_test_poll_backend)
+))]
+#[test]
+fn cloned() -> io::Result<()> {
+    let poller = PollerBuilder::new().track_sources(true).build()?;
+    let clone = poller.try_clone()?;
+    check_around_registration(&clone)?;
+    poller.check_invariants()
+}
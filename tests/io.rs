@@ -1,6 +1,9 @@
-use polling::{Event, Poller};
+mod common;
+
+use polling::{Event, Events, Interest, PollMode, Poller, PollerBuilder};
 use std::io::{self, Write};
 use std::net::{TcpListener, TcpStream};
+use std::thread;
 use std::time::Duration;
 
 #[test]
@@ -30,6 +33,982 @@ fn basic_io() {
     assert_eq!(&*events, &[Event::readable(1)]);
 }
 
+#[test]
+fn readable_and_writable() {
+    let poller = Poller::new().unwrap();
+    let (read, mut write) = tcp_pair().unwrap();
+    poller.add(&read, Event::all(1)).unwrap();
+
+    // The socket is writable right away, and readable after the write.
+    write.write_all(&[1]).unwrap();
+    thread::sleep(Duration::from_millis(100));
+
+    let mut events = vec![];
+    poller
+        .wait(&mut events, Some(Duration::from_secs(1)))
+        .unwrap();
+    assert_eq!(&*events, &[Event::all(1)]);
+
+    poller.delete(&read).unwrap();
+}
+
+#[test]
+fn hangup() {
+    use std::net::Shutdown;
+
+    let poller = Poller::new().unwrap();
+    let (read, write) = tcp_pair().unwrap();
+    poller.add(&read, Event::readable(1)).unwrap();
+
+    // Both directions of the connection are shut down.
+    drop(write);
+    read.shutdown(Shutdown::Write).unwrap();
+
+    let mut events = Events::new();
+    poller
+        .wait_events(&mut events, Some(Duration::from_secs(1)))
+        .unwrap();
+    let events = events.iter_details().collect::<Vec<_>>();
+    assert_eq!(events.len(), 1);
+    assert!(events[0].0.readable);
+    assert!(events[0].1.is_hup());
+    assert!(!events[0].1.is_pri());
+
+    poller.delete(&read).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn priority() {
+    use std::os::unix::io::AsRawFd;
+
+    let poller = Poller::new().unwrap();
+    let (read, write) = tcp_pair().unwrap();
+    poller
+        .add_with_interest(&read, 1, Interest::PRIORITY, PollMode::Oneshot)
+        .unwrap();
+
+    // Send a byte of out-of-band data.
+    let sent = unsafe { libc::send(write.as_raw_fd(), b"!".as_ptr().cast(), 1, libc::MSG_OOB) };
+    assert_eq!(sent, 1);
+
+    let mut events = Events::new();
+    poller
+        .wait_events(&mut events, Some(Duration::from_secs(1)))
+        .unwrap();
+    let events = events.iter_details().collect::<Vec<_>>();
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].0.key, 1);
+    assert!(events[0].1.is_pri());
+
+    poller.delete(&read).unwrap();
+}
+
+#[test]
+fn hup_interest() {
+    let poller = Poller::new().unwrap();
+    let (read, write) = tcp_pair().unwrap();
+    poller
+        .add_with_interest(&read, 1, Interest::HUP, PollMode::Oneshot)
+        .unwrap();
+
+    write.shutdown(std::net::Shutdown::Write).unwrap();
+    let mut events = Events::new();
+    poller
+        .wait_events(&mut events, Some(Duration::from_millis(500)))
+        .unwrap();
+    let events = events.iter_details().collect::<Vec<_>>();
+    if polling::BACKEND == "epoll" {
+        assert_eq!(events.len(), 1);
+        assert!(events[0].1.is_hup());
+    }
+
+    poller.delete(&read).unwrap();
+}
+
+#[test]
+fn eof() {
+    for &writable_on_eof in &[true, false] {
+        let poller = PollerBuilder::new()
+            .writable_on_eof(writable_on_eof)
+            .build()
+            .unwrap();
+        let (read, write) = tcp_pair().unwrap();
+        poller
+            .add_with_interest(
+                &read,
+                1,
+                Interest::READABLE | Interest::HUP,
+                PollMode::Oneshot,
+            )
+            .unwrap();
+
+        write.shutdown(std::net::Shutdown::Write).unwrap();
+        let mut events = Events::new();
+        poller
+            .wait_events(&mut events, Some(Duration::from_secs(1)))
+            .unwrap();
+        let events = events.iter_details().collect::<Vec<_>>();
+        assert_eq!(events.len(), 1);
+        assert!(events[0].0.readable);
+        if polling::BACKEND == "epoll" || polling::BACKEND == "kqueue" {
+            assert!(events[0].1.is_eof());
+        }
+        if polling::BACKEND == "kqueue" {
+            assert_eq!(events[0].0.writable, writable_on_eof);
+        }
+
+        poller.delete(&read).unwrap();
+    }
+}
+
+#[test]
+fn half_close() {
+    let poller = Poller::new().unwrap();
+    let (read, write) = tcp_pair().unwrap();
+    poller
+        .add_with_interest(&read, 1, Interest::HUP, PollMode::Oneshot)
+        .unwrap();
+
+    // The peer closing its writing half wakes up a source that isn't interested in data.
+    write.shutdown(std::net::Shutdown::Write).unwrap();
+    let mut events = Events::new();
+    poller
+        .wait_events(&mut events, Some(Duration::from_secs(1)))
+        .unwrap();
+    let events = events.iter_details().collect::<Vec<_>>();
+
+    // `poll()` only reports a half-closed connection on Linux and Android, and event ports only
+    // on illumos.
+    let reported = match polling::BACKEND {
+        "poll" => cfg!(any(target_os = "linux", target_os = "android")),
+        "port" => cfg!(target_os = "illumos"),
+        _ => true,
+    };
+    if reported {
+        assert_eq!(events.len(), 1);
+        assert!(events[0].0.readable);
+        assert!(events[0].1.is_hup());
+        assert!(events[0].1.is_eof());
+        assert!(!events[0].1.is_write_closed());
+    }
+
+    poller.delete(&read).unwrap();
+}
+
+#[test]
+fn write_closed() {
+    let poller = Poller::new().unwrap();
+    let (mut stream, peer) = tcp_pair().unwrap();
+    stream.set_nonblocking(true).unwrap();
+    drop(peer);
+
+    // Writing to a closed connection makes the peer reset it.
+    let _ = stream.write_all(&[1; 16]);
+    thread::sleep(Duration::from_millis(100));
+    poller.add(&stream, Event::writable(1)).unwrap();
+
+    let mut events = Events::new();
+    poller
+        .wait_events(&mut events, Some(Duration::from_secs(1)))
+        .unwrap();
+    let events = events.iter_details().collect::<Vec<_>>();
+    assert_eq!(events.len(), 1);
+    if cfg!(unix) {
+        assert!(events[0].1.is_write_closed());
+    }
+
+    poller.delete(&stream).unwrap();
+}
+
+#[test]
+fn low_water_mark() {
+    let poller = Poller::new().unwrap();
+    let (read, mut write) = tcp_pair().unwrap();
+    if let Err(err) = poller.set_low_water_mark(&read, 4) {
+        assert!(common::is_unsupported(&err));
+        assert_eq!(polling::BACKEND, "iocp");
+        return;
+    }
+    poller
+        .add_with_mode(&read, Event::readable(1), PollMode::Level)
+        .unwrap();
+
+    // Fewer bytes than the low-water mark are not reported.
+    write.write_all(&[1, 2]).unwrap();
+    let mut events = Vec::new();
+    poller
+        .wait(&mut events, Some(Duration::from_millis(100)))
+        .unwrap();
+    assert!(events.is_empty());
+
+    write.write_all(&[3, 4]).unwrap();
+    poller
+        .wait(&mut events, Some(Duration::from_secs(1)))
+        .unwrap();
+    assert_eq!(events, [Event::readable(1)]);
+
+    poller.delete(&read).unwrap();
+}
+
+#[test]
+fn pointer_key() {
+    struct Connection {
+        id: u32,
+    }
+
+    let poller = Poller::new().unwrap();
+    let (read, mut write) = tcp_pair().unwrap();
+    let conn = Box::new(Connection { id: 42 });
+    let key = &*conn as *const Connection as usize;
+    poller.add(&read, Event::readable(key)).unwrap();
+
+    write.write_all(&[1]).unwrap();
+    let mut events = Vec::new();
+    poller
+        .wait(&mut events, Some(Duration::from_secs(1)))
+        .unwrap();
+    assert_eq!(events, [Event::readable(key)]);
+    let conn = unsafe { &*(events[0].key as *const Connection) };
+    assert_eq!(conn.id, 42);
+
+    poller.delete(&read).unwrap();
+}
+
+#[test]
+fn events_list() {
+    let poller = Poller::new().unwrap();
+    let (read, mut write) = tcp_pair().unwrap();
+    poller.add(&read, Event::readable(1)).unwrap();
+
+    // Nothing should be available at first.
+    let mut events = Events::new();
+    assert_eq!(
+        poller
+            .wait_events(&mut events, Some(Duration::from_secs(0)))
+            .unwrap(),
+        0
+    );
+    assert_eq!(events.iter().count(), 0);
+    assert_eq!(events.len(), 0);
+    assert!(events.is_empty());
+
+    // After a write, the event should be available now.
+    write.write_all(&[1]).unwrap();
+    assert_eq!(
+        poller
+            .wait_events(&mut events, Some(Duration::from_secs(1)))
+            .unwrap(),
+        1
+    );
+    assert_eq!(events.iter().collect::<Vec<_>>(), [Event::readable(1)]);
+    assert_eq!(events.len(), 1);
+    assert!(!events.is_empty());
+    assert!(!events.as_raw_slice().is_empty());
+    assert_eq!(events.iter_keys().collect::<Vec<_>>(), [1]);
+    assert_eq!(events.find(1), Some(Event::readable(1)));
+    assert_eq!(events.find(2), None);
+}
+
+#[test]
+fn events_capacity() {
+    let poller = Poller::new().unwrap();
+    let pairs = (0..3).map(|_| tcp_pair().unwrap()).collect::<Vec<_>>();
+    for (i, (read, write)) in pairs.iter().enumerate() {
+        poller.add(read, Event::readable(i)).unwrap();
+        (&*write).write_all(&[1]).unwrap();
+    }
+
+    // Only two events fit, the third one is reported by the next wait.
+    let mut events = Events::with_capacity(2);
+    assert!(events.capacity() >= 2);
+    let mut keys = Vec::new();
+    while keys.len() < 3 {
+        let n = poller
+            .wait_events(&mut events, Some(Duration::from_secs(1)))
+            .unwrap();
+        assert!(n >= 1 && n <= events.capacity());
+        keys.extend(events.iter_keys());
+    }
+    keys.sort_unstable();
+    assert_eq!(keys, [0, 1, 2]);
+
+    events.clear();
+    assert_eq!(events.iter().count(), 0);
+
+    for (read, _) in &pairs {
+        poller.delete(read).unwrap();
+    }
+}
+
+#[test]
+fn dedup() {
+    let poller = Poller::new().unwrap();
+    let (read, _write) = tcp_pair().unwrap();
+    poller.add(&read, Event::writable(1)).unwrap();
+
+    let mut events = Events::new();
+    poller
+        .wait_events(&mut events, Some(Duration::from_secs(1)))
+        .unwrap();
+
+    // Append more events with the same keys.
+    poller.wake(2).unwrap();
+    poller.wake(1).unwrap();
+    poller.wake(2).unwrap();
+    poller.drain_all(&mut events, usize::MAX).unwrap();
+    assert_eq!(events.iter().count(), 4);
+
+    events.dedup();
+    assert_eq!(
+        events.iter().collect::<Vec<_>>(),
+        [Event::all(1), Event::readable(2)]
+    );
+    assert_eq!(events.iter_keys().collect::<Vec<_>>(), [1, 2]);
+
+    poller.delete(&read).unwrap();
+}
+
+#[test]
+fn wait_sink() {
+    use polling::WaitSink;
+
+    #[derive(Default)]
+    struct Sink {
+        events: Events,
+        calls: Vec<(&'static str, usize)>,
+    }
+
+    impl AsMut<Events> for Sink {
+        fn as_mut(&mut self) -> &mut Events {
+            &mut self.events
+        }
+    }
+
+    impl WaitSink for Sink {
+        fn pre_wait(&mut self, capacity: usize) {
+            self.calls.push(("pre", capacity));
+        }
+
+        fn post_wait(&mut self, n: usize) {
+            self.calls.push(("post", n));
+        }
+    }
+
+    let poller = Poller::new().unwrap();
+    let (read, mut write) = tcp_pair().unwrap();
+    poller.add(&read, Event::readable(1)).unwrap();
+    write.write_all(&[1]).unwrap();
+
+    let mut sink = Sink::default();
+    poller
+        .wait_sink(&mut sink, Some(Duration::from_secs(1)))
+        .unwrap();
+    assert_eq!(sink.calls.len(), 2);
+    assert_eq!(sink.calls[0].0, "pre");
+    assert!(sink.calls[0].1 > 0);
+    assert_eq!(sink.calls[1], ("post", 1));
+    assert_eq!(sink.events.find(1), Some(Event::readable(1)));
+
+    poller.delete(&read).unwrap();
+}
+
+#[test]
+fn try_wait() {
+    let poller = Poller::new().unwrap();
+    let (read, mut write) = tcp_pair().unwrap();
+    poller.add(&read, Event::readable(1)).unwrap();
+
+    let mut events = Events::new();
+    assert_eq!(poller.try_wait(&mut events).unwrap(), 0);
+
+    // Notifications are not reported.
+    poller.notify().unwrap();
+    assert_eq!(poller.try_wait(&mut events).unwrap(), 0);
+
+    write.write_all(&[1]).unwrap();
+    thread::sleep(Duration::from_millis(100));
+    assert_eq!(poller.try_wait(&mut events).unwrap(), 1);
+    assert_eq!(events.iter().collect::<Vec<_>>(), [Event::readable(1)]);
+    assert_eq!(poller.try_wait(&mut events).unwrap(), 0);
+
+    poller.delete(&read).unwrap();
+}
+
+#[test]
+fn drain_all() {
+    let poller = Poller::new().unwrap();
+    let mut pairs = Vec::new();
+    for key in 0..3 {
+        let (read, mut write) = tcp_pair().unwrap();
+        poller.add(&read, Event::readable(key)).unwrap();
+        write.write_all(&[1]).unwrap();
+        pairs.push((read, write));
+    }
+    std::thread::sleep(Duration::from_millis(100));
+
+    let mut events = Events::new();
+    assert_eq!(poller.drain_all(&mut events, 1024).unwrap(), 3);
+    let mut keys = events.iter_keys().collect::<Vec<_>>();
+    keys.sort_unstable();
+    assert_eq!(keys, [0, 1, 2]);
+
+    // Events are appended, and oneshot sources are not reported again.
+    assert_eq!(poller.drain_all(&mut events, 1024).unwrap(), 0);
+    assert_eq!(events.iter().count(), 3);
+
+    for (read, _) in &pairs {
+        poller.delete(read).unwrap();
+    }
+}
+
+#[test]
+fn drain() {
+    let poller = Poller::new().unwrap();
+    let mut pairs = Vec::new();
+    for key in 0..3 {
+        let (read, mut write) = tcp_pair().unwrap();
+        poller.add(&read, Event::readable(key)).unwrap();
+        write.write_all(&[1]).unwrap();
+        pairs.push((read, write));
+    }
+    std::thread::sleep(Duration::from_millis(100));
+    poller.wake(7).unwrap();
+
+    assert_eq!(poller.drain().unwrap(), 4);
+    let mut events = Vec::new();
+    poller
+        .wait(&mut events, Some(Duration::from_secs(0)))
+        .unwrap();
+    assert!(events.is_empty());
+
+    // Level-triggered sources stay ready, but draining still ends.
+    if poller.supports_level() {
+        for (key, (read, _)) in pairs.iter().enumerate() {
+            poller
+                .modify_with_mode(read, Event::readable(key), PollMode::Level)
+                .unwrap();
+        }
+        assert!(poller.drain().unwrap() >= 3);
+    }
+
+    for (read, _) in &pairs {
+        poller.delete(read).unwrap();
+    }
+}
+
+#[test]
+fn probe() {
+    let (read, mut write) = tcp_pair().unwrap();
+
+    let event = Event::probe(&read).unwrap();
+    assert!(!event.readable);
+    assert!(event.writable);
+
+    write.write_all(&[1]).unwrap();
+    let start = std::time::Instant::now();
+    while !Event::probe(&read).unwrap().readable {
+        assert!(start.elapsed() < Duration::from_secs(5));
+        std::thread::sleep(Duration::from_millis(10));
+    }
+}
+
+#[test]
+fn wait_one() {
+    let poller = Poller::new().unwrap();
+    let (read1, mut write1) = tcp_pair().unwrap();
+    let (read2, mut write2) = tcp_pair().unwrap();
+    poller.add(&read1, Event::readable(1)).unwrap();
+    poller.add(&read2, Event::readable(2)).unwrap();
+
+    assert_eq!(poller.wait_one(Some(Duration::from_secs(0))).unwrap(), None);
+
+    write1.write_all(&[1]).unwrap();
+    write2.write_all(&[1]).unwrap();
+    std::thread::sleep(Duration::from_millis(100));
+
+    // Both events are reported, one at a time, even though the sources are oneshot.
+    let mut keys = Vec::new();
+    for _ in 0..2 {
+        let event = poller.wait_one(Some(Duration::from_secs(1))).unwrap();
+        keys.push(event.unwrap().key);
+    }
+    keys.sort_unstable();
+    assert_eq!(keys, [1, 2]);
+    assert_eq!(poller.wait_one(Some(Duration::from_secs(0))).unwrap(), None);
+
+    // Woken keys are kept for the next wait as well.
+    poller.wake(3).unwrap();
+    poller.wake(4).unwrap();
+    assert_eq!(
+        poller.wait_one(Some(Duration::from_secs(1))).unwrap(),
+        Some(Event::readable(3))
+    );
+    assert_eq!(
+        poller.wait_one(Some(Duration::from_secs(1))).unwrap(),
+        Some(Event::readable(4))
+    );
+
+    poller.delete(&read1).unwrap();
+    poller.delete(&read2).unwrap();
+}
+
+#[test]
+fn max_fds() {
+    let poller = Poller::new().unwrap();
+    let max = poller.max_fds();
+
+    if cfg!(unix) {
+        // The limit may be infinite, but never lower than the file descriptors that are open.
+        assert!(max.map_or(true, |max| max > 3));
+    } else {
+        assert_eq!(max, None);
+    }
+}
+
+#[test]
+fn debug() {
+    let poller = PollerBuilder::new().track_sources(true).build().unwrap();
+    let (read, _write) = tcp_pair().unwrap();
+    poller.add(&read, Event::readable(1)).unwrap();
+    poller.notify().unwrap();
+
+    let debug = format!("{:?}", poller);
+    assert!(debug.starts_with("Poller {"), "{}", debug);
+    assert!(debug.contains(&format!("backend: {:?}", polling::BACKEND)));
+    if cfg!(unix) {
+        assert!(debug.contains("registered_fds: 1"), "{}", debug);
+    } else {
+        assert!(debug.contains("registered_handles: 1"), "{}", debug);
+    }
+    assert!(debug.contains("notify_armed: true"), "{}", debug);
+
+    poller.delete(&read).unwrap();
+}
+
+#[test]
+fn add_with_interest() {
+    let poller = Poller::new().unwrap();
+    let (read, mut write) = tcp_pair().unwrap();
+    poller
+        .add_with_interest(&read, 3, Interest::READABLE, PollMode::Oneshot)
+        .unwrap();
+
+    write.write_all(&[1]).unwrap();
+    let mut events = Vec::new();
+    poller
+        .wait(&mut events, Some(Duration::from_secs(1)))
+        .unwrap();
+    assert_eq!(events, [Event::readable(3)]);
+    assert_eq!(Interest::from(events[0]), Interest::READABLE);
+
+    // Hangups are reported, but aren't part of the interest.
+    poller
+        .modify_with_interest(&read, 4, Interest::READABLE, PollMode::Oneshot)
+        .unwrap();
+    drop(write);
+    events.clear();
+    poller
+        .wait(&mut events, Some(Duration::from_secs(1)))
+        .unwrap();
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].key, 4);
+    assert_eq!(events[0].interest(), Interest::READABLE);
+
+    poller.delete(&read).unwrap();
+}
+
+#[test]
+fn available() {
+    let poller = Poller::new().unwrap();
+    let (read, mut write) = tcp_pair().unwrap();
+    poller.add(&read, Event::readable(1)).unwrap();
+
+    write.write_all(&[1, 2, 3]).unwrap();
+    let mut events = Events::new();
+    poller
+        .wait_events(&mut events, Some(Duration::from_secs(1)))
+        .unwrap();
+    let events = events.iter_details().collect::<Vec<_>>();
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].0, Event::readable(1));
+
+    let reported =
+        polling::BACKEND_KIND == polling::BackendKind::Kqueue && cfg!(target_pointer_width = "64");
+    if reported {
+        assert_eq!(events[0].1.available(), Some(3));
+    } else {
+        assert_eq!(events[0].1.available(), None);
+    }
+
+    poller.delete(&read).unwrap();
+}
+
+#[test]
+fn add64() {
+    use polling::{Event64, PollMode};
+
+    let poller = Poller::new().unwrap();
+    let (read, mut write) = tcp_pair().unwrap();
+    let key = (1 << 40) | 5;
+    poller
+        .add64(&read, Event64::readable(key), PollMode::Oneshot)
+        .unwrap();
+
+    write.write_all(&[1]).unwrap();
+    let mut events = Vec::new();
+    assert_eq!(
+        poller
+            .wait64(&mut events, Some(Duration::from_secs(1)))
+            .unwrap(),
+        1
+    );
+    assert_eq!(events, [Event64::readable(key)]);
+
+    poller.delete(&read).unwrap();
+}
+
+#[test]
+fn register_batch_with_errors() {
+    use polling::{PollMode, Source};
+
+    let poller = Poller::new().unwrap();
+    let (read1, mut write1) = tcp_pair().unwrap();
+    let (read2, mut write2) = tcp_pair().unwrap();
+
+    let results = poller.register_batch_with_errors(&[
+        ((&read1).raw(), Event::readable(1), PollMode::Oneshot),
+        (
+            (&read2).raw(),
+            Event::readable(polling::NOTIFY_KEY),
+            PollMode::Oneshot,
+        ),
+        ((&read2).raw(), Event::readable(2), PollMode::Oneshot),
+    ]);
+    assert_eq!(results.len(), 3);
+    assert!(results[0].is_ok());
+    assert!(results[1].is_err());
+    assert!(results[2].is_ok());
+
+    write1.write_all(&[1]).unwrap();
+    write2.write_all(&[1]).unwrap();
+
+    let mut keys = Vec::new();
+    let mut events = Events::new();
+    while keys.len() < 2 {
+        poller
+            .wait_events(&mut events, Some(Duration::from_secs(1)))
+            .unwrap();
+        keys.extend(events.iter_keys());
+    }
+    keys.sort_unstable();
+    assert_eq!(keys, [1, 2]);
+
+    poller.delete(&read1).unwrap();
+    poller.delete(&read2).unwrap();
+}
+
+#[test]
+fn add_many_from_slice() {
+    use polling::{PollMode, Source};
+
+    let poller = PollerBuilder::new().track_sources(true).build().unwrap();
+    let pairs = (0..80).map(|_| tcp_pair().unwrap()).collect::<Vec<_>>();
+    let entries = pairs
+        .iter()
+        .enumerate()
+        .map(|(i, (read, _))| (read.raw(), Event::readable(i), PollMode::Oneshot))
+        .collect::<Vec<_>>();
+
+    // Both a batch that fits on the stack and one that doesn't.
+    poller.add_many_from_slice(&entries[..10]).unwrap();
+    poller.add_many_from_slice(&entries[10..]).unwrap();
+
+    (&pairs[25].1).write_all(&[1]).unwrap();
+    let mut events = Vec::new();
+    poller
+        .wait(&mut events, Some(Duration::from_secs(1)))
+        .unwrap();
+    assert_eq!(events, [Event::readable(25)]);
+
+    // The added sources are tracked.
+    assert!(poller
+        .modify_if_registered(&pairs[70].0, Event::readable(70), PollMode::Oneshot)
+        .unwrap());
+
+    // The first error is returned, and an invalid source keeps the others from being added.
+    let (read, _write) = tcp_pair().unwrap();
+    let err = poller
+        .add_many_from_slice(&[
+            ((&read).raw(), Event::readable(100), PollMode::Oneshot),
+            (
+                entries[0].0,
+                Event::readable(polling::NOTIFY_KEY),
+                PollMode::Oneshot,
+            ),
+        ])
+        .unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    assert!(!poller
+        .modify_if_registered(&read, Event::readable(100), PollMode::Oneshot)
+        .unwrap());
+
+    for (read, _) in &pairs {
+        poller.delete(read).unwrap();
+    }
+}
+
+#[test]
+fn modify_if_registered() {
+    use polling::PollMode;
+
+    let poller = PollerBuilder::new().track_sources(true).build().unwrap();
+    let (read, mut write) = tcp_pair().unwrap();
+    write.write_all(&[1]).unwrap();
+    assert!(!poller
+        .modify_if_registered(&read, Event::readable(1), PollMode::Oneshot)
+        .unwrap());
+
+    poller.add(&read, Event::none(1)).unwrap();
+    assert!(poller
+        .modify_if_registered(&read, Event::readable(1), PollMode::Oneshot)
+        .unwrap());
+    let mut events = Vec::new();
+    poller
+        .wait(&mut events, Some(Duration::from_secs(1)))
+        .unwrap();
+    assert_eq!(events, [Event::readable(1)]);
+
+    // A removed source is not added back.
+    poller.delete(&read).unwrap();
+    assert!(!poller
+        .modify_if_registered(&read, Event::readable(1), PollMode::Oneshot)
+        .unwrap());
+    events.clear();
+    poller
+        .wait(&mut events, Some(Duration::from_millis(100)))
+        .unwrap();
+    assert!(events.is_empty());
+
+    // A closed file descriptor is not registered either. `poll()` only notices this when it
+    // waits.
+    #[cfg(unix)]
+    if polling::BACKEND != "poll" {
+        use std::os::unix::io::AsRawFd;
+
+        poller.add(&read, Event::none(1)).unwrap();
+        let fd = read.as_raw_fd();
+        drop(read);
+        assert!(!poller
+            .modify_if_registered(fd, Event::readable(1), PollMode::Oneshot)
+            .unwrap());
+    }
+}
+
+#[test]
+fn split() {
+    let poller = Poller::new().unwrap();
+    let (read, mut write) = tcp_pair().unwrap();
+    poller
+        .add_split(&read, Event::readable(1), PollMode::Oneshot)
+        .unwrap();
+    poller
+        .add_split(&read, Event::writable(2), PollMode::Oneshot)
+        .unwrap();
+    assert!(poller
+        .add_split(&read, Event::writable(2), PollMode::Oneshot)
+        .is_err());
+    assert!(poller
+        .add_split(&read, Event::writable(3), PollMode::Level)
+        .is_err());
+
+    let mut events = Vec::new();
+    poller
+        .wait(&mut events, Some(Duration::from_secs(1)))
+        .unwrap();
+    assert_eq!(events, [Event::writable(2)]);
+
+    // The readable key is still enabled after the event of the writable key.
+    write.write_all(&[1]).unwrap();
+    events.clear();
+    poller
+        .wait(&mut events, Some(Duration::from_secs(1)))
+        .unwrap();
+    assert_eq!(events, [Event::readable(1)]);
+
+    // Both keys are disabled now.
+    events.clear();
+    poller
+        .wait(&mut events, Some(Duration::from_secs(0)))
+        .unwrap();
+    assert!(events.is_empty());
+
+    poller.modify_split(&read, Event::writable(2)).unwrap();
+    poller
+        .wait(&mut events, Some(Duration::from_secs(1)))
+        .unwrap();
+    assert_eq!(events, [Event::writable(2)]);
+
+    // Removing the key the source is registered under keeps the other key working.
+    poller.delete_split(&read, 1).unwrap();
+    assert!(poller.modify_split(&read, Event::readable(1)).is_err());
+    poller.modify_split(&read, Event::all(2)).unwrap();
+    events.clear();
+    poller
+        .wait(&mut events, Some(Duration::from_secs(1)))
+        .unwrap();
+    assert_eq!(events, [Event::all(2)]);
+
+    // The source is removed with its last key.
+    poller.delete_split(&read, 2).unwrap();
+    poller.add(&read, Event::readable(1)).unwrap();
+    poller.delete(&read).unwrap();
+}
+
+#[test]
+fn modify_batch() {
+    use polling::{PollMode, Source};
+
+    let poller = Poller::new().unwrap();
+    let pairs = (0..3).map(|_| tcp_pair().unwrap()).collect::<Vec<_>>();
+    let entries = pairs
+        .iter()
+        .enumerate()
+        .map(|(i, (read, _))| (read.raw(), Event::none(i), PollMode::Oneshot))
+        .collect::<Vec<_>>();
+    poller.add_many_from_slice(&entries).unwrap();
+
+    (&pairs[1].1).write_all(&[1]).unwrap();
+    let mut events = Vec::new();
+    poller
+        .wait(&mut events, Some(Duration::from_millis(100)))
+        .unwrap();
+    assert!(events.is_empty());
+
+    let entries = entries
+        .iter()
+        .map(|&(fd, ev, mode)| (fd, Event::readable(ev.key + 10), mode))
+        .collect::<Vec<_>>();
+    poller.modify_batch(&entries).unwrap();
+    poller
+        .wait(&mut events, Some(Duration::from_secs(1)))
+        .unwrap();
+    assert_eq!(events, [Event::readable(11)]);
+
+    for (read, _) in &pairs {
+        poller.delete(read).unwrap();
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn invalid_fd() {
+    use polling::PollMode;
+
+    let poller = Poller::new().unwrap();
+
+    // Found by the `poller_ops` fuzz target.
+    for &fd in &[-1, -2, std::i32::MIN] {
+        assert!(poller.add(fd, Event::readable(1)).is_err());
+        assert!(poller.modify(fd, Event::readable(1)).is_err());
+        assert!(poller.delete(fd).is_err());
+        assert!(Event::probe(fd).is_err());
+    }
+
+    let results = poller.register_batch_with_errors(&[(-1, Event::readable(1), PollMode::Oneshot)]);
+    assert!(results[0].is_err());
+}
+
+#[cfg(unix)]
+#[test]
+fn pipe() {
+    use std::io::Read;
+    use std::os::unix::io::AsRawFd;
+
+    let (reader, writer) = polling::pipe().unwrap();
+    for fd in &[reader.as_raw_fd(), writer.as_raw_fd()] {
+        let fd_flags = unsafe { libc::fcntl(*fd, libc::F_GETFD) };
+        assert!(fd_flags & libc::FD_CLOEXEC != 0);
+        let fl_flags = unsafe { libc::fcntl(*fd, libc::F_GETFL) };
+        assert!(fl_flags & libc::O_NONBLOCK != 0);
+    }
+
+    // Reading from the empty pipe doesn't block.
+    let err = std::fs::File::from(reader).read(&mut [0]).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::WouldBlock);
+}
+
+#[cfg(windows)]
+#[test]
+fn pipe() {
+    use std::io::Read;
+    use std::net::TcpStream;
+
+    let (reader, writer) = polling::pipe().unwrap();
+    let mut reader = TcpStream::from(reader);
+    let mut writer = TcpStream::from(writer);
+
+    // Reading from the empty pipe doesn't block.
+    let err = reader.read(&mut [0]).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::WouldBlock);
+
+    let poller = Poller::new().unwrap();
+    poller.add(&reader, Event::readable(1)).unwrap();
+    writer.write_all(&[1]).unwrap();
+    let mut events = Vec::new();
+    poller
+        .wait(&mut events, Some(Duration::from_secs(1)))
+        .unwrap();
+    assert_eq!(events, [Event::readable(1)]);
+    poller.delete(&reader).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn io_safe_sources() {
+    use std::os::unix::io::{AsFd, OwnedFd};
+
+    let poller = Poller::new().unwrap();
+    let (read, mut write) = tcp_pair().unwrap();
+    let read = OwnedFd::from(read);
+    poller.add(read.as_fd(), Event::readable(1)).unwrap();
+
+    write.write_all(&[1]).unwrap();
+    let mut events = Vec::new();
+    poller
+        .wait(&mut events, Some(Duration::from_secs(1)))
+        .unwrap();
+    assert_eq!(events, [Event::readable(1)]);
+
+    poller.modify(&read, Event::none(1)).unwrap();
+    poller.delete(read.as_fd()).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn delete_and_close() {
+    use std::os::unix::io::OwnedFd;
+
+    let poller = Poller::new().unwrap();
+    let (read, mut write) = tcp_pair().unwrap();
+    let dup = read.try_clone().unwrap();
+    poller.add(&read, Event::readable(1)).unwrap();
+    poller.delete_and_close(OwnedFd::from(read)).unwrap();
+
+    // The duplicate is still open, but no events are reported for it.
+    write.write_all(&[1]).unwrap();
+    let mut events = Events::new();
+    poller
+        .wait_events(&mut events, Some(Duration::from_millis(100)))
+        .unwrap();
+    assert!(events.iter().all(|ev| ev.key != 1));
+    drop(dup);
+
+    // The file descriptor is closed even if it wasn't added.
+    let (read, _write) = tcp_pair().unwrap();
+    assert!(poller.delete_and_close(OwnedFd::from(read)).is_err());
+}
+
 fn tcp_pair() -> io::Result<(TcpStream, TcpStream)> {
     let listener = TcpListener::bind("127.0.0.1:0")?;
     let a = TcpStream::connect(listener.local_addr()?)?;
@@ -0,0 +1,30 @@
+//! Tests for `KeyAllocator`.
+
+use polling::{KeyAllocator, Poller, PollerBuilder};
+
+#[test]
+fn reuse() {
+    let poller = Poller::new().unwrap();
+    let mut keys = KeyAllocator::new(&poller);
+    assert!(keys.is_empty());
+
+    let a = keys.allocate();
+    let b = keys.allocate();
+    assert_ne!(a, b);
+    assert_eq!(keys.len(), 2);
+
+    keys.free(a);
+    assert_eq!(keys.len(), 1);
+    assert_eq!(keys.allocate(), a);
+    assert_eq!(keys.len(), 2);
+}
+
+#[test]
+fn skips_notify_key() {
+    let poller = PollerBuilder::new().notify_key(2).build().unwrap();
+    let mut keys = KeyAllocator::new(&poller);
+
+    let allocated = (0..4).map(|_| keys.allocate()).collect::<Vec<_>>();
+    assert_eq!(allocated, [0, 1, 3, 4]);
+    assert_eq!(keys.len(), 4);
+}
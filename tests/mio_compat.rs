@@ -0,0 +1,67 @@
+//! Tests for the `mio`-compatible interface.
+
+#![cfg(feature = "mio-compat")]
+
+mod common;
+
+use std::io::Write;
+use std::time::Duration;
+
+use polling::compat::{Events, Interest, Poll, Token};
+
+#[test]
+fn readable() {
+    let mut poll = Poll::new().unwrap();
+    let mut events = Events::with_capacity(8);
+    let (mut read, mut write) = common::tcp_pair().unwrap();
+
+    poll.registry()
+        .register(&mut read, Token(1), Interest::READABLE)
+        .unwrap();
+
+    // Nothing should be available at first.
+    poll.poll(&mut events, Some(Duration::from_secs(0)))
+        .unwrap();
+    assert!(events.is_empty());
+
+    // After a write, the event should be available now.
+    write.write_all(&[1]).unwrap();
+    poll.poll(&mut events, Some(Duration::from_secs(1)))
+        .unwrap();
+
+    let event = events.iter().next().unwrap();
+    assert_eq!(event.token(), Token(1));
+    assert!(event.is_readable());
+    assert!(!event.is_writable());
+
+    poll.registry().deregister(&mut read).unwrap();
+}
+
+#[test]
+fn reregister() {
+    let mut poll = Poll::new().unwrap();
+    let mut events = Events::with_capacity(8);
+    let (mut read, _write) = common::tcp_pair().unwrap();
+
+    poll.registry()
+        .register(&mut read, Token(1), Interest::READABLE)
+        .unwrap();
+    poll.registry()
+        .reregister(&mut read, Token(2), Interest::READABLE | Interest::WRITABLE)
+        .unwrap();
+
+    poll.poll(&mut events, Some(Duration::from_secs(1)))
+        .unwrap();
+    assert!(events
+        .iter()
+        .any(|ev| ev.token() == Token(2) && ev.is_writable()));
+}
+
+#[test]
+fn interest() {
+    let both = Interest::READABLE.add(Interest::WRITABLE);
+    assert!(both.is_readable());
+    assert!(both.is_writable());
+    assert_eq!(both.remove(Interest::WRITABLE), Some(Interest::READABLE));
+    assert_eq!(Interest::READABLE.remove(Interest::READABLE), None);
+}
@@ -0,0 +1,70 @@
+//! Tests for `MockPoller`.
+
+use std::io;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use polling::mock::MockPoller;
+use polling::{Event, PollMode};
+
+#[test]
+fn registration() {
+    let poller = MockPoller::new();
+
+    poller.add(3, Event::readable(1)).unwrap();
+    assert_eq!(
+        poller.add(3, Event::readable(1)).unwrap_err().kind(),
+        io::ErrorKind::AlreadyExists
+    );
+    assert!(poller.add(4, Event::readable(polling::NOTIFY_KEY)).is_err());
+
+    poller
+        .modify_with_mode(3, Event::all(2), PollMode::Level)
+        .unwrap();
+    assert_eq!(
+        poller.registration(3),
+        Some((Event::all(2), PollMode::Level))
+    );
+
+    poller.delete(3).unwrap();
+    assert_eq!(poller.registration(3), None);
+    assert_eq!(
+        poller.modify(3, Event::none(2)).unwrap_err().kind(),
+        io::ErrorKind::NotFound
+    );
+    assert!(poller.delete(3).is_err());
+}
+
+#[test]
+fn inject_from_another_thread() {
+    let poller = Arc::new(MockPoller::new());
+
+    let handle = thread::spawn({
+        let poller = poller.clone();
+        move || {
+            thread::sleep(Duration::from_millis(50));
+            poller.inject(1, false, true);
+        }
+    });
+
+    let mut events = Vec::new();
+    assert_eq!(poller.wait(&mut events, None).unwrap(), 1);
+    assert_eq!(events, [Event::writable(1)]);
+    handle.join().unwrap();
+}
+
+#[test]
+fn timeout_and_notify() {
+    let poller = MockPoller::new();
+    let mut events = Vec::new();
+
+    let timeout = Duration::from_millis(50);
+    let start = Instant::now();
+    assert_eq!(poller.wait(&mut events, Some(timeout)).unwrap(), 0);
+    assert!(start.elapsed() >= timeout);
+
+    poller.notify().unwrap();
+    assert_eq!(poller.wait(&mut events, None).unwrap(), 0);
+    assert!(events.is_empty());
+}
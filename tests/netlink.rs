@@ -0,0 +1,65 @@
+//! Tests for `Poller::register_netlink()`.
+
+#![cfg(any(target_os = "linux", target_os = "android"))]
+
+use std::io;
+use std::net::TcpListener;
+use std::os::unix::io::AsRawFd;
+
+use polling::{Event, Poller};
+
+#[test]
+fn register_and_deregister() -> io::Result<()> {
+    let socket = unsafe {
+        libc::socket(
+            libc::AF_NETLINK,
+            libc::SOCK_RAW | libc::SOCK_NONBLOCK | libc::SOCK_CLOEXEC,
+            libc::NETLINK_ROUTE,
+        )
+    };
+    assert!(socket >= 0, "{}", io::Error::last_os_error());
+
+    let poller = Poller::new()?;
+    poller.register_netlink(1, socket)?;
+    assert_eq!(
+        poller.deregister_netlink(2).unwrap_err().kind(),
+        io::ErrorKind::NotFound
+    );
+    poller.deregister_netlink(1)?;
+    assert_eq!(
+        poller.deregister_netlink(1).unwrap_err().kind(),
+        io::ErrorKind::NotFound
+    );
+
+    unsafe { libc::close(socket) };
+    Ok(())
+}
+
+#[test]
+fn other_sources_are_kept() -> io::Result<()> {
+    let listener = TcpListener::bind("127.0.0.1:0")?;
+
+    let poller = Poller::new()?;
+    poller.add(&listener, Event::readable(1))?;
+    assert_eq!(
+        poller.deregister_netlink(1).unwrap_err().kind(),
+        io::ErrorKind::NotFound
+    );
+
+    // The listener is still registered.
+    poller.delete(&listener)?;
+    Ok(())
+}
+
+#[test]
+fn not_netlink() -> io::Result<()> {
+    let listener = TcpListener::bind("127.0.0.1:0")?;
+
+    let poller = Poller::new()?;
+    let err = poller
+        .register_netlink(1, listener.as_raw_fd())
+        .unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+
+    Ok(())
+}
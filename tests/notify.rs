@@ -1,4 +1,6 @@
 use std::io;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
 use std::thread;
 use std::time::Duration;
 
@@ -34,3 +36,266 @@ fn concurrent() -> io::Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn notify_hook() -> io::Result<()> {
+    let poller = Poller::new()?;
+    let count = Arc::new(AtomicUsize::new(0));
+    let hook_count = count.clone();
+    poller.set_notify_hook(Box::new(move || {
+        hook_count.fetch_add(1, Ordering::SeqCst);
+    }));
+
+    // A pending notification isn't sent again.
+    poller.notify()?;
+    poller.notify()?;
+    assert_eq!(count.load(Ordering::SeqCst), 1);
+
+    // Once the notification was consumed, the next one invokes the hook again.
+    let mut events = Vec::new();
+    poller.wait(&mut events, None)?;
+    poller.notify()?;
+    assert_eq!(count.load(Ordering::SeqCst), 2);
+
+    // `notify_one()` always sends a notification, and so always invokes the hook, as do
+    // notifications sent through a handle.
+    poller.notify_one()?;
+    assert_eq!(count.load(Ordering::SeqCst), 3);
+    poller.wait(&mut events, None)?;
+    poller.handle().notify()?;
+    assert_eq!(count.load(Ordering::SeqCst), 4);
+    poller.wait(&mut events, None)?;
+
+    // The hook can replace itself, and the replacement is invoked by the next notification.
+    let hook_poller = Arc::new(poller);
+    let weak = Arc::downgrade(&hook_poller);
+    let replaced = Arc::new(AtomicUsize::new(0));
+    let hook_replaced = replaced.clone();
+    let hook_count = count.clone();
+    hook_poller.set_notify_hook(Box::new(move || {
+        hook_replaced.fetch_add(1, Ordering::SeqCst);
+        if let Some(poller) = weak.upgrade() {
+            let count = hook_count.clone();
+            poller.set_notify_hook(Box::new(move || {
+                count.fetch_add(1, Ordering::SeqCst);
+            }));
+        }
+    }));
+    hook_poller.notify()?;
+    assert_eq!(replaced.load(Ordering::SeqCst), 1);
+    hook_poller.wait(&mut events, None)?;
+    hook_poller.notify()?;
+    assert_eq!(replaced.load(Ordering::SeqCst), 1);
+    assert_eq!(count.load(Ordering::SeqCst), 5);
+
+    Ok(())
+}
+
+#[test]
+fn is_notified() -> io::Result<()> {
+    use polling::Events;
+
+    let poller = Poller::new()?;
+    let mut events = Events::new();
+
+    poller.notify()?;
+    poller.wait_events(&mut events, None)?;
+    assert!(events.is_notified());
+
+    // The notification was consumed.
+    poller.wait_events(&mut events, Some(Duration::from_millis(10)))?;
+    assert!(!events.is_notified());
+
+    // Waking a key is also a notification.
+    poller.wake(7)?;
+    assert_eq!(poller.try_wait(&mut events)?, 1);
+    assert!(events.is_notified());
+
+    events.clear();
+    assert!(!events.is_notified());
+
+    Ok(())
+}
+
+#[test]
+fn shutdown() -> io::Result<()> {
+    use polling::{Event, Events};
+
+    let poller = Arc::new(Poller::new()?);
+    let waiter = thread::spawn({
+        let poller = poller.clone();
+        move || -> io::Result<bool> {
+            let mut events = Events::new();
+            poller.wait_events(&mut events, None)?;
+            Ok(events.is_notified())
+        }
+    });
+    thread::sleep(Duration::from_millis(100));
+    assert!(!poller.is_shut_down());
+    poller.shutdown()?;
+    assert!(waiter.join().unwrap()?);
+    assert!(poller.is_shut_down());
+
+    // Following waits return right away.
+    let mut events = Events::new();
+    for _ in 0..3 {
+        poller.wait_events(&mut events, None)?;
+        assert!(events.is_notified());
+    }
+    poller.wait_while(&mut events, None, || true)?;
+    assert!(events.is_notified());
+
+    // Sources can't be added anymore, not even through a handle.
+    let socket = std::net::TcpListener::bind("127.0.0.1:0")?;
+    let err = poller.add(&socket, Event::readable(1)).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::NotConnected);
+    let err = poller
+        .handle()
+        .add(&socket, Event::readable(1))
+        .unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::NotConnected);
+    poller.shutdown()?;
+
+    Ok(())
+}
+
+#[test]
+fn wait_while() -> io::Result<()> {
+    use polling::Events;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::time::Instant;
+
+    let poller = Poller::new()?;
+    let mut events = Events::new();
+
+    // A notification alone doesn't end the wait.
+    let start = Instant::now();
+    Parallel::new()
+        .add(|| {
+            thread::sleep(Duration::from_millis(50));
+            poller.notify().unwrap();
+        })
+        .finish(|| {
+            poller
+                .wait_while(&mut events, Some(Duration::from_millis(500)), || true)
+                .unwrap()
+        });
+    assert!(start.elapsed() >= Duration::from_millis(500));
+    assert_eq!(events.iter().count(), 0);
+
+    // Once the predicate is false, it does.
+    let running = AtomicBool::new(true);
+    let start = Instant::now();
+    Parallel::new()
+        .add(|| {
+            thread::sleep(Duration::from_millis(50));
+            running.store(false, Ordering::SeqCst);
+            poller.notify().unwrap();
+        })
+        .finish(|| {
+            poller
+                .wait_while(&mut events, Some(Duration::from_secs(10)), || {
+                    running.load(Ordering::SeqCst)
+                })
+                .unwrap()
+        });
+    assert!(start.elapsed() < Duration::from_secs(10));
+
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn new_piped() -> io::Result<()> {
+    use std::fs::File;
+    use std::io::Write;
+
+    let (poller, waker) = Poller::new_piped()?;
+    let mut waker = File::from(waker);
+    let mut events = Vec::new();
+
+    // Every write wakes the poller once.
+    for _ in 0..3 {
+        waker.write_all(&[1; 3])?;
+        let start = std::time::Instant::now();
+        poller.wait(&mut events, Some(Duration::from_secs(10)))?;
+        assert!(start.elapsed() < Duration::from_secs(5));
+        assert!(events.is_empty());
+
+        poller.wait(&mut events, Some(Duration::from_secs(0)))?;
+        assert!(events.is_empty());
+    }
+
+    // A write from another thread wakes a blocked wait.
+    Parallel::new()
+        .add(|| {
+            thread::sleep(Duration::from_millis(50));
+            (&waker).write_all(&[1]).unwrap();
+        })
+        .finish(|| poller.wait(&mut events, None).unwrap());
+    assert!(events.is_empty());
+
+    poller.check_invariants()?;
+
+    Ok(())
+}
+
+#[test]
+fn notify_count() -> io::Result<()> {
+    use polling::{Events, PollerBuilder};
+
+    let poller = PollerBuilder::new().count_notifications(true).build()?;
+    let handle = poller.handle();
+    let mut events = Events::new();
+
+    // Notifications are coalesced into one wakeup, but each of them is counted.
+    poller.notify()?;
+    handle.notify()?;
+    poller.notify_one()?;
+    poller.wait_events(&mut events, None)?;
+    assert!(events.is_notified());
+    assert_eq!(events.notify_count(), 3);
+
+    poller.wait_events(&mut events, Some(Duration::from_millis(10)))?;
+    assert_eq!(events.notify_count(), 0);
+
+    // Without counting, the count stays at zero.
+    let poller = Poller::new()?;
+    poller.notify()?;
+    poller.wait_events(&mut events, None)?;
+    assert!(events.is_notified());
+    assert_eq!(events.notify_count(), 0);
+
+    Ok(())
+}
+
+#[test]
+fn post_value() -> io::Result<()> {
+    use polling::{Event, Events};
+
+    let poller = Arc::new(Poller::new()?);
+    let handle = poller.handle();
+    let mut events = Events::new();
+
+    // Each queue posts its own key from its own thread.
+    Parallel::new()
+        .each(0..4, |queue| poller.post_value(queue, queue * 10))
+        .add(|| handle.post_value(4, 40))
+        .run()
+        .into_iter()
+        .collect::<io::Result<()>>()?;
+
+    poller.wait_events(&mut events, Some(Duration::from_secs(1)))?;
+    let mut posted: Vec<_> = events.posted().collect();
+    posted.sort_unstable();
+    assert_eq!(posted, [(0, 0), (1, 10), (2, 20), (3, 30), (4, 40)]);
+    assert_eq!(events.len(), 5);
+    assert!(events.iter().all(|ev| ev == Event::readable(ev.key)));
+
+    assert_eq!(
+        poller.post_value(usize::MAX, 1).unwrap_err().kind(),
+        io::ErrorKind::InvalidInput
+    );
+
+    Ok(())
+}
@@ -2,11 +2,13 @@
 
 #![allow(clippy::unused_io_amount)]
 
+mod common;
+
 use std::io::{self, prelude::*};
 use std::net::{TcpListener, TcpStream};
 use std::time::Duration;
 
-use polling::{Event, PollMode, Poller};
+use polling::{Event, PollMode, Poller, PollerBuilder};
 
 #[test]
 fn level_triggered() {
@@ -237,6 +239,279 @@ fn edge_oneshot_triggered() {
     assert_eq!(events, [Event::readable(reader_token)]);
 }
 
+#[test]
+fn exclusive() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    listener.set_nonblocking(true).unwrap();
+
+    // Two pollers share the listener, like two processes accepting connections.
+    let pollers = [Poller::new().unwrap(), Poller::new().unwrap()];
+    for poller in &pollers {
+        if let Err(err) = poller.add_with_mode(&listener, Event::readable(1), PollMode::Exclusive) {
+            // Exclusive mode falls back to level-triggered mode where that is supported.
+            assert!(!poller.supports_level(), "{}", err);
+            return;
+        }
+    }
+    if polling::BACKEND != "epoll" {
+        assert!(!pollers[0].supports_exclusive());
+    }
+
+    // Exclusive mode is level-triggered: the event is reported until the connection is accepted.
+    let _stream = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+    let mut events = Vec::new();
+    for _ in 0..2 {
+        events.clear();
+        pollers[0]
+            .wait(&mut events, Some(Duration::from_secs(10)))
+            .unwrap();
+        assert_eq!(events, [Event::readable(1)]);
+    }
+
+    listener.accept().unwrap();
+    events.clear();
+    pollers[0]
+        .wait(&mut events, Some(Duration::from_secs(0)))
+        .unwrap();
+    assert_eq!(events, []);
+
+    // Exclusive registrations can be modified, also into other modes.
+    pollers[0]
+        .modify_with_mode(&listener, Event::none(1), PollMode::Exclusive)
+        .unwrap();
+    pollers[1]
+        .modify_with_mode(&listener, Event::readable(2), PollMode::Level)
+        .unwrap();
+    let _stream = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+    events.clear();
+    pollers[1]
+        .wait(&mut events, Some(Duration::from_secs(10)))
+        .unwrap();
+    assert_eq!(events, [Event::readable(2)]);
+    events.clear();
+    pollers[0]
+        .wait(&mut events, Some(Duration::from_secs(0)))
+        .unwrap();
+    assert_eq!(events, []);
+
+    for poller in &pollers {
+        poller.delete(&listener).unwrap();
+    }
+
+    // Other registrations can't be modified to exclusive mode.
+    if pollers[0].supports_exclusive() {
+        pollers[0].add(&listener, Event::readable(1)).unwrap();
+        let err = pollers[0]
+            .modify_with_mode(&listener, Event::readable(1), PollMode::Exclusive)
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+
+        // The source keeps its registration.
+        let _stream = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        events.clear();
+        pollers[0]
+            .wait(&mut events, Some(Duration::from_secs(10)))
+            .unwrap();
+        assert_eq!(events, [Event::readable(1)]);
+        pollers[0].delete(&listener).unwrap();
+    }
+}
+
+#[test]
+fn dispatch() {
+    let (mut reader, mut writer) = tcp_pair().unwrap();
+    let reader_token = 1;
+
+    let poller = Poller::new().unwrap();
+    poller
+        .add_with_mode(&reader, Event::readable(reader_token), PollMode::Dispatch)
+        .unwrap();
+
+    // The first event is delivered, and then the source is disabled.
+    writer.write_all(&[1, 2, 3, 4, 5]).unwrap();
+    let mut events = Vec::new();
+    poller
+        .wait(&mut events, Some(Duration::from_secs(10)))
+        .unwrap();
+    assert_eq!(events, [Event::readable(reader_token)]);
+
+    events.clear();
+    poller
+        .wait(&mut events, Some(Duration::from_secs(0)))
+        .unwrap();
+    assert_eq!(events, []);
+
+    // Modifying the source enables it again.
+    reader.read_exact(&mut [0; 3]).unwrap();
+    poller
+        .modify_with_mode(&reader, Event::readable(reader_token), PollMode::Dispatch)
+        .unwrap();
+    poller
+        .wait(&mut events, Some(Duration::from_secs(10)))
+        .unwrap();
+    assert_eq!(events, [Event::readable(reader_token)]);
+
+    poller.delete(&reader).unwrap();
+}
+
+#[test]
+fn edge_unsupported() {
+    let (reader, _writer) = tcp_pair().unwrap();
+    let poller = Poller::new().unwrap();
+    if poller.supports_edge() {
+        return;
+    }
+
+    for &mode in &[PollMode::Edge, PollMode::EdgeOneshot] {
+        let err = poller
+            .add_with_mode(&reader, Event::readable(1), mode)
+            .unwrap_err();
+        assert!(common::is_unsupported(&err), "{}", err);
+    }
+}
+
+#[test]
+fn set_interest_keeps_mode() {
+    let (reader, mut writer) = tcp_pair().unwrap();
+    let reader_token = 1;
+
+    let poller = PollerBuilder::new().track_sources(true).build().unwrap();
+    if poller
+        .add_with_mode(&reader, Event::none(reader_token), PollMode::Level)
+        .is_err()
+    {
+        return;
+    }
+
+    // Sources that were never added can't be changed.
+    assert_eq!(
+        poller
+            .set_interest(&writer, true, false)
+            .unwrap_err()
+            .kind(),
+        io::ErrorKind::NotFound
+    );
+
+    writer.write_all(&[1]).unwrap();
+    poller.set_interest(&reader, true, false).unwrap();
+
+    // The source is still level-triggered, so the event is delivered twice.
+    let mut events = Vec::new();
+    for _ in 0..2 {
+        events.clear();
+        poller
+            .wait(&mut events, Some(Duration::from_secs(10)))
+            .unwrap();
+        assert_eq!(events, [Event::readable(reader_token)]);
+    }
+
+    poller.delete(&reader).unwrap();
+    assert!(poller.set_interest(&reader, true, false).is_err());
+}
+
+#[test]
+fn untracked_sources() {
+    let (reader, _writer) = tcp_pair().unwrap();
+
+    // Without tracking, the interest and mode of the source are not known.
+    let poller = Poller::new().unwrap();
+    poller.add(&reader, Event::readable(1)).unwrap();
+    assert_eq!(
+        poller
+            .set_interest(&reader, true, false)
+            .unwrap_err()
+            .kind(),
+        io::ErrorKind::Other
+    );
+    poller.delete(&reader).unwrap();
+}
+
+#[test]
+fn forgotten_after_delete() {
+    let (reader, _writer) = tcp_pair().unwrap();
+
+    let poller = PollerBuilder::new().track_sources(true).build().unwrap();
+    poller.add(&reader, Event::readable(1)).unwrap();
+    poller.enable(&reader).unwrap();
+    poller.delete(&reader).unwrap();
+    assert_eq!(
+        poller.enable(&reader).unwrap_err().kind(),
+        io::ErrorKind::NotFound
+    );
+}
+
+#[test]
+fn disable_enable() {
+    let (reader, mut writer) = tcp_pair().unwrap();
+    let reader_token = 1;
+
+    let poller = PollerBuilder::new().track_sources(true).build().unwrap();
+    poller.add(&reader, Event::readable(reader_token)).unwrap();
+    poller.disable(&reader).unwrap();
+
+    // No events are delivered while the source is disabled.
+    writer.write_all(&[1]).unwrap();
+    let mut events = Vec::new();
+    poller
+        .wait(&mut events, Some(Duration::from_millis(100)))
+        .unwrap();
+    assert!(events.is_empty());
+
+    // The pending event is delivered once it is enabled again.
+    poller.enable(&reader).unwrap();
+    poller
+        .wait(&mut events, Some(Duration::from_secs(10)))
+        .unwrap();
+    assert_eq!(events, [Event::readable(reader_token)]);
+
+    poller.delete(&reader).unwrap();
+    assert_eq!(
+        poller.enable(&reader).unwrap_err().kind(),
+        io::ErrorKind::NotFound
+    );
+}
+
+#[test]
+fn rearm() {
+    let (reader, mut writer) = tcp_pair().unwrap();
+    let reader_token = 1;
+
+    let poller = PollerBuilder::new().track_sources(true).build().unwrap();
+    poller.add(&reader, Event::readable(reader_token)).unwrap();
+
+    writer.write_all(&[1]).unwrap();
+    let mut events = Vec::new();
+    poller
+        .wait(&mut events, Some(Duration::from_secs(10)))
+        .unwrap();
+    assert_eq!(events, [Event::readable(reader_token)]);
+
+    // Re-arming with the same interest delivers the pending event again, under the new key.
+    poller.rearm(&reader, Event::readable(2)).unwrap();
+    events.clear();
+    poller
+        .wait(&mut events, Some(Duration::from_secs(10)))
+        .unwrap();
+    assert_eq!(events, [Event::readable(2)]);
+
+    // A different interest is also accepted.
+    poller.rearm(&reader, Event::all(3)).unwrap();
+    events.clear();
+    poller
+        .wait(&mut events, Some(Duration::from_secs(10)))
+        .unwrap();
+    assert_eq!(events, [Event::all(3)]);
+
+    poller.delete(&reader).unwrap();
+    assert_eq!(
+        poller
+            .rearm(&reader, Event::readable(reader_token))
+            .unwrap_err()
+            .kind(),
+        io::ErrorKind::NotFound
+    );
+}
+
 fn tcp_pair() -> io::Result<(TcpStream, TcpStream)> {
     let listener = TcpListener::bind("127.0.0.1:0")?;
     let a = TcpStream::connect(listener.local_addr()?)?;
@@ -1,7 +1,7 @@
 use std::io;
 use std::time::{Duration, Instant};
 
-use polling::Poller;
+use polling::{Poller, PollerBuilder};
 
 #[test]
 fn below_ms() -> io::Result<()> {
@@ -76,3 +76,51 @@ fn above_ms() -> io::Result<()> {
     }
     Ok(())
 }
+
+#[test]
+fn granularity() -> io::Result<()> {
+    let poller = Poller::new()?;
+
+    let expected = match polling::BACKEND {
+        "epoll" | "kqueue" | "port" => Duration::from_nanos(1),
+        _ => Duration::from_millis(1),
+    };
+    assert_eq!(poller.timeout_granularity(), expected);
+
+    // A timeout below the granularity still blocks instead of returning immediately.
+    let now = Instant::now();
+    poller.wait(&mut Vec::new(), Some(Duration::from_nanos(500)))?;
+    assert!(now.elapsed() >= Duration::from_nanos(500));
+    Ok(())
+}
+
+#[test]
+fn below_ms_cloned() -> io::Result<()> {
+    let poller = PollerBuilder::new().epoll_pwait2(true).build()?;
+    let poller = match poller.try_clone() {
+        Ok(clone) => clone,
+        Err(_) => return Ok(()),
+    };
+    let mut events = Vec::new();
+
+    let dur = Duration::from_micros(100);
+    let margin = Duration::from_micros(500);
+    let mut lowest = Duration::from_secs(1000);
+
+    for _ in 0..1_000 {
+        let now = Instant::now();
+        let n = poller.wait(&mut events, Some(dur))?;
+        let elapsed = now.elapsed();
+
+        assert_eq!(n, 0);
+        assert!(elapsed >= dur, "{:?} < {:?}", elapsed, dur);
+        lowest = lowest.min(elapsed);
+    }
+
+    // A poller that shares its instance can't use its own timer, so it is only precise if the
+    // system call takes a finer timeout, like `epoll_pwait2()` does once it is enabled.
+    if poller.timeout_granularity() < Duration::from_millis(1) {
+        assert!(lowest < dur + margin);
+    }
+    Ok(())
+}
@@ -0,0 +1,97 @@
+mod common;
+
+use polling::{Event, Events, Poller};
+use std::process::{Child, Command};
+use std::time::Duration;
+
+fn spawn(secs: u32) -> Child {
+    if cfg!(windows) {
+        Command::new("powershell")
+            .args(&["-Command", &format!("Start-Sleep -Seconds {}", secs)])
+            .spawn()
+            .unwrap()
+    } else {
+        Command::new("sleep").arg(secs.to_string()).spawn().unwrap()
+    }
+}
+
+#[test]
+fn child_exit() {
+    let poller = Poller::new().unwrap();
+    let mut child = spawn(1);
+    if let Err(err) = poller.add_process(&mut child, 3) {
+        assert!(common::is_unsupported(&err));
+        assert!(polling::BACKEND == "poll" || polling::BACKEND == "port");
+        child.wait().unwrap();
+        return;
+    }
+
+    let mut list = Events::new();
+    poller
+        .wait_events(&mut list, Some(Duration::from_secs(10)))
+        .unwrap();
+    let mut events = list.iter().collect::<Vec<_>>();
+    assert_eq!(events, [Event::readable(3)]);
+
+    // kqueue tells what happened to the process.
+    let (_, details) = list.iter_details().next().unwrap();
+    if polling::BACKEND == "kqueue" && cfg!(target_pointer_width = "64") {
+        const NOTE_EXIT: u32 = 0x8000_0000;
+        assert_eq!(
+            details.backend_flags().map(|flags| flags & NOTE_EXIT),
+            Some(NOTE_EXIT)
+        );
+    } else {
+        assert_eq!(details.backend_flags(), None);
+    }
+
+    // The exit is only reported once.
+    events.clear();
+    poller
+        .wait(&mut events, Some(Duration::from_millis(50)))
+        .unwrap();
+    assert!(events.is_empty());
+
+    poller.delete_process(&child).unwrap();
+    child.wait().unwrap();
+}
+
+#[test]
+fn delete_before_exit() {
+    let poller = Poller::new().unwrap();
+    let mut child = spawn(1);
+    if poller.add_process(&mut child, 3).is_err() {
+        child.kill().unwrap();
+        child.wait().unwrap();
+        return;
+    }
+    poller.delete_process(&child).unwrap();
+
+    child.kill().unwrap();
+    child.wait().unwrap();
+    let mut events = Vec::new();
+    poller
+        .wait(&mut events, Some(Duration::from_millis(50)))
+        .unwrap();
+    assert!(events.is_empty());
+}
+
+#[test]
+fn reaped_child() {
+    let poller = Poller::new().unwrap();
+    let mut child = spawn(0);
+    child.wait().unwrap();
+
+    // The exit of a child that was reaped already is reported right away.
+    if let Err(err) = poller.add_process(&mut child, 3) {
+        assert!(common::is_unsupported(&err));
+        return;
+    }
+    let mut events = Vec::new();
+    poller
+        .wait(&mut events, Some(Duration::from_secs(10)))
+        .unwrap();
+    assert_eq!(events, [Event::readable(3)]);
+
+    poller.delete_process(&child).unwrap();
+}
@@ -0,0 +1,99 @@
+//! Tests for `Poller::from_raw_fd()` and `Poller::into_raw_fd()`.
+
+#![cfg(any(
+    target_os = "linux",
+    target_os = "android",
+    target_os = "macos",
+    target_os = "ios",
+    target_os = "tvos",
+    target_os = "watchos",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd",
+    target_os = "dragonfly",
+))]
+#![cfg(not(polling_test_poll_backend))]
+
+mod common;
+
+use std::io::Write;
+use std::net::TcpListener;
+use std::os::unix::io::AsRawFd;
+use std::sync::mpsc;
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+use polling::{Event, Poller};
+
+#[test]
+fn round_trip() {
+    let poller = Poller::new().unwrap();
+    let (read, mut write) = common::tcp_pair().unwrap();
+    poller.add(&read, Event::readable(1)).unwrap();
+    write.write_all(&[1]).unwrap();
+
+    // The registration survives the hand-over.
+    let fd = poller.into_raw_fd();
+    let poller = unsafe { Poller::from_raw_fd(fd).unwrap() };
+    assert_eq!(poller.as_raw_fd(), fd);
+
+    let mut events = Vec::new();
+    poller
+        .wait(&mut events, Some(Duration::from_secs(1)))
+        .unwrap();
+    assert_eq!(events, [Event::readable(1)]);
+
+    // The new notification mechanism works.
+    events.clear();
+    poller.notify().unwrap();
+    poller.wait(&mut events, None).unwrap();
+    assert!(events.is_empty());
+
+    poller.delete(&read).unwrap();
+}
+
+#[test]
+fn with_handle() {
+    let poller = Poller::new().unwrap();
+    let handle = poller.handle();
+
+    // Handles don't keep the instance from being released, and stop working once it is.
+    let fd = poller.into_raw_fd();
+    assert_eq!(
+        handle.notify().unwrap_err().kind(),
+        std::io::ErrorKind::NotConnected
+    );
+    drop(unsafe { Poller::from_raw_fd(fd).unwrap() });
+}
+
+#[test]
+fn blocking_hook() {
+    let poller = Poller::new().unwrap();
+    let handle = poller.handle();
+    let (entered, hook_entered) = mpsc::channel();
+    let (release, released) = mpsc::channel::<()>();
+    let hook = Mutex::new((entered, released));
+    poller.set_notify_hook(Box::new(move || {
+        let hook = hook.lock().unwrap();
+        hook.0.send(()).unwrap();
+        hook.1.recv().unwrap();
+    }));
+
+    // A hook that blocks in a handle doesn't keep the instance from being released.
+    let notifier = thread::spawn(move || handle.notify());
+    hook_entered.recv().unwrap();
+    let fd = poller.into_raw_fd();
+    release.send(()).unwrap();
+    notifier.join().unwrap().unwrap();
+    drop(unsafe { Poller::from_raw_fd(fd).unwrap() });
+}
+
+#[test]
+fn not_a_poller() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    assert!(unsafe { Poller::from_raw_fd(listener.as_raw_fd()) }.is_err());
+
+    // The file descriptor is still open.
+    listener.local_addr().unwrap();
+}
@@ -0,0 +1,53 @@
+//! Tests for the `serde` implementations.
+
+#![cfg(feature = "serde")]
+
+use polling::{Event, PollMode};
+
+#[test]
+fn event_json() {
+    let event = Event::readable(42);
+    let json = serde_json::to_string(&event).unwrap();
+    assert_eq!(json, r#"{"key":42,"readable":true,"writable":false}"#);
+    assert_eq!(serde_json::from_str::<Event>(&json).unwrap(), event);
+}
+
+#[test]
+fn poll_mode_json() {
+    for &(mode, name) in &[
+        (PollMode::Oneshot, "\"oneshot\""),
+        (PollMode::Level, "\"level\""),
+        (PollMode::Edge, "\"edge\""),
+        (PollMode::EdgeOneshot, "\"edge-oneshot\""),
+        (PollMode::Exclusive, "\"exclusive\""),
+        (PollMode::Dispatch, "\"dispatch\""),
+    ] {
+        let json = serde_json::to_string(&mode).unwrap();
+        assert_eq!(json, name);
+        assert_eq!(serde_json::from_str::<PollMode>(&json).unwrap(), mode);
+    }
+}
+
+#[test]
+fn cbor_round_trip() {
+    let event = Event::all(7);
+    let mut buf = Vec::new();
+    ciborium::ser::into_writer(&event, &mut buf).unwrap();
+    assert_eq!(ciborium::de::from_reader::<Event, _>(&*buf).unwrap(), event);
+
+    for &mode in &[
+        PollMode::Oneshot,
+        PollMode::Level,
+        PollMode::Edge,
+        PollMode::EdgeOneshot,
+        PollMode::Exclusive,
+        PollMode::Dispatch,
+    ] {
+        let mut buf = Vec::new();
+        ciborium::ser::into_writer(&mode, &mut buf).unwrap();
+        assert_eq!(
+            ciborium::de::from_reader::<PollMode, _>(&*buf).unwrap(),
+            mode
+        );
+    }
+}
@@ -0,0 +1,93 @@
+//! Tests for `Poller::wait_with_sigmask()`.
+
+#![cfg(unix)]
+#![cfg(not(polling_test_poll_backend))]
+
+use std::io;
+use std::mem::MaybeUninit;
+use std::ptr;
+use std::time::Duration;
+
+use polling::{Events, Poller, SigSet};
+
+extern "C" fn handler(_: libc::c_int) {}
+
+#[test]
+fn pending_signal_interrupts_wait() {
+    let poller = Poller::new().unwrap();
+    let mut events = Events::new();
+
+    unsafe {
+        // Install a handler, so that the signal doesn't terminate the process.
+        let mut action: libc::sigaction = MaybeUninit::zeroed().assume_init();
+        action.sa_sigaction = handler as extern "C" fn(libc::c_int) as libc::sighandler_t;
+        assert_eq!(libc::sigaction(libc::SIGUSR1, &action, ptr::null_mut()), 0);
+    }
+
+    // Block the signal and make it pending.
+    let mut old = MaybeUninit::<libc::sigset_t>::uninit();
+    unsafe {
+        let mut blocked = MaybeUninit::<libc::sigset_t>::uninit();
+        libc::sigemptyset(blocked.as_mut_ptr());
+        libc::sigaddset(blocked.as_mut_ptr(), libc::SIGUSR1);
+        assert_eq!(
+            libc::pthread_sigmask(libc::SIG_BLOCK, blocked.as_ptr(), old.as_mut_ptr()),
+            0
+        );
+        assert_eq!(libc::raise(libc::SIGUSR1), 0);
+    }
+
+    // Waiting with the current mask keeps the signal blocked.
+    let current = SigSet::current().unwrap();
+    assert!(current.contains(libc::SIGUSR1));
+    assert_eq!(
+        poller
+            .wait_with_sigmask(&mut events, Some(Duration::from_millis(10)), &current)
+            .unwrap(),
+        0
+    );
+
+    // Unblocking the signal while waiting delivers it without a race.
+    let mut unblocked = current;
+    unblocked.remove(libc::SIGUSR1).unwrap();
+    let err = poller
+        .wait_with_sigmask(&mut events, None, &unblocked)
+        .unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::Interrupted);
+
+    // The signal is blocked again after the wait.
+    assert!(SigSet::current().unwrap().contains(libc::SIGUSR1));
+
+    unsafe {
+        libc::pthread_sigmask(libc::SIG_SETMASK, old.as_ptr(), ptr::null_mut());
+    }
+}
+
+#[test]
+fn notify_wakes_up() {
+    let poller = Poller::new().unwrap();
+    let mut events = Events::new();
+
+    poller.notify().unwrap();
+    let n = poller
+        .wait_with_sigmask(&mut events, None, &SigSet::current().unwrap())
+        .unwrap();
+    assert_eq!(n, 0);
+}
+
+#[test]
+fn sigset() {
+    let mut set = SigSet::empty();
+    assert!(!set.contains(libc::SIGUSR1));
+    set.add(libc::SIGUSR1).unwrap();
+    assert!(set.contains(libc::SIGUSR1));
+    assert_eq!(format!("{:?}", set), format!("{{{}}}", libc::SIGUSR1));
+    set.remove(libc::SIGUSR1).unwrap();
+    assert!(!set.contains(libc::SIGUSR1));
+
+    assert!(SigSet::all().contains(libc::SIGUSR2));
+    assert_eq!(
+        SigSet::empty().add(0).unwrap_err().kind(),
+        io::ErrorKind::InvalidInput
+    );
+}
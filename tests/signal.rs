@@ -0,0 +1,69 @@
+//! Tests for `Poller::add_signal()`.
+
+#![cfg(unix)]
+
+use std::mem::MaybeUninit;
+use std::ptr;
+use std::time::Duration;
+
+use polling::{Event, Poller};
+
+#[test]
+fn signal() {
+    let poller = Poller::new().unwrap();
+
+    unsafe {
+        match polling::BACKEND {
+            // The signalfd only receives the signal while it is blocked.
+            "epoll" => {
+                let mut blocked = MaybeUninit::<libc::sigset_t>::uninit();
+                libc::sigemptyset(blocked.as_mut_ptr());
+                libc::sigaddset(blocked.as_mut_ptr(), libc::SIGUSR2);
+                assert_eq!(
+                    libc::pthread_sigmask(libc::SIG_BLOCK, blocked.as_ptr(), ptr::null_mut()),
+                    0
+                );
+            }
+            // The filter doesn't stop the default action of the signal.
+            "kqueue" => {
+                libc::signal(libc::SIGUSR2, libc::SIG_IGN);
+            }
+            _ => {}
+        }
+    }
+
+    poller.add_signal(libc::SIGUSR2, 1).unwrap();
+
+    // Nothing is reported until the signal is delivered.
+    let mut events = Vec::new();
+    poller
+        .wait(&mut events, Some(Duration::from_millis(10)))
+        .unwrap();
+    assert!(events.is_empty());
+
+    // Deliveries are reported once.
+    for _ in 0..2 {
+        assert_eq!(unsafe { libc::raise(libc::SIGUSR2) }, 0);
+    }
+    poller
+        .wait(&mut events, Some(Duration::from_secs(1)))
+        .unwrap();
+    assert_eq!(events, [Event::readable(1)]);
+
+    events.clear();
+    poller
+        .wait(&mut events, Some(Duration::from_millis(10)))
+        .unwrap();
+    assert!(events.is_empty());
+
+    // Adding the signal again replaces its key.
+    poller.add_signal(libc::SIGUSR2, 2).unwrap();
+    assert_eq!(unsafe { libc::raise(libc::SIGUSR2) }, 0);
+    poller
+        .wait(&mut events, Some(Duration::from_secs(1)))
+        .unwrap();
+    assert_eq!(events, [Event::readable(2)]);
+
+    poller.delete_signal(libc::SIGUSR2).unwrap();
+    poller.delete_signal(libc::SIGUSR2).unwrap();
+}
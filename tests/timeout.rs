@@ -1,7 +1,9 @@
 use std::io;
+use std::sync::Arc;
+use std::thread;
 use std::time::{Duration, Instant};
 
-use polling::Poller;
+use polling::{Events, Poller, WaitOutcome};
 
 #[test]
 fn twice() -> io::Result<()> {
@@ -30,3 +32,103 @@ fn non_blocking() -> io::Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn wait_or_timeout() -> io::Result<()> {
+    let poller = Poller::new()?;
+    let mut events = Events::new();
+
+    let err = poller
+        .wait_or_timeout(&mut events, Duration::from_millis(100))
+        .unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+
+    // A notification is not a timeout.
+    poller.notify()?;
+    poller.wait_or_timeout(&mut events, Duration::from_secs(10))?;
+    assert_eq!(events.iter().count(), 0);
+
+    Ok(())
+}
+
+#[test]
+fn wait_deadline() -> io::Result<()> {
+    let poller = Poller::new()?;
+    let mut events = Events::new();
+
+    // Notifications end the wait early, but the deadline stays the same.
+    let start = Instant::now();
+    let deadline = start + Duration::from_millis(200);
+    poller.notify()?;
+    while Instant::now() < deadline {
+        poller.wait_deadline(&mut events, Some(deadline))?;
+    }
+    assert!(start.elapsed() >= Duration::from_millis(200));
+
+    // A deadline in the past doesn't block.
+    let start = Instant::now();
+    poller.wait_deadline(&mut events, Some(start - Duration::from_secs(1)))?;
+    assert!(start.elapsed() < Duration::from_secs(1));
+
+    Ok(())
+}
+
+#[test]
+fn outcome() -> io::Result<()> {
+    let poller = Poller::new()?;
+    let mut events = Events::new();
+    assert_eq!(events.outcome(), WaitOutcome::TimedOut);
+
+    let start = Instant::now();
+    poller.wait_events(&mut events, Some(Duration::from_millis(100)))?;
+    assert!(start.elapsed() >= Duration::from_millis(100));
+    assert_eq!(events.outcome(), WaitOutcome::TimedOut);
+
+    poller.notify()?;
+    poller.wait_events(&mut events, None)?;
+    assert_eq!(events.outcome(), WaitOutcome::Notified);
+
+    poller.wake(1)?;
+    poller.wake(2)?;
+    poller.wait_events(&mut events, None)?;
+    assert_eq!(events.outcome(), WaitOutcome::Events(2));
+
+    // The wait ends without events when another thread is already waiting.
+    let poller = Arc::new(poller);
+    let waiter = thread::spawn({
+        let poller = poller.clone();
+        move || poller.wait_events(&mut Events::new(), None)
+    });
+    thread::sleep(Duration::from_millis(100));
+    let start = Instant::now();
+    poller.wait_events(&mut events, Some(Duration::from_secs(10)))?;
+    assert!(start.elapsed() < Duration::from_secs(10));
+    assert_eq!(events.outcome(), WaitOutcome::Interrupted);
+    poller.notify()?;
+    waiter.join().unwrap()?;
+
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn outcome_early_wakeup() -> io::Result<()> {
+    use std::fs::File;
+    use std::io::Write;
+
+    // A write to the pipe wakes the poller up without a notification or events, so the wait
+    // ends before its timeout without timing out.
+    let (poller, waker) = Poller::new_piped()?;
+    let mut events = Events::new();
+    let writer = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(100));
+        File::from(waker).write_all(&[1])
+    });
+    let start = Instant::now();
+    poller.wait_events(&mut events, Some(Duration::from_secs(10)))?;
+    assert!(start.elapsed() < Duration::from_secs(10));
+    assert_eq!(events.outcome(), WaitOutcome::Interrupted);
+    writer.join().unwrap()?;
+
+    Ok(())
+}
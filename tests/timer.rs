@@ -0,0 +1,151 @@
+mod common;
+
+use polling::timer::TimerWheel;
+use polling::{Event, Events, Poller, PollerBuilder};
+use std::io;
+use std::time::{Duration, Instant};
+
+#[test]
+fn fires_in_order() {
+    let poller = Poller::new().unwrap();
+    let mut timers = TimerWheel::new(&poller);
+
+    let start = Instant::now();
+    timers.insert(2, start + Duration::from_millis(300));
+    timers.insert(1, start + Duration::from_millis(20));
+    timers.insert(3, start + Duration::from_secs(3600));
+    assert_eq!(timers.len(), 3);
+    assert_eq!(
+        timers.next_deadline(),
+        Some(start + Duration::from_millis(20))
+    );
+
+    let mut fired = Vec::new();
+    let mut events = Events::new();
+    while fired.len() < 2 {
+        poller
+            .wait_deadline(&mut events, timers.next_deadline())
+            .unwrap();
+        timers.advance().unwrap();
+        poller
+            .wait_events(&mut events, Some(Duration::from_secs(0)))
+            .unwrap();
+        fired.extend(events.iter());
+    }
+
+    assert!(start.elapsed() >= Duration::from_millis(300));
+    assert_eq!(fired, [Event::readable(1), Event::readable(2)]);
+    assert_eq!(timers.len(), 1);
+    assert_eq!(
+        timers.next_deadline(),
+        Some(start + Duration::from_secs(3600))
+    );
+}
+
+#[test]
+fn expired_deadline() {
+    let poller = Poller::new().unwrap();
+    let mut timers = TimerWheel::new(&poller);
+
+    timers.insert(1, Instant::now() - Duration::from_secs(1));
+    assert_eq!(timers.advance().unwrap(), 1);
+    assert!(timers.is_empty());
+
+    let mut events = Vec::new();
+    poller
+        .wait(&mut events, Some(Duration::from_secs(0)))
+        .unwrap();
+    assert_eq!(events, [Event::readable(1)]);
+}
+
+#[test]
+fn remove() {
+    let poller = Poller::new().unwrap();
+    let mut timers = TimerWheel::new(&poller);
+
+    let soon = Instant::now() + Duration::from_millis(10);
+    let later = Instant::now() + Duration::from_secs(100_000_000);
+    timers.insert(1, soon);
+    timers.insert(2, later);
+    assert_eq!(timers.next_deadline(), Some(soon));
+    assert!(timers.remove(1));
+    assert_eq!(timers.next_deadline(), Some(later));
+    assert!(!timers.remove(1));
+    assert!(timers.remove(2));
+    assert!(timers.is_empty());
+    assert_eq!(timers.next_deadline(), None);
+
+    std::thread::sleep(Duration::from_millis(20));
+    assert_eq!(timers.advance().unwrap(), 0);
+}
+
+#[test]
+fn wake_notify_key() {
+    let poller = Poller::new().unwrap();
+    assert!(poller.wake(polling::NOTIFY_KEY).is_err());
+}
+
+#[test]
+fn os_timer() {
+    let poller = Poller::new().unwrap();
+    if let Err(err) = poller.add_timer(1, Duration::from_millis(20), false) {
+        assert!(common::is_unsupported(&err));
+        assert!(polling::BACKEND == "poll" || polling::BACKEND == "port");
+        return;
+    }
+
+    // A timer that doesn't repeat fires once.
+    let start = Instant::now();
+    let mut events = Vec::new();
+    poller
+        .wait(&mut events, Some(Duration::from_secs(10)))
+        .unwrap();
+    assert_eq!(events, [Event::readable(1)]);
+    assert!(start.elapsed() >= Duration::from_millis(20));
+
+    events.clear();
+    poller
+        .wait(&mut events, Some(Duration::from_millis(50)))
+        .unwrap();
+    assert!(events.is_empty());
+    poller.delete_timer(1).unwrap();
+
+    // A repeating timer fires until it is removed.
+    poller
+        .add_timer(2, Duration::from_millis(10), true)
+        .unwrap();
+    for _ in 0..3 {
+        events.clear();
+        poller
+            .wait(&mut events, Some(Duration::from_secs(10)))
+            .unwrap();
+        assert_eq!(events, [Event::readable(2)]);
+    }
+
+    poller.delete_timer(2).unwrap();
+    poller.delete_timer(2).unwrap();
+    events.clear();
+    poller
+        .wait(&mut events, Some(Duration::from_millis(50)))
+        .unwrap();
+    assert!(events.is_empty());
+
+    assert!(poller
+        .add_timer(polling::NOTIFY_KEY, Duration::from_millis(10), false)
+        .is_err());
+}
+
+#[test]
+fn os_timer_key_of_source() {
+    let poller = PollerBuilder::new().track_sources(true).build().unwrap();
+    let (stream, _peer) = common::tcp_pair().unwrap();
+    poller.add(&stream, Event::readable(1)).unwrap();
+
+    // Events of the timer and the source couldn't be told apart.
+    let err = poller
+        .add_timer(1, Duration::from_millis(10), false)
+        .unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+
+    poller.delete(&stream).unwrap();
+}
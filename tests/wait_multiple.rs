@@ -0,0 +1,153 @@
+//! Tests for `Poller::wait_multiple()`.
+
+mod common;
+
+use std::io::{self, Write};
+use std::time::Duration;
+
+use polling::{Event, Events, Poller, PollerBuilder};
+
+fn supported() -> bool {
+    polling::BACKEND == "epoll" || polling::BACKEND == "kqueue"
+}
+
+#[test]
+fn events_from_all_pollers() {
+    let first = Poller::new().unwrap();
+    let second = Poller::new().unwrap();
+    let mut events = Events::new();
+
+    if !supported() {
+        let err = Poller::wait_multiple(&[&first, &second], &mut events, None).unwrap_err();
+        assert!(common::is_unsupported(&err));
+        return;
+    }
+
+    let (read1, mut write1) = common::tcp_pair().unwrap();
+    let (read2, mut write2) = common::tcp_pair().unwrap();
+    first.add(&read1, Event::readable(1)).unwrap();
+    second.add(&read2, Event::readable(2)).unwrap();
+
+    // Nothing is ready at first.
+    let n = Poller::wait_multiple(
+        &[&first, &second],
+        &mut events,
+        Some(Duration::from_millis(10)),
+    )
+    .unwrap();
+    assert_eq!(n, 0);
+
+    write2.write_all(&[1]).unwrap();
+    let n = Poller::wait_multiple(
+        &[&first, &second],
+        &mut events,
+        Some(Duration::from_secs(1)),
+    )
+    .unwrap();
+    assert_eq!(n, 1);
+    assert_eq!(events.iter().collect::<Vec<_>>(), [Event::readable(2)]);
+
+    write1.write_all(&[1]).unwrap();
+    let n = Poller::wait_multiple(
+        &[&first, &second],
+        &mut events,
+        Some(Duration::from_secs(1)),
+    )
+    .unwrap();
+    assert_eq!(n, 1);
+    assert_eq!(events.iter().collect::<Vec<_>>(), [Event::readable(1)]);
+
+    first.delete(&read1).unwrap();
+    second.delete(&read2).unwrap();
+}
+
+#[test]
+fn notify_wakes_up() {
+    if !supported() {
+        return;
+    }
+
+    let first = Poller::new().unwrap();
+    let second = Poller::new().unwrap();
+    let mut events = Events::new();
+
+    second.notify().unwrap();
+    let n = Poller::wait_multiple(&[&first, &second], &mut events, None).unwrap();
+    assert_eq!(n, 0);
+    assert_eq!(events.notified_pollers().collect::<Vec<_>>(), [1]);
+}
+
+#[test]
+fn same_key_in_both_pollers() {
+    if !supported() {
+        return;
+    }
+
+    let first = Poller::new().unwrap();
+    let second = Poller::new().unwrap();
+    let mut events = Events::new();
+
+    let (read1, mut write1) = common::tcp_pair().unwrap();
+    let (read2, mut write2) = common::tcp_pair().unwrap();
+    first.add(&read1, Event::readable(1)).unwrap();
+    second.add(&read2, Event::readable(1)).unwrap();
+    write1.write_all(&[1]).unwrap();
+    write2.write_all(&[1]).unwrap();
+
+    // Both events have the same key, but tell which poller reported them.
+    let mut reported = Vec::new();
+    while reported.len() < 2 {
+        Poller::wait_multiple(
+            &[&first, &second],
+            &mut events,
+            Some(Duration::from_secs(1)),
+        )
+        .unwrap();
+        reported.extend(
+            events
+                .iter_with_poller()
+                .map(|(poller, ev, _)| (poller, ev)),
+        );
+    }
+    reported.sort_by_key(|&(poller, _)| poller);
+    assert_eq!(reported, [(0, Event::readable(1)), (1, Event::readable(1))]);
+
+    // Merging events doesn't merge those of different pollers.
+    first.modify(&read1, Event::readable(1)).unwrap();
+    second.modify(&read2, Event::readable(1)).unwrap();
+    first.wake(2).unwrap();
+    second.wake(2).unwrap();
+    let mut woken = Vec::new();
+    while woken.len() < 2 {
+        Poller::wait_multiple(
+            &[&first, &second],
+            &mut events,
+            Some(Duration::from_secs(1)),
+        )
+        .unwrap();
+        events.dedup();
+        woken.extend(
+            events
+                .iter_with_poller()
+                .filter(|(_, ev, _)| ev.key == 2)
+                .map(|(poller, _, _)| poller),
+        );
+    }
+    woken.sort_unstable();
+    assert_eq!(woken, [0, 1]);
+
+    first.delete(&read1).unwrap();
+    second.delete(&read2).unwrap();
+}
+
+#[test]
+fn different_notify_keys() {
+    if !supported() {
+        return;
+    }
+
+    let first = Poller::new().unwrap();
+    let second = PollerBuilder::new().notify_key(0).build().unwrap();
+    let err = Poller::wait_multiple(&[&first, &second], &mut Events::new(), None).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+}
@@ -0,0 +1,114 @@
+//! Tests for several threads waiting on the same poller.
+
+mod common;
+
+use std::io;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use polling::{Events, Poller};
+
+const THREADS: usize = 4;
+
+/// Starts `THREADS` threads that wait on `poller` once.
+fn spawn_waiters(
+    poller: &Arc<Poller>,
+    timeout: Option<Duration>,
+) -> Vec<thread::JoinHandle<io::Result<usize>>> {
+    (0..THREADS)
+        .map(|_| {
+            let poller = poller.clone();
+            thread::spawn(move || {
+                let mut events = Events::new();
+                poller.wait_shared(&mut events, timeout)
+            })
+        })
+        .collect()
+}
+
+fn supported(poller: &Poller) -> bool {
+    let mut events = Events::new();
+    match poller.wait_shared(&mut events, Some(Duration::from_secs(0))) {
+        Ok(_) => true,
+        Err(e) if common::is_unsupported(&e) => false,
+        Err(e) => panic!("{}", e),
+    }
+}
+
+#[test]
+fn notify_all() {
+    let poller = Arc::new(Poller::new().unwrap());
+    if !supported(&poller) {
+        return;
+    }
+
+    let threads = spawn_waiters(&poller, None);
+    thread::sleep(Duration::from_millis(200));
+    poller.notify_all().unwrap();
+
+    for thread in threads {
+        assert_eq!(thread.join().unwrap().unwrap(), 0);
+    }
+}
+
+#[test]
+fn notify_all_with_late_waiters() {
+    let poller = Arc::new(Poller::new().unwrap());
+    if !supported(&poller) {
+        return;
+    }
+
+    let start = Instant::now();
+    let threads = spawn_waiters(&poller, Some(Duration::from_secs(10)));
+    thread::sleep(Duration::from_millis(200));
+    poller.notify_all().unwrap();
+
+    // Threads that start waiting now may take the wakeups that are passed on, and have to pass
+    // them on as well.
+    let late = spawn_waiters(&poller, Some(Duration::from_millis(500)));
+
+    for thread in threads {
+        assert_eq!(thread.join().unwrap().unwrap(), 0);
+    }
+    assert!(start.elapsed() < Duration::from_secs(5));
+    for thread in late {
+        thread.join().unwrap().unwrap();
+    }
+}
+
+#[test]
+fn shutdown() {
+    let poller = Arc::new(Poller::new().unwrap());
+    if !supported(&poller) {
+        return;
+    }
+
+    let threads = spawn_waiters(&poller, None);
+    thread::sleep(Duration::from_millis(200));
+    poller.handle().shutdown().unwrap();
+
+    for thread in threads {
+        assert_eq!(thread.join().unwrap().unwrap(), 0);
+    }
+    assert_eq!(poller.wait_shared(&mut Events::new(), None).unwrap(), 0);
+}
+
+#[test]
+fn notify_one() {
+    let poller = Arc::new(Poller::new().unwrap());
+    if !supported(&poller) {
+        return;
+    }
+
+    let threads = spawn_waiters(&poller, None);
+    thread::sleep(Duration::from_millis(200));
+    for _ in 0..THREADS {
+        poller.notify_one().unwrap();
+        thread::sleep(Duration::from_millis(10));
+    }
+
+    for thread in threads {
+        assert_eq!(thread.join().unwrap().unwrap(), 0);
+    }
+}
@@ -0,0 +1,107 @@
+#![cfg(any(target_os = "linux", target_os = "android"))]
+
+use polling::{Event, Poller, WatchFlags};
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// Creates an empty directory that is unique to this test.
+fn test_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("polling-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir(&dir).unwrap();
+    dir
+}
+
+#[test]
+fn inotify() {
+    let dir = test_dir("inotify");
+    let poller = Poller::new().unwrap();
+    let inotify = poller.register_inotify(1, &dir, libc::IN_CREATE).unwrap();
+
+    fs::write(dir.join("file"), b"hello").unwrap();
+
+    let mut events = Vec::new();
+    poller
+        .wait(&mut events, Some(Duration::from_secs(1)))
+        .unwrap();
+    assert_eq!(events, [Event::readable(1)]);
+
+    let mut buf = [0; 1024];
+    let n = inotify.read(&mut buf).unwrap();
+    assert!(n >= std::mem::size_of::<libc::inotify_event>());
+    let event = unsafe { std::ptr::read_unaligned(buf.as_ptr() as *const libc::inotify_event) };
+    assert_eq!(event.wd, inotify.watch_descriptor());
+    assert_eq!(event.mask & libc::IN_CREATE, libc::IN_CREATE);
+
+    poller.delete(&inotify).unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn register_path() {
+    let dir = test_dir("register_path");
+    let poller = Poller::new().unwrap();
+    let watch = poller.register_path(&dir, 1).unwrap();
+
+    let mut events = Vec::new();
+    poller
+        .wait(&mut events, Some(Duration::from_secs(0)))
+        .unwrap();
+    assert!(events.is_empty());
+
+    fs::write(dir.join("file"), b"hello").unwrap();
+
+    // The change is reported until it is cleared.
+    for _ in 0..2 {
+        events.clear();
+        poller
+            .wait(&mut events, Some(Duration::from_secs(1)))
+            .unwrap();
+        assert_eq!(events, [Event::readable(1)]);
+    }
+
+    watch.clear().unwrap();
+    events.clear();
+    poller
+        .wait(&mut events, Some(Duration::from_secs(0)))
+        .unwrap();
+    assert!(events.is_empty());
+
+    drop(watch);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn add_path() {
+    let dir = test_dir("add_path");
+    let file = dir.join("config");
+    fs::write(&file, b"").unwrap();
+
+    let poller = Poller::new().unwrap();
+    let err = poller.add_path(&file, WatchFlags::empty(), 1).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+
+    let removed = poller.add_path(&file, WatchFlags::REMOVE, 1).unwrap();
+    let modified = poller.add_path(&file, WatchFlags::MODIFY, 2).unwrap();
+
+    // Only the watch that is interested in writes reports them.
+    fs::write(&file, b"reload").unwrap();
+    let mut events = Vec::new();
+    poller
+        .wait(&mut events, Some(Duration::from_secs(1)))
+        .unwrap();
+    assert_eq!(events, [Event::readable(2)]);
+    modified.clear().unwrap();
+
+    fs::remove_file(&file).unwrap();
+    events.clear();
+    poller
+        .wait(&mut events, Some(Duration::from_secs(1)))
+        .unwrap();
+    assert!(events.contains(&Event::readable(1)));
+    removed.clear().unwrap();
+
+    drop((removed, modified));
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,93 @@
+//! Tests for waitable handles on Windows.
+
+#![cfg(windows)]
+
+use polling::os::iocp::PollerIocpExt;
+use polling::{Event, PollMode, Poller};
+
+use std::io;
+use std::os::windows::io::AsRawHandle;
+use std::process::{Child, Command};
+use std::time::Duration;
+
+fn exited_child() -> Child {
+    let mut child = Command::new("cmd").args(&["/C", "exit"]).spawn().unwrap();
+    child.wait().unwrap();
+    child
+}
+
+#[test]
+fn waitable_oneshot() {
+    let child = exited_child();
+    let poller = Poller::new().unwrap();
+    poller
+        .add_waitable(child.as_raw_handle(), Event::readable(1), PollMode::Oneshot)
+        .unwrap();
+
+    let mut events = Vec::new();
+    poller
+        .wait(&mut events, Some(Duration::from_secs(10)))
+        .unwrap();
+    assert_eq!(events, [Event::readable(1)]);
+
+    // The handle is still signaled, but it has to be re-enabled.
+    events.clear();
+    poller
+        .wait(&mut events, Some(Duration::from_millis(100)))
+        .unwrap();
+    assert!(events.is_empty());
+
+    poller
+        .modify_waitable(child.as_raw_handle(), Event::readable(2), PollMode::Oneshot)
+        .unwrap();
+    poller
+        .wait(&mut events, Some(Duration::from_secs(10)))
+        .unwrap();
+    assert_eq!(events, [Event::readable(2)]);
+
+    poller.delete_waitable(child.as_raw_handle()).unwrap();
+}
+
+#[test]
+fn waitable_level() {
+    let child = exited_child();
+    let poller = Poller::new().unwrap();
+    poller
+        .add_waitable(child.as_raw_handle(), Event::readable(1), PollMode::Level)
+        .unwrap();
+
+    // The event is reported again while the handle stays signaled.
+    let mut events = Vec::new();
+    for _ in 0..3 {
+        events.clear();
+        poller
+            .wait(&mut events, Some(Duration::from_secs(10)))
+            .unwrap();
+        assert_eq!(events, [Event::readable(1)]);
+    }
+
+    // No more events are reported once the handle is removed.
+    poller.delete_waitable(child.as_raw_handle()).unwrap();
+    events.clear();
+    poller
+        .wait(&mut events, Some(Duration::from_millis(100)))
+        .unwrap();
+    assert!(events.is_empty());
+
+    assert_eq!(
+        poller
+            .delete_waitable(child.as_raw_handle())
+            .unwrap_err()
+            .kind(),
+        io::ErrorKind::NotFound
+    );
+}
+
+#[test]
+fn waitable_edge() {
+    let child = exited_child();
+    let poller = Poller::new().unwrap();
+    assert!(poller
+        .add_waitable(child.as_raw_handle(), Event::readable(1), PollMode::Edge)
+        .is_err());
+}